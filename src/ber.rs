@@ -0,0 +1,296 @@
+//! A small reader for the Basic Encoding Rules (X.690) of ASN.1, as used by SNMP
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::fmt;
+
+// BER Identifier and Length Octets (X.690)
+//
+//     8   7   6   5   4   3   2   1
+//   +---+---+---+---+---+---+---+---+
+//   | Class | C |    Tag number     |   tag number 31 means the number follows in
+//   +---+---+---+---+---+---+---+---+   base 128, high bit set on all but the last
+//   | 0 |   Length (0..127)         |   short form
+//   +---+---------------------------+
+//   | 1 |   Number of length octets |   long form, followed by the length octets
+//   +---+---------------------------+
+
+pub const TAG_INTEGER: u32 = 0x02;
+pub const TAG_OCTET_STRING: u32 = 0x04;
+pub const TAG_NULL: u32 = 0x05;
+pub const TAG_OBJECT_IDENTIFIER: u32 = 0x06;
+pub const TAG_SEQUENCE: u32 = 0x10;
+
+const HIGH_TAG_NUMBER: u8 = 0x1f;
+/// Lengths are limited to what fits in four octets
+const MAX_LENGTH_OCTETS: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BerClass {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private,
+}
+
+impl From<u8> for BerClass {
+    fn from(raw: u8) -> Self {
+        match raw & 0b11 {
+            0 => Self::Universal,
+            1 => Self::Application,
+            2 => Self::ContextSpecific,
+            _ => Self::Private,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BerTag {
+    pub class: BerClass,
+    pub constructed: bool,
+    pub number: u32,
+}
+
+impl BerTag {
+    pub fn universal(number: u32) -> Self {
+        BerTag {
+            class: BerClass::Universal,
+            constructed: number == TAG_SEQUENCE,
+            number,
+        }
+    }
+}
+
+/// A TLV whose content is left undecoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BerObject<'a> {
+    pub tag: BerTag,
+    pub content: &'a [u8],
+}
+
+/// An OBJECT IDENTIFIER as its list of arcs, displayed in dotted form
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectIdentifier(pub Vec<u64>);
+
+impl fmt::Display for ObjectIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, arc) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", arc)?;
+        }
+        Ok(())
+    }
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+fn tag(input: &[u8]) -> IResult<&[u8], BerTag> {
+    let (mut rest, first) = number::streaming::be_u8(input)?;
+    let mut number = u32::from(first & HIGH_TAG_NUMBER);
+    if number == u32::from(HIGH_TAG_NUMBER) {
+        number = 0;
+        loop {
+            let (next, byte) = number::streaming::be_u8(rest)?;
+            rest = next;
+            if number >> 25 != 0 {
+                return verify_error(input);
+            }
+            number = number << 7 | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+
+    Ok((
+        rest,
+        BerTag {
+            class: BerClass::from(first >> 6),
+            constructed: first & 0x20 != 0,
+            number,
+        },
+    ))
+}
+
+/// Parses a length in the definite form. The indefinite form is rejected, since the encodings
+/// built on this reader do not allow it.
+fn length(input: &[u8]) -> IResult<&[u8], usize> {
+    let (rest, first) = number::streaming::be_u8(input)?;
+    if first & 0x80 == 0 {
+        return Ok((rest, usize::from(first)));
+    }
+    let octets = first & 0x7f;
+    if octets == 0 || octets > MAX_LENGTH_OCTETS {
+        return verify_error(input);
+    }
+    let (rest, raw) = bytes::streaming::take(octets)(rest)?;
+    let length = raw
+        .iter()
+        .fold(0usize, |length, &b| length << 8 | usize::from(b));
+
+    Ok((rest, length))
+}
+
+pub fn parse_ber_object(input: &[u8]) -> IResult<&[u8], BerObject<'_>> {
+    let (input, tag) = tag(input)?;
+    let (input, length) = length(input)?;
+    let (input, content) = bytes::streaming::take(length)(input)?;
+
+    Ok((input, BerObject { tag, content }))
+}
+
+/// Parses a TLV and returns its content, failing with `ErrorKind::Tag` when the tag differs
+pub fn parse_ber_tagged(expected: BerTag) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |input| {
+        let (rest, object) = parse_ber_object(input)?;
+        if object.tag != expected {
+            return Err(Err::Error(Error::new(input, ErrorKind::Tag)));
+        }
+        Ok((rest, object.content))
+    }
+}
+
+/// Decodes the content of an INTEGER, or of an application type derived from it, which must
+/// fit in 64 bits
+pub fn decode_integer(content: &[u8]) -> Option<i64> {
+    if content.is_empty() || content.len() > 8 {
+        return None;
+    }
+    let sign = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    Some(
+        content
+            .iter()
+            .fold(sign, |value, &b| value << 8 | i64::from(b)),
+    )
+}
+
+/// Decodes the content of an unsigned application type such as Counter64, where a leading zero
+/// octet keeps the top bit clear
+pub fn decode_unsigned(content: &[u8]) -> Option<u64> {
+    let content = match content {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => content,
+    };
+    if content.is_empty() || content.len() > 8 {
+        return None;
+    }
+    Some(
+        content
+            .iter()
+            .fold(0, |value, &b| value << 8 | u64::from(b)),
+    )
+}
+
+pub fn decode_object_identifier(content: &[u8]) -> Option<ObjectIdentifier> {
+    let mut arcs = vec![];
+    let mut value: u64 = 0;
+    for (i, &b) in content.iter().enumerate() {
+        if value >> 57 != 0 {
+            return None;
+        }
+        value = value << 7 | u64::from(b & 0x7f);
+        if b & 0x80 != 0 {
+            if i == content.len() - 1 {
+                return None;
+            }
+            continue;
+        }
+        if arcs.is_empty() {
+            // The first subidentifier packs the first two arcs as 40 * X + Y
+            let first = (value / 40).min(2);
+            arcs.push(first);
+            arcs.push(value - first * 40);
+        } else {
+            arcs.push(value);
+        }
+        value = 0;
+    }
+
+    if arcs.is_empty() {
+        return None;
+    }
+    Some(ObjectIdentifier(arcs))
+}
+
+pub fn parse_ber_integer(input: &[u8]) -> IResult<&[u8], i64> {
+    let (rest, content) = parse_ber_tagged(BerTag::universal(TAG_INTEGER))(input)?;
+    match decode_integer(content) {
+        Some(value) => Ok((rest, value)),
+        None => verify_error(input),
+    }
+}
+
+pub fn parse_ber_octet_string(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    parse_ber_tagged(BerTag::universal(TAG_OCTET_STRING))(input)
+}
+
+pub fn parse_ber_object_identifier(input: &[u8]) -> IResult<&[u8], ObjectIdentifier> {
+    let (rest, content) = parse_ber_tagged(BerTag::universal(TAG_OBJECT_IDENTIFIER))(input)?;
+    match decode_object_identifier(content) {
+        Some(oid) => Ok((rest, oid)),
+        None => verify_error(input),
+    }
+}
+
+/// Parses a SEQUENCE and returns its content
+pub fn parse_ber_sequence(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    parse_ber_tagged(BerTag::universal(TAG_SEQUENCE))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn integers_and_lengths_work() {
+        assert_eq!(
+            parse_ber_integer(&[0x02, 0x01, 0x7f]),
+            Ok((EMPTY_SLICE, 127))
+        );
+        assert_eq!(
+            parse_ber_integer(&[0x02, 0x02, 0xff, 0x7f]),
+            Ok((EMPTY_SLICE, -129))
+        );
+        assert_eq!(decode_unsigned(&[0x00, 0xff, 0xff]), Some(0xffff));
+
+        let mut bytes = vec![0x04, 0x81, 0x80]; // long form length
+        bytes.extend_from_slice(&[0x61; 0x80]);
+        let (remaining, content) = parse_ber_octet_string(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(content.len(), 0x80);
+
+        // Indefinite length
+        assert!(parse_ber_object(&[0x30, 0x80, 0x00, 0x00]).is_err());
+        // Wrong tag
+        assert!(parse_ber_integer(&[0x04, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn object_identifiers_work() {
+        let bytes = [
+            0x06, 0x08, // OBJECT IDENTIFIER, length 8
+            0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05, 0x00, // 1.3.6.1.2.1.1.5.0
+        ];
+        let (_, oid) = parse_ber_object_identifier(&bytes).unwrap();
+        assert_eq!(oid.to_string(), "1.3.6.1.2.1.1.5.0");
+
+        // 2.999.3, with a multi-octet first subidentifier
+        assert_eq!(
+            decode_object_identifier(&[0x88, 0x37, 0x03]),
+            Some(ObjectIdentifier(vec![2, 999, 3]))
+        );
+        // Truncated subidentifier
+        assert_eq!(decode_object_identifier(&[0x2b, 0x86]), None);
+    }
+}
@@ -0,0 +1,1044 @@
+//! Handles parsing of BGP-4 messages from a reassembled TCP stream
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// BGP Message Header (RFC 4271)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                                                               |
+//   +                                                               +
+//   |                           Marker                              |
+//   +                      (16 octets of 0xff)                      +
+//   |                                                               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |          Length               |      Type     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// UPDATE Message Body (RFC 4271)
+//
+//   +-----------------------------------------------------+
+//   |   Withdrawn Routes Length (2 octets)                |
+//   +-----------------------------------------------------+
+//   |   Withdrawn Routes (variable)                       |
+//   +-----------------------------------------------------+
+//   |   Total Path Attribute Length (2 octets)            |
+//   +-----------------------------------------------------+
+//   |   Path Attributes (variable)                        |
+//   +-----------------------------------------------------+
+//   |   Network Layer Reachability Information (variable) |
+//   +-----------------------------------------------------+
+
+pub const BGP_PORT: u16 = 179;
+/// The 2-byte AS number placed in OPEN and AS_PATH by speakers with a larger one (RFC 6793)
+pub const AS_TRANS: u16 = 23456;
+
+const HEADER_LENGTH: u16 = 19;
+
+const MESSAGE_OPEN: u8 = 1;
+const MESSAGE_UPDATE: u8 = 2;
+const MESSAGE_NOTIFICATION: u8 = 3;
+const MESSAGE_KEEPALIVE: u8 = 4;
+const MESSAGE_ROUTE_REFRESH: u8 = 5;
+
+const PARAMETER_CAPABILITIES: u8 = 2;
+/// Marks the extended optional parameters length of RFC 9072
+const PARAMETER_EXTENDED_LENGTH: u8 = 255;
+
+const CAPABILITY_MULTIPROTOCOL: u8 = 1;
+const CAPABILITY_ROUTE_REFRESH: u8 = 2;
+const CAPABILITY_EXTENDED_MESSAGE: u8 = 6;
+const CAPABILITY_FOUR_OCTET_AS: u8 = 65;
+const CAPABILITY_ADD_PATH: u8 = 69;
+const CAPABILITY_ENHANCED_ROUTE_REFRESH: u8 = 70;
+
+const ATTRIBUTE_FLAG_EXTENDED_LENGTH: u8 = 0x10;
+
+const ATTRIBUTE_ORIGIN: u8 = 1;
+const ATTRIBUTE_AS_PATH: u8 = 2;
+const ATTRIBUTE_NEXT_HOP: u8 = 3;
+const ATTRIBUTE_MULTI_EXIT_DISC: u8 = 4;
+const ATTRIBUTE_LOCAL_PREF: u8 = 5;
+const ATTRIBUTE_ATOMIC_AGGREGATE: u8 = 6;
+const ATTRIBUTE_AGGREGATOR: u8 = 7;
+const ATTRIBUTE_COMMUNITIES: u8 = 8;
+const ATTRIBUTE_ORIGINATOR_ID: u8 = 9;
+const ATTRIBUTE_CLUSTER_LIST: u8 = 10;
+const ATTRIBUTE_MP_REACH_NLRI: u8 = 14;
+const ATTRIBUTE_MP_UNREACH_NLRI: u8 = 15;
+const ATTRIBUTE_EXTENDED_COMMUNITIES: u8 = 16;
+const ATTRIBUTE_AS4_PATH: u8 = 17;
+const ATTRIBUTE_AS4_AGGREGATOR: u8 = 18;
+const ATTRIBUTE_LARGE_COMMUNITIES: u8 = 32;
+
+/// Route distinguisher in front of each next hop address of VPN families
+const ROUTE_DISTINGUISHER_LENGTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpAfi {
+    Ipv4,
+    Ipv6,
+    L2Vpn,
+    Other(u16),
+}
+
+impl From<u16> for BgpAfi {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => Self::Ipv4,
+            2 => Self::Ipv6,
+            25 => Self::L2Vpn,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpSafi {
+    Unicast,
+    Multicast,
+    LabeledUnicast,
+    Evpn,
+    MplsVpn,
+    FlowSpec,
+    Other(u8),
+}
+
+impl From<u8> for BgpSafi {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Unicast,
+            2 => Self::Multicast,
+            4 => Self::LabeledUnicast,
+            70 => Self::Evpn,
+            128 => Self::MplsVpn,
+            133 => Self::FlowSpec,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpAddressFamily {
+    pub afi: BgpAfi,
+    pub safi: BgpSafi,
+}
+
+impl BgpAddressFamily {
+    /// The family of the NLRI and withdrawn routes fields of UPDATE messages
+    pub const IPV4_UNICAST: BgpAddressFamily = BgpAddressFamily {
+        afi: BgpAfi::Ipv4,
+        safi: BgpSafi::Unicast,
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpAddPathMode {
+    Receive,
+    Send,
+    SendReceive,
+    Other(u8),
+}
+
+impl From<u8> for BgpAddPathMode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Receive,
+            2 => Self::Send,
+            3 => Self::SendReceive,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl BgpAddPathMode {
+    fn sends(self) -> bool {
+        matches!(self, Self::Send | Self::SendReceive)
+    }
+
+    fn receives(self) -> bool {
+        matches!(self, Self::Receive | Self::SendReceive)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpCapability {
+    Multiprotocol(BgpAddressFamily),
+    RouteRefresh,
+    ExtendedMessage,
+    FourOctetAs(u32),
+    AddPath(Vec<(BgpAddressFamily, BgpAddPathMode)>),
+    EnhancedRouteRefresh,
+    Other { code: u8, value: Vec<u8> },
+}
+
+/// An OPEN message. Optional parameters other than capabilities are deprecated and skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpOpen {
+    pub version: u8,
+    pub my_as: u16,
+    pub hold_time: u16,
+    pub bgp_identifier: Ipv4Addr,
+    pub capabilities: Vec<BgpCapability>,
+}
+
+impl BgpOpen {
+    /// The AS number of the speaker, taken from the four-octet AS capability when present
+    pub fn asn(&self) -> u32 {
+        self.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                BgpCapability::FourOctetAs(asn) => Some(*asn),
+                _ => None,
+            })
+            .unwrap_or_else(|| u32::from(self.my_as))
+    }
+
+    fn add_path_mode(&self, family: BgpAddressFamily) -> Option<BgpAddPathMode> {
+        self.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                BgpCapability::AddPath(families) => families
+                    .iter()
+                    .find(|(f, _)| *f == family)
+                    .map(|(_, mode)| *mode),
+                _ => None,
+            })
+    }
+}
+
+/// What the two OPEN messages of a session negotiated, as needed to parse its UPDATE messages
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpOptions {
+    /// AS numbers in AS_PATH and AGGREGATOR are four octets wide
+    pub four_octet_as: bool,
+    /// Families whose prefixes are preceded by a path identifier
+    pub add_path: Vec<BgpAddressFamily>,
+}
+
+impl BgpOptions {
+    /// Options for UPDATE messages sent by the speaker of `sender` to the speaker of `receiver`
+    pub fn negotiate(sender: &BgpOpen, receiver: &BgpOpen) -> Self {
+        let four_octet_as = |open: &BgpOpen| {
+            open.capabilities
+                .iter()
+                .any(|capability| matches!(capability, BgpCapability::FourOctetAs(_)))
+        };
+        let add_path = sender
+            .capabilities
+            .iter()
+            .filter_map(|capability| match capability {
+                BgpCapability::AddPath(families) => Some(families),
+                _ => None,
+            })
+            .flatten()
+            .filter(|(family, mode)| {
+                mode.sends() && matches!(receiver.add_path_mode(*family), Some(m) if m.receives())
+            })
+            .map(|(family, _)| *family)
+            .collect();
+
+        BgpOptions {
+            four_octet_as: four_octet_as(sender) && four_octet_as(receiver),
+            add_path,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpPrefix {
+    /// Only present for families with add-path negotiated
+    pub path_id: Option<u32>,
+    pub address: IpAddr,
+    pub length: u8,
+}
+
+/// Reachability information of a multiprotocol family. Only IPv4 and IPv6 unicast and multicast
+/// prefixes are decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpNlri {
+    Prefixes(Vec<BgpPrefix>),
+    Unparsed(Vec<u8>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpOrigin {
+    Igp,
+    Egp,
+    Incomplete,
+    Other(u8),
+}
+
+impl From<u8> for BgpOrigin {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::Igp,
+            1 => Self::Egp,
+            2 => Self::Incomplete,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpAsPathSegmentType {
+    AsSet,
+    AsSequence,
+    ConfedSequence,
+    ConfedSet,
+    Other(u8),
+}
+
+impl From<u8> for BgpAsPathSegmentType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::AsSet,
+            2 => Self::AsSequence,
+            3 => Self::ConfedSequence,
+            4 => Self::ConfedSet,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpAsPathSegment {
+    pub segment_type: BgpAsPathSegmentType,
+    pub asns: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpLargeCommunity {
+    pub global_administrator: u32,
+    pub local_data_1: u32,
+    pub local_data_2: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpPathAttribute {
+    Origin(BgpOrigin),
+    AsPath(Vec<BgpAsPathSegment>),
+    NextHop(Ipv4Addr),
+    MultiExitDisc(u32),
+    LocalPref(u32),
+    AtomicAggregate,
+    Aggregator {
+        asn: u32,
+        address: Ipv4Addr,
+    },
+    Communities(Vec<u32>),
+    OriginatorId(Ipv4Addr),
+    ClusterList(Vec<Ipv4Addr>),
+    /// A global IPv6 next hop may be followed by a link-local one
+    MpReachNlri {
+        family: BgpAddressFamily,
+        next_hops: Vec<IpAddr>,
+        nlri: BgpNlri,
+    },
+    MpUnreachNlri {
+        family: BgpAddressFamily,
+        withdrawn_routes: BgpNlri,
+    },
+    ExtendedCommunities(Vec<u64>),
+    As4Path(Vec<BgpAsPathSegment>),
+    As4Aggregator {
+        asn: u32,
+        address: Ipv4Addr,
+    },
+    LargeCommunities(Vec<BgpLargeCommunity>),
+    Other {
+        flags: u8,
+        type_code: u8,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpUpdate {
+    pub withdrawn_routes: Vec<BgpPrefix>,
+    pub path_attributes: Vec<BgpPathAttribute>,
+    pub nlri: Vec<BgpPrefix>,
+}
+
+impl BgpUpdate {
+    /// Whether this is the End-of-RIB marker of graceful restart, for any family
+    pub fn is_end_of_rib(&self) -> bool {
+        if !self.withdrawn_routes.is_empty() || !self.nlri.is_empty() {
+            return false;
+        }
+        match self.path_attributes.as_slice() {
+            [] => true,
+            [BgpPathAttribute::MpUnreachNlri {
+                withdrawn_routes, ..
+            }] => match withdrawn_routes {
+                BgpNlri::Prefixes(prefixes) => prefixes.is_empty(),
+                BgpNlri::Unparsed(raw) => raw.is_empty(),
+            },
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpErrorCode {
+    MessageHeader,
+    OpenMessage,
+    UpdateMessage,
+    HoldTimerExpired,
+    FiniteStateMachine,
+    Cease,
+    RouteRefreshMessage,
+    Other(u8),
+}
+
+impl From<u8> for BgpErrorCode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::MessageHeader,
+            2 => Self::OpenMessage,
+            3 => Self::UpdateMessage,
+            4 => Self::HoldTimerExpired,
+            5 => Self::FiniteStateMachine,
+            6 => Self::Cease,
+            7 => Self::RouteRefreshMessage,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BgpNotification {
+    pub error_code: BgpErrorCode,
+    pub error_subcode: u8,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BgpMessage {
+    Open(BgpOpen),
+    Update(BgpUpdate),
+    Notification(BgpNotification),
+    Keepalive,
+    /// The subtype is 0 for a plain refresh, and marks its beginning or end with enhanced route
+    /// refresh
+    RouteRefresh {
+        family: BgpAddressFamily,
+        subtype: u8,
+    },
+    Other {
+        message_type: u8,
+        body: Vec<u8>,
+    },
+}
+
+fn length_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::LengthValue)))
+}
+
+fn address(input: &[u8]) -> IResult<&[u8], Ipv4Addr> {
+    let (input, raw) = number::complete::be_u32(input)?;
+
+    Ok((input, Ipv4Addr::from(raw)))
+}
+
+/// Applies `parser` until `input`, which must hold nothing else, is used up
+fn list<'a, T>(
+    mut input: &'a [u8],
+    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+) -> IResult<&'a [u8], Vec<T>> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        let (rest, item) = parser(input)?;
+        items.push(item);
+        input = rest;
+    }
+    Ok((input, items))
+}
+
+fn address_family(input: &[u8]) -> IResult<&[u8], BgpAddressFamily> {
+    let (input, afi) = number::complete::be_u16(input)?;
+    let (input, safi) = number::complete::be_u8(input)?;
+
+    Ok((
+        input,
+        BgpAddressFamily {
+            afi: afi.into(),
+            safi: safi.into(),
+        },
+    ))
+}
+
+fn capability(input: &[u8]) -> IResult<&[u8], BgpCapability> {
+    let (rest, code) = number::complete::be_u8(input)?;
+    let (rest, length) = number::complete::be_u8(rest)?;
+    let (rest, value) = bytes::complete::take(length)(rest)?;
+
+    let capability = match code {
+        CAPABILITY_MULTIPROTOCOL => {
+            let (value, afi) = number::complete::be_u16(value)?;
+            let (value, _reserved) = number::complete::be_u8(value)?;
+            let (_, safi) = number::complete::be_u8(value)?;
+            BgpCapability::Multiprotocol(BgpAddressFamily {
+                afi: afi.into(),
+                safi: safi.into(),
+            })
+        }
+        CAPABILITY_ROUTE_REFRESH => BgpCapability::RouteRefresh,
+        CAPABILITY_EXTENDED_MESSAGE => BgpCapability::ExtendedMessage,
+        CAPABILITY_FOUR_OCTET_AS => BgpCapability::FourOctetAs(number::complete::be_u32(value)?.1),
+        CAPABILITY_ADD_PATH => {
+            let (_, families) = list(value, |input| {
+                let (input, family) = address_family(input)?;
+                let (input, mode) = number::complete::be_u8(input)?;
+                Ok((input, (family, mode.into())))
+            })?;
+            BgpCapability::AddPath(families)
+        }
+        CAPABILITY_ENHANCED_ROUTE_REFRESH => BgpCapability::EnhancedRouteRefresh,
+        code => BgpCapability::Other {
+            code,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((rest, capability))
+}
+
+fn open(input: &[u8]) -> IResult<&[u8], BgpOpen> {
+    let (rest, version) = number::complete::be_u8(input)?;
+    let (rest, my_as) = number::complete::be_u16(rest)?;
+    let (rest, hold_time) = number::complete::be_u16(rest)?;
+    let (rest, bgp_identifier) = address(rest)?;
+    let (rest, parameters_length) = number::complete::be_u8(rest)?;
+    let extended = parameters_length == PARAMETER_EXTENDED_LENGTH
+        && rest.first() == Some(&PARAMETER_EXTENDED_LENGTH);
+    let (rest, parameters) = if extended {
+        let (rest, _) = number::complete::be_u8(rest)?;
+        let (rest, length) = number::complete::be_u16(rest)?;
+        bytes::complete::take(length)(rest)?
+    } else {
+        bytes::complete::take(parameters_length)(rest)?
+    };
+
+    let mut capabilities = vec![];
+    let mut parameters = parameters;
+    while !parameters.is_empty() {
+        let (rest, parameter_type) = number::complete::be_u8(parameters)?;
+        let (rest, length) = if extended {
+            number::complete::be_u16(rest)?
+        } else {
+            let (rest, length) = number::complete::be_u8(rest)?;
+            (rest, u16::from(length))
+        };
+        let (rest, value) = bytes::complete::take(length)(rest)?;
+        if parameter_type == PARAMETER_CAPABILITIES {
+            capabilities.extend(list(value, capability)?.1);
+        }
+        parameters = rest;
+    }
+
+    Ok((
+        rest,
+        BgpOpen {
+            version,
+            my_as,
+            hold_time,
+            bgp_identifier,
+            capabilities,
+        },
+    ))
+}
+
+fn prefix(family: BgpAddressFamily, add_path: bool) -> impl Fn(&[u8]) -> IResult<&[u8], BgpPrefix> {
+    move |input| {
+        let (rest, path_id) = if add_path {
+            let (rest, path_id) = number::complete::be_u32(input)?;
+            (rest, Some(path_id))
+        } else {
+            (input, None)
+        };
+        let (rest, length) = number::complete::be_u8(rest)?;
+        let max_length = if family.afi == BgpAfi::Ipv6 { 128 } else { 32 };
+        if length > max_length {
+            return length_error(input);
+        }
+        let (rest, raw) = bytes::complete::take(usize::from(length).div_ceil(8))(rest)?;
+        let address = if family.afi == BgpAfi::Ipv6 {
+            let mut octets = [0; 16];
+            octets[..raw.len()].copy_from_slice(raw);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        } else {
+            let mut octets = [0; 4];
+            octets[..raw.len()].copy_from_slice(raw);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        };
+
+        Ok((
+            rest,
+            BgpPrefix {
+                path_id,
+                address,
+                length,
+            },
+        ))
+    }
+}
+
+fn nlri<'a>(
+    input: &'a [u8],
+    family: BgpAddressFamily,
+    options: &BgpOptions,
+) -> IResult<&'a [u8], BgpNlri> {
+    let decoded = matches!(family.afi, BgpAfi::Ipv4 | BgpAfi::Ipv6)
+        && matches!(family.safi, BgpSafi::Unicast | BgpSafi::Multicast);
+    if !decoded {
+        return Ok((&input[input.len()..], BgpNlri::Unparsed(input.to_vec())));
+    }
+    let add_path = options.add_path.contains(&family);
+    let (rest, prefixes) = list(input, prefix(family, add_path))?;
+
+    Ok((rest, BgpNlri::Prefixes(prefixes)))
+}
+
+/// Decodes one or two next hop addresses, skipping the route distinguishers of VPN families
+fn next_hops(input: &[u8]) -> IResult<&[u8], Vec<IpAddr>> {
+    let (count, length, distinguished) = match input.len() {
+        0 => (0, 0, false),
+        4 => (1, 4, false),
+        16 => (1, 16, false),
+        32 => (2, 16, false),
+        12 => (1, 4, true),
+        24 => (1, 16, true),
+        48 => (2, 16, true),
+        _ => return length_error(input),
+    };
+    let step = length
+        + if distinguished {
+            ROUTE_DISTINGUISHER_LENGTH
+        } else {
+            0
+        };
+    let next_hops = input
+        .chunks(step)
+        .take(count)
+        .map(|chunk| {
+            let raw = &chunk[step - length..];
+            if length == 4 {
+                IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3]))
+            } else {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(raw);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        })
+        .collect();
+
+    Ok((&input[input.len()..], next_hops))
+}
+
+fn as_path(four_octet_as: bool) -> impl Fn(&[u8]) -> IResult<&[u8], BgpAsPathSegment> {
+    move |input| {
+        let (mut rest, segment_type) = number::complete::be_u8(input)?;
+        let (next, count) = number::complete::be_u8(rest)?;
+        rest = next;
+        let mut asns = Vec::with_capacity(usize::from(count));
+        for _ in 0..count {
+            let (next, asn) = if four_octet_as {
+                number::complete::be_u32(rest)?
+            } else {
+                let (next, asn) = number::complete::be_u16(rest)?;
+                (next, u32::from(asn))
+            };
+            asns.push(asn);
+            rest = next;
+        }
+
+        Ok((
+            rest,
+            BgpAsPathSegment {
+                segment_type: segment_type.into(),
+                asns,
+            },
+        ))
+    }
+}
+
+fn aggregator(value: &[u8], four_octet_as: bool) -> IResult<&[u8], (u32, Ipv4Addr)> {
+    let (rest, asn) = if four_octet_as {
+        number::complete::be_u32(value)?
+    } else {
+        let (rest, asn) = number::complete::be_u16(value)?;
+        (rest, u32::from(asn))
+    };
+    let (rest, address) = address(rest)?;
+
+    Ok((rest, (asn, address)))
+}
+
+fn path_attribute<'a>(
+    input: &'a [u8],
+    options: &BgpOptions,
+) -> IResult<&'a [u8], BgpPathAttribute> {
+    let (rest, flags) = number::complete::be_u8(input)?;
+    let (rest, type_code) = number::complete::be_u8(rest)?;
+    let (rest, length) = if flags & ATTRIBUTE_FLAG_EXTENDED_LENGTH != 0 {
+        number::complete::be_u16(rest)?
+    } else {
+        let (rest, length) = number::complete::be_u8(rest)?;
+        (rest, u16::from(length))
+    };
+    let (rest, value) = bytes::complete::take(length)(rest)?;
+
+    let attribute = match type_code {
+        ATTRIBUTE_ORIGIN => BgpPathAttribute::Origin(number::complete::be_u8(value)?.1.into()),
+        ATTRIBUTE_AS_PATH => {
+            BgpPathAttribute::AsPath(list(value, as_path(options.four_octet_as))?.1)
+        }
+        ATTRIBUTE_NEXT_HOP => BgpPathAttribute::NextHop(address(value)?.1),
+        ATTRIBUTE_MULTI_EXIT_DISC => {
+            BgpPathAttribute::MultiExitDisc(number::complete::be_u32(value)?.1)
+        }
+        ATTRIBUTE_LOCAL_PREF => BgpPathAttribute::LocalPref(number::complete::be_u32(value)?.1),
+        ATTRIBUTE_ATOMIC_AGGREGATE => BgpPathAttribute::AtomicAggregate,
+        ATTRIBUTE_AGGREGATOR => {
+            let (_, (asn, address)) = aggregator(value, options.four_octet_as)?;
+            BgpPathAttribute::Aggregator { asn, address }
+        }
+        ATTRIBUTE_COMMUNITIES => {
+            BgpPathAttribute::Communities(list(value, number::complete::be_u32)?.1)
+        }
+        ATTRIBUTE_ORIGINATOR_ID => BgpPathAttribute::OriginatorId(address(value)?.1),
+        ATTRIBUTE_CLUSTER_LIST => BgpPathAttribute::ClusterList(list(value, address)?.1),
+        ATTRIBUTE_MP_REACH_NLRI => {
+            let (value, family) = address_family(value)?;
+            let (value, next_hop_length) = number::complete::be_u8(value)?;
+            let (value, next_hop) = bytes::complete::take(next_hop_length)(value)?;
+            let (value, _reserved) = number::complete::be_u8(value)?;
+            BgpPathAttribute::MpReachNlri {
+                family,
+                next_hops: next_hops(next_hop)?.1,
+                nlri: nlri(value, family, options)?.1,
+            }
+        }
+        ATTRIBUTE_MP_UNREACH_NLRI => {
+            let (value, family) = address_family(value)?;
+            BgpPathAttribute::MpUnreachNlri {
+                family,
+                withdrawn_routes: nlri(value, family, options)?.1,
+            }
+        }
+        ATTRIBUTE_EXTENDED_COMMUNITIES => {
+            BgpPathAttribute::ExtendedCommunities(list(value, number::complete::be_u64)?.1)
+        }
+        ATTRIBUTE_AS4_PATH => BgpPathAttribute::As4Path(list(value, as_path(true))?.1),
+        ATTRIBUTE_AS4_AGGREGATOR => {
+            let (_, (asn, address)) = aggregator(value, true)?;
+            BgpPathAttribute::As4Aggregator { asn, address }
+        }
+        ATTRIBUTE_LARGE_COMMUNITIES => {
+            let (_, communities) = list(value, |input| {
+                let (input, global_administrator) = number::complete::be_u32(input)?;
+                let (input, local_data_1) = number::complete::be_u32(input)?;
+                let (input, local_data_2) = number::complete::be_u32(input)?;
+                let community = BgpLargeCommunity {
+                    global_administrator,
+                    local_data_1,
+                    local_data_2,
+                };
+                Ok((input, community))
+            })?;
+            BgpPathAttribute::LargeCommunities(communities)
+        }
+        type_code => BgpPathAttribute::Other {
+            flags,
+            type_code,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((rest, attribute))
+}
+
+fn update<'a>(input: &'a [u8], options: &BgpOptions) -> IResult<&'a [u8], BgpUpdate> {
+    let family = BgpAddressFamily::IPV4_UNICAST;
+    let add_path = options.add_path.contains(&family);
+    let (rest, withdrawn_length) = number::complete::be_u16(input)?;
+    let (rest, withdrawn) = bytes::complete::take(withdrawn_length)(rest)?;
+    let (_, withdrawn_routes) = list(withdrawn, prefix(family, add_path))?;
+    let (rest, attributes_length) = number::complete::be_u16(rest)?;
+    let (rest, attributes) = bytes::complete::take(attributes_length)(rest)?;
+    let (_, path_attributes) = list(attributes, |input| path_attribute(input, options))?;
+    let (rest, nlri) = list(rest, prefix(family, add_path))?;
+
+    Ok((
+        rest,
+        BgpUpdate {
+            withdrawn_routes,
+            path_attributes,
+            nlri,
+        },
+    ))
+}
+
+/// Parses one message. UPDATE messages are decoded as `options` say; use
+/// `BgpOptions::negotiate` once both OPEN messages of the session have been seen.
+///
+/// Returns `Incomplete` until the whole message is available.
+pub fn parse_bgp_message<'a>(
+    input: &'a [u8],
+    options: &BgpOptions,
+) -> IResult<&'a [u8], BgpMessage> {
+    let (rest, marker) = bytes::streaming::take(16u8)(input)?;
+    if marker.iter().any(|&b| b != 0xff) {
+        return Err(Err::Error(Error::new(input, ErrorKind::Tag)));
+    }
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    let (rest, message_type) = number::streaming::be_u8(rest)?;
+    if length < HEADER_LENGTH {
+        return length_error(input);
+    }
+    let (rest, body) = bytes::streaming::take(length - HEADER_LENGTH)(rest)?;
+
+    let message = match message_type {
+        MESSAGE_OPEN => BgpMessage::Open(open(body)?.1),
+        MESSAGE_UPDATE => BgpMessage::Update(update(body, options)?.1),
+        MESSAGE_NOTIFICATION => {
+            let (data, error_code) = number::complete::be_u8(body)?;
+            let (data, error_subcode) = number::complete::be_u8(data)?;
+            BgpMessage::Notification(BgpNotification {
+                error_code: error_code.into(),
+                error_subcode,
+                data: data.to_vec(),
+            })
+        }
+        MESSAGE_KEEPALIVE => BgpMessage::Keepalive,
+        MESSAGE_ROUTE_REFRESH => {
+            let (body, afi) = number::complete::be_u16(body)?;
+            let (body, subtype) = number::complete::be_u8(body)?;
+            let (_, safi) = number::complete::be_u8(body)?;
+            BgpMessage::RouteRefresh {
+                family: BgpAddressFamily {
+                    afi: afi.into(),
+                    safi: safi.into(),
+                },
+                subtype,
+            }
+        }
+        message_type => BgpMessage::Other {
+            message_type,
+            body: body.to_vec(),
+        },
+    };
+
+    Ok((rest, message))
+}
+
+/// Parses messages until the input runs out. A message cut short is left in the remaining
+/// input, to be parsed again once more data has arrived.
+pub fn parse_bgp_messages<'a>(
+    mut input: &'a [u8],
+    options: &BgpOptions,
+) -> IResult<&'a [u8], Vec<BgpMessage>> {
+    let mut messages = vec![];
+    while !input.is_empty() {
+        match parse_bgp_message(input, options) {
+            Ok((rest, message)) => {
+                messages.push(message);
+                input = rest;
+            }
+            Err(Err::Incomplete(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((input, messages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+    const MARKER: [u8; 16] = [0xff; 16];
+
+    fn framed(message_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut bytes = MARKER.to_vec();
+        bytes.extend_from_slice(&(HEADER_LENGTH + body.len() as u16).to_be_bytes());
+        bytes.push(message_type);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn open_and_negotiation_work() {
+        let body = [
+            0x04, // version
+            0x5b, 0xa0, // AS_TRANS
+            0x00, 0xb4, // hold time 180
+            0xc0, 0x00, 0x02, 0x01, // BGP identifier
+            0x16, // optional parameters length
+            0x02, 0x14, // capabilities
+            0x01, 0x04, 0x00, 0x01, 0x00, 0x01, // multiprotocol IPv4 unicast
+            0x02, 0x00, // route refresh
+            0x41, 0x04, 0x00, 0x01, 0x00, 0x00, // four-octet AS 65536
+            0x45, 0x04, 0x00, 0x01, 0x01, 0x03, // add-path IPv4 unicast, send/receive
+        ];
+        let bytes = framed(1, &body);
+        let (remaining, open) = match parse_bgp_message(&bytes, &BgpOptions::default()) {
+            Ok((remaining, BgpMessage::Open(open))) => (remaining, open),
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(open.my_as, AS_TRANS);
+        assert_eq!(open.asn(), 65536);
+        assert_eq!(open.hold_time, 180);
+        assert_eq!(
+            open.capabilities[3],
+            BgpCapability::AddPath(vec![(
+                BgpAddressFamily::IPV4_UNICAST,
+                BgpAddPathMode::SendReceive
+            )])
+        );
+
+        let options = BgpOptions::negotiate(&open, &open);
+        assert!(options.four_octet_as);
+        assert_eq!(options.add_path, vec![BgpAddressFamily::IPV4_UNICAST]);
+
+        let mut old_peer = open.clone();
+        old_peer.capabilities.truncate(2);
+        assert_eq!(
+            BgpOptions::negotiate(&open, &old_peer),
+            BgpOptions::default()
+        );
+    }
+
+    #[test]
+    fn update_works() {
+        let body = [
+            0x00, 0x00, // no withdrawn routes
+            0x00, 0x2e, // path attributes length
+            0x40, 0x01, 0x01, 0x00, // ORIGIN IGP
+            0x40, 0x02, 0x0a, // AS_PATH
+            0x02, 0x02, // AS_SEQUENCE of two
+            0x00, 0x01, 0x00, 0x00, // 65536
+            0x00, 0x00, 0xfd, 0xe8, // 65000
+            0x40, 0x03, 0x04, 0xc0, 0x00, 0x02, 0x01, // NEXT_HOP
+            0xc0, 0x08, 0x04, 0xfd, 0xe8, 0x00, 0x64, // COMMUNITIES 65000:100
+            0xc0, 0x20, 0x0c, // LARGE_COMMUNITY
+            0x00, 0x01, 0x00, 0x00, // 65536
+            0x00, 0x00, 0x00, 0x01, // 1
+            0x00, 0x00, 0x00, 0x02, // 2
+            0x00, 0x00, 0x00, 0x07, 0x18, 0xcb, 0x00, 0x71, // path 7, 203.0.113.0/24
+        ];
+        let options = BgpOptions {
+            four_octet_as: true,
+            add_path: vec![BgpAddressFamily::IPV4_UNICAST],
+        };
+        let expectation = BgpUpdate {
+            withdrawn_routes: vec![],
+            path_attributes: vec![
+                BgpPathAttribute::Origin(BgpOrigin::Igp),
+                BgpPathAttribute::AsPath(vec![BgpAsPathSegment {
+                    segment_type: BgpAsPathSegmentType::AsSequence,
+                    asns: vec![65536, 65000],
+                }]),
+                BgpPathAttribute::NextHop(Ipv4Addr::new(192, 0, 2, 1)),
+                BgpPathAttribute::Communities(vec![0xfde8_0064]),
+                BgpPathAttribute::LargeCommunities(vec![BgpLargeCommunity {
+                    global_administrator: 65536,
+                    local_data_1: 1,
+                    local_data_2: 2,
+                }]),
+            ],
+            nlri: vec![BgpPrefix {
+                path_id: Some(7),
+                address: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 0)),
+                length: 24,
+            }],
+        };
+
+        // A keepalive and the start of the next message, as handed over by stream reassembly
+        let mut bytes = framed(2, &body);
+        bytes.extend_from_slice(&framed(4, &[]));
+        bytes.extend_from_slice(&MARKER[..5]);
+        assert_eq!(
+            parse_bgp_messages(&bytes, &options),
+            Ok((
+                &MARKER[..5],
+                vec![BgpMessage::Update(expectation), BgpMessage::Keepalive]
+            ))
+        );
+    }
+
+    #[test]
+    fn mp_reach_and_end_of_rib_work() {
+        let body = [
+            0x00, 0x00, // no withdrawn routes
+            0x00, 0x2f, // path attributes length
+            0x80, 0x0e, 0x2c, // MP_REACH_NLRI
+            0x00, 0x02, 0x01, // IPv6 unicast
+            0x20, // global and link-local next hops
+            0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, // 2001:db8::1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, // fe80::1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, // reserved
+            0x30, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x01, // 2001:db8:1::/48
+        ];
+        let (_, message) = parse_bgp_message(&framed(2, &body), &BgpOptions::default()).unwrap();
+        let update = match message {
+            BgpMessage::Update(update) => update,
+            other => panic!("unexpected message {:?}", other),
+        };
+        assert!(!update.is_end_of_rib());
+        assert_eq!(
+            update.path_attributes,
+            vec![BgpPathAttribute::MpReachNlri {
+                family: BgpAddressFamily {
+                    afi: BgpAfi::Ipv6,
+                    safi: BgpSafi::Unicast,
+                },
+                next_hops: vec![
+                    IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                    IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)),
+                ],
+                nlri: BgpNlri::Prefixes(vec![BgpPrefix {
+                    path_id: None,
+                    address: IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0)),
+                    length: 48,
+                }]),
+            }]
+        );
+
+        let end_of_rib = [
+            0x00, 0x00, // no withdrawn routes
+            0x00, 0x06, // path attributes length
+            0x80, 0x0f, 0x03, 0x00, 0x02, 0x01, // MP_UNREACH_NLRI for IPv6 unicast
+        ];
+        let (_, message) =
+            parse_bgp_message(&framed(2, &end_of_rib), &BgpOptions::default()).unwrap();
+        assert!(matches!(message, BgpMessage::Update(update) if update.is_end_of_rib()));
+    }
+}
@@ -0,0 +1,232 @@
+//! Handles parsing of Cisco Discovery Protocol packets, which follow an LLC/SNAP header
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// CDP Packet Format
+//
+//   +--------+--------+-----------------+
+//   | Version|   TTL  |    Checksum     |
+//   +--------+--------+-----------------+
+//   |     Type (2)    |   Length (2)    |   repeated TLVs, the length including this header
+//   +-----------------+-----------------+
+//   |               Value               |
+//   +-----------------------------------+
+
+/// The SNAP OUI and protocol ID of CDP
+pub const CDP_SNAP_OUI: [u8; 3] = [0x00, 0x00, 0x0c];
+pub const CDP_SNAP_PROTOCOL_ID: u16 = 0x2000;
+
+pub const CDP_CAPABILITY_ROUTER: u32 = 0x01;
+pub const CDP_CAPABILITY_TRANSPARENT_BRIDGE: u32 = 0x02;
+pub const CDP_CAPABILITY_SOURCE_ROUTE_BRIDGE: u32 = 0x04;
+pub const CDP_CAPABILITY_SWITCH: u32 = 0x08;
+pub const CDP_CAPABILITY_HOST: u32 = 0x10;
+pub const CDP_CAPABILITY_IGMP: u32 = 0x20;
+pub const CDP_CAPABILITY_REPEATER: u32 = 0x40;
+
+const TLV_HEADER_LENGTH: u16 = 4;
+
+const TLV_DEVICE_ID: u16 = 0x0001;
+const TLV_ADDRESSES: u16 = 0x0002;
+const TLV_PORT_ID: u16 = 0x0003;
+const TLV_CAPABILITIES: u16 = 0x0004;
+const TLV_SOFTWARE_VERSION: u16 = 0x0005;
+const TLV_PLATFORM: u16 = 0x0006;
+const TLV_NATIVE_VLAN: u16 = 0x000a;
+const TLV_DUPLEX: u16 = 0x000b;
+const TLV_MANAGEMENT_ADDRESSES: u16 = 0x0016;
+
+const PROTOCOL_TYPE_NLPID: u8 = 1;
+const PROTOCOL_TYPE_802_2: u8 = 2;
+const NLPID_IPV4: &[u8] = &[0xcc];
+/// An LLC/SNAP header for EtherType IPv6
+const PROTOCOL_802_2_IPV6: &[u8] = &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x86, 0xdd];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CdpAddress {
+    Ip(IpAddr),
+    Other {
+        protocol_type: u8,
+        protocol: Vec<u8>,
+        address: Vec<u8>,
+    },
+}
+
+/// Strings are decoded lossily as UTF-8
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CdpTlv {
+    DeviceId(String),
+    Addresses(Vec<CdpAddress>),
+    PortId(String),
+    /// Bitmap of the `CDP_CAPABILITY_*` values
+    Capabilities(u32),
+    SoftwareVersion(String),
+    Platform(String),
+    NativeVlan(u16),
+    FullDuplex(bool),
+    ManagementAddresses(Vec<CdpAddress>),
+    Other {
+        tlv_type: u16,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CdpPacket {
+    pub version: u8,
+    /// Seconds the information stays valid
+    pub ttl: u8,
+    pub checksum: u16,
+    pub tlvs: Vec<CdpTlv>,
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+fn address(input: &[u8]) -> IResult<&[u8], CdpAddress> {
+    let (rest, protocol_type) = number::complete::be_u8(input)?;
+    let (rest, protocol_length) = number::complete::be_u8(rest)?;
+    let (rest, protocol) = bytes::complete::take(protocol_length)(rest)?;
+    let (rest, address_length) = number::complete::be_u16(rest)?;
+    let (rest, address) = bytes::complete::take(address_length)(rest)?;
+
+    let decoded = match (protocol_type, protocol, address.len()) {
+        (PROTOCOL_TYPE_NLPID, NLPID_IPV4, 4) => Some(IpAddr::V4(Ipv4Addr::new(
+            address[0], address[1], address[2], address[3],
+        ))),
+        (PROTOCOL_TYPE_802_2, PROTOCOL_802_2_IPV6, 16) => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(address);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    };
+    let address = match decoded {
+        Some(ip) => CdpAddress::Ip(ip),
+        None => CdpAddress::Other {
+            protocol_type,
+            protocol: protocol.to_vec(),
+            address: address.to_vec(),
+        },
+    };
+
+    Ok((rest, address))
+}
+
+fn addresses(value: &[u8]) -> IResult<&[u8], Vec<CdpAddress>> {
+    let (mut rest, count) = number::complete::be_u32(value)?;
+    let mut addresses = vec![];
+    for _ in 0..count {
+        let (next, address) = address(rest)?;
+        addresses.push(address);
+        rest = next;
+    }
+
+    Ok((rest, addresses))
+}
+
+fn tlv(input: &[u8]) -> IResult<&[u8], CdpTlv> {
+    let (rest, tlv_type) = number::streaming::be_u16(input)?;
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    if length < TLV_HEADER_LENGTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (rest, value) = bytes::streaming::take(length - TLV_HEADER_LENGTH)(rest)?;
+
+    let tlv = match tlv_type {
+        TLV_DEVICE_ID => CdpTlv::DeviceId(text(value)),
+        TLV_ADDRESSES => CdpTlv::Addresses(addresses(value)?.1),
+        TLV_PORT_ID => CdpTlv::PortId(text(value)),
+        TLV_CAPABILITIES => CdpTlv::Capabilities(number::complete::be_u32(value)?.1),
+        TLV_SOFTWARE_VERSION => CdpTlv::SoftwareVersion(text(value)),
+        TLV_PLATFORM => CdpTlv::Platform(text(value)),
+        TLV_NATIVE_VLAN => CdpTlv::NativeVlan(number::complete::be_u16(value)?.1),
+        TLV_DUPLEX => CdpTlv::FullDuplex(number::complete::be_u8(value)?.1 != 0),
+        TLV_MANAGEMENT_ADDRESSES => CdpTlv::ManagementAddresses(addresses(value)?.1),
+        tlv_type => CdpTlv::Other {
+            tlv_type,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((rest, tlv))
+}
+
+/// Parses a CDP packet, starting after the LLC/SNAP header. The input must end where the frame
+/// ends, as the TLVs are read until then.
+pub fn parse_cdp_packet(input: &[u8]) -> IResult<&[u8], CdpPacket> {
+    let (input, version) = number::streaming::be_u8(input)?;
+    let (input, ttl) = number::streaming::be_u8(input)?;
+    let (mut input, checksum) = number::streaming::be_u16(input)?;
+
+    let mut tlvs = vec![];
+    while !input.is_empty() {
+        let (rest, tlv) = tlv(input)?;
+        tlvs.push(tlv);
+        input = rest;
+    }
+
+    Ok((
+        input,
+        CdpPacket {
+            version,
+            ttl,
+            checksum,
+            tlvs,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llc::parse_llc_header;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn cdp_over_snap_works() {
+        let bytes = [
+            0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00, // LLC/SNAP
+            0x02, 0xb4, 0x12, 0x34, // version 2, TTL 180, checksum
+            0x00, 0x01, 0x00, 0x08, b's', b'w', b'-', b'1', // device ID
+            0x00, 0x02, 0x00, 0x11, // addresses
+            0x00, 0x00, 0x00, 0x01, // one address
+            0x01, 0x01, 0xcc, // NLPID IPv4
+            0x00, 0x04, 0xc0, 0x00, 0x02, 0x01, // 192.0.2.1
+            0x00, 0x03, 0x00, 0x0a, b'G', b'i', b'0', b'/', b'1', b'1', // port ID
+            0x00, 0x04, 0x00, 0x08, 0x00, 0x00, 0x00, 0x29, // router, switch, IGMP
+            0x00, 0x0a, 0x00, 0x06, 0x00, 0x64, // native VLAN 100
+        ];
+        let expectation = CdpPacket {
+            version: 2,
+            ttl: 180,
+            checksum: 0x1234,
+            tlvs: vec![
+                CdpTlv::DeviceId("sw-1".to_string()),
+                CdpTlv::Addresses(vec![CdpAddress::Ip(IpAddr::V4(Ipv4Addr::new(
+                    192, 0, 2, 1,
+                )))]),
+                CdpTlv::PortId("Gi0/11".to_string()),
+                CdpTlv::Capabilities(
+                    CDP_CAPABILITY_ROUTER | CDP_CAPABILITY_SWITCH | CDP_CAPABILITY_IGMP,
+                ),
+                CdpTlv::NativeVlan(100),
+            ],
+        };
+
+        let (rest, llc) = parse_llc_header(&bytes).unwrap();
+        let snap = llc.snap.unwrap();
+        assert_eq!(snap.oui, CDP_SNAP_OUI);
+        assert_eq!(snap.protocol_id, CDP_SNAP_PROTOCOL_ID);
+        assert_eq!(parse_cdp_packet(rest), Ok((EMPTY_SLICE, expectation)));
+    }
+}
@@ -0,0 +1,456 @@
+//! Handles parsing of DHCP (and BOOTP) messages
+
+use crate::arp::HardwareAddressType;
+use crate::ethernet::{self, MacAddress};
+use crate::ipv4;
+use nom::bytes;
+use nom::error::Error;
+use nom::number;
+use nom::IResult;
+use std::net::Ipv4Addr;
+
+// DHCP Message Format (RFC 2131)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +---------------+---------------+---------------+---------------+
+//   |     op (1)    |   htype (1)   |   hlen (1)    |   hops (1)    |
+//   +---------------+---------------+---------------+---------------+
+//   |                            xid (4)                            |
+//   +-------------------------------+-------------------------------+
+//   |           secs (2)            |           flags (2)           |
+//   +-------------------------------+-------------------------------+
+//   |                          ciaddr  (4)                          |
+//   +---------------------------------------------------------------+
+//   |                          yiaddr  (4)                          |
+//   +---------------------------------------------------------------+
+//   |                          siaddr  (4)                          |
+//   +---------------------------------------------------------------+
+//   |                          giaddr  (4)                          |
+//   +---------------------------------------------------------------+
+//   |                          chaddr  (16)                         |
+//   +---------------------------------------------------------------+
+//   |                          sname   (64)                         |
+//   +---------------------------------------------------------------+
+//   |                          file    (128)                        |
+//   +---------------------------------------------------------------+
+//   |                          options (variable)                   |
+//   +---------------------------------------------------------------+
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+const MAGIC_COOKIE: u32 = 0x6382_5363;
+const FLAG_BROADCAST: u16 = 0x8000;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DOMAIN_NAME_SERVER: u8 = 6;
+const OPTION_HOST_NAME: u8 = 12;
+const OPTION_REQUESTED_IP_ADDRESS: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_OVERLOAD: u8 = 52;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_IDENTIFIER: u8 = 54;
+const OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPTION_CLIENT_IDENTIFIER: u8 = 61;
+const OPTION_RELAY_AGENT_INFORMATION: u8 = 82;
+const OPTION_END: u8 = 255;
+
+const OVERLOAD_FILE: u8 = 1;
+const OVERLOAD_SNAME: u8 = 2;
+
+const RELAY_AGENT_CIRCUIT_ID: u8 = 1;
+const RELAY_AGENT_REMOTE_ID: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DhcpOperation {
+    BootRequest,
+    BootReply,
+    Other(u8),
+}
+
+impl From<u8> for DhcpOperation {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::BootRequest,
+            2 => Self::BootReply,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DhcpMessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Other(u8),
+}
+
+impl From<u8> for DhcpMessageType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Discover,
+            2 => Self::Offer,
+            3 => Self::Request,
+            4 => Self::Decline,
+            5 => Self::Ack,
+            6 => Self::Nak,
+            7 => Self::Release,
+            8 => Self::Inform,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClientHardwareAddress {
+    Ethernet(MacAddress),
+    /// The first `hlen` bytes of `chaddr`, for other hardware types
+    Other(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelayAgentSubOption {
+    CircuitId(Vec<u8>),
+    RemoteId(Vec<u8>),
+    Other { code: u8, data: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DhcpOption {
+    SubnetMask(Ipv4Addr),
+    Router(Vec<Ipv4Addr>),
+    DomainNameServer(Vec<Ipv4Addr>),
+    HostName(String),
+    RequestedIpAddress(Ipv4Addr),
+    /// Lease time in seconds
+    LeaseTime(u32),
+    Overload(u8),
+    MessageType(DhcpMessageType),
+    ServerIdentifier(Ipv4Addr),
+    ParameterRequestList(Vec<u8>),
+    ClientIdentifier {
+        hw_type: u8,
+        id: Vec<u8>,
+    },
+    RelayAgentInformation(Vec<RelayAgentSubOption>),
+    Other {
+        code: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DhcpMessage {
+    pub operation: DhcpOperation,
+    pub hw_addr_type: HardwareAddressType,
+    pub hw_addr_size: u8,
+    pub hops: u8,
+    pub transaction_id: u32,
+    pub seconds: u16,
+    pub flag_broadcast: bool,
+    pub client_addr: Ipv4Addr,
+    pub your_addr: Ipv4Addr,
+    pub server_addr: Ipv4Addr,
+    pub relay_addr: Ipv4Addr,
+    pub client_hw_addr: ClientHardwareAddress,
+    /// `None` when the field is overloaded with options
+    pub server_name: Option<String>,
+    /// `None` when the field is overloaded with options
+    pub boot_file: Option<String>,
+    /// Options from the options field, then from `file` and `sname` when overloaded
+    pub options: Vec<DhcpOption>,
+}
+
+fn addresses(data: &[u8]) -> Vec<Ipv4Addr> {
+    data.chunks_exact(4)
+        .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+        .collect()
+}
+
+/// Reads a NUL-terminated string from a fixed-size field
+fn fixed_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn relay_agent_sub_options(mut input: &[u8]) -> IResult<&[u8], Vec<RelayAgentSubOption>> {
+    let mut sub_options = vec![];
+    while !input.is_empty() {
+        let (rest, code) = number::complete::be_u8(input)?;
+        let (rest, length) = number::complete::be_u8(rest)?;
+        let (rest, data) = bytes::complete::take(length)(rest)?;
+        sub_options.push(match code {
+            RELAY_AGENT_CIRCUIT_ID => RelayAgentSubOption::CircuitId(data.to_vec()),
+            RELAY_AGENT_REMOTE_ID => RelayAgentSubOption::RemoteId(data.to_vec()),
+            _ => RelayAgentSubOption::Other {
+                code,
+                data: data.to_vec(),
+            },
+        });
+        input = rest;
+    }
+
+    Ok((input, sub_options))
+}
+
+fn option_value(code: u8, data: &[u8]) -> IResult<&[u8], DhcpOption> {
+    let option = match (code, data.len()) {
+        (OPTION_SUBNET_MASK, 4) => DhcpOption::SubnetMask(ipv4::address(data)?.1),
+        (OPTION_ROUTER, _) => DhcpOption::Router(addresses(data)),
+        (OPTION_DOMAIN_NAME_SERVER, _) => DhcpOption::DomainNameServer(addresses(data)),
+        (OPTION_HOST_NAME, _) => DhcpOption::HostName(String::from_utf8_lossy(data).into_owned()),
+        (OPTION_REQUESTED_IP_ADDRESS, 4) => DhcpOption::RequestedIpAddress(ipv4::address(data)?.1),
+        (OPTION_LEASE_TIME, 4) => DhcpOption::LeaseTime(number::complete::be_u32(data)?.1),
+        (OPTION_OVERLOAD, 1) => DhcpOption::Overload(data[0]),
+        (OPTION_MESSAGE_TYPE, 1) => DhcpOption::MessageType(data[0].into()),
+        (OPTION_SERVER_IDENTIFIER, 4) => DhcpOption::ServerIdentifier(ipv4::address(data)?.1),
+        (OPTION_PARAMETER_REQUEST_LIST, _) => DhcpOption::ParameterRequestList(data.to_vec()),
+        (OPTION_CLIENT_IDENTIFIER, n) if n >= 1 => DhcpOption::ClientIdentifier {
+            hw_type: data[0],
+            id: data[1..].to_vec(),
+        },
+        (OPTION_RELAY_AGENT_INFORMATION, _) => {
+            DhcpOption::RelayAgentInformation(relay_agent_sub_options(data)?.1)
+        }
+        _ => DhcpOption::Other {
+            code,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok((&data[data.len()..], option))
+}
+
+/// Parses options up to the End option or the end of `input`, skipping Pad options
+fn options(mut input: &[u8]) -> IResult<&[u8], Vec<DhcpOption>> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, code) = number::complete::be_u8(input)?;
+        match code {
+            OPTION_PAD => input = rest,
+            OPTION_END => return Ok((rest, options)),
+            _ => {
+                let (rest, length) = number::complete::be_u8(rest)?;
+                let (rest, data) = bytes::complete::take(length)(rest)?;
+                let (_, option) = option_value(code, data)?;
+                options.push(option);
+                input = rest;
+            }
+        }
+    }
+
+    Ok((input, options))
+}
+
+fn client_hw_addr(
+    hw_addr_type: HardwareAddressType,
+    hw_addr_size: u8,
+    chaddr: &[u8],
+) -> IResult<&[u8], ClientHardwareAddress> {
+    if hw_addr_type == HardwareAddressType::Ethernet && hw_addr_size == 6 {
+        let (_, mac) = ethernet::mac_address(chaddr)?;
+        return Ok((
+            &chaddr[chaddr.len()..],
+            ClientHardwareAddress::Ethernet(mac),
+        ));
+    }
+
+    let length = usize::from(hw_addr_size).min(chaddr.len());
+    Ok((
+        &chaddr[chaddr.len()..],
+        ClientHardwareAddress::Other(chaddr[..length].to_vec()),
+    ))
+}
+
+/// Parses a DHCP message from a UDP payload. The remaining input is whatever follows the End
+/// option, usually padding. A BOOTP message without the magic cookie has no options, and the
+/// remaining input is its vendor area.
+pub fn parse_dhcp_message(input: &[u8]) -> IResult<&[u8], DhcpMessage> {
+    let (input, operation) = number::streaming::be_u8(input)?;
+    let (input, hw_addr_type) = number::streaming::be_u8(input)?;
+    let (input, hw_addr_size) = number::streaming::be_u8(input)?;
+    let (input, hops) = number::streaming::be_u8(input)?;
+    let (input, transaction_id) = number::streaming::be_u32(input)?;
+    let (input, seconds) = number::streaming::be_u16(input)?;
+    let (input, flags) = number::streaming::be_u16(input)?;
+    let (input, client_addr) = ipv4::address(input)?;
+    let (input, your_addr) = ipv4::address(input)?;
+    let (input, server_addr) = ipv4::address(input)?;
+    let (input, relay_addr) = ipv4::address(input)?;
+    let (input, chaddr) = bytes::streaming::take(16u8)(input)?;
+    let (input, sname) = bytes::streaming::take(64u8)(input)?;
+    let (input, file) = bytes::streaming::take(128u8)(input)?;
+    let (input, mut options) = match number::complete::be_u32::<_, Error<&[u8]>>(input) {
+        Ok((rest, MAGIC_COOKIE)) => options(rest)?,
+        _ => (input, vec![]),
+    };
+
+    let hw_addr_type = HardwareAddressType::from(u16::from(hw_addr_type));
+    let (_, client_hw_addr) = client_hw_addr(hw_addr_type, hw_addr_size, chaddr)?;
+
+    // The file field is read before sname when both are overloaded (RFC 2131 section 4.1)
+    let overload = options.iter().find_map(|option| match option {
+        DhcpOption::Overload(overload) => Some(*overload),
+        _ => None,
+    });
+    let overload = overload.unwrap_or(0);
+    let boot_file = if overload & OVERLOAD_FILE != 0 {
+        options.extend(self::options(file)?.1);
+        None
+    } else {
+        Some(fixed_string(file))
+    };
+    let server_name = if overload & OVERLOAD_SNAME != 0 {
+        options.extend(self::options(sname)?.1);
+        None
+    } else {
+        Some(fixed_string(sname))
+    };
+
+    Ok((
+        input,
+        DhcpMessage {
+            operation: operation.into(),
+            hw_addr_type,
+            hw_addr_size,
+            hops,
+            transaction_id,
+            seconds,
+            flag_broadcast: flags & FLAG_BROADCAST != 0,
+            client_addr,
+            your_addr,
+            server_addr,
+            relay_addr,
+            client_hw_addr,
+            server_name,
+            boot_file,
+            options,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::ErrorKind;
+    use nom::Err;
+
+    fn message_bytes(options: &[u8], sname: &[u8], file: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![
+            0x01, 0x01, 0x06, 0x00, /* op, htype, hlen, hops */
+            0x39, 0x03, 0xf3, 0x26, /* xid */
+            0x00, 0x00, 0x80, 0x00, /* secs, flags */
+            0x00, 0x00, 0x00, 0x00, /* ciaddr */
+            0x00, 0x00, 0x00, 0x00, /* yiaddr */
+            0x00, 0x00, 0x00, 0x00, /* siaddr */
+            0xc0, 0xa8, 0x01, 0x01, /* giaddr */
+            0x00, 0x05, 0x3c, 0x04, 0x8d, 0x59, /* chaddr */
+        ];
+        bytes.resize(44, 0);
+        bytes.extend_from_slice(sname);
+        bytes.resize(108, 0);
+        bytes.extend_from_slice(file);
+        bytes.resize(236, 0);
+        bytes.extend_from_slice(&[0x63, 0x82, 0x53, 0x63]);
+        bytes.extend_from_slice(options);
+        bytes
+    }
+
+    #[test]
+    fn bootp_and_truncated_options_work() {
+        let mut bytes = message_bytes(&[], b"server", b"");
+        bytes.truncate(236);
+        bytes.extend_from_slice(&[0x00; 64]); // vendor area without the magic cookie
+        let (remaining, message) = parse_dhcp_message(&bytes).unwrap();
+        assert_eq!(remaining, &[0x00; 64][..]);
+        assert_eq!(message.server_name, Some("server".to_string()));
+        assert_eq!(message.options, vec![]);
+
+        let bytes = message_bytes(&[0x35, 0x01, 0x01, 0x0c, 0x08, b'h', b'o'], b"", b"");
+        assert!(matches!(
+            parse_dhcp_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dhcp_discover_works() {
+        let options = [
+            0x35, 0x01, 0x01, /* message type */
+            0x32, 0x04, 0xc0, 0xa8, 0x01, 0x64, /* requested IP */
+            0x37, 0x03, 0x01, 0x03, 0x06, /* parameter request list */
+            0x52, 0x08, 0x01, 0x02, 0x00, 0x07, /* relay agent: circuit ID */
+            0x02, 0x02, 0xab, 0xcd, /* relay agent: remote ID */
+            0xff, 0x00, 0x00, /* end, padding */
+        ];
+        let bytes = message_bytes(&options, b"", b"pxelinux.0");
+
+        let (remaining, message) = parse_dhcp_message(&bytes).unwrap();
+        assert_eq!(remaining, &[0x00, 0x00]);
+        assert_eq!(message.operation, DhcpOperation::BootRequest);
+        assert_eq!(message.hw_addr_type, HardwareAddressType::Ethernet);
+        assert!(message.flag_broadcast);
+        assert_eq!(message.relay_addr, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(
+            message.client_hw_addr,
+            ClientHardwareAddress::Ethernet(MacAddress([0x00, 0x05, 0x3c, 0x04, 0x8d, 0x59]))
+        );
+        assert_eq!(message.server_name, Some(String::new()));
+        assert_eq!(message.boot_file, Some("pxelinux.0".to_string()));
+        assert_eq!(
+            message.options,
+            vec![
+                DhcpOption::MessageType(DhcpMessageType::Discover),
+                DhcpOption::RequestedIpAddress(Ipv4Addr::new(192, 168, 1, 100)),
+                DhcpOption::ParameterRequestList(vec![1, 3, 6]),
+                DhcpOption::RelayAgentInformation(vec![
+                    RelayAgentSubOption::CircuitId(vec![0x00, 0x07]),
+                    RelayAgentSubOption::RemoteId(vec![0xab, 0xcd]),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn dhcp_overloaded_fields_work() {
+        let options = [
+            0x35, 0x01, 0x05, /* message type */
+            0x34, 0x01, 0x03, /* overload both fields */
+            0xff,
+        ];
+        let file = [0x33, 0x04, 0x00, 0x00, 0x0e, 0x10, 0xff]; // lease time
+        let sname = [0x0c, 0x04, b'h', b'o', b's', b't', 0xff]; // host name
+        let bytes = message_bytes(&options, &sname, &file);
+
+        let (_, message) = parse_dhcp_message(&bytes).unwrap();
+        assert_eq!(message.server_name, None);
+        assert_eq!(message.boot_file, None);
+        assert_eq!(
+            message.options,
+            vec![
+                DhcpOption::MessageType(DhcpMessageType::Ack),
+                DhcpOption::Overload(3),
+                DhcpOption::LeaseTime(3600),
+                DhcpOption::HostName("host".to_string()),
+            ]
+        );
+    }
+}
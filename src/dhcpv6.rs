@@ -0,0 +1,555 @@
+//! Handles parsing of DHCPv6 messages, including relay messages
+
+use crate::arp::HardwareAddressType;
+use crate::dns;
+use crate::ipv6;
+use nom::bytes;
+use nom::combinator;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::convert::TryFrom;
+use std::net::Ipv6Addr;
+
+// DHCPv6 Client/Server Message Format (RFC 8415)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |    msg-type   |               transaction-id                  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   .                            options                            .
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// DHCPv6 Relay Message Format (RFC 8415)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |    msg-type   |   hop-count   |                               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+//   |                         link-address (16)                     |
+//   |                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                               |                               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+//   |                         peer-address (16)                     |
+//   |                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                               |                               .
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               .
+//   .            options (variable number and length)   ....        .
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const DHCPV6_CLIENT_PORT: u16 = 546;
+pub const DHCPV6_SERVER_PORT: u16 = 547;
+
+/// Relay messages and encapsulated options nested deeper than this are rejected with
+/// `ErrorKind::TooLarge`
+pub const MAX_OPTION_DEPTH: usize = 32;
+
+const MSG_RELAY_FORW: u8 = 12;
+const MSG_RELAY_REPL: u8 = 13;
+
+const OPTION_CLIENTID: u16 = 1;
+const OPTION_SERVERID: u16 = 2;
+const OPTION_IA_NA: u16 = 3;
+const OPTION_IAADDR: u16 = 5;
+const OPTION_ORO: u16 = 6;
+const OPTION_ELAPSED_TIME: u16 = 8;
+const OPTION_RELAY_MSG: u16 = 9;
+const OPTION_STATUS_CODE: u16 = 13;
+const OPTION_INTERFACE_ID: u16 = 18;
+const OPTION_DNS_SERVERS: u16 = 23;
+const OPTION_DOMAIN_LIST: u16 = 24;
+const OPTION_IA_PD: u16 = 25;
+const OPTION_IAPREFIX: u16 = 26;
+
+const DUID_LLT: u16 = 1;
+const DUID_EN: u16 = 2;
+const DUID_LL: u16 = 3;
+const DUID_UUID: u16 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dhcpv6MessageType {
+    Solicit,
+    Advertise,
+    Request,
+    Confirm,
+    Renew,
+    Rebind,
+    Reply,
+    Release,
+    Decline,
+    Reconfigure,
+    InformationRequest,
+    RelayForw,
+    RelayRepl,
+    Other(u8),
+}
+
+impl From<u8> for Dhcpv6MessageType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Solicit,
+            2 => Self::Advertise,
+            3 => Self::Request,
+            4 => Self::Confirm,
+            5 => Self::Renew,
+            6 => Self::Rebind,
+            7 => Self::Reply,
+            8 => Self::Release,
+            9 => Self::Decline,
+            10 => Self::Reconfigure,
+            11 => Self::InformationRequest,
+            MSG_RELAY_FORW => Self::RelayForw,
+            MSG_RELAY_REPL => Self::RelayRepl,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// DHCP Unique Identifier (RFC 8415 section 11)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Duid {
+    LinkLayerTime {
+        hw_type: HardwareAddressType,
+        /// Seconds since midnight (UTC), January 1, 2000, modulo 2^32
+        time: u32,
+        link_layer_address: Vec<u8>,
+    },
+    Enterprise {
+        enterprise_number: u32,
+        identifier: Vec<u8>,
+    },
+    LinkLayer {
+        hw_type: HardwareAddressType,
+        link_layer_address: Vec<u8>,
+    },
+    Uuid([u8; 16]),
+    Other {
+        duid_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+/// IA_NA or IA_PD
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentityAssociation {
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub options: Vec<Dhcpv6Option>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IaAddress {
+    pub address: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub options: Vec<Dhcpv6Option>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IaPrefix {
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub prefix_length: u8,
+    pub prefix: Ipv6Addr,
+    pub options: Vec<Dhcpv6Option>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dhcpv6Option {
+    ClientId(Duid),
+    ServerId(Duid),
+    IaNa(IdentityAssociation),
+    IaAddr(IaAddress),
+    OptionRequest(Vec<u16>),
+    /// Time since the client began the exchange, in hundredths of a second
+    ElapsedTime(u16),
+    RelayMessage(Box<Dhcpv6Message>),
+    StatusCode {
+        code: u16,
+        message: String,
+    },
+    InterfaceId(Vec<u8>),
+    DnsServers(Vec<Ipv6Addr>),
+    DomainList(Vec<String>),
+    IaPd(IdentityAssociation),
+    IaPrefix(IaPrefix),
+    Other {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dhcpv6Message {
+    Client {
+        message_type: Dhcpv6MessageType,
+        /// 24-bit transaction ID
+        transaction_id: u32,
+        options: Vec<Dhcpv6Option>,
+    },
+    Relay {
+        message_type: Dhcpv6MessageType,
+        hop_count: u8,
+        link_address: Ipv6Addr,
+        peer_address: Ipv6Addr,
+        options: Vec<Dhcpv6Option>,
+    },
+}
+
+fn duid(input: &[u8]) -> IResult<&[u8], Duid> {
+    let (input, duid_type) = number::complete::be_u16(input)?;
+    let rest = &input[input.len()..];
+
+    let duid = match duid_type {
+        DUID_LLT => {
+            let (input, hw_type) = number::complete::be_u16(input)?;
+            let (input, time) = number::complete::be_u32(input)?;
+            Duid::LinkLayerTime {
+                hw_type: hw_type.into(),
+                time,
+                link_layer_address: input.to_vec(),
+            }
+        }
+        DUID_EN => {
+            let (input, enterprise_number) = number::complete::be_u32(input)?;
+            Duid::Enterprise {
+                enterprise_number,
+                identifier: input.to_vec(),
+            }
+        }
+        DUID_LL => {
+            let (input, hw_type) = number::complete::be_u16(input)?;
+            Duid::LinkLayer {
+                hw_type: hw_type.into(),
+                link_layer_address: input.to_vec(),
+            }
+        }
+        DUID_UUID if input.len() == 16 => Duid::Uuid(<[u8; 16]>::try_from(input).unwrap()),
+        _ => Duid::Other {
+            duid_type,
+            data: input.to_vec(),
+        },
+    };
+
+    Ok((rest, duid))
+}
+
+fn identity_association(input: &[u8], depth: usize) -> IResult<&[u8], IdentityAssociation> {
+    let (input, iaid) = number::complete::be_u32(input)?;
+    let (input, t1) = number::complete::be_u32(input)?;
+    let (input, t2) = number::complete::be_u32(input)?;
+    let (input, options) = options(input, depth)?;
+
+    Ok((
+        input,
+        IdentityAssociation {
+            iaid,
+            t1,
+            t2,
+            options,
+        },
+    ))
+}
+
+fn ia_address(input: &[u8], depth: usize) -> IResult<&[u8], IaAddress> {
+    let (input, address) = combinator::complete(ipv6::address)(input)?;
+    let (input, preferred_lifetime) = number::complete::be_u32(input)?;
+    let (input, valid_lifetime) = number::complete::be_u32(input)?;
+    let (input, options) = options(input, depth)?;
+
+    Ok((
+        input,
+        IaAddress {
+            address,
+            preferred_lifetime,
+            valid_lifetime,
+            options,
+        },
+    ))
+}
+
+fn ia_prefix(input: &[u8], depth: usize) -> IResult<&[u8], IaPrefix> {
+    let (input, preferred_lifetime) = number::complete::be_u32(input)?;
+    let (input, valid_lifetime) = number::complete::be_u32(input)?;
+    let (input, prefix_length) = number::complete::be_u8(input)?;
+    let (input, prefix) = combinator::complete(ipv6::address)(input)?;
+    let (input, options) = options(input, depth)?;
+
+    Ok((
+        input,
+        IaPrefix {
+            preferred_lifetime,
+            valid_lifetime,
+            prefix_length,
+            prefix,
+            options,
+        },
+    ))
+}
+
+fn option_value(code: u16, data: &[u8], depth: usize) -> IResult<&[u8], Dhcpv6Option> {
+    let option = match code {
+        OPTION_CLIENTID => Dhcpv6Option::ClientId(duid(data)?.1),
+        OPTION_SERVERID => Dhcpv6Option::ServerId(duid(data)?.1),
+        OPTION_IA_NA => Dhcpv6Option::IaNa(identity_association(data, depth + 1)?.1),
+        OPTION_IAADDR => Dhcpv6Option::IaAddr(ia_address(data, depth + 1)?.1),
+        OPTION_ORO => Dhcpv6Option::OptionRequest(
+            data.chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        OPTION_ELAPSED_TIME => Dhcpv6Option::ElapsedTime(number::complete::be_u16(data)?.1),
+        OPTION_RELAY_MSG => {
+            let (_, message) = combinator::complete(|data| message(data, depth + 1))(data)?;
+            Dhcpv6Option::RelayMessage(Box::new(message))
+        }
+        OPTION_STATUS_CODE => {
+            let (message, code) = number::complete::be_u16(data)?;
+            Dhcpv6Option::StatusCode {
+                code,
+                message: String::from_utf8_lossy(message).into_owned(),
+            }
+        }
+        OPTION_INTERFACE_ID => Dhcpv6Option::InterfaceId(data.to_vec()),
+        OPTION_DNS_SERVERS => {
+            let mut servers = vec![];
+            let mut rest = data;
+            while !rest.is_empty() {
+                let (r, server) = combinator::complete(ipv6::address)(rest)?;
+                servers.push(server);
+                rest = r;
+            }
+            Dhcpv6Option::DnsServers(servers)
+        }
+        OPTION_DOMAIN_LIST => {
+            // Names are encoded as in DNS, without compression
+            let mut domains = vec![];
+            let mut rest = data;
+            while !rest.is_empty() {
+                let (r, domain) = combinator::complete(|r| dns::name(r, r, 0))(rest)?;
+                domains.push(domain);
+                rest = r;
+            }
+            Dhcpv6Option::DomainList(domains)
+        }
+        OPTION_IA_PD => Dhcpv6Option::IaPd(identity_association(data, depth + 1)?.1),
+        OPTION_IAPREFIX => Dhcpv6Option::IaPrefix(ia_prefix(data, depth + 1)?.1),
+        _ => Dhcpv6Option::Other {
+            code,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok((&data[data.len()..], option))
+}
+
+fn options(mut input: &[u8], depth: usize) -> IResult<&[u8], Vec<Dhcpv6Option>> {
+    if depth > MAX_OPTION_DEPTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::TooLarge)));
+    }
+
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, code) = number::complete::be_u16(input)?;
+        let (rest, length) = number::complete::be_u16(rest)?;
+        let (rest, data) = bytes::complete::take(length)(rest)?;
+        let (_, option) = option_value(code, data, depth)?;
+        options.push(option);
+        input = rest;
+    }
+
+    Ok((input, options))
+}
+
+fn message(input: &[u8], depth: usize) -> IResult<&[u8], Dhcpv6Message> {
+    let (input, message_type) = number::streaming::be_u8(input)?;
+    if message_type == MSG_RELAY_FORW || message_type == MSG_RELAY_REPL {
+        let (input, hop_count) = number::streaming::be_u8(input)?;
+        let (input, link_address) = ipv6::address(input)?;
+        let (input, peer_address) = ipv6::address(input)?;
+        let (input, options) = options(input, depth)?;
+        return Ok((
+            input,
+            Dhcpv6Message::Relay {
+                message_type: message_type.into(),
+                hop_count,
+                link_address,
+                peer_address,
+                options,
+            },
+        ));
+    }
+
+    let (input, transaction_id) = bytes::streaming::take(3u8)(input)?;
+    let (input, options) = options(input, depth)?;
+
+    Ok((
+        input,
+        Dhcpv6Message::Client {
+            message_type: message_type.into(),
+            transaction_id: u32::from_be_bytes([
+                0,
+                transaction_id[0],
+                transaction_id[1],
+                transaction_id[2],
+            ]),
+            options,
+        },
+    ))
+}
+
+/// Parses a DHCPv6 message from a UDP payload; options run to the end of `input`. Relay
+/// messages carry the relayed message in a `Dhcpv6Option::RelayMessage`. Relayed messages and
+/// encapsulated options are nested at most `MAX_OPTION_DEPTH` times.
+pub fn parse_dhcpv6_message(input: &[u8]) -> IResult<&[u8], Dhcpv6Message> {
+    message(input, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn dhcpv6_solicit_works() {
+        let bytes = [
+            0x01, 0x10, 0x08, 0x74, /* Solicit, transaction ID */
+            0x00, 0x01, 0x00, 0x0e, /* client ID */
+            0x00, 0x01, 0x00, 0x01, /* DUID-LLT, Ethernet */
+            0x1c, 0x39, 0xcf, 0x88, /* time */
+            0x08, 0x00, 0x27, 0xfe, 0x8f, 0x95, /* link-layer address */
+            0x00, 0x19, 0x00, 0x29, /* IA_PD */
+            0x27, 0xfe, 0x8f, 0x95, /* IAID */
+            0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x15, 0x18, /* T1, T2 */
+            0x00, 0x1a, 0x00, 0x19, /* IA prefix */
+            0x00, 0x00, 0x1c, 0x20, 0x00, 0x00, 0x1d, 0x4c, /* lifetimes */
+            0x38, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, /* prefix length, prefix */
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, /* prefix */
+            0x00, 0x18, 0x00, 0x0d, /* domain list */
+            0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00,
+        ];
+        let expectation = Dhcpv6Message::Client {
+            message_type: Dhcpv6MessageType::Solicit,
+            transaction_id: 0x100874,
+            options: vec![
+                Dhcpv6Option::ClientId(Duid::LinkLayerTime {
+                    hw_type: HardwareAddressType::Ethernet,
+                    time: 0x1c39cf88,
+                    link_layer_address: vec![0x08, 0x00, 0x27, 0xfe, 0x8f, 0x95],
+                }),
+                Dhcpv6Option::IaPd(IdentityAssociation {
+                    iaid: 0x27fe8f95,
+                    t1: 3600,
+                    t2: 5400,
+                    options: vec![Dhcpv6Option::IaPrefix(IaPrefix {
+                        preferred_lifetime: 7200,
+                        valid_lifetime: 7500,
+                        prefix_length: 56,
+                        prefix: Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 0),
+                        options: vec![],
+                    })],
+                }),
+                Dhcpv6Option::DomainList(vec!["example.com".to_string()]),
+            ],
+        };
+        assert_eq!(parse_dhcpv6_message(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn dhcpv6_relay_forward_works() {
+        let mut bytes = vec![0x0c, 0x00]; // Relay-forward, hop count
+        bytes.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        bytes.extend_from_slice(&[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        bytes.extend_from_slice(&[0x00, 0x09, 0x00, 0x0a]); // relay message
+        bytes.extend_from_slice(&[0x0b, 0x00, 0x00, 0x01]); // Information-request
+        bytes.extend_from_slice(&[0x00, 0x08, 0x00, 0x02, 0x00, 0x00]); // elapsed time
+
+        let (remaining, message) = parse_dhcpv6_message(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        match message {
+            Dhcpv6Message::Relay {
+                message_type,
+                peer_address,
+                options,
+                ..
+            } => {
+                assert_eq!(message_type, Dhcpv6MessageType::RelayForw);
+                assert_eq!(peer_address, Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2));
+                assert_eq!(
+                    options,
+                    vec![Dhcpv6Option::RelayMessage(Box::new(
+                        Dhcpv6Message::Client {
+                            message_type: Dhcpv6MessageType::InformationRequest,
+                            transaction_id: 1,
+                            options: vec![Dhcpv6Option::ElapsedTime(0)],
+                        }
+                    ))]
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn dhcpv6_deeply_nested_options_fail() {
+        // IA_NA options, each wrapping the next
+        let mut option = vec![];
+        for _ in 0..=MAX_OPTION_DEPTH {
+            let length = (option.len() + 12) as u16;
+            let mut outer = vec![0x00, 0x03, (length >> 8) as u8, length as u8];
+            outer.extend_from_slice(&[0x00; 12]); // IAID, T1, T2
+            outer.extend(option);
+            option = outer;
+        }
+        let mut bytes = vec![0x01, 0x10, 0x08, 0x74]; // Solicit, transaction ID
+        bytes.extend(option);
+
+        assert!(matches!(
+            parse_dhcpv6_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::TooLarge,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dhcpv6_truncated_options_fail() {
+        let bytes = [
+            0x01, 0x10, 0x08, 0x74, /* Solicit, transaction ID */
+            0x00, 0x03, 0x00, 0x08, /* IA_NA, too short for T1 and T2 */
+            0x27, 0xfe, 0x8f, 0x95, 0x00, 0x00, 0x0e, 0x10, /* IAID, T1 */
+        ];
+        assert!(matches!(
+            parse_dhcpv6_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+
+        let bytes = [
+            0x01, 0x10, 0x08, 0x74, /* Solicit, transaction ID */
+            0x00, 0x01, 0x00, 0x0e, /* client ID running past the message */
+            0x00, 0x01, 0x00, 0x01, /* DUID-LLT, Ethernet */
+        ];
+        assert!(matches!(
+            parse_dhcpv6_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
+}
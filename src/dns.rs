@@ -0,0 +1,940 @@
+//! Handles parsing of DNS messages, over UDP or TCP
+
+use crate::ipv4;
+use crate::ipv6;
+use nom::bytes;
+use nom::combinator;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// DNS Header Format (RFC 1035)
+//
+//    0  1  2  3  4  5  6  7  8  9 10 11 12 13 14 15
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |                      ID                       |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |QR|   Opcode  |AA|TC|RD|RA| Z|AD|CD|   RCODE   |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |                    QDCOUNT                    |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |                    ANCOUNT                    |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |                    NSCOUNT                    |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//   |                    ARCOUNT                    |
+//   +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+
+pub const DNS_PORT: u16 = 53;
+
+const MAX_NAME_LENGTH: usize = 255;
+const LABEL_POINTER: u8 = 0xc0;
+
+const EDNS_NSID: u16 = 3;
+const EDNS_CLIENT_SUBNET: u16 = 8;
+const EDNS_COOKIE: u16 = 10;
+const EDNS_PADDING: u16 = 12;
+
+const SVC_MANDATORY: u16 = 0;
+const SVC_ALPN: u16 = 1;
+const SVC_NO_DEFAULT_ALPN: u16 = 2;
+const SVC_PORT: u16 = 3;
+const SVC_IPV4_HINT: u16 = 4;
+const SVC_ECH: u16 = 5;
+const SVC_IPV6_HINT: u16 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnsOpcode {
+    Query,
+    IQuery,
+    Status,
+    Notify,
+    Update,
+    Dso,
+    Other(u8),
+}
+
+impl From<u8> for DnsOpcode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::Query,
+            1 => Self::IQuery,
+            2 => Self::Status,
+            4 => Self::Notify,
+            5 => Self::Update,
+            6 => Self::Dso,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnsRcode {
+    NoError,
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    Other(u8),
+}
+
+impl From<u8> for DnsRcode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::NoError,
+            1 => Self::FormErr,
+            2 => Self::ServFail,
+            3 => Self::NXDomain,
+            4 => Self::NotImp,
+            5 => Self::Refused,
+            6 => Self::YXDomain,
+            7 => Self::YXRRSet,
+            8 => Self::NXRRSet,
+            9 => Self::NotAuth,
+            10 => Self::NotZone,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnsType {
+    A,
+    NS,
+    CNAME,
+    SOA,
+    PTR,
+    MX,
+    TXT,
+    AAAA,
+    SRV,
+    OPT,
+    SVCB,
+    HTTPS,
+    ANY,
+    CAA,
+    Other(u16),
+}
+
+impl From<u16> for DnsType {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => Self::A,
+            2 => Self::NS,
+            5 => Self::CNAME,
+            6 => Self::SOA,
+            12 => Self::PTR,
+            15 => Self::MX,
+            16 => Self::TXT,
+            28 => Self::AAAA,
+            33 => Self::SRV,
+            41 => Self::OPT,
+            64 => Self::SVCB,
+            65 => Self::HTTPS,
+            255 => Self::ANY,
+            257 => Self::CAA,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnsClass {
+    IN,
+    CH,
+    HS,
+    NONE,
+    ANY,
+    Other(u16),
+}
+
+impl From<u16> for DnsClass {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => Self::IN,
+            3 => Self::CH,
+            4 => Self::HS,
+            254 => Self::NONE,
+            255 => Self::ANY,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DnsHeader {
+    pub id: u16,
+    pub flag_response: bool,
+    pub opcode: DnsOpcode,
+    pub flag_authoritative: bool,
+    pub flag_truncated: bool,
+    pub flag_recursion_desired: bool,
+    pub flag_recursion_available: bool,
+    pub flag_authentic_data: bool,
+    pub flag_checking_disabled: bool,
+    pub rcode: DnsRcode,
+    pub question_count: u16,
+    pub answer_count: u16,
+    pub authority_count: u16,
+    pub additional_count: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: DnsType,
+    pub qclass: DnsClass,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdnsOption {
+    Nsid(Vec<u8>),
+    ClientSubnet {
+        family: u16,
+        source_prefix_length: u8,
+        scope_prefix_length: u8,
+        address: Vec<u8>,
+    },
+    Cookie {
+        client: Vec<u8>,
+        server: Vec<u8>,
+    },
+    Padding(u16),
+    Other {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+/// The EDNS(0) pseudo-record (RFC 6891), decoded from the CLASS and TTL fields of an OPT record
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptRecord {
+    pub udp_payload_size: u16,
+    pub extended_rcode: u8,
+    pub version: u8,
+    pub flag_dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SvcParam {
+    Mandatory(Vec<u16>),
+    Alpn(Vec<String>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    Other { key: u16, value: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnsRData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    CNAME(String),
+    NS(String),
+    PTR(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    TXT(Vec<Vec<u8>>),
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    CAA {
+        flags: u8,
+        tag: String,
+        value: Vec<u8>,
+    },
+    /// Both SVCB and HTTPS records
+    SVCB {
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+    },
+    OPT(OptRecord),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: DnsType,
+    pub class: DnsClass,
+    pub ttl: u32,
+    pub data: DnsRData,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DnsMessage {
+    pub header: DnsHeader,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+    pub authorities: Vec<DnsRecord>,
+    pub additionals: Vec<DnsRecord>,
+}
+
+fn failure<T>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], T> {
+    Err(Err::Failure(Error::new(input, kind)))
+}
+
+/// Appends a label in presentation format, escaping dots, backslashes and unprintable bytes
+fn push_label(name: &mut String, label: &[u8]) {
+    for &byte in label {
+        match byte {
+            b'.' | b'\\' => {
+                name.push('\\');
+                name.push(char::from(byte));
+            }
+            0x21..=0x7e => name.push(char::from(byte)),
+            _ => name.push_str(&format!("\\{:03}", byte)),
+        }
+    }
+}
+
+/// Parses a possibly compressed domain name from `input`, which starts at `offset` within
+/// `message`. Every compression pointer must point before the start of the labels read so far,
+/// so that decompression always terminates. The root name is returned as ".".
+pub(crate) fn name<'a>(
+    message: &'a [u8],
+    input: &'a [u8],
+    offset: usize,
+) -> IResult<&'a [u8], String> {
+    let mut name = String::new();
+    let mut length = 0;
+    let mut cursor = input;
+    let mut segment_start = offset;
+    let mut rest = None;
+
+    loop {
+        let (after, label_length) = number::streaming::be_u8(cursor)?;
+        if label_length & LABEL_POINTER == LABEL_POINTER {
+            let (after, low) = number::streaming::be_u8(after)?;
+            let pointer = usize::from(label_length & !LABEL_POINTER) << 8 | usize::from(low);
+            if pointer >= segment_start {
+                return failure(cursor, ErrorKind::Verify);
+            }
+            rest.get_or_insert(after);
+            segment_start = pointer;
+            cursor = &message[pointer..];
+        } else if label_length & LABEL_POINTER != 0 {
+            // Extended label types (RFC 6891) are obsolete
+            return failure(cursor, ErrorKind::Tag);
+        } else if label_length == 0 {
+            if name.is_empty() {
+                name.push('.');
+            }
+            return Ok((rest.unwrap_or(after), name));
+        } else {
+            let (after, label) = bytes::streaming::take(label_length)(after)?;
+            length += label.len() + 1;
+            if length > MAX_NAME_LENGTH {
+                return failure(cursor, ErrorKind::TooLarge);
+            }
+            if !name.is_empty() {
+                name.push('.');
+            }
+            push_label(&mut name, label);
+            cursor = after;
+        }
+    }
+}
+
+fn character_string(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = number::streaming::be_u8(input)?;
+    bytes::streaming::take(length)(input)
+}
+
+fn edns_option(input: &[u8]) -> IResult<&[u8], EdnsOption> {
+    let (input, code) = number::streaming::be_u16(input)?;
+    let (input, length) = number::streaming::be_u16(input)?;
+    let (input, data) = bytes::streaming::take(length)(input)?;
+
+    let option = match code {
+        EDNS_NSID => EdnsOption::Nsid(data.to_vec()),
+        EDNS_CLIENT_SUBNET if data.len() >= 4 => EdnsOption::ClientSubnet {
+            family: u16::from_be_bytes([data[0], data[1]]),
+            source_prefix_length: data[2],
+            scope_prefix_length: data[3],
+            address: data[4..].to_vec(),
+        },
+        EDNS_COOKIE if data.len() >= 8 => EdnsOption::Cookie {
+            client: data[..8].to_vec(),
+            server: data[8..].to_vec(),
+        },
+        EDNS_PADDING => EdnsOption::Padding(length),
+        _ => EdnsOption::Other {
+            code,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok((input, option))
+}
+
+fn svc_param(input: &[u8]) -> IResult<&[u8], SvcParam> {
+    let (input, key) = number::streaming::be_u16(input)?;
+    let (input, length) = number::streaming::be_u16(input)?;
+    let (input, value) = bytes::streaming::take(length)(input)?;
+
+    let param = match key {
+        SVC_MANDATORY => SvcParam::Mandatory(
+            value
+                .chunks_exact(2)
+                .map(|k| u16::from_be_bytes([k[0], k[1]]))
+                .collect(),
+        ),
+        SVC_ALPN => {
+            let mut ids = vec![];
+            let mut rest = value;
+            while !rest.is_empty() {
+                let (r, id) = character_string(rest)?;
+                ids.push(String::from_utf8_lossy(id).into_owned());
+                rest = r;
+            }
+            SvcParam::Alpn(ids)
+        }
+        SVC_NO_DEFAULT_ALPN => SvcParam::NoDefaultAlpn,
+        SVC_PORT if value.len() == 2 => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+        SVC_IPV4_HINT => {
+            let mut addresses = vec![];
+            let mut rest = value;
+            while !rest.is_empty() {
+                let (r, address) = ipv4::address(rest)?;
+                addresses.push(address);
+                rest = r;
+            }
+            SvcParam::Ipv4Hint(addresses)
+        }
+        SVC_ECH => SvcParam::Ech(value.to_vec()),
+        SVC_IPV6_HINT => {
+            let mut addresses = vec![];
+            let mut rest = value;
+            while !rest.is_empty() {
+                let (r, address) = ipv6::address(rest)?;
+                addresses.push(address);
+                rest = r;
+            }
+            SvcParam::Ipv6Hint(addresses)
+        }
+        _ => SvcParam::Other {
+            key,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((input, param))
+}
+
+/// Parses the RDATA of a record. `rdata` is the sub-slice of `message` starting at `offset`, so
+/// names inside it can be decompressed. Addresses of the wrong length, such as the empty RDATA of
+/// UPDATE deletes, are kept as unknown data.
+fn rdata<'a>(
+    message: &'a [u8],
+    rdata: &'a [u8],
+    offset: usize,
+    rtype: DnsType,
+    class: u16,
+    ttl: u32,
+) -> IResult<&'a [u8], DnsRData> {
+    let name = |rest: &'a [u8]| name(message, rest, offset + rdata.len() - rest.len());
+    let data = match rtype {
+        DnsType::A if rdata.len() == 4 => DnsRData::A(ipv4::address(rdata)?.1),
+        DnsType::AAAA if rdata.len() == 16 => DnsRData::AAAA(ipv6::address(rdata)?.1),
+        DnsType::CNAME => DnsRData::CNAME(name(rdata)?.1),
+        DnsType::NS => DnsRData::NS(name(rdata)?.1),
+        DnsType::PTR => DnsRData::PTR(name(rdata)?.1),
+        DnsType::MX => {
+            let (rest, preference) = number::streaming::be_u16(rdata)?;
+            let (_, exchange) = name(rest)?;
+            DnsRData::MX {
+                preference,
+                exchange,
+            }
+        }
+        DnsType::SOA => {
+            let (rest, mname) = name(rdata)?;
+            let (rest, rname) = name(rest)?;
+            let (rest, serial) = number::streaming::be_u32(rest)?;
+            let (rest, refresh) = number::streaming::be_u32(rest)?;
+            let (rest, retry) = number::streaming::be_u32(rest)?;
+            let (rest, expire) = number::streaming::be_u32(rest)?;
+            let (_, minimum) = number::streaming::be_u32(rest)?;
+            DnsRData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            }
+        }
+        DnsType::TXT => {
+            let mut strings = vec![];
+            let mut rest = rdata;
+            while !rest.is_empty() {
+                let (r, string) = character_string(rest)?;
+                strings.push(string.to_vec());
+                rest = r;
+            }
+            DnsRData::TXT(strings)
+        }
+        DnsType::SRV => {
+            let (rest, priority) = number::streaming::be_u16(rdata)?;
+            let (rest, weight) = number::streaming::be_u16(rest)?;
+            let (rest, port) = number::streaming::be_u16(rest)?;
+            let (_, target) = name(rest)?;
+            DnsRData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            }
+        }
+        DnsType::CAA => {
+            let (rest, flags) = number::streaming::be_u8(rdata)?;
+            let (value, tag) = character_string(rest)?;
+            DnsRData::CAA {
+                flags,
+                tag: String::from_utf8_lossy(tag).into_owned(),
+                value: value.to_vec(),
+            }
+        }
+        DnsType::SVCB | DnsType::HTTPS => {
+            let (rest, priority) = number::streaming::be_u16(rdata)?;
+            let (mut rest, target) = name(rest)?;
+            let mut params = vec![];
+            while !rest.is_empty() {
+                let (r, param) = svc_param(rest)?;
+                params.push(param);
+                rest = r;
+            }
+            DnsRData::SVCB {
+                priority,
+                target,
+                params,
+            }
+        }
+        DnsType::OPT => {
+            let mut options = vec![];
+            let mut rest = rdata;
+            while !rest.is_empty() {
+                let (r, option) = edns_option(rest)?;
+                options.push(option);
+                rest = r;
+            }
+            DnsRData::OPT(OptRecord {
+                udp_payload_size: class,
+                extended_rcode: (ttl >> 24) as u8,
+                version: (ttl >> 16) as u8,
+                flag_dnssec_ok: ttl & 0x8000 != 0,
+                options,
+            })
+        }
+        _ => DnsRData::Unknown(rdata.to_vec()),
+    };
+
+    Ok((&rdata[rdata.len()..], data))
+}
+
+fn question<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], DnsQuestion> {
+    let (input, name) = name(message, input, message.len() - input.len())?;
+    let (input, qtype) = number::streaming::be_u16(input)?;
+    let (input, qclass) = number::streaming::be_u16(input)?;
+
+    Ok((
+        input,
+        DnsQuestion {
+            name,
+            qtype: qtype.into(),
+            qclass: qclass.into(),
+        },
+    ))
+}
+
+fn record<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], DnsRecord> {
+    let (input, name) = name(message, input, message.len() - input.len())?;
+    let (input, rtype) = number::streaming::be_u16(input)?;
+    let (input, class) = number::streaming::be_u16(input)?;
+    let (input, ttl) = number::streaming::be_u32(input)?;
+    let (input, length) = number::streaming::be_u16(input)?;
+    let offset = message.len() - input.len();
+    let (input, data) = bytes::streaming::take(length)(input)?;
+    let rtype = DnsType::from(rtype);
+    // The RDATA is complete once taken, so running short inside it is an error
+    let (_, data) =
+        combinator::complete(|data| rdata(message, data, offset, rtype, class, ttl))(data)?;
+
+    Ok((
+        input,
+        DnsRecord {
+            name,
+            rtype,
+            class: class.into(),
+            ttl,
+            data,
+        },
+    ))
+}
+
+fn records<'a>(
+    message: &'a [u8],
+    mut input: &'a [u8],
+    count: u16,
+) -> IResult<&'a [u8], Vec<DnsRecord>> {
+    let mut records = vec![];
+    for _ in 0..count {
+        let (rest, record) = record(message, input)?;
+        records.push(record);
+        input = rest;
+    }
+
+    Ok((input, records))
+}
+
+pub fn parse_dns_header(input: &[u8]) -> IResult<&[u8], DnsHeader> {
+    let (input, id) = number::streaming::be_u16(input)?;
+    let (input, flags) = number::streaming::be_u16(input)?;
+    let (input, question_count) = number::streaming::be_u16(input)?;
+    let (input, answer_count) = number::streaming::be_u16(input)?;
+    let (input, authority_count) = number::streaming::be_u16(input)?;
+    let (input, additional_count) = number::streaming::be_u16(input)?;
+
+    Ok((
+        input,
+        DnsHeader {
+            id,
+            flag_response: flags & 0x8000 != 0,
+            opcode: (((flags >> 11) & 0x0f) as u8).into(),
+            flag_authoritative: flags & 0x0400 != 0,
+            flag_truncated: flags & 0x0200 != 0,
+            flag_recursion_desired: flags & 0x0100 != 0,
+            flag_recursion_available: flags & 0x0080 != 0,
+            flag_authentic_data: flags & 0x0020 != 0,
+            flag_checking_disabled: flags & 0x0010 != 0,
+            rcode: ((flags & 0x0f) as u8).into(),
+            question_count,
+            answer_count,
+            authority_count,
+            additional_count,
+        },
+    ))
+}
+
+/// Parses a whole DNS message, as carried in a UDP payload. Compressed names may point anywhere
+/// before them in `input`, so it must start at the DNS header.
+pub fn parse_dns_message(input: &[u8]) -> IResult<&[u8], DnsMessage> {
+    let message = input;
+    let (mut input, header) = parse_dns_header(input)?;
+
+    let mut questions = vec![];
+    for _ in 0..header.question_count {
+        let (rest, q) = question(message, input)?;
+        questions.push(q);
+        input = rest;
+    }
+    let (input, answers) = records(message, input, header.answer_count)?;
+    let (input, authorities) = records(message, input, header.authority_count)?;
+    let (input, additionals) = records(message, input, header.additional_count)?;
+
+    Ok((
+        input,
+        DnsMessage {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+        },
+    ))
+}
+
+/// Parses a DNS message preceded by the 2-byte length used over TCP (RFC 1035 section 4.2.2).
+/// Returns `Incomplete` until the whole message is available; the remaining input starts at the
+/// next message on the stream.
+pub fn parse_dns_tcp_message(input: &[u8]) -> IResult<&[u8], DnsMessage> {
+    let (input, length) = number::streaming::be_u16(input)?;
+    let (input, message) = bytes::streaming::take(length)(input)?;
+    let (_, message) = combinator::complete(parse_dns_message)(message)?;
+
+    Ok((input, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::Needed;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    fn response_bytes() -> Vec<u8> {
+        vec![
+            0x12, 0x34, 0x81, 0x80, /* ID, flags */
+            0x00, 0x01, 0x00, 0x02, /* QDCOUNT, ANCOUNT */
+            0x00, 0x00, 0x00, 0x01, /* NSCOUNT, ARCOUNT */
+            0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c',
+            b'o', b'm', 0x00, /* www.example.com */
+            0x00, 0x01, 0x00, 0x01, /* QTYPE A, QCLASS IN */
+            0xc0, 0x0c, /* pointer to www.example.com */
+            0x00, 0x05, 0x00, 0x01, /* CNAME, IN */
+            0x00, 0x00, 0x0e, 0x10, /* TTL */
+            0x00, 0x06, /* RDLENGTH */
+            0x03, b'w', b'e', b'b', 0xc0, 0x10, /* web.example.com */
+            0xc0, 0x2d, /* pointer to web.example.com */
+            0x00, 0x01, 0x00, 0x01, /* A, IN */
+            0x00, 0x00, 0x00, 0x3c, /* TTL */
+            0x00, 0x04, /* RDLENGTH */
+            0x5d, 0xb8, 0xd8, 0x22, /* address */
+            0x00, /* root */
+            0x00, 0x29, 0x04, 0xd0, /* OPT, UDP payload size */
+            0x00, 0x00, 0x80, 0x00, /* extended RCODE, version, DO */
+            0x00, 0x0c, /* RDLENGTH */
+            0x00, 0x0a, 0x00, 0x08, /* cookie option */
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ]
+    }
+
+    #[test]
+    fn dns_response_works() {
+        let bytes = response_bytes();
+        let (remaining, message) = parse_dns_message(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(
+            message.header,
+            DnsHeader {
+                id: 0x1234,
+                flag_response: true,
+                opcode: DnsOpcode::Query,
+                flag_authoritative: false,
+                flag_truncated: false,
+                flag_recursion_desired: true,
+                flag_recursion_available: true,
+                flag_authentic_data: false,
+                flag_checking_disabled: false,
+                rcode: DnsRcode::NoError,
+                question_count: 1,
+                answer_count: 2,
+                authority_count: 0,
+                additional_count: 1,
+            }
+        );
+        assert_eq!(
+            message.questions,
+            vec![DnsQuestion {
+                name: "www.example.com".to_string(),
+                qtype: DnsType::A,
+                qclass: DnsClass::IN,
+            }]
+        );
+        assert_eq!(
+            message.answers,
+            vec![
+                DnsRecord {
+                    name: "www.example.com".to_string(),
+                    rtype: DnsType::CNAME,
+                    class: DnsClass::IN,
+                    ttl: 3600,
+                    data: DnsRData::CNAME("web.example.com".to_string()),
+                },
+                DnsRecord {
+                    name: "web.example.com".to_string(),
+                    rtype: DnsType::A,
+                    class: DnsClass::IN,
+                    ttl: 60,
+                    data: DnsRData::A(Ipv4Addr::new(93, 184, 216, 34)),
+                },
+            ]
+        );
+        assert_eq!(
+            message.additionals[0].data,
+            DnsRData::OPT(OptRecord {
+                udp_payload_size: 1232,
+                extended_rcode: 0,
+                version: 0,
+                flag_dnssec_ok: true,
+                options: vec![EdnsOption::Cookie {
+                    client: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                    server: vec![],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn dns_tcp_message_works() {
+        let message = response_bytes();
+        let mut bytes = (message.len() as u16).to_be_bytes().to_vec();
+        bytes.extend(&message);
+        bytes.extend(&[0x00, 0x20]);
+
+        let (remaining, _) = parse_dns_tcp_message(&bytes).unwrap();
+        assert_eq!(remaining, &[0x00, 0x20]);
+        assert_eq!(
+            parse_dns_tcp_message(remaining),
+            Err(Err::Incomplete(Needed::new(32)))
+        );
+    }
+
+    #[test]
+    fn dns_tcp_message_with_short_message_fails() {
+        let message = response_bytes();
+        let mut bytes = 8u16.to_be_bytes().to_vec();
+        bytes.extend(&message[..8]);
+
+        assert!(matches!(
+            parse_dns_tcp_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Complete,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dns_compression_loop_fails() {
+        let bytes = [
+            0x00, 0x01, 0x00, 0x00, /* ID, flags */
+            0x00, 0x01, 0x00, 0x00, /* QDCOUNT, ANCOUNT */
+            0x00, 0x00, 0x00, 0x00, /* NSCOUNT, ARCOUNT */
+            0x01, b'a', 0xc0, 0x0c, /* label pointing back to itself */
+            0x00, 0x01, 0x00, 0x01, /* QTYPE A, QCLASS IN */
+        ];
+        assert!(matches!(
+            parse_dns_message(&bytes),
+            Err(Err::Failure(Error {
+                code: ErrorKind::Verify,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dns_forward_pointer_in_rdata_fails() {
+        let bytes = [
+            0x00, 0x01, 0x81, 0x80, /* ID, flags */
+            0x00, 0x00, 0x00, 0x01, /* QDCOUNT, ANCOUNT */
+            0x00, 0x00, 0x00, 0x00, /* NSCOUNT, ARCOUNT */
+            0x00, /* root */
+            0x00, 0x05, 0x00, 0x01, /* CNAME, IN */
+            0x00, 0x00, 0x00, 0x3c, /* TTL */
+            0x00, 0x02, /* RDLENGTH */
+            0xc0, 0x19, /* pointer past the RDATA */
+            0x01, b'a', 0x00, /* a */
+        ];
+        assert!(matches!(
+            parse_dns_message(&bytes),
+            Err(Err::Failure(Error {
+                code: ErrorKind::Verify,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dns_empty_and_short_rdata_work() {
+        let bytes = [
+            0x00, 0x01, 0x28, 0x00, /* ID, flags (UPDATE) */
+            0x00, 0x00, 0x00, 0x00, /* ZOCOUNT, PRCOUNT */
+            0x00, 0x01, 0x00, 0x00, /* UPCOUNT, ADCOUNT */
+            0x00, /* root */
+            0x00, 0x01, 0x00, 0xff, /* A, ANY */
+            0x00, 0x00, 0x00, 0x00, /* TTL */
+            0x00, 0x00, /* RDLENGTH */
+        ];
+        let mut tcp = (bytes.len() as u16).to_be_bytes().to_vec();
+        tcp.extend(&bytes);
+        let (remaining, message) = parse_dns_tcp_message(&tcp).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(message.authorities[0].data, DnsRData::Unknown(vec![]));
+
+        let bytes = [
+            0x00, 0x01, 0x81, 0x80, /* ID, flags */
+            0x00, 0x00, 0x00, 0x01, /* QDCOUNT, ANCOUNT */
+            0x00, 0x00, 0x00, 0x00, /* NSCOUNT, ARCOUNT */
+            0x00, /* root */
+            0x00, 0x0f, 0x00, 0x01, /* MX, IN */
+            0x00, 0x00, 0x00, 0x3c, /* TTL */
+            0x00, 0x01, /* RDLENGTH */
+            0x00, /* truncated preference */
+        ];
+        assert!(matches!(
+            parse_dns_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Complete,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn dns_svcb_and_caa_work() {
+        let message = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let svcb = [
+            0x00, 0x01, 0x00, /* priority, target "." */
+            0x00, 0x01, 0x00, 0x03, 0x02, b'h', b'2', /* alpn */
+            0x00, 0x03, 0x00, 0x02, 0x01, 0xbb, /* port */
+        ];
+        assert_eq!(
+            rdata(&svcb, &svcb, 0, DnsType::HTTPS, 1, 0),
+            Ok((
+                EMPTY_SLICE,
+                DnsRData::SVCB {
+                    priority: 1,
+                    target: ".".to_string(),
+                    params: vec![SvcParam::Alpn(vec!["h2".to_string()]), SvcParam::Port(443)],
+                }
+            ))
+        );
+
+        let caa = [
+            0x00, 0x05, b'i', b's', b's', b'u', b'e', b'c', b'a', b'.', b'o', b'r', b'g',
+        ];
+        assert_eq!(
+            rdata(&message, &caa, message.len(), DnsType::CAA, 1, 0),
+            Ok((
+                EMPTY_SLICE,
+                DnsRData::CAA {
+                    flags: 0,
+                    tag: "issue".to_string(),
+                    value: b"ca.org".to_vec(),
+                }
+            ))
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Handles parsing of EAP packets, as carried by EAPOL
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// EAP Packet Format (RFC 3748); Success and Failure packets end after the length
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |     Code      |  Identifier   |            Length             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |     Type      |  Type-Data ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-
+//
+// EAP-TLS Type-Data (RFC 5216), shared by EAP-TTLS and PEAP which carry their version in the low
+// bits of the flags
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |L M S R R Ver  |  TLS Message Length (when L is set)           |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |               |  TLS Data ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-
+
+const HEADER_LENGTH: u16 = 4;
+
+const TYPE_IDENTITY: u8 = 1;
+const TYPE_NOTIFICATION: u8 = 2;
+const TYPE_NAK: u8 = 3;
+const TYPE_MD5_CHALLENGE: u8 = 4;
+const TYPE_TLS: u8 = 13;
+const TYPE_TTLS: u8 = 21;
+const TYPE_PEAP: u8 = 25;
+
+const TLS_FLAG_LENGTH_INCLUDED: u8 = 0x80;
+const TLS_FLAG_MORE_FRAGMENTS: u8 = 0x40;
+const TLS_FLAG_START: u8 = 0x20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EapCode {
+    Request,
+    Response,
+    Success,
+    Failure,
+    Other(u8),
+}
+
+impl From<u8> for EapCode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Request,
+            2 => Self::Response,
+            3 => Self::Success,
+            4 => Self::Failure,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A fragment of the TLS records exchanged by EAP-TLS, EAP-TTLS or PEAP
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EapTlsFragment {
+    pub more_fragments: bool,
+    pub start: bool,
+    /// Always 0 for EAP-TLS
+    pub version: u8,
+    /// The length of the whole TLS message, sent with its first fragment
+    pub tls_message_length: Option<u32>,
+    pub data: Vec<u8>,
+}
+
+/// Strings are decoded lossily as UTF-8
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EapMethod {
+    Identity(String),
+    Notification(String),
+    /// The methods the peer would rather use
+    Nak(Vec<u8>),
+    Md5Challenge {
+        value: Vec<u8>,
+        name: String,
+    },
+    Tls(EapTlsFragment),
+    Ttls(EapTlsFragment),
+    Peap(EapTlsFragment),
+    Other {
+        method_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EapPacket {
+    pub code: EapCode,
+    pub identifier: u8,
+    /// Set for requests and responses
+    pub method: Option<EapMethod>,
+}
+
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+fn tls_fragment(data: &[u8]) -> IResult<&[u8], EapTlsFragment> {
+    let (data, flags) = number::complete::be_u8(data)?;
+    let (data, tls_message_length) = if flags & TLS_FLAG_LENGTH_INCLUDED != 0 {
+        let (data, length) = number::complete::be_u32(data)?;
+        (data, Some(length))
+    } else {
+        (data, None)
+    };
+
+    Ok((
+        &data[data.len()..],
+        EapTlsFragment {
+            more_fragments: flags & TLS_FLAG_MORE_FRAGMENTS != 0,
+            start: flags & TLS_FLAG_START != 0,
+            version: flags & 0x07,
+            tls_message_length,
+            data: data.to_vec(),
+        },
+    ))
+}
+
+fn method(data: &[u8]) -> IResult<&[u8], EapMethod> {
+    let (data, method_type) = number::complete::be_u8(data)?;
+    let method = match method_type {
+        TYPE_IDENTITY => EapMethod::Identity(text(data)),
+        TYPE_NOTIFICATION => EapMethod::Notification(text(data)),
+        TYPE_NAK => EapMethod::Nak(data.to_vec()),
+        TYPE_MD5_CHALLENGE => {
+            let (data, value_size) = number::complete::be_u8(data)?;
+            let (name, value) = bytes::complete::take(value_size)(data)?;
+            EapMethod::Md5Challenge {
+                value: value.to_vec(),
+                name: text(name),
+            }
+        }
+        TYPE_TLS => EapMethod::Tls(tls_fragment(data)?.1),
+        TYPE_TTLS => EapMethod::Ttls(tls_fragment(data)?.1),
+        TYPE_PEAP => EapMethod::Peap(tls_fragment(data)?.1),
+        method_type => EapMethod::Other {
+            method_type,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok((&data[data.len()..], method))
+}
+
+pub fn parse_eap_packet(input: &[u8]) -> IResult<&[u8], EapPacket> {
+    let (rest, code) = number::streaming::be_u8(input)?;
+    let (rest, identifier) = number::streaming::be_u8(rest)?;
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    if length < HEADER_LENGTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (rest, data) = bytes::streaming::take(length - HEADER_LENGTH)(rest)?;
+
+    let code = EapCode::from(code);
+    let method = match code {
+        EapCode::Request | EapCode::Response => Some(method(data)?.1),
+        _ => None,
+    };
+
+    Ok((
+        rest,
+        EapPacket {
+            code,
+            identifier,
+            method,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn identity_and_md5_work() {
+        let bytes = [
+            0x02, 0x01, 0x00, 0x0a, // Response, ID 1, length 10
+            0x01, b'a', b'l', b'i', b'c', b'e', // Identity
+        ];
+        let expectation = EapPacket {
+            code: EapCode::Response,
+            identifier: 1,
+            method: Some(EapMethod::Identity("alice".to_string())),
+        };
+        assert_eq!(parse_eap_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+
+        let bytes = [
+            0x01, 0x02, 0x00, 0x0c, // Request, ID 2, length 12
+            0x04, 0x04, 0xde, 0xad, 0xbe, 0xef, b's', b'w', // MD5-Challenge
+        ];
+        let expectation = EapPacket {
+            code: EapCode::Request,
+            identifier: 2,
+            method: Some(EapMethod::Md5Challenge {
+                value: vec![0xde, 0xad, 0xbe, 0xef],
+                name: "sw".to_string(),
+            }),
+        };
+        assert_eq!(parse_eap_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+
+        let bytes = [0x03, 0x02, 0x00, 0x04]; // Success
+        let (_, packet) = parse_eap_packet(&bytes).unwrap();
+        assert_eq!(packet.code, EapCode::Success);
+        assert_eq!(packet.method, None);
+    }
+
+    #[test]
+    fn peap_fragment_works() {
+        let bytes = [
+            0x02, 0x05, 0x00, 0x0f, // Response, ID 5, length 15
+            0x19, 0xc1, // PEAP, length included, more fragments, version 1
+            0x00, 0x00, 0x05, 0xdc, // TLS message length 1500
+            0x16, 0x03, 0x01, 0x05, 0xd7, // TLS record header
+        ];
+        let expectation = EapPacket {
+            code: EapCode::Response,
+            identifier: 5,
+            method: Some(EapMethod::Peap(EapTlsFragment {
+                more_fragments: true,
+                start: false,
+                version: 1,
+                tls_message_length: Some(1500),
+                data: vec![0x16, 0x03, 0x01, 0x05, 0xd7],
+            })),
+        };
+        assert_eq!(parse_eap_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+}
@@ -0,0 +1,463 @@
+//! Handles parsing of EAPOL (IEEE 802.1X) frames, carried in Ethernet frames of EtherType 0x888E
+
+use crate::eap::{self, EapPacket};
+use crate::ethernet::{self, MacAddress};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// EAPOL Header (IEEE 802.1X-2010)
+//
+//   +---------+---------+-------------------+
+//   | Version |  Type   | Body Length (2)   |
+//   +---------+---------+-------------------+
+//
+// EAPOL-Key body with the RSN or WPA descriptor (IEEE 802.11)
+//
+//   +---------+-------------------+-------------------+----------------------------+
+//   |  Type   | Key Information(2)| Key Length (2)    | Replay Counter (8)         |
+//   +---------+-------------------+-------------------+----------------------------+
+//   | Key Nonce (32) | Key IV (16) | Key RSC (8) | Reserved (8) | Key MIC (16)     |
+//   +------------------------------------------------------------------------------+
+//   | Key Data Length (2) | Key Data                                               |
+//   +------------------------------------------------------------------------------+
+//
+// MKPDU body: a Basic Parameter Set, further parameter sets, then the 16 octet ICV
+//
+//   +---------+---------+-+-+---+-----------------------+
+//   | Version | Priority|K|D|Cap| Body Length (12 bits) |
+//   +---------+---------+-+-+---+-----------------------+
+//   | SCI (8) | Member ID (12) | Message Number (4)     |
+//   | Algorithm Agility (4) | CAK Name                  |
+//   +---------------------------------------------------+
+//   |  Type   | (flags)   | Body Length (12 bits) | ... |   parameter sets, padded to 4 octets
+//   +---------------------------------------------------+
+
+pub const EAPOL_KEY_INFO_DESCRIPTOR_VERSION: u16 = 0x0007;
+pub const EAPOL_KEY_INFO_PAIRWISE: u16 = 0x0008;
+pub const EAPOL_KEY_INFO_INSTALL: u16 = 0x0040;
+pub const EAPOL_KEY_INFO_ACK: u16 = 0x0080;
+pub const EAPOL_KEY_INFO_MIC: u16 = 0x0100;
+pub const EAPOL_KEY_INFO_SECURE: u16 = 0x0200;
+pub const EAPOL_KEY_INFO_ERROR: u16 = 0x0400;
+pub const EAPOL_KEY_INFO_REQUEST: u16 = 0x0800;
+pub const EAPOL_KEY_INFO_ENCRYPTED_KEY_DATA: u16 = 0x1000;
+
+const KEY_DESCRIPTOR_RSN: u8 = 2;
+const KEY_DESCRIPTOR_WPA: u8 = 254;
+
+const MKA_BASIC_PARAMETER_SET_LENGTH: usize = 28;
+const MKA_LIVE_PEER_LIST: u8 = 1;
+const MKA_POTENTIAL_PEER_LIST: u8 = 2;
+const MKA_ICV_INDICATOR: u8 = 255;
+const MKA_ICV_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EapolPacketType {
+    EapPacket,
+    Start,
+    Logoff,
+    Key,
+    EncapsulatedAsfAlert,
+    Mka,
+    Announcement,
+    SpecificAnnouncement,
+    AnnouncementRequest,
+    Other(u8),
+}
+
+impl From<u8> for EapolPacketType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::EapPacket,
+            1 => Self::Start,
+            2 => Self::Logoff,
+            3 => Self::Key,
+            4 => Self::EncapsulatedAsfAlert,
+            5 => Self::Mka,
+            6 => Self::Announcement,
+            7 => Self::SpecificAnnouncement,
+            8 => Self::AnnouncementRequest,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// An EAPOL-Key frame with the RSN or WPA descriptor. The Key MIC is assumed to be 16 octets,
+/// as it is for all but the Suite B 192-bit and FT-SAE-EXT-KEY AKMs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EapolKey {
+    pub descriptor_type: u8,
+    /// Bitmap of the `EAPOL_KEY_INFO_*` values
+    pub key_information: u16,
+    pub key_length: u16,
+    pub replay_counter: u64,
+    pub nonce: [u8; 32],
+    pub iv: [u8; 16],
+    pub rsc: [u8; 8],
+    pub mic: [u8; 16],
+    pub key_data: Vec<u8>,
+}
+
+impl EapolKey {
+    /// Which message of the 4-way handshake this frame is, if any
+    pub fn handshake_message(&self) -> Option<u8> {
+        let info = self.key_information;
+        if info & EAPOL_KEY_INFO_PAIRWISE == 0 || info & EAPOL_KEY_INFO_REQUEST != 0 {
+            return None;
+        }
+        let ack = info & EAPOL_KEY_INFO_ACK != 0;
+        let mic = info & EAPOL_KEY_INFO_MIC != 0;
+        let install = info & EAPOL_KEY_INFO_INSTALL != 0;
+        let secure = info & EAPOL_KEY_INFO_SECURE != 0;
+        match (ack, mic, install, secure) {
+            (true, false, false, _) => Some(1),
+            (false, true, false, false) => Some(2),
+            (true, true, true, _) => Some(3),
+            (false, true, false, true) => Some(4),
+            _ => None,
+        }
+    }
+}
+
+/// The secure channel identifier of an MKA participant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MkaSci {
+    pub mac: MacAddress,
+    pub port: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MkaPeer {
+    pub member_id: [u8; 12],
+    pub message_number: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MkaParameterSet {
+    LivePeerList(Vec<MkaPeer>),
+    PotentialPeerList(Vec<MkaPeer>),
+    /// SAK use, distributed SAK or CAK, KMD and announcement sets, with their 4 octet header
+    Other {
+        parameter_set_type: u8,
+        raw: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MkaPdu {
+    pub version: u8,
+    pub key_server_priority: u8,
+    pub key_server: bool,
+    pub macsec_desired: bool,
+    pub macsec_capability: u8,
+    pub sci: MkaSci,
+    pub actor_member_id: [u8; 12],
+    pub actor_message_number: u32,
+    pub algorithm_agility: u32,
+    pub cak_name: Vec<u8>,
+    pub parameter_sets: Vec<MkaParameterSet>,
+    pub icv: [u8; 16],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EapolBody {
+    Eap(EapPacket),
+    Start,
+    Logoff,
+    Key(EapolKey),
+    Mka(MkaPdu),
+    /// Announcements, alerts and key descriptors other than RSN and WPA
+    Other(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EapolPacket {
+    pub version: u8,
+    pub packet_type: EapolPacketType,
+    pub body: EapolBody,
+}
+
+fn array<const N: usize>(input: &[u8]) -> IResult<&[u8], [u8; N]> {
+    let (input, raw) = bytes::complete::take(N)(input)?;
+    let mut array = [0; N];
+    array.copy_from_slice(raw);
+
+    Ok((input, array))
+}
+
+fn key(body: &[u8]) -> IResult<&[u8], EapolKey> {
+    let (body, descriptor_type) = number::complete::be_u8(body)?;
+    let (body, key_information) = number::complete::be_u16(body)?;
+    let (body, key_length) = number::complete::be_u16(body)?;
+    let (body, replay_counter) = number::complete::be_u64(body)?;
+    let (body, nonce) = array(body)?;
+    let (body, iv) = array(body)?;
+    let (body, rsc) = array(body)?;
+    let (body, _reserved) = bytes::complete::take(8u8)(body)?;
+    let (body, mic) = array(body)?;
+    let (body, key_data_length) = number::complete::be_u16(body)?;
+    let (body, key_data) = bytes::complete::take(key_data_length)(body)?;
+
+    Ok((
+        body,
+        EapolKey {
+            descriptor_type,
+            key_information,
+            key_length,
+            replay_counter,
+            nonce,
+            iv,
+            rsc,
+            mic,
+            key_data: key_data.to_vec(),
+        },
+    ))
+}
+
+fn mka_peer(input: &[u8]) -> IResult<&[u8], MkaPeer> {
+    let (input, member_id) = array(input)?;
+    let (input, message_number) = number::complete::be_u32(input)?;
+
+    Ok((
+        input,
+        MkaPeer {
+            member_id,
+            message_number,
+        },
+    ))
+}
+
+fn mka_peers(mut input: &[u8]) -> IResult<&[u8], Vec<MkaPeer>> {
+    let mut peers = vec![];
+    while !input.is_empty() {
+        let (rest, peer) = mka_peer(input)?;
+        peers.push(peer);
+        input = rest;
+    }
+
+    Ok((input, peers))
+}
+
+/// Reads a parameter set header, returning the type, the body and the input after the padding
+fn mka_parameter_set(input: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
+    let (rest, parameter_set_type) = number::complete::be_u8(input)?;
+    let (rest, _) = number::complete::be_u8(rest)?;
+    let (rest, length) = number::complete::be_u16(rest)?;
+    let length = usize::from(length & 0x0fff);
+    let (rest, body) = bytes::complete::take(length)(rest)?;
+    let (rest, _padding) = bytes::complete::take(length.next_multiple_of(4) - length)(rest)?;
+
+    Ok((rest, (parameter_set_type, body)))
+}
+
+fn mka_pdu(body: &[u8]) -> IResult<&[u8], MkaPdu> {
+    let (rest, (version, basic)) = mka_parameter_set(body)?;
+    if basic.len() < MKA_BASIC_PARAMETER_SET_LENGTH {
+        return Err(Err::Error(Error::new(body, ErrorKind::LengthValue)));
+    }
+    let key_server_priority = body[1];
+    let flags = body[2];
+    let (basic, mac) = ethernet::mac_address(basic)?;
+    let (basic, port) = number::complete::be_u16(basic)?;
+    let (basic, actor_member_id) = array(basic)?;
+    let (basic, actor_message_number) = number::complete::be_u32(basic)?;
+    let (cak_name, algorithm_agility) = number::complete::be_u32(basic)?;
+
+    let mut input = rest;
+    let mut parameter_sets = vec![];
+    let icv = loop {
+        if input.len() == MKA_ICV_LENGTH {
+            break array(input)?.1;
+        }
+        let (rest, (parameter_set_type, set_body)) = mka_parameter_set(input)?;
+        let parameter_set = match parameter_set_type {
+            MKA_LIVE_PEER_LIST => MkaParameterSet::LivePeerList(mka_peers(set_body)?.1),
+            MKA_POTENTIAL_PEER_LIST => MkaParameterSet::PotentialPeerList(mka_peers(set_body)?.1),
+            MKA_ICV_INDICATOR => break array(set_body)?.1,
+            parameter_set_type => MkaParameterSet::Other {
+                parameter_set_type,
+                raw: input[..input.len() - rest.len()].to_vec(),
+            },
+        };
+        parameter_sets.push(parameter_set);
+        input = rest;
+    };
+
+    Ok((
+        &input[input.len()..],
+        MkaPdu {
+            version,
+            key_server_priority,
+            key_server: flags & 0x80 != 0,
+            macsec_desired: flags & 0x40 != 0,
+            macsec_capability: (flags >> 4) & 0x03,
+            sci: MkaSci { mac, port },
+            actor_member_id,
+            actor_message_number,
+            algorithm_agility,
+            cak_name: cak_name.to_vec(),
+            parameter_sets,
+            icv,
+        },
+    ))
+}
+
+/// Parses an EAPOL frame, starting after the EtherType. The remaining input starts after the
+/// body, which skips any Ethernet padding.
+pub fn parse_eapol_packet(input: &[u8]) -> IResult<&[u8], EapolPacket> {
+    let (input, version) = number::streaming::be_u8(input)?;
+    let (input, packet_type) = number::streaming::be_u8(input)?;
+    let (input, body_length) = number::streaming::be_u16(input)?;
+    let (input, body) = bytes::streaming::take(body_length)(input)?;
+
+    let packet_type = EapolPacketType::from(packet_type);
+    let body = match packet_type {
+        EapolPacketType::EapPacket => match eap::parse_eap_packet(body) {
+            Ok((_, packet)) => EapolBody::Eap(packet),
+            Err(Err::Incomplete(_)) => {
+                return Err(Err::Error(Error::new(body, ErrorKind::LengthValue)))
+            }
+            Err(e) => return Err(e),
+        },
+        EapolPacketType::Start => EapolBody::Start,
+        EapolPacketType::Logoff => EapolBody::Logoff,
+        EapolPacketType::Key
+            if matches!(
+                body.first(),
+                Some(&KEY_DESCRIPTOR_RSN | &KEY_DESCRIPTOR_WPA)
+            ) =>
+        {
+            EapolBody::Key(key(body)?.1)
+        }
+        EapolPacketType::Mka => EapolBody::Mka(mka_pdu(body)?.1),
+        _ => EapolBody::Other(body.to_vec()),
+    };
+
+    Ok((
+        input,
+        EapolPacket {
+            version,
+            packet_type,
+            body,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eap::{EapCode, EapMethod};
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    fn key_frame(key_information: u16, key_data: &[u8]) -> Vec<u8> {
+        let body_length = 95 + key_data.len() as u16;
+        let mut bytes = vec![0x02, 0x03]; // 802.1X-2004, Key
+        bytes.extend_from_slice(&body_length.to_be_bytes());
+        bytes.push(0x02); // RSN
+        bytes.extend_from_slice(&key_information.to_be_bytes());
+        bytes.extend_from_slice(&[0x00, 0x10]); // key length 16
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]); // replay counter
+        bytes.extend_from_slice(&[0x11; 32]); // nonce
+        bytes.extend_from_slice(&[0; 16 + 8 + 8]); // IV, RSC, reserved
+        bytes.extend_from_slice(&[0x22; 16]); // MIC
+        bytes.extend_from_slice(&(key_data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(key_data);
+        bytes
+    }
+
+    #[test]
+    fn eap_and_key_frames_work() {
+        let bytes = [
+            0x01, 0x00, 0x00, 0x05, // 802.1X-2001, EAP-Packet
+            0x01, 0x07, 0x00, 0x05, 0x01, // EAP Request Identity
+            0x00, 0x00, // padding
+        ];
+        let expectation = EapolPacket {
+            version: 1,
+            packet_type: EapolPacketType::EapPacket,
+            body: EapolBody::Eap(EapPacket {
+                code: EapCode::Request,
+                identifier: 7,
+                method: Some(EapMethod::Identity(String::new())),
+            }),
+        };
+        assert_eq!(
+            parse_eapol_packet(&bytes),
+            Ok((&[0x00, 0x00][..], expectation))
+        );
+
+        // Message 2 of the 4-way handshake, with the RSN IE as key data
+        let bytes = key_frame(0x010a, &[0x30, 0x02, 0x01, 0x00]);
+        let (rest, packet) = parse_eapol_packet(&bytes).unwrap();
+        assert_eq!(rest, EMPTY_SLICE);
+        let key = match packet.body {
+            EapolBody::Key(key) => key,
+            body => panic!("unexpected body {:?}", body),
+        };
+        assert_eq!(key.key_information & EAPOL_KEY_INFO_DESCRIPTOR_VERSION, 2);
+        assert_eq!(key.replay_counter, 1);
+        assert_eq!(key.nonce, [0x11; 32]);
+        assert_eq!(key.mic, [0x22; 16]);
+        assert_eq!(key.key_data, vec![0x30, 0x02, 0x01, 0x00]);
+        assert_eq!(key.handshake_message(), Some(2));
+
+        let (_, packet) = parse_eapol_packet(&key_frame(0x008a, &[])).unwrap();
+        assert!(matches!(packet.body, EapolBody::Key(key) if key.handshake_message() == Some(1)));
+        let (_, packet) = parse_eapol_packet(&key_frame(0x13ca, &[])).unwrap();
+        assert!(matches!(packet.body, EapolBody::Key(key) if key.handshake_message() == Some(3)));
+        let (_, packet) = parse_eapol_packet(&key_frame(0x030a, &[])).unwrap();
+        assert!(matches!(packet.body, EapolBody::Key(key) if key.handshake_message() == Some(4)));
+    }
+
+    #[test]
+    fn mka_works() {
+        let mut bytes = vec![
+            0x03, 0x05, 0x00, 0x54, // 802.1X-2010, MKA, body length 84
+            0x01, 0x10, 0xe0, 0x2c, // version 1, priority 16, key server, desired, length 44
+            0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, 0x00, 0x01, // SCI
+        ];
+        bytes.extend_from_slice(&[0xaa; 12]); // actor member ID
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x09]); // actor message number
+        bytes.extend_from_slice(&[0x00, 0x80, 0xc2, 0x01]); // IEEE 802.1X-2010 agility
+        bytes.extend_from_slice(&[0xcc; 16]); // CAK name
+        bytes.extend_from_slice(&[0x01, 0x00, 0x00, 0x10]); // live peer list, length 16
+        bytes.extend_from_slice(&[0xbb; 12]); // peer member ID
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x04]); // peer message number
+        bytes.extend_from_slice(&[0xdd; 16]); // ICV
+
+        let (rest, packet) = parse_eapol_packet(&bytes).unwrap();
+        assert_eq!(rest, EMPTY_SLICE);
+        let expectation = MkaPdu {
+            version: 1,
+            key_server_priority: 16,
+            key_server: true,
+            macsec_desired: true,
+            macsec_capability: 2,
+            sci: MkaSci {
+                mac: MacAddress([0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f]),
+                port: 1,
+            },
+            actor_member_id: [0xaa; 12],
+            actor_message_number: 9,
+            algorithm_agility: 0x0080_c201,
+            cak_name: vec![0xcc; 16],
+            parameter_sets: vec![MkaParameterSet::LivePeerList(vec![MkaPeer {
+                member_id: [0xbb; 12],
+                message_number: 4,
+            }])],
+            icv: [0xdd; 16],
+        };
+        assert_eq!(packet.body, EapolBody::Mka(expectation));
+    }
+}
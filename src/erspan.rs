@@ -0,0 +1,293 @@
+//! Handles parsing of ERSPAN Type II and Type III headers
+
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// ERSPAN Type II Header Format
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Ver  |          VLAN         | COS | En|T|    Session ID     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |      Reserved         |                  Index                |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// ERSPAN Type III Header Format
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Ver  |          VLAN         | COS |BSO|T|     Session ID    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                          Timestamp                            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |             SGT               |P|    FT   |   Hw ID   |D|Gra|O|
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |      Platform Specific SubHeader (optional, 8 octets)         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const VERSION_TYPE_II: u8 = 1;
+const VERSION_TYPE_III: u8 = 2;
+
+/// Type III frame type of a mirrored Ethernet frame
+pub const FRAME_TYPE_ETHERNET: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErspanEncapsulation {
+    NotTagged,
+    IslTagged,
+    Dot1QTagged,
+    VlanPreserved,
+}
+
+impl From<u8> for ErspanEncapsulation {
+    fn from(raw: u8) -> Self {
+        match raw & 0b11 {
+            0 => Self::NotTagged,
+            1 => Self::IslTagged,
+            2 => Self::Dot1QTagged,
+            _ => Self::VlanPreserved,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErspanDirection {
+    Ingress,
+    Egress,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimestampGranularity {
+    Microseconds100,
+    Nanoseconds100,
+    Ieee1588,
+    UserDefined,
+}
+
+impl From<u8> for TimestampGranularity {
+    fn from(raw: u8) -> Self {
+        match raw & 0b11 {
+            0 => Self::Microseconds100,
+            1 => Self::Nanoseconds100,
+            2 => Self::Ieee1588,
+            _ => Self::UserDefined,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErspanTypeIIHeader {
+    pub version: u8,
+    pub vlan: u16,
+    pub cos: u8,
+    pub encapsulation: ErspanEncapsulation,
+    pub truncated: bool,
+    pub session_id: u16,
+    pub index: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlatformSubHeader {
+    pub platform_id: u8,
+    pub info: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErspanTypeIIIHeader {
+    pub version: u8,
+    pub vlan: u16,
+    pub cos: u8,
+    pub bso: u8,
+    pub truncated: bool,
+    pub session_id: u16,
+    pub timestamp: u32,
+    pub sgt: u16,
+    pub flag_pdu: bool,
+    /// `FRAME_TYPE_ETHERNET`, or 2 for a mirrored IP packet
+    pub frame_type: u8,
+    pub hardware_id: u8,
+    pub direction: ErspanDirection,
+    pub granularity: TimestampGranularity,
+    pub platform: Option<PlatformSubHeader>,
+}
+
+/// ERSPAN Type I carries no header of its own: the mirrored frame directly follows GRE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErspanHeader {
+    TypeI,
+    TypeII(ErspanTypeIIHeader),
+    TypeIII(ErspanTypeIIIHeader),
+}
+
+/// Fields shared by the first word of Type II and Type III headers
+struct FirstWord {
+    version: u8,
+    vlan: u16,
+    cos: u8,
+    en_bso: u8,
+    truncated: bool,
+    session_id: u16,
+}
+
+fn first_word(input: &[u8]) -> IResult<&[u8], FirstWord> {
+    let (input, word) = number::streaming::be_u32(input)?;
+
+    Ok((
+        input,
+        FirstWord {
+            version: (word >> 28) as u8,
+            vlan: ((word >> 16) & 0x0fff) as u16,
+            cos: ((word >> 13) & 0b111) as u8,
+            en_bso: ((word >> 11) & 0b11) as u8,
+            truncated: word & 0x0400 != 0,
+            session_id: (word & 0x03ff) as u16,
+        },
+    ))
+}
+
+fn platform_sub_header(input: &[u8]) -> IResult<&[u8], PlatformSubHeader> {
+    let (input, raw) = number::streaming::be_u64(input)?;
+
+    Ok((
+        input,
+        PlatformSubHeader {
+            platform_id: (raw >> 58) as u8,
+            info: raw & 0x03ff_ffff_ffff_ffff,
+        },
+    ))
+}
+
+pub fn parse_erspan_type2_header(input: &[u8]) -> IResult<&[u8], ErspanTypeIIHeader> {
+    let (rest, first) = first_word(input)?;
+    if first.version != VERSION_TYPE_II {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (rest, reserved_index) = number::streaming::be_u32(rest)?;
+
+    Ok((
+        rest,
+        ErspanTypeIIHeader {
+            version: first.version,
+            vlan: first.vlan,
+            cos: first.cos,
+            encapsulation: first.en_bso.into(),
+            truncated: first.truncated,
+            session_id: first.session_id,
+            index: reserved_index & 0x000f_ffff,
+        },
+    ))
+}
+
+pub fn parse_erspan_type3_header(input: &[u8]) -> IResult<&[u8], ErspanTypeIIIHeader> {
+    let (rest, first) = first_word(input)?;
+    if first.version != VERSION_TYPE_III {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (rest, timestamp) = number::streaming::be_u32(rest)?;
+    let (rest, sgt) = number::streaming::be_u16(rest)?;
+    let (rest, flags) = number::streaming::be_u16(rest)?;
+    let (rest, platform) = if flags & 0x0001 != 0 {
+        let (rest, platform) = platform_sub_header(rest)?;
+        (rest, Some(platform))
+    } else {
+        (rest, None)
+    };
+
+    Ok((
+        rest,
+        ErspanTypeIIIHeader {
+            version: first.version,
+            vlan: first.vlan,
+            cos: first.cos,
+            bso: first.en_bso,
+            truncated: first.truncated,
+            session_id: first.session_id,
+            timestamp,
+            sgt,
+            flag_pdu: flags & 0x8000 != 0,
+            frame_type: ((flags >> 10) & 0b1_1111) as u8,
+            hardware_id: ((flags >> 4) & 0b11_1111) as u8,
+            direction: if flags & 0x0008 != 0 {
+                ErspanDirection::Egress
+            } else {
+                ErspanDirection::Ingress
+            },
+            granularity: ((flags >> 1) as u8).into(),
+            platform,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn erspan_type2_works() {
+        let bytes = [
+            0x10, 0x64, /* version 1, VLAN 100 */
+            0xa8, 0x2a, /* COS 5, En 1, T 0, session 42 */
+            0x00, 0x00, 0x00, 0x07, /* index */
+        ];
+        let expectation = ErspanTypeIIHeader {
+            version: 1,
+            vlan: 100,
+            cos: 5,
+            encapsulation: ErspanEncapsulation::IslTagged,
+            truncated: false,
+            session_id: 42,
+            index: 7,
+        };
+        assert_eq!(
+            parse_erspan_type2_header(&bytes),
+            Ok((EMPTY_SLICE, expectation))
+        );
+    }
+
+    #[test]
+    fn erspan_type3_works() {
+        let bytes = [
+            0x20, 0x0a, /* version 2, VLAN 10 */
+            0x04, 0x01, /* COS 0, BSO 0, T 1, session 1 */
+            0x12, 0x34, 0x56, 0x78, /* timestamp */
+            0x00, 0x10, /* SGT */
+            0x00, 0x2b, /* P 0, FT 0, Hw ID 2, D 1, Gra 1, O 1 */
+            0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, /* platform sub-header */
+        ];
+        let expectation = ErspanTypeIIIHeader {
+            version: 2,
+            vlan: 10,
+            cos: 0,
+            bso: 0,
+            truncated: true,
+            session_id: 1,
+            timestamp: 0x12345678,
+            sgt: 16,
+            flag_pdu: false,
+            frame_type: 0,
+            hardware_id: 2,
+            direction: ErspanDirection::Egress,
+            granularity: TimestampGranularity::Nanoseconds100,
+            platform: Some(PlatformSubHeader {
+                platform_id: 1,
+                info: 9,
+            }),
+        };
+        assert_eq!(
+            parse_erspan_type3_header(&bytes),
+            Ok((EMPTY_SLICE, expectation))
+        );
+    }
+}
@@ -17,8 +17,10 @@ pub enum EtherType {
     IPv4,
     ARP,
     WOL,
+    ERSPANIII,
     TRILL,
     DECnet,
+    TEB,
     RARP,
     AppleTalk,
     AARP,
@@ -27,6 +29,7 @@ pub enum EtherType {
     Qnet,
     IPv6,
     FlowControl,
+    SlowProtocols,
     CobraNet,
     MPLSuni,
     MPLSmulti,
@@ -42,6 +45,7 @@ pub enum EtherType {
     Powerlink,
     GOOSE,
     GSE,
+    ERSPAN,
     LLDP,
     SERCOS,
     HomePlugAV,
@@ -92,8 +96,10 @@ impl From<u16> for EtherType {
             0x0800 => Self::IPv4,           // Internet Protocol version 4 (IPv4)
             0x0806 => Self::ARP,            // Address Resolution Protocol (ARP)
             0x0842 => Self::WOL,            // Wake-on-LAN[4]
+            0x22EB => Self::ERSPANIII,      // ERSPAN Type III
             0x22F3 => Self::TRILL,          // IETF TRILL Protocol
             0x6003 => Self::DECnet,         // DECnet Phase IV
+            0x6558 => Self::TEB,            // Transparent Ethernet Bridging
             0x8035 => Self::RARP,           // Reverse Address Resolution Protocol
             0x809B => Self::AppleTalk,      // AppleTalk (Ethertalk)
             0x80F3 => Self::AARP,           // AppleTalk Address Resolution Protocol (AARP)
@@ -102,6 +108,7 @@ impl From<u16> for EtherType {
             0x8204 => Self::Qnet, // QNX Qnet
             0x86DD => Self::IPv6, // Internet Protocol Version 6 (IPv6)
             0x8808 => Self::FlowControl, // Ethernet flow control
+            0x8809 => Self::SlowProtocols, // Slow Protocols (IEEE 802.3), e.g. LACP
             0x8819 => Self::CobraNet, // CobraNet
             0x8847 => Self::MPLSuni, // MPLS unicast
             0x8848 => Self::MPLSmulti, // MPLS multicast
@@ -117,6 +124,7 @@ impl From<u16> for EtherType {
             0x88AB => Self::Powerlink, // Ethernet Powerlink[citation needed]
             0x88B8 => Self::GOOSE, // GOOSE (Generic Object Oriented Substation event)
             0x88B9 => Self::GSE,  // GSE (Generic Substation Events) Management Services
+            0x88BE => Self::ERSPAN, // ERSPAN Type I and Type II
             0x88CC => Self::LLDP, // Link Layer Discovery Protocol (LLDP)
             0x88CD => Self::SERCOS, // SERCOS III
             0x88E1 => Self::HomePlugAV, // HomePlug AV MME[citation needed]
@@ -138,6 +146,18 @@ impl From<u16> for EtherType {
     }
 }
 
+impl EtherType {
+    /// Whether the field holds the length of an IEEE 802.3 frame rather than an EtherType, in
+    /// which case an LLC header follows
+    pub fn is_length(&self) -> bool {
+        match self {
+            Self::LANMIN | Self::LANMAX => true,
+            Self::Other(raw) => *raw <= 0x05DC,
+            _ => false,
+        }
+    }
+}
+
 pub(crate) fn mac_address(input: &[u8]) -> IResult<&[u8], MacAddress> {
     let (input, mac) = bytes::streaming::take(6u8)(input)?;
 
@@ -207,7 +227,7 @@ mod tests {
         mac_address, parse_ethernet_frame, parse_ethertype, EtherType, EthernetFrame, MacAddress,
     };
 
-    const EMPTY_SLICE: &'static [u8] = &[];
+    const EMPTY_SLICE: &[u8] = &[];
 
     #[test]
     fn mac_address_works() {
@@ -232,6 +252,19 @@ mod tests {
     mk_ethertype_test!(ethertype_gets_arp_correct, [0x08, 0x06], EtherType::ARP);
     mk_ethertype_test!(ethertype_gets_ipv6_correct, [0x86, 0xDD], EtherType::IPv6);
     mk_ethertype_test!(ethertype_gets_vlan_correct, [0x81, 0x00], EtherType::VLAN);
+    mk_ethertype_test!(
+        ethertype_gets_slow_protocols_correct,
+        [0x88, 0x09],
+        EtherType::SlowProtocols
+    );
+
+    #[test]
+    fn ethertype_is_length_works() {
+        assert!(EtherType::from(0x0026).is_length());
+        assert!(EtherType::LANMAX.is_length());
+        assert!(!EtherType::from(0x0600).is_length());
+        assert!(!EtherType::LLDP.is_length());
+    }
 
     #[test]
     fn ethernet_frame_works() {
@@ -0,0 +1,354 @@
+//! Computes JA3/JA3S and JA4/JA4S fingerprints of TLS hellos
+
+use crate::tls::{self, ClientHello, ServerHello, TlsExtension, TlsHandshake, TlsMessage};
+use md5::Md5;
+use nom::IResult;
+use sha2::{Digest, Sha256};
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_ALPN: u16 = 16;
+
+/// Number of hex digits kept from the SHA-256 hashes in JA4 fingerprints
+const JA4_HASH_LENGTH: usize = 12;
+
+/// The transport carrying the handshake, which is the first character of a JA4 fingerprint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsTransport {
+    Tcp,
+    Quic,
+    Dtls,
+}
+
+/// A JA3 or JA3S fingerprint: the comma-separated string and its MD5 hash
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ja3Fingerprint {
+    pub full: String,
+    pub hash: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsFingerprint {
+    Client { ja3: Ja3Fingerprint, ja4: String },
+    Server { ja3s: Ja3Fingerprint, ja4s: String },
+}
+
+/// GREASE values (RFC 8701) are 0x?A?A with both bytes equal
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn join<T: ToString>(values: impl Iterator<Item = T>, separator: &str) -> String {
+    values
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn ja3_fingerprint(full: String) -> Ja3Fingerprint {
+    let hash = hex(&Md5::digest(full.as_bytes()));
+    Ja3Fingerprint { full, hash }
+}
+
+fn extension_types(extensions: &[TlsExtension]) -> impl Iterator<Item = u16> + '_ {
+    extensions
+        .iter()
+        .map(TlsExtension::extension_type)
+        .filter(|&t| !is_grease(t))
+}
+
+pub fn ja3(hello: &ClientHello) -> Ja3Fingerprint {
+    let mut groups = &[][..];
+    let mut point_formats = &[][..];
+    for extension in &hello.extensions {
+        match extension {
+            TlsExtension::SupportedGroups(list) => groups = list,
+            TlsExtension::EcPointFormats(list) => point_formats = list,
+            _ => (),
+        }
+    }
+
+    ja3_fingerprint(format!(
+        "{},{},{},{},{}",
+        u16::from(hello.version),
+        join(hello.cipher_suites.iter().filter(|&&c| !is_grease(c)), "-"),
+        join(extension_types(&hello.extensions), "-"),
+        join(groups.iter().filter(|&&g| !is_grease(g)), "-"),
+        join(point_formats.iter(), "-"),
+    ))
+}
+
+pub fn ja3s(hello: &ServerHello) -> Ja3Fingerprint {
+    ja3_fingerprint(format!(
+        "{},{},{}",
+        u16::from(hello.version),
+        hello.cipher_suite,
+        join(extension_types(&hello.extensions), "-"),
+    ))
+}
+
+fn transport_code(transport: TlsTransport) -> char {
+    match transport {
+        TlsTransport::Tcp => 't',
+        TlsTransport::Quic => 'q',
+        TlsTransport::Dtls => 'd',
+    }
+}
+
+/// The highest non-GREASE supported_versions entry, or else the legacy version
+fn version_code(legacy: tls::TlsVersion, extensions: &[TlsExtension]) -> &'static str {
+    let version = extensions
+        .iter()
+        .find_map(|extension| match extension {
+            TlsExtension::SupportedVersions(versions) => versions
+                .iter()
+                .map(|&v| u16::from(v))
+                .filter(|&v| !is_grease(v))
+                .max(),
+            _ => None,
+        })
+        .unwrap_or_else(|| u16::from(legacy));
+
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0002 => "s2",
+        0xfeff => "d1",
+        0xfefd => "d2",
+        0xfefc => "d3",
+        _ => "00",
+    }
+}
+
+/// First and last characters of the first ALPN value, or of its hex form when either is not
+/// alphanumeric
+fn alpn_code(extensions: &[TlsExtension]) -> String {
+    let alpn = extensions.iter().find_map(|extension| match extension {
+        TlsExtension::Alpn(protocols) => protocols.first(),
+        _ => None,
+    });
+    let alpn = match alpn {
+        Some(alpn) if !alpn.is_empty() => alpn,
+        _ => return "00".to_string(),
+    };
+
+    let first = alpn[0];
+    let last = alpn[alpn.len() - 1];
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        format!("{}{}", char::from(first), char::from(last))
+    } else {
+        let hex = hex(alpn);
+        format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+    }
+}
+
+fn truncated_sha256(input: &str) -> String {
+    if input.is_empty() {
+        return "0".repeat(JA4_HASH_LENGTH);
+    }
+    let mut hash = hex(&Sha256::digest(input.as_bytes()));
+    hash.truncate(JA4_HASH_LENGTH);
+    hash
+}
+
+fn hex_list(values: &[u16]) -> String {
+    join(values.iter().map(|v| format!("{:04x}", v)), ",")
+}
+
+pub fn ja4(hello: &ClientHello, transport: TlsTransport) -> String {
+    let mut ciphers: Vec<u16> = hello
+        .cipher_suites
+        .iter()
+        .copied()
+        .filter(|&c| !is_grease(c))
+        .collect();
+    let all_extensions: Vec<u16> = extension_types(&hello.extensions).collect();
+    let has_sni = all_extensions.contains(&EXT_SERVER_NAME);
+    let signature_algorithms = hello
+        .extensions
+        .iter()
+        .find_map(|extension| match extension {
+            TlsExtension::SignatureAlgorithms(list) => Some(list),
+            _ => None,
+        });
+
+    let a = format!(
+        "{}{}{}{:02}{:02}{}",
+        transport_code(transport),
+        version_code(hello.version, &hello.extensions),
+        if has_sni { 'd' } else { 'i' },
+        ciphers.len().min(99),
+        all_extensions.len().min(99),
+        alpn_code(&hello.extensions),
+    );
+
+    ciphers.sort_unstable();
+    let b = truncated_sha256(&hex_list(&ciphers));
+
+    let mut extensions: Vec<u16> = all_extensions
+        .into_iter()
+        .filter(|&t| t != EXT_SERVER_NAME && t != EXT_ALPN)
+        .collect();
+    extensions.sort_unstable();
+    let mut c = hex_list(&extensions);
+    if let Some(algorithms) = signature_algorithms {
+        let algorithms: Vec<u16> = algorithms
+            .iter()
+            .copied()
+            .filter(|&a| !is_grease(a))
+            .collect();
+        if !algorithms.is_empty() {
+            c.push('_');
+            c.push_str(&hex_list(&algorithms));
+        }
+    }
+    let c = if extensions.is_empty() {
+        "0".repeat(JA4_HASH_LENGTH)
+    } else {
+        truncated_sha256(&c)
+    };
+
+    format!("{}_{}_{}", a, b, c)
+}
+
+pub fn ja4s(hello: &ServerHello, transport: TlsTransport) -> String {
+    let extensions: Vec<u16> = extension_types(&hello.extensions).collect();
+
+    format!(
+        "{}{}{:02}{}_{:04x}_{}",
+        transport_code(transport),
+        version_code(hello.version, &hello.extensions),
+        extensions.len().min(99),
+        alpn_code(&hello.extensions),
+        hello.cipher_suite,
+        truncated_sha256(&hex_list(&extensions)),
+    )
+}
+
+/// Parses the TLS records in a reassembled TCP payload and fingerprints every ClientHello and
+/// ServerHello found in them
+pub fn parse_tls_fingerprints(input: &[u8]) -> IResult<&[u8], Vec<TlsFingerprint>> {
+    let (input, packet) = tls::parse_tls_packet(input)?;
+    let fingerprints = packet
+        .messages
+        .iter()
+        .filter_map(|message| match message {
+            TlsMessage::Handshake(TlsHandshake::ClientHello(hello)) => {
+                Some(TlsFingerprint::Client {
+                    ja3: ja3(hello),
+                    ja4: ja4(hello, TlsTransport::Tcp),
+                })
+            }
+            TlsMessage::Handshake(TlsHandshake::ServerHello(hello)) => {
+                Some(TlsFingerprint::Server {
+                    ja3s: ja3s(hello),
+                    ja4s: ja4s(hello, TlsTransport::Tcp),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok((input, fingerprints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{KeyShareEntry, TlsVersion};
+
+    fn client_hello() -> ClientHello {
+        ClientHello {
+            version: TlsVersion::Tls12,
+            random: [0; 32],
+            session_id: vec![],
+            cipher_suites: vec![0x2a2a, 0x1302, 0x1301, 0xc02f],
+            compression_methods: vec![0],
+            extensions: vec![
+                TlsExtension::Unknown {
+                    extension_type: 0x3a3a,
+                    data: vec![],
+                },
+                TlsExtension::ServerName(vec![b"example.com".to_vec()]),
+                TlsExtension::SupportedGroups(vec![0x4a4a, 0x001d, 0x0017]),
+                TlsExtension::EcPointFormats(vec![0]),
+                TlsExtension::SignatureAlgorithms(vec![0x0403, 0x0804]),
+                TlsExtension::Alpn(vec![b"h2".to_vec(), b"http/1.1".to_vec()]),
+                TlsExtension::SupportedVersions(vec![
+                    TlsVersion::Other(0x5a5a),
+                    TlsVersion::Tls13,
+                    TlsVersion::Tls12,
+                ]),
+                TlsExtension::KeyShare(vec![KeyShareEntry {
+                    group: 0x001d,
+                    key_exchange: vec![],
+                }]),
+            ],
+        }
+    }
+
+    #[test]
+    fn grease_detection_works() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[test]
+    fn ja3_and_ja4_work() {
+        let hello = client_hello();
+        assert_eq!(
+            ja3(&hello),
+            Ja3Fingerprint {
+                full: "771,4866-4865-49199,0-10-11-13-16-43-51,29-23,0".to_string(),
+                hash: "a4a83c9652197cc65948d93497d62c31".to_string(),
+            }
+        );
+        assert_eq!(
+            ja4(&hello, TlsTransport::Tcp),
+            "t13d0307h2_40b44b994229_078775ef5e04"
+        );
+    }
+
+    #[test]
+    fn ja4_non_ascii_alpn_works() {
+        let mut hello = client_hello();
+        for extension in &mut hello.extensions {
+            if let TlsExtension::Alpn(protocols) = extension {
+                // "é1" in UTF-8; the hex fallback uses the first and last bytes as sent
+                *protocols = vec![vec![0xc3, 0xa9, b'1']];
+            }
+        }
+        assert!(ja4(&hello, TlsTransport::Tcp).starts_with("t13d0307c1_"));
+    }
+
+    #[test]
+    fn ja3s_and_ja4s_work() {
+        let hello = ServerHello {
+            version: TlsVersion::Tls12,
+            random: [0; 32],
+            session_id: vec![],
+            cipher_suite: 0x1301,
+            compression_method: 0,
+            extensions: vec![
+                TlsExtension::SupportedVersions(vec![TlsVersion::Tls13]),
+                TlsExtension::KeyShare(vec![]),
+            ],
+        };
+        assert_eq!(ja3s(&hello).full, "771,4865,43-51");
+        assert_eq!(
+            ja4s(&hello, TlsTransport::Quic),
+            "q130200_1301_a56c5b993250"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Handles parsing of Geneve headers and options
+
+use crate::ethernet::{self, EtherType, VlanEthernetFrame};
+use crate::ipv4::{self, IPv4Header};
+use crate::ipv6::{self, IPv6Header};
+use nom::bytes;
+use nom::number;
+use nom::IResult;
+
+// Geneve Header Format (RFC 8926)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |Ver|  Opt Len  |O|C|    Rsvd.  |          Protocol Type        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |        Virtual Network Identifier (VNI)       |    Reserved   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Variable-Length Options                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// Geneve Option Format
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |          Option Class         |      Type     |R|R|R| Length  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                 Variable-Length Option Data                   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const GENEVE_PORT: u16 = 6081;
+
+const FLAG_OAM: u8 = 0x80;
+const FLAG_CRITICAL: u8 = 0x40;
+const OPTION_TYPE_CRITICAL: u8 = 0x80;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneveOption {
+    pub class: u16,
+    pub option_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl GeneveOption {
+    /// Whether a tunnel endpoint that does not understand this option must drop the packet
+    pub fn is_critical(&self) -> bool {
+        self.option_type & OPTION_TYPE_CRITICAL != 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneveHeader {
+    pub version: u8,
+    /// Length of the options, in 4-byte words
+    pub options_length: u8,
+    pub flag_oam: bool,
+    pub flag_critical: bool,
+    pub protocol_type: EtherType,
+    pub vni: u32,
+    pub options: Vec<GeneveOption>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GenevePayload {
+    Ethernet(VlanEthernetFrame),
+    IPv4(IPv4Header),
+    IPv6(IPv6Header),
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenevePacket {
+    pub header: GeneveHeader,
+    pub payload: GenevePayload,
+}
+
+fn geneve_parse_option(input: &[u8]) -> IResult<&[u8], GeneveOption> {
+    let (input, class) = number::complete::be_u16(input)?;
+    let (input, option_type) = number::complete::be_u8(input)?;
+    let (input, length) = number::complete::be_u8(input)?;
+    let (input, data) = bytes::complete::take(usize::from(length & 0b1_1111) * 4)(input)?;
+
+    Ok((
+        input,
+        GeneveOption {
+            class,
+            option_type,
+            data: data.to_vec(),
+        },
+    ))
+}
+
+/// Parses the options region of a Geneve header. The region is already sized by the header's
+/// option length, so an option running past its end is an error rather than `Incomplete`.
+fn geneve_parse_options(mut input: &[u8]) -> IResult<&[u8], Vec<GeneveOption>> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, option) = geneve_parse_option(input)?;
+        options.push(option);
+        input = rest;
+    }
+
+    Ok((input, options))
+}
+
+pub fn parse_geneve_header(input: &[u8]) -> IResult<&[u8], GeneveHeader> {
+    let (input, ver_opt_len) = number::streaming::be_u8(input)?;
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, protocol_type) = number::streaming::be_u16(input)?;
+    let (input, vni_reserved) = number::streaming::be_u32(input)?;
+    let options_length = ver_opt_len & 0b11_1111;
+    let (input, options) = bytes::streaming::take(usize::from(options_length) * 4)(input)?;
+    let (_, options) = geneve_parse_options(options)?;
+
+    Ok((
+        input,
+        GeneveHeader {
+            version: ver_opt_len >> 6,
+            options_length,
+            flag_oam: flags & FLAG_OAM != 0,
+            flag_critical: flags & FLAG_CRITICAL != 0,
+            protocol_type: protocol_type.into(),
+            vni: vni_reserved >> 8,
+            options,
+        },
+    ))
+}
+
+/// Parses a Geneve header and the header of the encapsulated packet, as selected by the Geneve
+/// protocol type. The remaining input is the payload of the innermost parsed header.
+pub fn parse_geneve_packet(input: &[u8]) -> IResult<&[u8], GenevePacket> {
+    let (input, header) = parse_geneve_header(input)?;
+
+    let (input, payload) = match header.protocol_type {
+        EtherType::TEB => {
+            let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+            (input, GenevePayload::Ethernet(frame))
+        }
+        EtherType::IPv4 => {
+            let (input, ip) = ipv4::parse_ipv4_header(input)?;
+            (input, GenevePayload::IPv4(ip))
+        }
+        EtherType::IPv6 => {
+            let (input, ip) = ipv6::parse_ipv6_header(input)?;
+            (input, GenevePayload::IPv6(ip))
+        }
+        _ => (input, GenevePayload::Other),
+    };
+
+    Ok((input, GenevePacket { header, payload }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::MacAddress;
+    use nom::error::{Error, ErrorKind};
+    use nom::Err;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn geneve_packet_with_options_works() {
+        let bytes = [
+            0x03, 0x40, 0x65, 0x58, /* version, option length, flags, protocol type */
+            0x00, 0x00, 0x2a, 0x00, /* VNI and reserved */
+            0x01, 0x02, 0x80, 0x01, /* option class, type and length */
+            0xde, 0xad, 0xbe, 0xef, /* option data */
+            0x01, 0x03, 0x01, 0x00, /* option class, type and length */
+            0x00, 0x23, 0x54, 0x07, 0x93, 0x6c, /* dest MAC */
+            0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, /* src MAC */
+            0x86, 0xdd, /* Ethertype */
+        ];
+        let expectation = GenevePacket {
+            header: GeneveHeader {
+                version: 0,
+                options_length: 3,
+                flag_oam: false,
+                flag_critical: true,
+                protocol_type: EtherType::TEB,
+                vni: 42,
+                options: vec![
+                    GeneveOption {
+                        class: 0x0102,
+                        option_type: 0x80,
+                        data: vec![0xde, 0xad, 0xbe, 0xef],
+                    },
+                    GeneveOption {
+                        class: 0x0103,
+                        option_type: 0x01,
+                        data: vec![],
+                    },
+                ],
+            },
+            payload: GenevePayload::Ethernet(VlanEthernetFrame {
+                source_mac: MacAddress([0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b]),
+                dest_mac: MacAddress([0x00, 0x23, 0x54, 0x07, 0x93, 0x6c]),
+                ethertype: EtherType::IPv6,
+                vid: None,
+            }),
+        };
+        let parsed = parse_geneve_packet(&bytes);
+        assert_eq!(parsed, Ok((EMPTY_SLICE, expectation)));
+        assert!(parsed.unwrap().1.header.options[0].is_critical());
+    }
+
+    #[test]
+    fn geneve_option_overrunning_options_region_fails() {
+        let bytes = [
+            0x02, 0x00, 0x65, 0x58, /* version, option length, flags, protocol type */
+            0x00, 0x00, 0x2a, 0x00, /* VNI and reserved */
+            0x01, 0x02, 0x80, 0x05, /* option class, type and length */
+            0xde, 0xad, 0xbe, 0xef, /* option data */
+            0x00, 0x23, 0x54, 0x07, 0x93, 0x6c, /* dest MAC */
+            0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, /* src MAC */
+            0x86, 0xdd, /* Ethertype */
+        ];
+        assert!(matches!(
+            parse_geneve_packet(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+//! Handles parsing of GRE headers and dispatching of their payload
+
+use crate::erspan::{self, ErspanHeader};
+use crate::ethernet::{self, EtherType, VlanEthernetFrame};
+use crate::ipv4::{self, IPv4Header};
+use crate::ipv6::{self, IPv6Header};
+use nom::bytes;
+use nom::number;
+use nom::IResult;
+
+// GRE Header Format (RFC 2784, RFC 2890)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |C|R|K|S|s|Recur|A| Flags | Ver |         Protocol Type         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |      Checksum (optional)      |       Offset (optional)       |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                         Key (optional)                        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                 Sequence Number (optional)                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |             Acknowledgment Number (optional, version 1)       |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Routing (optional, RFC 1701)               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const FLAG_CHECKSUM: u16 = 0x8000;
+const FLAG_ROUTING: u16 = 0x4000;
+const FLAG_KEY: u16 = 0x2000;
+const FLAG_SEQUENCE: u16 = 0x1000;
+const FLAG_ACK: u16 = 0x0080;
+const VERSION_MASK: u16 = 0x0007;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GreHeader {
+    pub flag_checksum: bool,
+    pub flag_routing: bool,
+    pub flag_key: bool,
+    pub flag_sequence: bool,
+    pub flag_ack: bool,
+    pub version: u8,
+    pub protocol_type: EtherType,
+    pub checksum: Option<u16>,
+    pub offset: Option<u16>,
+    pub key: Option<u32>,
+    pub sequence_no: Option<u32>,
+    pub ack_no: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GrePayload {
+    Ethernet(VlanEthernetFrame),
+    /// The mirrored frame is `None` when a Type III header says it is not Ethernet
+    Erspan(ErspanHeader, Option<VlanEthernetFrame>),
+    IPv4(IPv4Header),
+    IPv6(IPv6Header),
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GrePacket {
+    pub header: GreHeader,
+    pub payload: GrePayload,
+}
+
+fn optional_u32(input: &[u8], present: bool) -> IResult<&[u8], Option<u32>> {
+    if present {
+        let (input, value) = number::streaming::be_u32(input)?;
+        Ok((input, Some(value)))
+    } else {
+        Ok((input, None))
+    }
+}
+
+/// Skips the RFC 1701 source route entries, up to and including the terminating NULL entry.
+fn skip_routing(mut input: &[u8]) -> IResult<&[u8], ()> {
+    loop {
+        let (rest, address_family) = number::streaming::be_u16(input)?;
+        let (rest, _sre_offset) = number::streaming::be_u8(rest)?;
+        let (rest, sre_length) = number::streaming::be_u8(rest)?;
+        let (rest, _) = bytes::streaming::take(sre_length)(rest)?;
+        input = rest;
+        if address_family == 0 && sre_length == 0 {
+            return Ok((input, ()));
+        }
+    }
+}
+
+pub fn parse_gre_header(input: &[u8]) -> IResult<&[u8], GreHeader> {
+    let (input, flags_version) = number::streaming::be_u16(input)?;
+    let (input, protocol_type) = number::streaming::be_u16(input)?;
+
+    let flag_checksum = flags_version & FLAG_CHECKSUM != 0;
+    let flag_routing = flags_version & FLAG_ROUTING != 0;
+    let flag_key = flags_version & FLAG_KEY != 0;
+    let flag_sequence = flags_version & FLAG_SEQUENCE != 0;
+    let flag_ack = flags_version & FLAG_ACK != 0;
+
+    // Checksum and offset are both present if either of the C or R bits is set
+    let (input, checksum, offset) = if flag_checksum || flag_routing {
+        let (input, checksum) = number::streaming::be_u16(input)?;
+        let (input, offset) = number::streaming::be_u16(input)?;
+        (input, Some(checksum), Some(offset))
+    } else {
+        (input, None, None)
+    };
+    let (input, key) = optional_u32(input, flag_key)?;
+    let (input, sequence_no) = optional_u32(input, flag_sequence)?;
+    let (input, ack_no) = optional_u32(input, flag_ack)?;
+    let (input, _) = if flag_routing {
+        skip_routing(input)?
+    } else {
+        (input, ())
+    };
+
+    Ok((
+        input,
+        GreHeader {
+            flag_checksum,
+            flag_routing,
+            flag_key,
+            flag_sequence,
+            flag_ack,
+            version: (flags_version & VERSION_MASK) as u8,
+            protocol_type: protocol_type.into(),
+            checksum,
+            offset,
+            key,
+            sequence_no,
+            ack_no,
+        },
+    ))
+}
+
+/// Parses a GRE header and the header of the encapsulated packet, as selected by the GRE
+/// protocol type. ERSPAN headers are decoded and the mirrored Ethernet frame following them is
+/// parsed; other mirrored frames are left in the remaining input. The remaining input is the
+/// payload of the innermost parsed header.
+pub fn parse_gre_packet(input: &[u8]) -> IResult<&[u8], GrePacket> {
+    let (input, header) = parse_gre_header(input)?;
+
+    let (input, payload) = match header.protocol_type {
+        EtherType::TEB => {
+            let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+            (input, GrePayload::Ethernet(frame))
+        }
+        EtherType::ERSPAN if header.flag_sequence => {
+            let (input, erspan) = erspan::parse_erspan_type2_header(input)?;
+            let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+            (
+                input,
+                GrePayload::Erspan(ErspanHeader::TypeII(erspan), Some(frame)),
+            )
+        }
+        EtherType::ERSPAN => {
+            let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+            (input, GrePayload::Erspan(ErspanHeader::TypeI, Some(frame)))
+        }
+        EtherType::ERSPANIII => {
+            let (input, erspan) = erspan::parse_erspan_type3_header(input)?;
+            let (input, frame) = if erspan.frame_type == erspan::FRAME_TYPE_ETHERNET {
+                let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+                (input, Some(frame))
+            } else {
+                (input, None)
+            };
+            (
+                input,
+                GrePayload::Erspan(ErspanHeader::TypeIII(erspan), frame),
+            )
+        }
+        EtherType::IPv4 => {
+            let (input, ip) = ipv4::parse_ipv4_header(input)?;
+            (input, GrePayload::IPv4(ip))
+        }
+        EtherType::IPv6 => {
+            let (input, ip) = ipv6::parse_ipv6_header(input)?;
+            (input, GrePayload::IPv6(ip))
+        }
+        _ => (input, GrePayload::Other),
+    };
+
+    Ok((input, GrePacket { header, payload }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::erspan::{ErspanEncapsulation, ErspanTypeIIHeader};
+    use crate::ethernet::MacAddress;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn gre_header_with_all_fields_works() {
+        let bytes = [
+            0xb0, 0x00, /* C, K and S flags, version 0 */
+            0x08, 0x00, /* protocol type */
+            0xab, 0xcd, 0x00, 0x00, /* checksum and offset */
+            0x00, 0x00, 0x00, 0x2a, /* key */
+            0x00, 0x00, 0x01, 0x00, /* sequence number */
+        ];
+        let expectation = GreHeader {
+            flag_checksum: true,
+            flag_routing: false,
+            flag_key: true,
+            flag_sequence: true,
+            flag_ack: false,
+            version: 0,
+            protocol_type: EtherType::IPv4,
+            checksum: Some(0xabcd),
+            offset: Some(0),
+            key: Some(42),
+            sequence_no: Some(256),
+            ack_no: None,
+        };
+        assert_eq!(parse_gre_header(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn gre_erspan_type2_packet_works() {
+        let bytes = [
+            0x10, 0x00, /* S flag, version 0 */
+            0x88, 0xbe, /* protocol type */
+            0x00, 0x00, 0x00, 0x05, /* sequence number */
+            0x10, 0x64, 0x00, 0x2a, /* ERSPAN version, VLAN, COS, En, T, session ID */
+            0x00, 0x00, 0x00, 0x00, /* ERSPAN reserved and index */
+            0x00, 0x23, 0x54, 0x07, 0x93, 0x6c, /* dest MAC */
+            0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, /* src MAC */
+            0x08, 0x06, /* Ethertype */
+        ];
+        let expectation = GrePacket {
+            header: GreHeader {
+                flag_checksum: false,
+                flag_routing: false,
+                flag_key: false,
+                flag_sequence: true,
+                flag_ack: false,
+                version: 0,
+                protocol_type: EtherType::ERSPAN,
+                checksum: None,
+                offset: None,
+                key: None,
+                sequence_no: Some(5),
+                ack_no: None,
+            },
+            payload: GrePayload::Erspan(
+                ErspanHeader::TypeII(ErspanTypeIIHeader {
+                    version: 1,
+                    vlan: 100,
+                    cos: 0,
+                    encapsulation: ErspanEncapsulation::NotTagged,
+                    truncated: false,
+                    session_id: 42,
+                    index: 0,
+                }),
+                Some(VlanEthernetFrame {
+                    source_mac: MacAddress([0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b]),
+                    dest_mac: MacAddress([0x00, 0x23, 0x54, 0x07, 0x93, 0x6c]),
+                    ethertype: EtherType::ARP,
+                    vid: None,
+                }),
+            ),
+        };
+        assert_eq!(parse_gre_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn gre_erspan_type3_ip_frame_is_left_raw() {
+        let bytes = [
+            0x00, 0x00, /* no flags, version 0 */
+            0x22, 0xeb, /* protocol type */
+            0x20, 0x0a, 0x00, 0x01, /* ERSPAN version, VLAN, COS, BSO, T, session ID */
+            0x12, 0x34, 0x56, 0x78, /* timestamp */
+            0x00, 0x10, 0x08, 0x00, /* SGT, P 0, FT 2 */
+            0x45, 0x00, 0x00, 0x14, /* mirrored IPv4 packet */
+        ];
+        let (remaining, packet) = parse_gre_packet(&bytes).unwrap();
+        assert_eq!(remaining, &[0x45, 0x00, 0x00, 0x14]);
+        match packet.payload {
+            GrePayload::Erspan(ErspanHeader::TypeIII(erspan), None) => {
+                assert_eq!(erspan.frame_type, 2);
+                assert_eq!(erspan.session_id, 1);
+            }
+            _ => panic!(),
+        }
+    }
+}
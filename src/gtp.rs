@@ -0,0 +1,686 @@
+//! Handles parsing of GTPv1-U and GTPv2-C headers
+
+use crate::ip::{self, IPHeader};
+use crate::ipv4;
+use crate::ipv6;
+use nom::bytes;
+use nom::combinator;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// GTPv1 Header Format (3GPP TS 29.281)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | Ver |P|R|E|S|N| Message Type  |            Length             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |            Tunnel Endpoint Identifier (TEID)                  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |        Sequence Number        |  N-PDU Number |Next Ext. Type |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// GTPv2-C Header Format (3GPP TS 29.274)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | Ver |P|T|M|Spr| Message Type  |            Length             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |            Tunnel Endpoint Identifier (if T = 1)              |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                Sequence Number                |Prio.  | Spare |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const GTPU_PORT: u16 = 2152;
+pub const GTPC_PORT: u16 = 2123;
+
+const FLAG_PROTOCOL_TYPE: u8 = 0x10;
+const FLAG_EXTENSION: u8 = 0x04;
+const FLAG_SEQUENCE: u8 = 0x02;
+const FLAG_NPDU: u8 = 0x01;
+
+const FLAG_PIGGYBACK: u8 = 0x10;
+const FLAG_TEID: u8 = 0x08;
+const FLAG_PRIORITY: u8 = 0x04;
+
+const EXT_PDU_SESSION_CONTAINER: u8 = 0x85;
+const EXT_PDCP_PDU_NUMBER: u8 = 0xc0;
+const EXT_UDP_PORT: u8 = 0x40;
+
+const IE_IMSI: u8 = 1;
+const IE_CAUSE: u8 = 2;
+const IE_RECOVERY: u8 = 3;
+const IE_APN: u8 = 71;
+const IE_EBI: u8 = 73;
+const IE_MEI: u8 = 75;
+const IE_MSISDN: u8 = 76;
+const IE_RAT_TYPE: u8 = 82;
+const IE_FTEID: u8 = 87;
+const IE_BEARER_CONTEXT: u8 = 93;
+const IE_PDN_CONNECTION: u8 = 109;
+const IE_OVERLOAD_CONTROL_INFO: u8 = 180;
+const IE_LOAD_CONTROL_INFO: u8 = 181;
+const IE_REMOTE_UE_CONTEXT: u8 = 191;
+const IE_SCEF_PDN_CONNECTION: u8 = 195;
+
+/// Grouped IEs nested deeper than this are rejected
+const MAX_GROUPED_IE_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GtpV1MessageType {
+    EchoRequest,
+    EchoResponse,
+    ErrorIndication,
+    SupportedExtensionHeadersNotification,
+    EndMarker,
+    GPdu,
+    Other(u8),
+}
+
+impl From<u8> for GtpV1MessageType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::EchoRequest,
+            2 => Self::EchoResponse,
+            26 => Self::ErrorIndication,
+            31 => Self::SupportedExtensionHeadersNotification,
+            254 => Self::EndMarker,
+            255 => Self::GPdu,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PduType {
+    DownlinkPduSessionInformation,
+    UplinkPduSessionInformation,
+    Other(u8),
+}
+
+impl From<u8> for PduType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::DownlinkPduSessionInformation,
+            1 => Self::UplinkPduSessionInformation,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// PDU Session Container extension header (3GPP TS 38.415)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PduSessionContainer {
+    pub pdu_type: PduType,
+    pub qfi: u8,
+    /// Reflective QoS Indicator, only meaningful in downlink containers
+    pub rqi: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GtpExtensionHeader {
+    PduSessionContainer(PduSessionContainer),
+    PdcpPduNumber(u16),
+    UdpPort(u16),
+    Other { header_type: u8, content: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GtpV1Header {
+    pub version: u8,
+    /// Set for GTP, unset for GTP'
+    pub flag_protocol_type: bool,
+    pub flag_extension: bool,
+    pub flag_sequence: bool,
+    pub flag_npdu: bool,
+    pub message_type: GtpV1MessageType,
+    pub length: u16,
+    pub teid: u32,
+    pub sequence_no: Option<u16>,
+    pub npdu_number: Option<u8>,
+    pub extension_headers: Vec<GtpExtensionHeader>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GtpuPacket {
+    pub header: GtpV1Header,
+    /// The header of the user packet carried by a G-PDU
+    pub inner: Option<IPHeader>,
+}
+
+fn gtp_extension_header(input: &[u8], header_type: u8) -> IResult<&[u8], (GtpExtensionHeader, u8)> {
+    let (input, length) = number::streaming::be_u8(input)?;
+    if length == 0 {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (input, content) = bytes::streaming::take(usize::from(length) * 4 - 2)(input)?;
+    let (input, next_type) = number::streaming::be_u8(input)?;
+
+    let header = match header_type {
+        EXT_PDU_SESSION_CONTAINER if content.len() >= 2 => {
+            GtpExtensionHeader::PduSessionContainer(PduSessionContainer {
+                pdu_type: (content[0] >> 4).into(),
+                qfi: content[1] & 0x3f,
+                rqi: content[0] >> 4 == 0 && content[1] & 0x40 != 0,
+            })
+        }
+        EXT_PDCP_PDU_NUMBER if content.len() >= 2 => {
+            GtpExtensionHeader::PdcpPduNumber(u16::from_be_bytes([content[0], content[1]]))
+        }
+        EXT_UDP_PORT if content.len() >= 2 => {
+            GtpExtensionHeader::UdpPort(u16::from_be_bytes([content[0], content[1]]))
+        }
+        _ => GtpExtensionHeader::Other {
+            header_type,
+            content: content.to_vec(),
+        },
+    };
+
+    Ok((input, (header, next_type)))
+}
+
+/// Parses a GTPv1 header. The length field bounds the optional fields, the extension headers and
+/// the payload, so the remaining input is the payload and ends where the message ends.
+pub fn parse_gtpv1_header(input: &[u8]) -> IResult<&[u8], GtpV1Header> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, message_type) = number::streaming::be_u8(input)?;
+    let (input, length) = number::streaming::be_u16(input)?;
+    let (input, teid) = number::streaming::be_u32(input)?;
+    let (_, mut input) = bytes::streaming::take(length)(input)?;
+
+    let mut header = GtpV1Header {
+        version: flags >> 5,
+        flag_protocol_type: flags & FLAG_PROTOCOL_TYPE != 0,
+        flag_extension: flags & FLAG_EXTENSION != 0,
+        flag_sequence: flags & FLAG_SEQUENCE != 0,
+        flag_npdu: flags & FLAG_NPDU != 0,
+        message_type: message_type.into(),
+        length,
+        teid,
+        sequence_no: None,
+        npdu_number: None,
+        extension_headers: vec![],
+    };
+
+    // The optional fields are all present as soon as any of the E, S or PN flags is set
+    if header.flag_extension || header.flag_sequence || header.flag_npdu {
+        let (rest, sequence_no) = number::complete::be_u16(input)?;
+        let (rest, npdu_number) = number::complete::be_u8(rest)?;
+        let (rest, mut next_type) = number::complete::be_u8(rest)?;
+        input = rest;
+        if header.flag_sequence {
+            header.sequence_no = Some(sequence_no);
+        }
+        if header.flag_npdu {
+            header.npdu_number = Some(npdu_number);
+        }
+        while header.flag_extension && next_type != 0 {
+            let (rest, (extension_header, next)) =
+                combinator::complete(|input| gtp_extension_header(input, next_type))(input)?;
+            header.extension_headers.push(extension_header);
+            next_type = next;
+            input = rest;
+        }
+    }
+
+    Ok((input, header))
+}
+
+/// Parses a GTPv1-U header and, for G-PDUs, the header of the encapsulated IPv4 or IPv6 packet.
+/// The remaining input is the payload of the innermost parsed header.
+pub fn parse_gtpu_packet(input: &[u8]) -> IResult<&[u8], GtpuPacket> {
+    let (input, header) = parse_gtpv1_header(input)?;
+    let (input, inner) = match header.message_type {
+        GtpV1MessageType::GPdu => {
+            let (input, inner) = combinator::complete(ip::parse_ip_header)(input)?;
+            (input, Some(inner))
+        }
+        _ => (input, None),
+    };
+
+    Ok((input, GtpuPacket { header, inner }))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GtpV2MessageType {
+    EchoRequest,
+    EchoResponse,
+    VersionNotSupportedIndication,
+    CreateSessionRequest,
+    CreateSessionResponse,
+    ModifyBearerRequest,
+    ModifyBearerResponse,
+    DeleteSessionRequest,
+    DeleteSessionResponse,
+    ChangeNotificationRequest,
+    ChangeNotificationResponse,
+    CreateBearerRequest,
+    CreateBearerResponse,
+    UpdateBearerRequest,
+    UpdateBearerResponse,
+    DeleteBearerRequest,
+    DeleteBearerResponse,
+    ReleaseAccessBearersRequest,
+    ReleaseAccessBearersResponse,
+    DownlinkDataNotification,
+    DownlinkDataNotificationAcknowledge,
+    Other(u8),
+}
+
+impl From<u8> for GtpV2MessageType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::EchoRequest,
+            2 => Self::EchoResponse,
+            3 => Self::VersionNotSupportedIndication,
+            32 => Self::CreateSessionRequest,
+            33 => Self::CreateSessionResponse,
+            34 => Self::ModifyBearerRequest,
+            35 => Self::ModifyBearerResponse,
+            36 => Self::DeleteSessionRequest,
+            37 => Self::DeleteSessionResponse,
+            38 => Self::ChangeNotificationRequest,
+            39 => Self::ChangeNotificationResponse,
+            95 => Self::CreateBearerRequest,
+            96 => Self::CreateBearerResponse,
+            97 => Self::UpdateBearerRequest,
+            98 => Self::UpdateBearerResponse,
+            99 => Self::DeleteBearerRequest,
+            100 => Self::DeleteBearerResponse,
+            170 => Self::ReleaseAccessBearersRequest,
+            171 => Self::ReleaseAccessBearersResponse,
+            176 => Self::DownlinkDataNotification,
+            177 => Self::DownlinkDataNotificationAcknowledge,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Fully Qualified TEID
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FTeid {
+    pub interface_type: u8,
+    pub teid: u32,
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GtpV2IeValue {
+    Imsi(String),
+    Cause(u8),
+    Recovery(u8),
+    Apn(String),
+    Ebi(u8),
+    Mei(String),
+    Msisdn(String),
+    RatType(u8),
+    FTeid(FTeid),
+    /// Grouped IEs such as Bearer Context, holding their own list of IEs
+    Grouped(Vec<GtpV2Ie>),
+    Raw(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GtpV2Ie {
+    pub ie_type: u8,
+    pub instance: u8,
+    pub value: GtpV2IeValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GtpV2Header {
+    pub version: u8,
+    pub flag_piggyback: bool,
+    pub flag_teid: bool,
+    pub flag_priority: bool,
+    pub message_type: GtpV2MessageType,
+    pub length: u16,
+    pub teid: Option<u32>,
+    pub sequence_no: u32,
+    pub priority: Option<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GtpV2Message {
+    pub header: GtpV2Header,
+    pub ies: Vec<GtpV2Ie>,
+}
+
+/// Decodes TBCD-encoded digits (IMSI, MSISDN, MEI), stopping at the filler nibble
+fn tbcd_digits(value: &[u8]) -> String {
+    let mut digits = String::with_capacity(value.len() * 2);
+    for nibble in value.iter().flat_map(|b| [b & 0x0f, b >> 4]) {
+        if nibble > 9 {
+            break;
+        }
+        digits.push(char::from(b'0' + nibble));
+    }
+    digits
+}
+
+/// Decodes a DNS-style label sequence into a dotted name
+fn apn(value: &[u8]) -> String {
+    let mut labels = vec![];
+    let mut rest = value;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = usize::from(len).min(tail.len());
+        labels.push(String::from_utf8_lossy(&tail[..len]).into_owned());
+        rest = &tail[len..];
+    }
+    labels.join(".")
+}
+
+fn fteid(value: &[u8]) -> IResult<&[u8], FTeid> {
+    let (value, flags) = number::complete::be_u8(value)?;
+    let (value, teid) = number::complete::be_u32(value)?;
+    let (value, ipv4) = if flags & 0x80 != 0 {
+        let (value, address) = combinator::complete(ipv4::address)(value)?;
+        (value, Some(address))
+    } else {
+        (value, None)
+    };
+    let (value, ipv6) = if flags & 0x40 != 0 {
+        let (value, address) = combinator::complete(ipv6::address)(value)?;
+        (value, Some(address))
+    } else {
+        (value, None)
+    };
+
+    Ok((
+        value,
+        FTeid {
+            interface_type: flags & 0x3f,
+            teid,
+            ipv4,
+            ipv6,
+        },
+    ))
+}
+
+fn gtpv2_ie_value(ie_type: u8, value: &[u8], depth: usize) -> IResult<&[u8], GtpV2IeValue> {
+    let parsed = match ie_type {
+        IE_IMSI => GtpV2IeValue::Imsi(tbcd_digits(value)),
+        IE_CAUSE if !value.is_empty() => GtpV2IeValue::Cause(value[0]),
+        IE_RECOVERY if !value.is_empty() => GtpV2IeValue::Recovery(value[0]),
+        IE_APN => GtpV2IeValue::Apn(apn(value)),
+        IE_EBI if !value.is_empty() => GtpV2IeValue::Ebi(value[0] & 0x0f),
+        IE_MEI => GtpV2IeValue::Mei(tbcd_digits(value)),
+        IE_MSISDN => GtpV2IeValue::Msisdn(tbcd_digits(value)),
+        IE_RAT_TYPE if !value.is_empty() => GtpV2IeValue::RatType(value[0]),
+        IE_FTEID => GtpV2IeValue::FTeid(fteid(value)?.1),
+        IE_BEARER_CONTEXT
+        | IE_PDN_CONNECTION
+        | IE_OVERLOAD_CONTROL_INFO
+        | IE_LOAD_CONTROL_INFO
+        | IE_REMOTE_UE_CONTEXT
+        | IE_SCEF_PDN_CONNECTION => GtpV2IeValue::Grouped(gtpv2_ies(value, depth + 1)?.1),
+        _ => GtpV2IeValue::Raw(value.to_vec()),
+    };
+
+    Ok((&value[value.len()..], parsed))
+}
+
+// IEs are always parsed out of a message body whose length is already known
+fn gtpv2_ie(input: &[u8], depth: usize) -> IResult<&[u8], GtpV2Ie> {
+    let (input, ie_type) = number::complete::be_u8(input)?;
+    let (input, length) = number::complete::be_u16(input)?;
+    let (input, instance) = number::complete::be_u8(input)?;
+    let (input, value) = bytes::complete::take(length)(input)?;
+    let (_, value) = gtpv2_ie_value(ie_type, value, depth)?;
+
+    Ok((
+        input,
+        GtpV2Ie {
+            ie_type,
+            instance: instance & 0x0f,
+            value,
+        },
+    ))
+}
+
+fn gtpv2_ies(mut input: &[u8], depth: usize) -> IResult<&[u8], Vec<GtpV2Ie>> {
+    if depth > MAX_GROUPED_IE_DEPTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::TooLarge)));
+    }
+
+    let mut ies = vec![];
+    while !input.is_empty() {
+        let (rest, ie) = gtpv2_ie(input, depth)?;
+        ies.push(ie);
+        input = rest;
+    }
+
+    Ok((input, ies))
+}
+
+/// Walks a list of GTPv2 IEs until the input is exhausted
+pub fn parse_gtpv2_ies(input: &[u8]) -> IResult<&[u8], Vec<GtpV2Ie>> {
+    gtpv2_ies(input, 0)
+}
+
+pub fn parse_gtpv2_header(input: &[u8]) -> IResult<&[u8], GtpV2Header> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, message_type) = number::streaming::be_u8(input)?;
+    let (input, length) = number::streaming::be_u16(input)?;
+    let flag_teid = flags & FLAG_TEID != 0;
+    let flag_priority = flags & FLAG_PRIORITY != 0;
+    let (input, teid) = if flag_teid {
+        let (input, teid) = number::streaming::be_u32(input)?;
+        (input, Some(teid))
+    } else {
+        (input, None)
+    };
+    let (input, sequence_no) = number::streaming::be_u24(input)?;
+    let (input, priority_spare) = number::streaming::be_u8(input)?;
+
+    Ok((
+        input,
+        GtpV2Header {
+            version: flags >> 5,
+            flag_piggyback: flags & FLAG_PIGGYBACK != 0,
+            flag_teid,
+            flag_priority,
+            message_type: message_type.into(),
+            length,
+            teid,
+            sequence_no,
+            priority: if flag_priority {
+                Some(priority_spare >> 4)
+            } else {
+                None
+            },
+        },
+    ))
+}
+
+/// Parses a GTPv2-C message and its IEs. The remaining input starts after the message, which
+/// is where a piggybacked message would be.
+pub fn parse_gtpv2_message(input: &[u8]) -> IResult<&[u8], GtpV2Message> {
+    let (rest, header) = parse_gtpv2_header(input)?;
+    // The length counts everything after the first 4 octets
+    let header_length = if header.flag_teid { 8 } else { 4 };
+    let ies_length = usize::from(header.length)
+        .checked_sub(header_length)
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::LengthValue)))?;
+    let (rest, ies) = bytes::streaming::take(ies_length)(rest)?;
+    let (_, ies) = parse_gtpv2_ies(ies)?;
+
+    Ok((rest, GtpV2Message { header, ies }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip::IPProtocol;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn gtpu_gpdu_with_pdu_session_container_works() {
+        let bytes = [
+            0x34, 0xff, 0x00, 0x1c, /* flags, message type, length */
+            0x00, 0x00, 0x00, 0x01, /* TEID */
+            0x00, 0x00, 0x00, 0x85, /* sequence, N-PDU, next extension type */
+            0x01, 0x10, 0x09, 0x00, /* length, UL PDU session information, QFI 9 */
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, /* inner IPv4 header */
+            0x40, 0x11, 0x00, 0x00, 0x0a, 0x2d, 0x00, 0x01, /* ... */
+            0x08, 0x08, 0x08, 0x08, /* ... */
+        ];
+        let (remaining, packet) = parse_gtpu_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(
+            packet.header,
+            GtpV1Header {
+                version: 1,
+                flag_protocol_type: true,
+                flag_extension: true,
+                flag_sequence: false,
+                flag_npdu: false,
+                message_type: GtpV1MessageType::GPdu,
+                length: 28,
+                teid: 1,
+                sequence_no: None,
+                npdu_number: None,
+                extension_headers: vec![GtpExtensionHeader::PduSessionContainer(
+                    PduSessionContainer {
+                        pdu_type: PduType::UplinkPduSessionInformation,
+                        qfi: 9,
+                        rqi: false,
+                    }
+                )],
+            }
+        );
+        match packet.inner {
+            Some(IPHeader::V4(header)) => {
+                assert_eq!(header.protocol, IPProtocol::UDP);
+                assert_eq!(header.dest_addr, Ipv4Addr::new(8, 8, 8, 8));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn gtpv2_create_session_request_works() {
+        let bytes = [
+            0x48, 0x20, 0x00, 0x2a, /* flags, message type, length */
+            0x00, 0x00, 0x00, 0x00, /* TEID */
+            0x00, 0x00, 0x2a, 0x00, /* sequence, spare */
+            0x01, 0x00, 0x08, 0x00, /* IMSI IE */
+            0x21, 0x43, 0x65, 0x87, 0x09, 0x21, 0x43, 0xf5, /* ... */
+            0x5d, 0x00, 0x12, 0x00, /* Bearer Context IE */
+            0x49, 0x00, 0x01, 0x00, 0x05, /* EBI IE */
+            0x57, 0x00, 0x09, 0x02, /* F-TEID IE, instance 2 */
+            0x81, 0x00, 0x00, 0x10, 0x00, 0xc0, 0xa8, 0x00, 0x01, /* ... */
+        ];
+        let expectation = GtpV2Message {
+            header: GtpV2Header {
+                version: 2,
+                flag_piggyback: false,
+                flag_teid: true,
+                flag_priority: false,
+                message_type: GtpV2MessageType::CreateSessionRequest,
+                length: 42,
+                teid: Some(0),
+                sequence_no: 42,
+                priority: None,
+            },
+            ies: vec![
+                GtpV2Ie {
+                    ie_type: IE_IMSI,
+                    instance: 0,
+                    value: GtpV2IeValue::Imsi("123456789012345".to_string()),
+                },
+                GtpV2Ie {
+                    ie_type: IE_BEARER_CONTEXT,
+                    instance: 0,
+                    value: GtpV2IeValue::Grouped(vec![
+                        GtpV2Ie {
+                            ie_type: IE_EBI,
+                            instance: 0,
+                            value: GtpV2IeValue::Ebi(5),
+                        },
+                        GtpV2Ie {
+                            ie_type: IE_FTEID,
+                            instance: 2,
+                            value: GtpV2IeValue::FTeid(FTeid {
+                                interface_type: 1,
+                                teid: 0x1000,
+                                ipv4: Some(Ipv4Addr::new(192, 168, 0, 1)),
+                                ipv6: None,
+                            }),
+                        },
+                    ]),
+                },
+            ],
+        };
+        assert_eq!(parse_gtpv2_message(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn gtpv2_deeply_nested_grouped_ies_are_rejected() {
+        // Bearer Context IEs, each wrapping the next, with an EBI at the bottom
+        let mut bytes = vec![0x49, 0x00, 0x01, 0x00, 0x05];
+        for _ in 0..MAX_GROUPED_IE_DEPTH {
+            let length = bytes.len() as u16;
+            let mut ie = vec![IE_BEARER_CONTEXT, (length >> 8) as u8, length as u8, 0x00];
+            ie.extend(bytes);
+            bytes = ie;
+        }
+        assert!(parse_gtpv2_ies(&bytes).is_ok());
+
+        let length = bytes.len() as u16;
+        let mut ie = vec![IE_BEARER_CONTEXT, (length >> 8) as u8, length as u8, 0x00];
+        ie.extend(bytes);
+        assert!(matches!(
+            parse_gtpv2_ies(&ie),
+            Err(Err::Error(Error {
+                code: ErrorKind::TooLarge,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn gtpv2_truncated_ie_fails() {
+        let bytes = [
+            0x48, 0x01, 0x00, 0x0f, /* flags, message type, length */
+            0x00, 0x00, 0x00, 0x00, /* TEID */
+            0x00, 0x00, 0x01, 0x00, /* sequence number, spare */
+            IE_FTEID, 0x00, 0x09, 0x00, /* F-TEID IE header */
+            0x8a, 0x00, 0x00, /* F-TEID cut short */
+        ];
+        assert!(matches!(
+            parse_gtpv2_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+
+        let mut bytes = bytes;
+        bytes[14] = 0x02;
+        assert!(matches!(
+            parse_gtpv2_message(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
+}
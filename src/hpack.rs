@@ -0,0 +1,484 @@
+//! Handles decoding of HPACK (RFC 7541) header blocks for HTTP/2
+
+use crate::http::HttpHeader;
+use nom::bytes::complete::take;
+use nom::error::{Error, ErrorKind};
+use nom::number::complete::be_u8;
+use nom::{Err, IResult};
+use std::collections::VecDeque;
+
+// Header Field Representations (RFC 7541, Section 6)
+//
+//   Indexed Header Field              Literal Header Field
+//     0   1   2   3   4   5   6   7     0   1   2   3   4   5   6   7
+//   +---+---+---+---+---+---+---+---+ +---+---+---+---+---+---+---+---+
+//   | 1 |        Index (7+)         | | 0 | 1 |      Index (6+)       |
+//   +---+---------------------------+ +---+---+-----------------------+
+//                                     | H |     Value Length (7+)     |
+//   Dynamic Table Size Update         +---+---------------------------+
+//     0   1   2   3   4   5   6   7   | Value String (Length octets)  |
+//   +---+---+---+---+---+---+---+---+ +-------------------------------+
+//   | 0 | 0 | 1 |   Max size (5+)   |
+//   +---+---------------------------+ Literals without indexing use a 0000 prefix
+//                                     and never-indexed literals a 0001 prefix,
+//                                     both with a 4-bit index. A zero index is
+//                                     followed by a literal name string.
+
+/// Dynamic table size that applies until SETTINGS_HEADER_TABLE_SIZE says otherwise
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+/// Octets added to the length of the name and value when accounting for a table entry
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Upper bound on the decoded size of one header list, counted the same way as table entries.
+/// Indexed fields let a small block expand into a much larger list.
+const MAX_HEADER_LIST_SIZE: usize = 1 << 20;
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// The Huffman code of Appendix B is canonical, so it is fully described by the number of codes
+/// of each bit length and the symbols ordered by code
+const HUFFMAN_COUNTS: [u16; 31] = [
+    0, 0, 0, 0, 0, 10, 26, 32, 6, 0, 5, 3, 2, 6, 2, 3, 0, 0, 0, 3, 8, 13, 26, 29, 12, 4, 15, 19,
+    29, 0, 4,
+];
+
+const HUFFMAN_SYMBOLS: [u16; 257] = [
+    48, 49, 50, 97, 99, 101, 105, 111, 115, 116, 32, 37, 45, 46, 47, 51, 52, 53, 54, 55, 56, 57,
+    61, 65, 95, 98, 100, 102, 103, 104, 108, 109, 110, 112, 114, 117, 58, 66, 67, 68, 69, 70, 71,
+    72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 89, 106, 107, 113, 118, 119,
+    120, 121, 122, 38, 42, 44, 59, 88, 90, 33, 34, 40, 41, 63, 39, 43, 124, 35, 62, 0, 36, 64, 91,
+    93, 126, 94, 125, 60, 96, 123, 92, 195, 208, 128, 130, 131, 162, 184, 194, 224, 226, 153, 161,
+    167, 172, 176, 177, 179, 209, 216, 217, 227, 229, 230, 129, 132, 133, 134, 136, 146, 154, 156,
+    160, 163, 164, 169, 170, 173, 178, 181, 185, 186, 187, 189, 190, 196, 198, 228, 232, 233, 1,
+    135, 137, 138, 139, 140, 141, 143, 147, 149, 150, 151, 152, 155, 157, 158, 165, 166, 168, 174,
+    175, 180, 182, 183, 188, 191, 197, 231, 239, 9, 142, 144, 145, 148, 159, 171, 206, 215, 225,
+    236, 237, 199, 207, 234, 235, 192, 193, 200, 201, 202, 205, 210, 213, 218, 219, 238, 240, 242,
+    243, 255, 203, 204, 211, 212, 214, 221, 222, 223, 241, 244, 245, 246, 247, 248, 250, 251, 252,
+    253, 254, 2, 3, 4, 5, 6, 7, 8, 11, 12, 14, 15, 16, 17, 18, 19, 20, 21, 23, 24, 25, 26, 27, 28,
+    29, 30, 31, 127, 220, 249, 10, 13, 22, 256,
+];
+
+const HUFFMAN_EOS: u16 = 256;
+
+/// Decoding state for the header blocks sent in one direction of an HTTP/2 connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HpackDecoder {
+    /// Names and values as sent, most recently inserted entry first. They are kept as raw octets
+    /// so that entry sizes match the encoder's for fields that are not UTF-8.
+    table: VecDeque<(Vec<u8>, Vec<u8>)>,
+    size: usize,
+    max_size: usize,
+    /// Largest size a dynamic table size update may ask for
+    size_limit: usize,
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_SIZE)
+    }
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+/// Decodes an integer with an N-bit prefix (Section 5.1)
+fn integer(input: &[u8], prefix_bits: u8) -> IResult<&[u8], usize> {
+    let max = (1usize << prefix_bits) - 1;
+    let (mut rest, first) = be_u8(input)?;
+    let mut value = usize::from(first) & max;
+    if value < max {
+        return Ok((rest, value));
+    }
+
+    let mut shift = 0;
+    loop {
+        let (next, byte) = be_u8(rest)?;
+        rest = next;
+        if shift > 28 {
+            return verify_error(input);
+        }
+        // Bits shifted out, or a sum past usize::MAX, mean the value does not fit on this target
+        let bits = usize::from(byte & 0x7f);
+        value = match bits
+            .checked_shl(shift)
+            .filter(|shifted| shifted >> shift == bits)
+            .and_then(|shifted| value.checked_add(shifted))
+        {
+            Some(value) => value,
+            None => return verify_error(input),
+        };
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok((rest, value));
+        }
+    }
+}
+
+fn huffman_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 8 / 5);
+    let (mut code, mut first, mut index, mut length) = (0u32, 0u32, 0usize, 0usize);
+
+    for byte in input {
+        for shift in (0..8).rev() {
+            code |= u32::from(byte >> shift & 1);
+            length += 1;
+            let count = u32::from(HUFFMAN_COUNTS[length]);
+            if code < first + count {
+                match HUFFMAN_SYMBOLS[index + (code - first) as usize] {
+                    HUFFMAN_EOS => return None,
+                    symbol => output.push(symbol as u8),
+                }
+                code = 0;
+                first = 0;
+                index = 0;
+                length = 0;
+            } else {
+                index += count as usize;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+        }
+    }
+
+    // Padding is the most significant bits of EOS, which are all ones, and shorter than an octet.
+    // `code` has already been shifted for the next bit.
+    if length > 7 || code >> 1 != (1 << length) - 1 {
+        return None;
+    }
+    Some(output)
+}
+
+/// Decodes a string literal (Section 5.2), returning the raw octets
+fn string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let huffman = matches!(input.first(), Some(b) if b & 0x80 != 0);
+    let (rest, length) = integer(input, 7)?;
+    let (rest, data) = take(length)(rest)?;
+    if !huffman {
+        return Ok((rest, data.to_vec()));
+    }
+    match huffman_decode(data) {
+        Some(decoded) => Ok((rest, decoded)),
+        None => verify_error(input),
+    }
+}
+
+fn entry_size(name: &[u8], value: &[u8]) -> usize {
+    name.len() + value.len() + ENTRY_OVERHEAD
+}
+
+fn header_field(name: &[u8], value: &[u8]) -> HttpHeader {
+    HttpHeader {
+        name: String::from_utf8_lossy(name).into_owned(),
+        value: String::from_utf8_lossy(value).into_owned(),
+    }
+}
+
+impl HpackDecoder {
+    /// Creates a decoder with an empty dynamic table of up to `max_table_size` octets
+    pub fn new(max_table_size: usize) -> Self {
+        HpackDecoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: max_table_size,
+            size_limit: max_table_size,
+        }
+    }
+
+    /// Current size of the dynamic table in octets
+    pub fn table_size(&self) -> usize {
+        self.size
+    }
+
+    /// Names and values of the dynamic table entries, most recently inserted first
+    pub fn table(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.table
+            .iter()
+            .map(|(name, value)| (name.as_slice(), value.as_slice()))
+    }
+
+    /// Applies a SETTINGS_HEADER_TABLE_SIZE sent by the endpoint decoding these header blocks
+    pub fn set_max_table_size(&mut self, max_table_size: usize) {
+        self.size_limit = max_table_size;
+        if self.max_size > max_table_size {
+            self.max_size = max_table_size;
+            self.evict(0);
+        }
+    }
+
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= entry_size(&name, &value),
+                None => break,
+            }
+        }
+    }
+
+    fn insert(&mut self, name: &[u8], value: &[u8]) {
+        let size = entry_size(name, value);
+        if size > self.max_size {
+            self.table.clear();
+            self.size = 0;
+            return;
+        }
+        self.evict(size);
+        self.table.push_front((name.to_vec(), value.to_vec()));
+        self.size += size;
+    }
+
+    fn lookup(&self, index: usize) -> Option<(Vec<u8>, Vec<u8>)> {
+        match index {
+            0 => None,
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Some((name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            }
+            _ => self.table.get(index - 62).cloned(),
+        }
+    }
+
+    /// Decodes a complete header block, made of the fragments of a HEADERS or PUSH_PROMISE frame
+    /// and its CONTINUATION frames, updating the dynamic table as it goes
+    pub fn decode<'a>(&mut self, block: &'a [u8]) -> IResult<&'a [u8], Vec<HttpHeader>> {
+        let mut headers = vec![];
+        let mut list_size = 0;
+        let mut input = block;
+
+        while let Some(&first) = input.first() {
+            let (rest, name, value) = if first & 0x80 != 0 {
+                let (rest, index) = integer(input, 7)?;
+                match self.lookup(index) {
+                    Some((name, value)) => (rest, name, value),
+                    None => return verify_error(input),
+                }
+            } else if first & 0xe0 == 0x20 {
+                // Size updates are only allowed before the first header field
+                let (rest, size) = integer(input, 5)?;
+                if !headers.is_empty() || size > self.size_limit {
+                    return verify_error(input);
+                }
+                self.max_size = size;
+                self.evict(0);
+                input = rest;
+                continue;
+            } else {
+                let prefix_bits = if first & 0x40 != 0 { 6 } else { 4 };
+                let (rest, index) = integer(input, prefix_bits)?;
+                let (rest, name) = if index == 0 {
+                    string(rest)?
+                } else {
+                    match self.lookup(index) {
+                        Some((name, _)) => (rest, name),
+                        None => return verify_error(input),
+                    }
+                };
+                let (rest, value) = string(rest)?;
+                if prefix_bits == 6 {
+                    self.insert(&name, &value);
+                }
+                (rest, name, value)
+            };
+
+            list_size += entry_size(&name, &value);
+            if list_size > MAX_HEADER_LIST_SIZE {
+                return Err(Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+            }
+            headers.push(header_field(&name, &value));
+            input = rest;
+        }
+
+        Ok((input, headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EMPTY_SLICE: &[u8] = &[];
+
+    fn pairs(headers: &[HttpHeader]) -> Vec<(&str, &str)> {
+        headers
+            .iter()
+            .map(|h| (h.name.as_str(), h.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn huffman_requests_work() {
+        // RFC 7541, Appendix C.4
+        let mut decoder = HpackDecoder::default();
+
+        let first = [
+            0x82, 0x86, 0x84, 0x41, 0x8c, // :method GET, :scheme http, :path /, :authority
+            0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff,
+        ];
+        let (remaining, headers) = decoder.decode(&first).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(
+            pairs(&headers),
+            vec![
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ]
+        );
+        assert_eq!(decoder.table_size(), 57);
+
+        let second = [
+            0x82, 0x86, 0x84, 0xbe, // :authority from the dynamic table
+            0x58, 0x86, 0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf, // cache-control: no-cache
+        ];
+        let (_, headers) = decoder.decode(&second).unwrap();
+        assert_eq!(headers[3].value, "www.example.com");
+        assert_eq!(headers[4].value, "no-cache");
+        assert_eq!(decoder.table_size(), 110);
+
+        let third = [
+            0x82, 0x87, 0x85, 0xbf, // :method GET, :scheme https, :path /index.html
+            0x40, 0x88, 0x25, 0xa8, 0x49, 0xe9, 0x5b, 0xa9, 0x7d, 0x7f, // custom-key
+            0x89, 0x25, 0xa8, 0x49, 0xe9, 0x5b, 0xb8, 0xe8, 0xb4, 0xbf, // custom-value
+        ];
+        let (_, headers) = decoder.decode(&third).unwrap();
+        assert_eq!(
+            pairs(&headers),
+            vec![
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ]
+        );
+        assert_eq!(decoder.table_size(), 164);
+        assert_eq!(
+            decoder.table().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec![&b"custom-key"[..], b"cache-control", b":authority"]
+        );
+    }
+
+    #[test]
+    fn table_eviction_works() {
+        // RFC 7541, Appendix C.6.1 and C.6.2
+        let mut decoder = HpackDecoder::new(256);
+
+        let first = [
+            0x48, 0x82, 0x64, 0x02, // :status: 302
+            0x58, 0x85, 0xae, 0xc3, 0x77, 0x1a, 0x4b, // cache-control: private
+            0x61, 0x96, 0xd0, 0x7a, 0xbe, 0x94, 0x10, 0x54, 0xd4, 0x44, 0xa8, 0x20, 0x05, 0x95,
+            0x04, 0x0b, 0x81, 0x66, 0xe0, 0x82, 0xa6, 0x2d, 0x1b, 0xff, // date
+            0x6e, 0x91, 0x9d, 0x29, 0xad, 0x17, 0x18, 0x63, 0xc7, 0x8f, 0x0b, 0x97, 0xc8, 0xe9,
+            0xae, 0x82, 0xae, 0x43, 0xd3, // location
+        ];
+        let (_, headers) = decoder.decode(&first).unwrap();
+        assert_eq!(headers[2].value, "Mon, 21 Oct 2013 20:13:21 GMT");
+        assert_eq!(headers[3].value, "https://www.example.com");
+        assert_eq!(decoder.table_size(), 222);
+
+        let second = [
+            0x48, 0x83, 0x64, 0x0e, 0xff, // :status: 307
+            0xc1, 0xc0, 0xbf, // cache-control, date and location from the dynamic table
+        ];
+        let (_, headers) = decoder.decode(&second).unwrap();
+        assert_eq!(
+            pairs(&headers),
+            vec![
+                (":status", "307"),
+                ("cache-control", "private"),
+                ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                ("location", "https://www.example.com"),
+            ]
+        );
+        assert_eq!(decoder.table_size(), 222);
+
+        // A size update after a header field and an index past the table are both errors
+        assert!(decoder.decode(&[0x82, 0x20]).is_err());
+        assert!(decoder.decode(&[0xc6]).is_err());
+    }
+
+    #[test]
+    fn non_utf8_entries_keep_their_size() {
+        // Both entries fit in 68 octets only when sized by their raw one-octet names and values
+        let mut decoder = HpackDecoder::new(68);
+        decoder.decode(&[0x40, 0x01, 0xff, 0x01, 0xfe]).unwrap();
+        decoder.decode(&[0x7e, 0x01, 0xfd]).unwrap(); // indexed name, new value
+        assert_eq!(decoder.table_size(), 68);
+        assert_eq!(
+            decoder.table().collect::<Vec<_>>(),
+            vec![(&[0xff][..], &[0xfd][..]), (&[0xff][..], &[0xfe][..])]
+        );
+
+        // The first entry is still there, so the encoder's next insertion evicts it
+        let (_, headers) = decoder.decode(&[0xbf, 0x40, 0x01, b'a', 0x00]).unwrap();
+        assert_eq!(pairs(&headers), vec![("\u{fffd}", "\u{fffd}"), ("a", "")]);
+        assert_eq!(
+            decoder.table().collect::<Vec<_>>(),
+            vec![(&b"a"[..], &b""[..]), (&[0xff][..], &[0xfd][..])]
+        );
+        assert!(decoder.decode(&[0xc0]).is_err());
+    }
+}
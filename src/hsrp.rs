@@ -0,0 +1,353 @@
+//! Handles parsing of HSRP version 1 and 2 messages
+
+use crate::ethernet::{self, MacAddress};
+use crate::ipv4;
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+// HSRPv1 Packet Format (RFC 2281)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |   Version     |   Op Code     |     State     |   Hellotime   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |   Holdtime    |   Priority    |     Group     |   Reserved    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                      Authentication  Data                     |
+//   |                                                               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                      Virtual IP Address                       |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// HSRPv2 Group State TLV (Cisco); HSRPv2 messages are a sequence of TLVs
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |   Type = 1    |  Length = 40  |    Version    |    Op Code    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |     State     |  IP Version   |         Group Number          |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Identifier (6 octets)                        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |          Priority, Hello Time and Hold Time (32 bits each)    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                 Virtual IP Address (16 octets)                |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Used by HSRPv1, and by HSRPv2 for IPv4
+pub const HSRP_PORT: u16 = 1985;
+/// Used by HSRPv2 for IPv6
+pub const HSRP_V6_PORT: u16 = 2029;
+
+const TLV_GROUP_STATE: u8 = 1;
+const TLV_TEXT_AUTHENTICATION: u8 = 3;
+const TLV_MD5_AUTHENTICATION: u8 = 4;
+
+const GROUP_STATE_LENGTH: usize = 40;
+const TEXT_AUTHENTICATION_LENGTH: usize = 8;
+const MD5_AUTHENTICATION_LENGTH: usize = 28;
+const IP_VERSION_6: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HsrpOpCode {
+    Hello,
+    Coup,
+    Resign,
+    Advertise,
+    Other(u8),
+}
+
+impl From<u8> for HsrpOpCode {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::Hello,
+            1 => Self::Coup,
+            2 => Self::Resign,
+            3 => Self::Advertise,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HsrpState {
+    Initial,
+    Learn,
+    Listen,
+    Speak,
+    Standby,
+    Active,
+    Other(u8),
+}
+
+impl HsrpState {
+    /// HSRPv1 numbers states as bits, HSRPv2 sequentially
+    fn new(version: u8, raw: u8) -> Self {
+        match (version, raw) {
+            (_, 0) => Self::Initial,
+            (0, 1) | (2, 1) => Self::Learn,
+            (0, 2) | (2, 2) => Self::Listen,
+            (0, 4) | (2, 3) => Self::Speak,
+            (0, 8) | (2, 4) => Self::Standby,
+            (0, 16) | (2, 5) => Self::Active,
+            (_, other) => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HsrpGroupState {
+    /// 0 for HSRPv1, 2 for HSRPv2
+    pub version: u8,
+    pub op_code: HsrpOpCode,
+    pub state: HsrpState,
+    pub group: u16,
+    pub priority: u32,
+    pub hello_time: Duration,
+    pub hold_time: Duration,
+    pub virtual_address: IpAddr,
+    /// Only present in HSRPv2, usually the MAC address of the sender
+    pub identifier: Option<MacAddress>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HsrpAuthentication {
+    /// Cleartext password, "cisco" by default
+    Text([u8; 8]),
+    Md5 {
+        algorithm: u8,
+        flags: u16,
+        address: Ipv4Addr,
+        key_id: u32,
+        digest: [u8; 16],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HsrpPacket {
+    pub groups: Vec<HsrpGroupState>,
+    pub authentication: Option<HsrpAuthentication>,
+}
+
+fn length_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::LengthValue)))
+}
+
+fn text_authentication(input: &[u8]) -> IResult<&[u8], HsrpAuthentication> {
+    let (rest, raw) = bytes::streaming::take(8u8)(input)?;
+    let mut data = [0; 8];
+    data.copy_from_slice(raw);
+
+    Ok((rest, HsrpAuthentication::Text(data)))
+}
+
+fn v1_packet(input: &[u8]) -> IResult<&[u8], HsrpPacket> {
+    let (rest, version) = number::streaming::be_u8(input)?;
+    let (rest, op_code) = number::streaming::be_u8(rest)?;
+    let (rest, state) = number::streaming::be_u8(rest)?;
+    let (rest, hello_time) = number::streaming::be_u8(rest)?;
+    let (rest, hold_time) = number::streaming::be_u8(rest)?;
+    let (rest, priority) = number::streaming::be_u8(rest)?;
+    let (rest, group) = number::streaming::be_u8(rest)?;
+    let (rest, _reserved) = number::streaming::be_u8(rest)?;
+    let (rest, authentication) = text_authentication(rest)?;
+    let (rest, virtual_address) = ipv4::address(rest)?;
+
+    let group = HsrpGroupState {
+        version,
+        op_code: op_code.into(),
+        state: HsrpState::new(version, state),
+        group: u16::from(group),
+        priority: u32::from(priority),
+        hello_time: Duration::from_secs(u64::from(hello_time)),
+        hold_time: Duration::from_secs(u64::from(hold_time)),
+        virtual_address: IpAddr::V4(virtual_address),
+        identifier: None,
+    };
+
+    Ok((
+        rest,
+        HsrpPacket {
+            groups: vec![group],
+            authentication: Some(authentication),
+        },
+    ))
+}
+
+fn group_state(value: &[u8]) -> IResult<&[u8], HsrpGroupState> {
+    let (rest, version) = number::complete::be_u8(value)?;
+    let (rest, op_code) = number::complete::be_u8(rest)?;
+    let (rest, state) = number::complete::be_u8(rest)?;
+    let (rest, ip_version) = number::complete::be_u8(rest)?;
+    let (rest, group) = number::complete::be_u16(rest)?;
+    let (rest, identifier) = ethernet::mac_address(rest)?;
+    let (rest, priority) = number::complete::be_u32(rest)?;
+    let (rest, hello_time) = number::complete::be_u32(rest)?;
+    let (rest, hold_time) = number::complete::be_u32(rest)?;
+    let (rest, raw) = bytes::complete::take(16u8)(rest)?;
+    let virtual_address = if ip_version == IP_VERSION_6 {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(raw);
+        IpAddr::V6(Ipv6Addr::from(octets))
+    } else {
+        IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3]))
+    };
+
+    Ok((
+        rest,
+        HsrpGroupState {
+            version,
+            op_code: op_code.into(),
+            state: HsrpState::new(version, state),
+            group,
+            priority,
+            hello_time: Duration::from_millis(u64::from(hello_time)),
+            hold_time: Duration::from_millis(u64::from(hold_time)),
+            virtual_address,
+            identifier: Some(identifier),
+        },
+    ))
+}
+
+fn md5_authentication(value: &[u8]) -> IResult<&[u8], HsrpAuthentication> {
+    let (rest, algorithm) = number::complete::be_u8(value)?;
+    let (rest, _padding) = number::complete::be_u8(rest)?;
+    let (rest, flags) = number::complete::be_u16(rest)?;
+    let (rest, address) = ipv4::address(rest)?;
+    let (rest, key_id) = number::complete::be_u32(rest)?;
+    let (rest, raw) = bytes::complete::take(16u8)(rest)?;
+    let mut digest = [0; 16];
+    digest.copy_from_slice(raw);
+
+    Ok((
+        rest,
+        HsrpAuthentication::Md5 {
+            algorithm,
+            flags,
+            address,
+            key_id,
+            digest,
+        },
+    ))
+}
+
+/// Parses the TLVs of an HSRPv2 message; interface state and unknown TLVs are skipped
+fn v2_packet(mut input: &[u8]) -> IResult<&[u8], HsrpPacket> {
+    let mut packet = HsrpPacket {
+        groups: vec![],
+        authentication: None,
+    };
+    while !input.is_empty() {
+        let (rest, tlv_type) = number::streaming::be_u8(input)?;
+        let (rest, length) = number::streaming::be_u8(rest)?;
+        let (rest, value) = bytes::streaming::take(length)(rest)?;
+        let length = usize::from(length);
+        match tlv_type {
+            TLV_GROUP_STATE if length == GROUP_STATE_LENGTH => {
+                packet.groups.push(group_state(value)?.1);
+            }
+            TLV_TEXT_AUTHENTICATION if length == TEXT_AUTHENTICATION_LENGTH => {
+                packet.authentication = Some(text_authentication(value)?.1);
+            }
+            TLV_MD5_AUTHENTICATION if length == MD5_AUTHENTICATION_LENGTH => {
+                packet.authentication = Some(md5_authentication(value)?.1);
+            }
+            TLV_GROUP_STATE | TLV_TEXT_AUTHENTICATION | TLV_MD5_AUTHENTICATION => {
+                return length_error(input);
+            }
+            _ => {}
+        }
+        input = rest;
+    }
+
+    Ok((input, packet))
+}
+
+/// Parses an HSRP message. Version 1 messages start with their version field, which is 0, and
+/// version 2 messages with the type of their first TLV. The input must end where the UDP payload
+/// ends, as HSRPv2 messages are read until then.
+pub fn parse_hsrp_packet(input: &[u8]) -> IResult<&[u8], HsrpPacket> {
+    let (_, first) = number::streaming::be_u8(input)?;
+    if first == 0 {
+        v1_packet(input)
+    } else {
+        v2_packet(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn v1_works() {
+        let bytes = [
+            0x00, 0x00, 0x10, 0x03, // version 0, hello, active, hello time 3 s
+            0x0a, 0x6e, 0x01, 0x00, // hold time 10 s, priority 110, group 1
+            b'c', b'i', b's', b'c', b'o', 0x00, 0x00, 0x00, // authentication
+            0xc0, 0x00, 0x02, 0x01, // virtual IP
+        ];
+        let expectation = HsrpPacket {
+            groups: vec![HsrpGroupState {
+                version: 0,
+                op_code: HsrpOpCode::Hello,
+                state: HsrpState::Active,
+                group: 1,
+                priority: 110,
+                hello_time: Duration::from_secs(3),
+                hold_time: Duration::from_secs(10),
+                virtual_address: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                identifier: None,
+            }],
+            authentication: Some(HsrpAuthentication::Text(*b"cisco\0\0\0")),
+        };
+        assert_eq!(parse_hsrp_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn v2_works() {
+        let bytes = [
+            0x01, 0x28, // group state TLV
+            0x02, 0x00, 0x04, 0x06, // version 2, hello, standby, IPv6
+            0x00, 0x0a, // group 10
+            0x00, 0x00, 0x0c, 0x9f, 0xf0, 0x0a, // identifier
+            0x00, 0x00, 0x00, 0x64, // priority 100
+            0x00, 0x00, 0x0b, 0xb8, // hello time 3000 ms
+            0x00, 0x00, 0x27, 0x10, // hold time 10000 ms
+            0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fe80::5:73ff:fea0:a
+            0x00, 0x05, 0x73, 0xff, 0xfe, 0xa0, 0x00, 0x0a, 0x02, 0x04, 0x00, 0x01, 0x00,
+            0x00, // interface state TLV
+        ];
+
+        let (remaining, packet) = parse_hsrp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.authentication, None);
+        assert_eq!(
+            packet.groups,
+            vec![HsrpGroupState {
+                version: 2,
+                op_code: HsrpOpCode::Hello,
+                state: HsrpState::Standby,
+                group: 10,
+                priority: 100,
+                hello_time: Duration::from_secs(3),
+                hold_time: Duration::from_secs(10),
+                virtual_address: IpAddr::V6(Ipv6Addr::new(
+                    0xfe80, 0, 0, 0, 0x5, 0x73ff, 0xfea0, 0xa
+                )),
+                identifier: Some(MacAddress([0x00, 0x00, 0x0c, 0x9f, 0xf0, 0x0a])),
+            }]
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! Handles parsing of HTTP/1.0 and HTTP/1.1 messages from a reassembled TCP stream
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::{Err, IResult, Needed};
+
+// HTTP/1.1 Message Format (RFC 9112)
+//
+//   HTTP-message   = start-line CRLF
+//                    *( field-line CRLF )
+//                    CRLF
+//                    [ message-body ]
+//
+//   request-line   = method SP request-target SP HTTP-version
+//   status-line    = HTTP-version SP status-code SP [ reason-phrase ]
+//
+//   chunked-body   = *chunk
+//                    last-chunk
+//                    trailer-section
+//                    CRLF
+//   chunk          = chunk-size [ chunk-ext ] CRLF
+//                    chunk-data CRLF
+
+pub const HTTP_PORT: u16 = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HttpMethod {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    Other(String),
+}
+
+impl From<&str> for HttpMethod {
+    fn from(raw: &str) -> Self {
+        match raw {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "CONNECT" => Self::Connect,
+            "OPTIONS" => Self::Options,
+            "TRACE" => Self::Trace,
+            "PATCH" => Self::Patch,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Limits on the start line and header section, checked before the end of the header section is
+/// found. Exceeding them fails with `ErrorKind::TooLarge`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpLimits {
+    pub max_headers: usize,
+    /// Size of the start line and header section, including line endings
+    pub max_header_size: usize,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        HttpLimits {
+            max_headers: 100,
+            max_header_size: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub target: String,
+    pub version: HttpVersion,
+    pub headers: Vec<HttpHeader>,
+    /// The body, with any chunked transfer coding removed
+    pub body: Vec<u8>,
+    /// Trailer fields of a chunked body
+    pub trailers: Vec<HttpHeader>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HttpResponse {
+    pub version: HttpVersion,
+    pub status_code: u16,
+    pub reason: String,
+    pub headers: Vec<HttpHeader>,
+    /// The body, with any chunked transfer coding removed
+    pub body: Vec<u8>,
+    /// Trailer fields of a chunked body
+    pub trailers: Vec<HttpHeader>,
+}
+
+impl HttpRequest {
+    /// The value of the first header with this name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+impl HttpResponse {
+    /// The value of the first header with this name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+/// How the end of a message body is found (RFC 9112 section 6.3)
+enum BodyLength {
+    Empty,
+    Chunked,
+    Length(usize),
+    UntilClose,
+}
+
+pub(crate) fn header<'a>(headers: &'a [HttpHeader], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+/// Reads a line ending in CRLF or a bare LF, without the line ending. `budget` is the number of
+/// bytes the line may span before the header size limit is hit.
+fn line(input: &[u8], budget: usize) -> IResult<&[u8], &[u8]> {
+    match input.iter().take(budget).position(|&b| b == b'\n') {
+        Some(end) => {
+            let line = &input[..end];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            Ok((&input[end + 1..], line))
+        }
+        None if input.len() >= budget => Err(Err::Failure(Error::new(input, ErrorKind::TooLarge))),
+        None => Err(Err::Incomplete(Needed::Unknown)),
+    }
+}
+
+fn version(input: &[u8]) -> Result<HttpVersion, ()> {
+    match input {
+        b"HTTP/1.0" => Ok(HttpVersion::Http10),
+        b"HTTP/1.1" => Ok(HttpVersion::Http11),
+        _ => Err(()),
+    }
+}
+
+fn is_token(input: &[u8]) -> bool {
+    !input.is_empty()
+        && input
+            .iter()
+            .all(|&b| b.is_ascii_graphic() && !b"\"(),/:;<=>?@[\\]{}".contains(&b))
+}
+
+fn trim(input: &[u8]) -> &[u8] {
+    let start = input
+        .iter()
+        .position(|&b| b != b' ' && b != b'\t')
+        .unwrap_or(input.len());
+    let end = input
+        .iter()
+        .rposition(|&b| b != b' ' && b != b'\t')
+        .map_or(start, |end| end + 1);
+    &input[start..end]
+}
+
+/// Parses field lines up to and including the empty line ending them. Obsolete line folding is
+/// replaced by a single space.
+fn headers<'a>(
+    mut input: &'a [u8],
+    limits: &HttpLimits,
+    mut budget: usize,
+) -> IResult<&'a [u8], Vec<HttpHeader>> {
+    let mut headers: Vec<HttpHeader> = vec![];
+
+    loop {
+        let (rest, field) = line(input, budget)?;
+        budget -= input.len() - rest.len();
+        if field.is_empty() {
+            return Ok((rest, headers));
+        }
+
+        if field[0] == b' ' || field[0] == b'\t' {
+            match headers.last_mut() {
+                Some(last) => {
+                    last.value.push(' ');
+                    last.value.push_str(&String::from_utf8_lossy(trim(field)));
+                }
+                None => return verify_error(input),
+            }
+        } else {
+            let colon = match field.iter().position(|&b| b == b':') {
+                Some(colon) if is_token(&field[..colon]) => colon,
+                _ => return verify_error(input),
+            };
+            if headers.len() == limits.max_headers {
+                return Err(Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+            }
+            headers.push(HttpHeader {
+                name: String::from_utf8_lossy(&field[..colon]).into_owned(),
+                value: String::from_utf8_lossy(trim(&field[colon + 1..])).into_owned(),
+            });
+        }
+        input = rest;
+    }
+}
+
+/// Transfer-Encoding takes precedence over Content-Length, whose copies must all agree
+fn body_length<'a>(
+    input: &'a [u8],
+    headers: &[HttpHeader],
+    is_request: bool,
+) -> Result<BodyLength, Err<Error<&'a [u8]>>> {
+    if let Some(encoding) = header(headers, "transfer-encoding") {
+        let last = encoding.rsplit(',').next().unwrap_or("").trim();
+        return if last.eq_ignore_ascii_case("chunked") {
+            Ok(BodyLength::Chunked)
+        } else if is_request {
+            Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+        } else {
+            Ok(BodyLength::UntilClose)
+        };
+    }
+
+    let mut length = None;
+    for h in headers
+        .iter()
+        .filter(|h| h.name.eq_ignore_ascii_case("content-length"))
+    {
+        for value in h.value.split(',') {
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+            }
+            let value: usize = value
+                .parse()
+                .map_err(|_| Err::Error(Error::new(input, ErrorKind::TooLarge)))?;
+            if length.is_some() && length != Some(value) {
+                return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+            }
+            length = Some(value);
+        }
+    }
+
+    match length {
+        Some(length) => Ok(BodyLength::Length(length)),
+        None if is_request => Ok(BodyLength::Empty),
+        None => Ok(BodyLength::UntilClose),
+    }
+}
+
+fn chunk_size(line: &[u8]) -> Option<usize> {
+    let digits = line.split(|&b| b == b';').next().unwrap_or(line);
+    let digits = trim(digits);
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0usize, |size, &b| {
+        let digit = char::from(b).to_digit(16)?;
+        size.checked_mul(16)?.checked_add(digit as usize)
+    })
+}
+
+fn chunked_body<'a>(
+    mut input: &'a [u8],
+    limits: &HttpLimits,
+) -> IResult<&'a [u8], (Vec<u8>, Vec<HttpHeader>)> {
+    let mut body = vec![];
+
+    loop {
+        let (rest, size_line) = line(input, limits.max_header_size)?;
+        let size = match chunk_size(size_line) {
+            Some(size) => size,
+            None => return verify_error(input),
+        };
+        if size == 0 {
+            let (rest, trailers) = headers(rest, limits, limits.max_header_size)?;
+            return Ok((rest, (body, trailers)));
+        }
+        let (rest, data) = bytes::streaming::take(size)(rest)?;
+        let rest = match rest {
+            [b'\r', b'\n', rest @ ..] | [b'\n', rest @ ..] => rest,
+            [] | [b'\r'] => return Err(Err::Incomplete(Needed::Unknown)),
+            _ => return verify_error(rest),
+        };
+        body.extend_from_slice(data);
+        input = rest;
+    }
+}
+
+fn body<'a>(
+    input: &'a [u8],
+    length: BodyLength,
+    limits: &HttpLimits,
+) -> IResult<&'a [u8], (Vec<u8>, Vec<HttpHeader>)> {
+    match length {
+        BodyLength::Empty => Ok((input, (vec![], vec![]))),
+        BodyLength::Chunked => chunked_body(input, limits),
+        BodyLength::Length(length) => {
+            let (input, body) = bytes::streaming::take(length)(input)?;
+            Ok((input, (body.to_vec(), vec![])))
+        }
+        BodyLength::UntilClose => Ok((&input[input.len()..], (input.to_vec(), vec![]))),
+    }
+}
+
+pub fn parse_http_request<'a>(
+    input: &'a [u8],
+    limits: &HttpLimits,
+) -> IResult<&'a [u8], HttpRequest> {
+    let (rest, request_line) = line(input, limits.max_header_size)?;
+    let mut parts = request_line.splitn(3, |&b| b == b' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(v)) if is_token(method) && !target.is_empty() => {
+            match self::version(v) {
+                Ok(version) => (method, target, version),
+                Err(()) => return verify_error(input),
+            }
+        }
+        _ => return verify_error(input),
+    };
+
+    let budget = limits.max_header_size - (input.len() - rest.len());
+    let (rest, headers) = headers(rest, limits, budget)?;
+    let length = body_length(input, &headers, true)?;
+    let (rest, (body, trailers)) = self::body(rest, length, limits)?;
+
+    Ok((
+        rest,
+        HttpRequest {
+            method: HttpMethod::from(&*String::from_utf8_lossy(method)),
+            target: String::from_utf8_lossy(target).into_owned(),
+            version,
+            headers,
+            body,
+            trailers,
+        },
+    ))
+}
+
+/// Parses a response. Responses to HEAD requests and successful CONNECT requests have no body
+/// whatever their headers say, so the method of the matching request should be given when known.
+/// A response with neither Content-Length nor chunked coding lasts until the connection closes,
+/// and takes the whole remaining input as its body.
+pub fn parse_http_response<'a>(
+    input: &'a [u8],
+    limits: &HttpLimits,
+    request_method: Option<&HttpMethod>,
+) -> IResult<&'a [u8], HttpResponse> {
+    let (rest, status_line) = line(input, limits.max_header_size)?;
+    let mut parts = status_line.splitn(3, |&b| b == b' ');
+    let (version, status_code, reason) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(v), Some(code), reason) if code.len() == 3 && code.iter().all(u8::is_ascii_digit) => {
+            let status_code = std::str::from_utf8(code).ok().and_then(|c| c.parse().ok());
+            match (self::version(v), status_code) {
+                (Ok(version), Some(status_code)) => (version, status_code, reason.unwrap_or(b"")),
+                _ => return verify_error(input),
+            }
+        }
+        _ => return verify_error(input),
+    };
+
+    let budget = limits.max_header_size - (input.len() - rest.len());
+    let (rest, headers) = headers(rest, limits, budget)?;
+    let no_body = matches!(
+        (request_method, status_code),
+        (_, 100..=199)
+            | (_, 204)
+            | (_, 304)
+            | (Some(HttpMethod::Head), _)
+            | (Some(HttpMethod::Connect), 200..=299)
+    );
+    let length = if no_body {
+        BodyLength::Empty
+    } else {
+        body_length(input, &headers, false)?
+    };
+    let (rest, (body, trailers)) = self::body(rest, length, limits)?;
+
+    Ok((
+        rest,
+        HttpResponse {
+            version,
+            status_code,
+            reason: String::from_utf8_lossy(reason).into_owned(),
+            headers,
+            body,
+            trailers,
+        },
+    ))
+}
+
+/// Parses pipelined requests until the input runs out. A request cut short is left in the
+/// remaining input, to be parsed again once more data has arrived.
+pub fn parse_http_requests<'a>(
+    mut input: &'a [u8],
+    limits: &HttpLimits,
+) -> IResult<&'a [u8], Vec<HttpRequest>> {
+    let mut requests = vec![];
+    while !input.is_empty() {
+        match parse_http_request(input, limits) {
+            Ok((rest, request)) => {
+                requests.push(request);
+                input = rest;
+            }
+            Err(Err::Incomplete(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((input, requests))
+}
+
+/// Parses pipelined responses until the input runs out, like `parse_http_requests`. The
+/// methods of the requests they answer are given in order, as they decide which responses have
+/// a body; interim 1xx responses share the method of the final response that follows them.
+pub fn parse_http_responses<'a>(
+    mut input: &'a [u8],
+    limits: &HttpLimits,
+    request_methods: &[HttpMethod],
+) -> IResult<&'a [u8], Vec<HttpResponse>> {
+    let mut methods = request_methods.iter();
+    let mut method = methods.next();
+    let mut responses = vec![];
+    while !input.is_empty() {
+        match parse_http_response(input, limits, method) {
+            Ok((rest, response)) => {
+                if !matches!(response.status_code, 100 | 102..=199) {
+                    method = methods.next();
+                }
+                responses.push(response);
+                input = rest;
+            }
+            Err(Err::Incomplete(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((input, responses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn pipelined_requests_work() {
+        let bytes = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n\
+                      POST /form HTTP/1.1\r\nHost: example.com\r\nContent-Length: 7\r\n\r\na=1&b=2\
+                      GET /partial HTTP/1.1\r\nHo";
+
+        let (remaining, requests) = parse_http_requests(bytes, &HttpLimits::default()).unwrap();
+        assert_eq!(remaining, b"GET /partial HTTP/1.1\r\nHo");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0],
+            HttpRequest {
+                method: HttpMethod::Get,
+                target: "/index.html".to_string(),
+                version: HttpVersion::Http11,
+                headers: vec![HttpHeader {
+                    name: "Host".to_string(),
+                    value: "example.com".to_string(),
+                }],
+                body: vec![],
+                trailers: vec![],
+            }
+        );
+        assert_eq!(requests[1].method, HttpMethod::Post);
+        assert_eq!(requests[1].header("content-length"), Some("7"));
+        assert_eq!(requests[1].body, b"a=1&b=2");
+    }
+
+    #[test]
+    fn chunked_response_works() {
+        let bytes = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                      5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nExpires: never\r\n\r\n";
+
+        let (remaining, response) =
+            parse_http_response(bytes, &HttpLimits::default(), None).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.reason, "OK");
+        assert_eq!(response.body, b"hello, world");
+        assert_eq!(
+            response.trailers,
+            vec![HttpHeader {
+                name: "Expires".to_string(),
+                value: "never".to_string(),
+            }]
+        );
+
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n";
+        let (_, response) =
+            parse_http_response(head, &HttpLimits::default(), Some(&HttpMethod::Head)).unwrap();
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn pipelined_responses_work() {
+        let bytes = b"HTTP/1.1 100 Continue\r\n\r\n\
+                      HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok\
+                      HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+                      HTTP/1.1 404 Not Found\r\nContent-Length: 3\r\n\r\nn";
+        let methods = [HttpMethod::Post, HttpMethod::Head, HttpMethod::Get];
+
+        let (remaining, responses) =
+            parse_http_responses(bytes, &HttpLimits::default(), &methods).unwrap();
+        assert_eq!(
+            remaining,
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 3\r\n\r\nn"
+        );
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0].status_code, 100);
+        assert_eq!(responses[1].body, b"ok");
+        assert!(responses[2].body.is_empty());
+
+        let bad_status = b"HTTP/1.1 +12 OK\r\n\r\n";
+        assert!(matches!(
+            parse_http_response(bad_status, &HttpLimits::default(), None),
+            Err(Err::Error(Error {
+                code: ErrorKind::Verify,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn header_limits_work() {
+        let limits = HttpLimits {
+            max_headers: 1,
+            max_header_size: 64,
+        };
+        let too_many = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n";
+        let too_large = [b"GET / HTTP/1.1\r\nA: ".as_ref(), &[b'a'; 64]].concat();
+
+        for bytes in &[&too_many[..], &too_large[..]] {
+            assert!(matches!(
+                parse_http_request(bytes, &limits),
+                Err(Err::Failure(Error {
+                    code: ErrorKind::TooLarge,
+                    ..
+                }))
+            ));
+        }
+    }
+}
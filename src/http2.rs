@@ -0,0 +1,692 @@
+//! Handles parsing of HTTP/2 frames, and decoding of their header blocks with per-direction HPACK
+//! state
+
+use crate::hpack::{HpackDecoder, DEFAULT_TABLE_SIZE};
+use crate::http::{self, HttpHeader};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// HTTP/2 Frame Format (RFC 9113)
+//
+//   +-----------------------------------------------+
+//   |                 Length (24)                   |
+//   +---------------+---------------+---------------+
+//   |   Type (8)    |   Flags (8)   |
+//   +-+-------------+---------------+-------------------------------+
+//   |R|                 Stream Identifier (31)                      |
+//   +=+=============================================================+
+//   |                   Frame Payload (0...)                      ...
+//   +---------------------------------------------------------------+
+
+/// Sent by the client before its first frame
+pub const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const FLAG_END_STREAM: u8 = 0x01;
+pub const FLAG_ACK: u8 = 0x01;
+pub const FLAG_END_HEADERS: u8 = 0x04;
+pub const FLAG_PADDED: u8 = 0x08;
+pub const FLAG_PRIORITY: u8 = 0x20;
+
+/// SETTINGS_MAX_FRAME_SIZE until the peer advertises another
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 1 << 14;
+
+const STREAM_ID_MASK: u32 = 0x7fff_ffff;
+const SETTING_LENGTH: usize = 6;
+
+/// Header block fragments are joined up to this length while waiting for END_HEADERS
+const MAX_HEADER_BLOCK_LENGTH: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Http2FrameType {
+    Data,
+    Headers,
+    Priority,
+    RstStream,
+    Settings,
+    PushPromise,
+    Ping,
+    Goaway,
+    WindowUpdate,
+    Continuation,
+    Other(u8),
+}
+
+impl From<u8> for Http2FrameType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0x0 => Self::Data,
+            0x1 => Self::Headers,
+            0x2 => Self::Priority,
+            0x3 => Self::RstStream,
+            0x4 => Self::Settings,
+            0x5 => Self::PushPromise,
+            0x6 => Self::Ping,
+            0x7 => Self::Goaway,
+            0x8 => Self::WindowUpdate,
+            0x9 => Self::Continuation,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Http2ErrorCode {
+    NoError,
+    ProtocolError,
+    InternalError,
+    FlowControlError,
+    SettingsTimeout,
+    StreamClosed,
+    FrameSizeError,
+    RefusedStream,
+    Cancel,
+    CompressionError,
+    ConnectError,
+    EnhanceYourCalm,
+    InadequateSecurity,
+    Http11Required,
+    Other(u32),
+}
+
+impl From<u32> for Http2ErrorCode {
+    fn from(raw: u32) -> Self {
+        match raw {
+            0x0 => Self::NoError,
+            0x1 => Self::ProtocolError,
+            0x2 => Self::InternalError,
+            0x3 => Self::FlowControlError,
+            0x4 => Self::SettingsTimeout,
+            0x5 => Self::StreamClosed,
+            0x6 => Self::FrameSizeError,
+            0x7 => Self::RefusedStream,
+            0x8 => Self::Cancel,
+            0x9 => Self::CompressionError,
+            0xa => Self::ConnectError,
+            0xb => Self::EnhanceYourCalm,
+            0xc => Self::InadequateSecurity,
+            0xd => Self::Http11Required,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Http2FrameHeader {
+    pub length: u32,
+    pub frame_type: Http2FrameType,
+    pub flags: u8,
+    pub stream_id: u32,
+}
+
+impl Http2FrameHeader {
+    fn has_flag(&self, flag: u8, frame_types: &[Http2FrameType]) -> bool {
+        self.flags & flag != 0 && frame_types.contains(&self.frame_type)
+    }
+
+    pub fn end_stream(&self) -> bool {
+        use Http2FrameType::*;
+        self.has_flag(FLAG_END_STREAM, &[Data, Headers])
+    }
+
+    pub fn end_headers(&self) -> bool {
+        use Http2FrameType::*;
+        self.has_flag(FLAG_END_HEADERS, &[Headers, PushPromise, Continuation])
+    }
+
+    pub fn padded(&self) -> bool {
+        use Http2FrameType::*;
+        self.has_flag(FLAG_PADDED, &[Data, Headers, PushPromise])
+    }
+
+    pub fn priority(&self) -> bool {
+        self.has_flag(FLAG_PRIORITY, &[Http2FrameType::Headers])
+    }
+
+    pub fn ack(&self) -> bool {
+        use Http2FrameType::*;
+        self.has_flag(FLAG_ACK, &[Settings, Ping])
+    }
+}
+
+/// Stream dependency of a PRIORITY frame, or of a HEADERS frame with the PRIORITY flag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Http2Priority {
+    pub exclusive: bool,
+    pub stream_dependency: u32,
+    /// Weight on the wire, one less than the weight it stands for
+    pub weight: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Http2Setting {
+    HeaderTableSize(u32),
+    EnablePush(bool),
+    MaxConcurrentStreams(u32),
+    InitialWindowSize(u32),
+    MaxFrameSize(u32),
+    MaxHeaderListSize(u32),
+    /// RFC 8441
+    EnableConnectProtocol(bool),
+    /// RFC 9218
+    NoRfc7540Priorities(bool),
+    Other {
+        identifier: u16,
+        value: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Http2FramePayload {
+    Data(Vec<u8>),
+    Headers {
+        priority: Option<Http2Priority>,
+        header_block: Vec<u8>,
+    },
+    Priority(Http2Priority),
+    RstStream(Http2ErrorCode),
+    Settings(Vec<Http2Setting>),
+    PushPromise {
+        promised_stream_id: u32,
+        header_block: Vec<u8>,
+    },
+    Ping([u8; 8]),
+    Goaway {
+        last_stream_id: u32,
+        error_code: Http2ErrorCode,
+        debug_data: Vec<u8>,
+    },
+    WindowUpdate(u32),
+    Continuation(Vec<u8>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Http2Frame {
+    pub header: Http2FrameHeader,
+    pub payload: Http2FramePayload,
+}
+
+/// A decoded header block, from a HEADERS or PUSH_PROMISE frame and any CONTINUATION frames
+/// that followed it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Http2HeaderBlock {
+    pub stream_id: u32,
+    pub promised_stream_id: Option<u32>,
+    pub end_stream: bool,
+    pub priority: Option<Http2Priority>,
+    pub headers: Vec<HttpHeader>,
+}
+
+impl Http2HeaderBlock {
+    /// The value of the first header with this name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        http::header(&self.headers, name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Http2Message {
+    Frame(Http2Frame),
+    /// Follows the frame that ended the header block
+    HeaderBlock(Http2HeaderBlock),
+}
+
+/// Decoding state for one direction of an HTTP/2 connection. Each direction has its own HPACK
+/// dynamic table, so a capture needs one decoder for the client and one for the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Http2Decoder {
+    hpack: HpackDecoder,
+    max_frame_size: u32,
+    expect_preface: bool,
+    /// A header block still waiting for its END_HEADERS flag, with its fragments so far
+    pending: Option<(Http2HeaderBlock, Vec<u8>)>,
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+fn length_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::LengthValue)))
+}
+
+pub fn parse_http2_preface(input: &[u8]) -> IResult<&[u8], ()> {
+    let (input, _) = bytes::streaming::tag(HTTP2_PREFACE)(input)?;
+    Ok((input, ()))
+}
+
+pub fn parse_http2_frame_header(input: &[u8]) -> IResult<&[u8], Http2FrameHeader> {
+    let (input, length) = number::streaming::be_u24(input)?;
+    let (input, frame_type) = number::streaming::be_u8(input)?;
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, stream_id) = number::streaming::be_u32(input)?;
+
+    Ok((
+        input,
+        Http2FrameHeader {
+            length,
+            frame_type: frame_type.into(),
+            flags,
+            stream_id: stream_id & STREAM_ID_MASK,
+        },
+    ))
+}
+
+/// Strips the padding of a PADDED frame
+fn unpadded<'a>(header: &Http2FrameHeader, payload: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+    if !header.padded() {
+        return Ok((&[], payload));
+    }
+    match payload.split_first() {
+        Some((&pad_length, rest)) if usize::from(pad_length) <= rest.len() => {
+            Ok((&[], &rest[..rest.len() - usize::from(pad_length)]))
+        }
+        _ => length_error(payload),
+    }
+}
+
+fn priority(input: &[u8]) -> IResult<&[u8], Http2Priority> {
+    if input.len() < 5 {
+        return length_error(input);
+    }
+    let (input, dependency) = number::complete::be_u32(input)?;
+    let (input, weight) = number::complete::be_u8(input)?;
+
+    Ok((
+        input,
+        Http2Priority {
+            exclusive: dependency & !STREAM_ID_MASK != 0,
+            stream_dependency: dependency & STREAM_ID_MASK,
+            weight,
+        },
+    ))
+}
+
+fn setting(input: &[u8]) -> IResult<&[u8], Http2Setting> {
+    let (rest, identifier) = number::complete::be_u16(input)?;
+    let (rest, value) = number::complete::be_u32(rest)?;
+
+    let flag = |value| match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Err::Error(Error::new(input, ErrorKind::Verify))),
+    };
+    let setting = match identifier {
+        0x1 => Http2Setting::HeaderTableSize(value),
+        0x2 => Http2Setting::EnablePush(flag(value)?),
+        0x3 => Http2Setting::MaxConcurrentStreams(value),
+        0x4 if value > STREAM_ID_MASK => return verify_error(input),
+        0x4 => Http2Setting::InitialWindowSize(value),
+        0x5 if !(DEFAULT_MAX_FRAME_SIZE..=0x00ff_ffff).contains(&value) => {
+            return verify_error(input)
+        }
+        0x5 => Http2Setting::MaxFrameSize(value),
+        0x6 => Http2Setting::MaxHeaderListSize(value),
+        0x8 => Http2Setting::EnableConnectProtocol(flag(value)?),
+        0x9 => Http2Setting::NoRfc7540Priorities(flag(value)?),
+        identifier => Http2Setting::Other { identifier, value },
+    };
+
+    Ok((rest, setting))
+}
+
+fn frame_payload<'a>(
+    header: &Http2FrameHeader,
+    payload: &'a [u8],
+) -> IResult<&'a [u8], Http2FramePayload> {
+    use Http2FrameType::*;
+
+    let needs_stream = match header.frame_type {
+        Data | Headers | Priority | RstStream | PushPromise | Continuation => Some(true),
+        Settings | Ping | Goaway => Some(false),
+        WindowUpdate | Other(_) => None,
+    };
+    if matches!(needs_stream, Some(needed) if needed != (header.stream_id != 0)) {
+        return verify_error(payload);
+    }
+
+    let payload = match header.frame_type {
+        Data => Http2FramePayload::Data(unpadded(header, payload)?.1.to_vec()),
+        Headers => {
+            let (_, content) = unpadded(header, payload)?;
+            let (content, priority) = if header.priority() {
+                let (content, priority) = priority(content)?;
+                (content, Some(priority))
+            } else {
+                (content, None)
+            };
+            Http2FramePayload::Headers {
+                priority,
+                header_block: content.to_vec(),
+            }
+        }
+        Priority if payload.len() != 5 => return length_error(payload),
+        Priority => Http2FramePayload::Priority(priority(payload)?.1),
+        RstStream if payload.len() != 4 => return length_error(payload),
+        RstStream => {
+            let (_, code) = number::complete::be_u32(payload)?;
+            Http2FramePayload::RstStream(code.into())
+        }
+        Settings if payload.len() % SETTING_LENGTH != 0 => return length_error(payload),
+        Settings if header.ack() && !payload.is_empty() => return length_error(payload),
+        Settings => {
+            let settings = payload
+                .chunks(SETTING_LENGTH)
+                .map(|chunk| setting(chunk).map(|(_, s)| s))
+                .collect::<Result<_, _>>()?;
+            Http2FramePayload::Settings(settings)
+        }
+        PushPromise => {
+            let (_, content) = unpadded(header, payload)?;
+            if content.len() < 4 {
+                return length_error(payload);
+            }
+            let (content, promised) = number::complete::be_u32(content)?;
+            Http2FramePayload::PushPromise {
+                promised_stream_id: promised & STREAM_ID_MASK,
+                header_block: content.to_vec(),
+            }
+        }
+        Ping if payload.len() != 8 => return length_error(payload),
+        Ping => {
+            let mut data = [0; 8];
+            data.copy_from_slice(payload);
+            Http2FramePayload::Ping(data)
+        }
+        Goaway if payload.len() < 8 => return length_error(payload),
+        Goaway => {
+            let (rest, last_stream_id) = number::complete::be_u32(payload)?;
+            let (rest, code) = number::complete::be_u32(rest)?;
+            Http2FramePayload::Goaway {
+                last_stream_id: last_stream_id & STREAM_ID_MASK,
+                error_code: code.into(),
+                debug_data: rest.to_vec(),
+            }
+        }
+        WindowUpdate if payload.len() != 4 => return length_error(payload),
+        WindowUpdate => {
+            let (_, increment) = number::complete::be_u32(payload)?;
+            if increment & STREAM_ID_MASK == 0 {
+                return verify_error(payload);
+            }
+            Http2FramePayload::WindowUpdate(increment & STREAM_ID_MASK)
+        }
+        Continuation => Http2FramePayload::Continuation(payload.to_vec()),
+        Other(_) => Http2FramePayload::Unknown(payload.to_vec()),
+    };
+
+    Ok((&[], payload))
+}
+
+/// Parses one frame. Frame sizes are only checked against the 24-bit length field; use
+/// `Http2Decoder` to enforce SETTINGS_MAX_FRAME_SIZE.
+pub fn parse_http2_frame(input: &[u8]) -> IResult<&[u8], Http2Frame> {
+    let (input, header) = parse_http2_frame_header(input)?;
+    let (input, payload) = bytes::streaming::take(header.length)(input)?;
+    let (_, payload) = frame_payload(&header, payload)?;
+
+    Ok((input, Http2Frame { header, payload }))
+}
+
+impl Http2Decoder {
+    /// Decoder for the client-to-server direction, which starts with the connection preface
+    pub fn client() -> Self {
+        Http2Decoder {
+            expect_preface: true,
+            ..Self::server()
+        }
+    }
+
+    /// Decoder for the server-to-client direction
+    pub fn server() -> Self {
+        Http2Decoder {
+            hpack: HpackDecoder::new(DEFAULT_TABLE_SIZE),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            expect_preface: false,
+            pending: None,
+        }
+    }
+
+    /// Applies SETTINGS sent by the endpoint receiving this direction, i.e. those found by the
+    /// decoder of the opposite direction
+    pub fn apply_settings(&mut self, settings: &[Http2Setting]) {
+        for setting in settings {
+            match *setting {
+                Http2Setting::HeaderTableSize(size) => self.hpack.set_max_table_size(size as usize),
+                Http2Setting::MaxFrameSize(size) => self.max_frame_size = size,
+                _ => (),
+            }
+        }
+    }
+
+    /// Joins header block fragments, and decodes the block once END_HEADERS is seen
+    fn header_block<'a>(
+        &mut self,
+        frame: &Http2Frame,
+        input: &'a [u8],
+    ) -> Result<Option<Http2HeaderBlock>, Err<Error<&'a [u8]>>> {
+        let header = &frame.header;
+        let (mut block, fragments) = match (&frame.payload, self.pending.take()) {
+            (
+                Http2FramePayload::Headers {
+                    priority,
+                    header_block,
+                },
+                None,
+            ) => (
+                Http2HeaderBlock {
+                    stream_id: header.stream_id,
+                    promised_stream_id: None,
+                    end_stream: header.end_stream(),
+                    priority: *priority,
+                    headers: vec![],
+                },
+                header_block.clone(),
+            ),
+            (
+                Http2FramePayload::PushPromise {
+                    promised_stream_id,
+                    header_block,
+                },
+                None,
+            ) => (
+                Http2HeaderBlock {
+                    stream_id: header.stream_id,
+                    promised_stream_id: Some(*promised_stream_id),
+                    end_stream: false,
+                    priority: None,
+                    headers: vec![],
+                },
+                header_block.clone(),
+            ),
+            (Http2FramePayload::Continuation(fragment), Some((block, mut fragments)))
+                if block.stream_id == header.stream_id =>
+            {
+                fragments.extend_from_slice(fragment);
+                (block, fragments)
+            }
+            // Only CONTINUATION frames of the same stream may follow an unfinished header block
+            (_, Some(_)) | (Http2FramePayload::Continuation(_), None) => {
+                return Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+            }
+            _ => return Ok(None),
+        };
+
+        if fragments.len() > MAX_HEADER_BLOCK_LENGTH {
+            return Err(Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+        }
+        if !header.end_headers() {
+            self.pending = Some((block, fragments));
+            return Ok(None);
+        }
+
+        let (_, headers) = self
+            .hpack
+            .decode(&fragments)
+            .map_err(|e| e.map_input(|_| input))?;
+        block.headers = headers;
+        Ok(Some(block))
+    }
+
+    /// Parses frames until the input runs out. A frame cut short is left in the remaining input,
+    /// to be parsed again once more data has arrived.
+    pub fn parse<'a>(&mut self, mut input: &'a [u8]) -> IResult<&'a [u8], Vec<Http2Message>> {
+        let mut messages = vec![];
+
+        if self.expect_preface {
+            match parse_http2_preface(input) {
+                Ok((rest, ())) => input = rest,
+                Err(Err::Incomplete(_)) => return Ok((input, messages)),
+                Err(e) => return Err(e),
+            }
+            self.expect_preface = false;
+        }
+
+        while !input.is_empty() {
+            match parse_http2_frame_header(input) {
+                Ok((_, header)) if header.length > self.max_frame_size => {
+                    return Err(Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+                }
+                Err(Err::Incomplete(_)) => break,
+                Err(e) => return Err(e),
+                Ok(_) => (),
+            }
+            let (rest, frame) = match parse_http2_frame(input) {
+                Ok(result) => result,
+                Err(Err::Incomplete(_)) => break,
+                Err(e) => return Err(e),
+            };
+
+            let block = self.header_block(&frame, input)?;
+            messages.push(Http2Message::Frame(frame));
+            if let Some(block) = block {
+                messages.push(Http2Message::HeaderBlock(block));
+            }
+            input = rest;
+        }
+
+        Ok((input, messages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn settings_frame_works() {
+        let bytes = [
+            0x00, 0x00, 0x12, // length
+            0x04, // type: SETTINGS
+            0x00, // flags
+            0x00, 0x00, 0x00, 0x00, // stream id
+            0x00, 0x03, 0x00, 0x00, 0x00, 0x64, // MAX_CONCURRENT_STREAMS: 100
+            0x00, 0x04, 0x00, 0x01, 0x00, 0x00, // INITIAL_WINDOW_SIZE: 65536
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, // ENABLE_PUSH: 0
+        ];
+
+        let expectation = Http2Frame {
+            header: Http2FrameHeader {
+                length: 18,
+                frame_type: Http2FrameType::Settings,
+                flags: 0,
+                stream_id: 0,
+            },
+            payload: Http2FramePayload::Settings(vec![
+                Http2Setting::MaxConcurrentStreams(100),
+                Http2Setting::InitialWindowSize(65536),
+                Http2Setting::EnablePush(false),
+            ]),
+        };
+        assert_eq!(parse_http2_frame(&bytes), Ok((EMPTY_SLICE, expectation)));
+
+        // SETTINGS on a stream other than 0
+        let mut bytes = bytes;
+        bytes[8] = 1;
+        assert!(parse_http2_frame(&bytes).is_err());
+    }
+
+    #[test]
+    fn padded_data_frame_works() {
+        let bytes = [
+            0x00, 0x00, 0x08, // length
+            0x00, // type: DATA
+            0x09, // flags: END_STREAM, PADDED
+            0x80, 0x00, 0x00, 0x03, // reserved bit, stream id 3
+            0x03, // pad length
+            0x61, 0x62, 0x63, 0x64, // data
+            0x00, 0x00, 0x00, // padding
+        ];
+
+        let (remaining, frame) = parse_http2_frame(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(frame.header.stream_id, 3);
+        assert!(frame.header.end_stream());
+        assert!(!frame.header.end_headers());
+        assert_eq!(frame.payload, Http2FramePayload::Data(b"abcd".to_vec()));
+    }
+
+    #[test]
+    fn continued_header_block_works() {
+        let mut bytes = HTTP2_PREFACE.to_vec();
+        bytes.extend_from_slice(&[
+            0x00, 0x00, 0x09, // length
+            0x01, // type: HEADERS
+            0x21, // flags: END_STREAM, PRIORITY
+            0x00, 0x00, 0x00, 0x01, // stream id
+            0x00, 0x00, 0x00, 0x00, 0x0f, // priority: stream 0, weight 16
+            0x82, 0x86, 0x84, // :method GET, :scheme http, :path /
+            0x41, // :authority, with incremental indexing
+            0x00, 0x00, 0x0d, // length
+            0x09, // type: CONTINUATION
+            0x04, // flags: END_HEADERS
+            0x00, 0x00, 0x00, 0x01, // stream id
+            0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4,
+            0xff, // www.example.com
+            0x00, 0x00, 0x08, // length, cut short
+            0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02,
+        ]);
+
+        let mut decoder = Http2Decoder::client();
+        let (remaining, messages) = decoder.parse(&bytes).unwrap();
+        assert_eq!(remaining.len(), 11);
+        assert_eq!(messages.len(), 3);
+
+        let block = match &messages[2] {
+            Http2Message::HeaderBlock(block) => block,
+            other => panic!("unexpected message {:?}", other),
+        };
+        assert_eq!(block.stream_id, 1);
+        assert!(block.end_stream);
+        assert_eq!(
+            block.priority,
+            Some(Http2Priority {
+                exclusive: false,
+                stream_dependency: 0,
+                weight: 15,
+            })
+        );
+        assert_eq!(block.headers.len(), 4);
+        assert_eq!(block.header(":authority"), Some("www.example.com"));
+
+        // A DATA frame in the middle of a header block
+        let bytes = [
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x03, // HEADERS, no END_HEADERS
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, // DATA
+        ];
+        assert!(Http2Decoder::server().parse(&bytes).is_err());
+    }
+}
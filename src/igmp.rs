@@ -0,0 +1,339 @@
+//! Handles parsing of IGMPv1, IGMPv2 and IGMPv3 messages
+
+use crate::ipv4;
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::multi::count;
+use nom::number;
+use nom::{Err, IResult};
+use std::net::Ipv4Addr;
+
+// IGMPv3 Membership Query (RFC 3376); v1 and v2 messages stop after the group address
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Type = 0x11  | Max Resp Code |           Checksum            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                         Group Address                         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | Resv  |S| QRV |     QQIC      |     Number of Sources (N)     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Source Addresses [1..N]                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// IGMPv3 Membership Report and Group Record (RFC 3376)
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Type = 0x22  |    Reserved   |           Checksum            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |           Reserved            |  Number of Group Records (M)  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Record Type  |  Aux Data Len |     Number of Sources (N)     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                       Multicast Address                       |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |             Source Addresses [1..N], Auxiliary Data           |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const MEMBERSHIP_QUERY: u8 = 0x11;
+const V1_MEMBERSHIP_REPORT: u8 = 0x12;
+const V2_MEMBERSHIP_REPORT: u8 = 0x16;
+const LEAVE_GROUP: u8 = 0x17;
+const V3_MEMBERSHIP_REPORT: u8 = 0x22;
+
+/// Length of v1 and v2 messages, and of a v3 query without sources
+const V2_LENGTH: usize = 8;
+const V3_QUERY_MIN_LENGTH: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GroupRecordType {
+    ModeIsInclude,
+    ModeIsExclude,
+    ChangeToIncludeMode,
+    ChangeToExcludeMode,
+    AllowNewSources,
+    BlockOldSources,
+    Other(u8),
+}
+
+impl From<u8> for GroupRecordType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::ModeIsInclude,
+            2 => Self::ModeIsExclude,
+            3 => Self::ChangeToIncludeMode,
+            4 => Self::ChangeToExcludeMode,
+            5 => Self::AllowNewSources,
+            6 => Self::BlockOldSources,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A group record of an IGMPv3 report, or with IPv6 addresses an address record of an MLDv2
+/// report
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupRecord<A> {
+    pub record_type: GroupRecordType,
+    pub multicast_address: A,
+    pub sources: Vec<A>,
+    pub auxiliary_data: Vec<u8>,
+}
+
+/// Fields that IGMPv3 and MLDv2 queries add to the earlier versions
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceQuery<A> {
+    pub suppress_router_processing: bool,
+    pub robustness: u8,
+    /// Querier's query interval in seconds, already decoded from its exponential form
+    pub query_interval: u32,
+    pub sources: Vec<A>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IgmpMessage {
+    /// Group is unspecified for general queries. The maximum response time is in tenths of a
+    /// second, and is 0 in v1 queries.
+    Query {
+        max_response_time: u32,
+        group: Ipv4Addr,
+        v3: Option<SourceQuery<Ipv4Addr>>,
+    },
+    V1Report {
+        group: Ipv4Addr,
+    },
+    V2Report {
+        group: Ipv4Addr,
+    },
+    LeaveGroup {
+        group: Ipv4Addr,
+    },
+    V3Report(Vec<GroupRecord<Ipv4Addr>>),
+    Other {
+        message_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IgmpPacket {
+    pub checksum: u16,
+    pub message: IgmpMessage,
+}
+
+impl IgmpPacket {
+    /// The IGMP version a query was sent with, which is told apart by its length and maximum
+    /// response time
+    pub fn query_version(&self) -> Option<u8> {
+        match &self.message {
+            IgmpMessage::Query { v3: Some(_), .. } => Some(3),
+            IgmpMessage::Query {
+                max_response_time: 0,
+                ..
+            } => Some(1),
+            IgmpMessage::Query { .. } => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes an 8-bit code that switches to a floating point form from 128 on, as used by the
+/// IGMPv3 Max Resp Code and the QQIC of both IGMPv3 and MLDv2
+pub(crate) fn decode_code(code: u8) -> u32 {
+    if code < 0x80 {
+        return u32::from(code);
+    }
+    let exponent = (code >> 4) & 0x07;
+    let mantissa = code & 0x0f;
+    (u32::from(mantissa) | 0x10) << (exponent + 3)
+}
+
+/// Parses a record of a v3 report, with addresses of 4 (IGMP) or 16 (MLD) octets
+pub(crate) fn group_record<A>(
+    address: fn(&[u8]) -> IResult<&[u8], A>,
+) -> impl Fn(&[u8]) -> IResult<&[u8], GroupRecord<A>> {
+    move |input| {
+        let (input, record_type) = number::streaming::be_u8(input)?;
+        let (input, auxiliary_length) = number::streaming::be_u8(input)?;
+        let (input, source_count) = number::streaming::be_u16(input)?;
+        let (input, multicast_address) = address(input)?;
+        let (input, sources) = count(address, usize::from(source_count))(input)?;
+        let (input, auxiliary_data) =
+            bytes::streaming::take(usize::from(auxiliary_length) * 4)(input)?;
+
+        Ok((
+            input,
+            GroupRecord {
+                record_type: record_type.into(),
+                multicast_address,
+                sources,
+                auxiliary_data: auxiliary_data.to_vec(),
+            },
+        ))
+    }
+}
+
+/// Parses the S, QRV, QQIC and source list ending IGMPv3 and MLDv2 queries
+pub(crate) fn source_query<A>(
+    input: &[u8],
+    address: fn(&[u8]) -> IResult<&[u8], A>,
+) -> IResult<&[u8], SourceQuery<A>> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, qqic) = number::streaming::be_u8(input)?;
+    let (input, source_count) = number::streaming::be_u16(input)?;
+    let (input, sources) = count(address, usize::from(source_count))(input)?;
+
+    Ok((
+        input,
+        SourceQuery {
+            suppress_router_processing: flags & 0x08 != 0,
+            robustness: flags & 0x07,
+            query_interval: decode_code(qqic),
+            sources,
+        },
+    ))
+}
+
+/// Parses an IGMP message. The input must end where the IP payload ends, as the length of a
+/// query tells its version apart.
+pub fn parse_igmp_packet(input: &[u8]) -> IResult<&[u8], IgmpPacket> {
+    let length = input.len();
+    let (rest, message_type) = number::streaming::be_u8(input)?;
+    let (rest, max_response_code) = number::streaming::be_u8(rest)?;
+    let (rest, checksum) = number::streaming::be_u16(rest)?;
+
+    let (rest, message) = match message_type {
+        MEMBERSHIP_QUERY if length >= V3_QUERY_MIN_LENGTH => {
+            let (rest, group) = ipv4::address(rest)?;
+            let (rest, v3) = source_query(rest, ipv4::address)?;
+            let message = IgmpMessage::Query {
+                max_response_time: decode_code(max_response_code),
+                group,
+                v3: Some(v3),
+            };
+            (rest, message)
+        }
+        MEMBERSHIP_QUERY if length == V2_LENGTH => {
+            let (rest, group) = ipv4::address(rest)?;
+            let message = IgmpMessage::Query {
+                max_response_time: u32::from(max_response_code),
+                group,
+                v3: None,
+            };
+            (rest, message)
+        }
+        MEMBERSHIP_QUERY => {
+            return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+        }
+        V1_MEMBERSHIP_REPORT | V2_MEMBERSHIP_REPORT | LEAVE_GROUP => {
+            let (rest, group) = ipv4::address(rest)?;
+            let message = match message_type {
+                V1_MEMBERSHIP_REPORT => IgmpMessage::V1Report { group },
+                V2_MEMBERSHIP_REPORT => IgmpMessage::V2Report { group },
+                _ => IgmpMessage::LeaveGroup { group },
+            };
+            (rest, message)
+        }
+        V3_MEMBERSHIP_REPORT => {
+            let (rest, _reserved) = number::streaming::be_u16(rest)?;
+            let (rest, record_count) = number::streaming::be_u16(rest)?;
+            let (rest, records) =
+                count(group_record(ipv4::address), usize::from(record_count))(rest)?;
+            (rest, IgmpMessage::V3Report(records))
+        }
+        message_type => {
+            let message = IgmpMessage::Other {
+                message_type,
+                data: rest.to_vec(),
+            };
+            (&rest[rest.len()..], message)
+        }
+    };
+
+    Ok((rest, IgmpPacket { checksum, message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn queries_work() {
+        let v2_query = [
+            0x11, // Membership Query
+            0x64, // max response time: 10 s
+            0xee, 0x9b, // checksum
+            0x00, 0x00, 0x00, 0x00, // general query
+        ];
+        let (_, packet) = parse_igmp_packet(&v2_query).unwrap();
+        assert_eq!(packet.query_version(), Some(2));
+
+        let v3_query = [
+            0x11, // Membership Query
+            0x8c, // max response code: 22.4 s in floating point form
+            0x00, 0x00, // checksum
+            0xef, 0x01, 0x01, 0x01, // group
+            0x0a, // S flag, robustness 2
+            0x7d, // QQIC: 125 s
+            0x00, 0x01, // one source
+            0xc0, 0x00, 0x02, 0x01, // source
+        ];
+        let expectation = IgmpPacket {
+            checksum: 0,
+            message: IgmpMessage::Query {
+                max_response_time: 224,
+                group: Ipv4Addr::new(239, 1, 1, 1),
+                v3: Some(SourceQuery {
+                    suppress_router_processing: true,
+                    robustness: 2,
+                    query_interval: 125,
+                    sources: vec![Ipv4Addr::new(192, 0, 2, 1)],
+                }),
+            },
+        };
+        assert_eq!(parse_igmp_packet(&v3_query), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn v3_report_works() {
+        let bytes = [
+            0x22, 0x00, // Membership Report
+            0x00, 0x00, // checksum
+            0x00, 0x00, 0x00, 0x02, // two records
+            0x04, 0x00, 0x00, 0x00, // CHANGE_TO_EXCLUDE_MODE, no sources
+            0xef, 0x01, 0x01, 0x01, // multicast address
+            0x05, 0x01, 0x00, 0x02, // ALLOW_NEW_SOURCES, aux data, two sources
+            0xe8, 0x01, 0x02, 0x03, // multicast address
+            0xc0, 0x00, 0x02, 0x01, // source
+            0xc0, 0x00, 0x02, 0x02, // source
+            0xde, 0xad, 0xbe, 0xef, // auxiliary data
+        ];
+
+        let (remaining, packet) = parse_igmp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        let records = match packet.message {
+            IgmpMessage::V3Report(records) => records,
+            other => panic!("unexpected message {:?}", other),
+        };
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_type, GroupRecordType::ChangeToExcludeMode);
+        assert!(records[0].sources.is_empty());
+        assert_eq!(
+            records[1],
+            GroupRecord {
+                record_type: GroupRecordType::AllowNewSources,
+                multicast_address: Ipv4Addr::new(232, 1, 2, 3),
+                sources: vec![Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)],
+                auxiliary_data: vec![0xde, 0xad, 0xbe, 0xef],
+            }
+        );
+    }
+}
@@ -1,10 +1,12 @@
 //! Handles parsing of Internet Protocol fields (shared between ipv4 and ipv6)
 
+use crate::ipv4::{self, IPv4Header};
+use crate::ipv6::{self, IPv6Header};
 use nom::bits;
-use nom::error::Error;
+use nom::error::{Error, ErrorKind};
 use nom::number;
 use nom::sequence;
-use nom::IResult;
+use nom::{Err, IResult};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -28,7 +30,17 @@ pub enum IPProtocol {
     CHAOS,
     UDP,
     IPV6,
+    IPV6ROUTE,
+    IPV6FRAG,
+    GRE,
+    ESP,
+    AH,
     ICMP6,
+    IPV6NONXT,
+    IPV6OPTS,
+    OSPF,
+    VRRP,
+    SCTP,
     Other(u8),
 }
 
@@ -54,7 +66,17 @@ impl From<u8> for IPProtocol {
             16 => IPProtocol::CHAOS,
             17 => IPProtocol::UDP,
             41 => IPProtocol::IPV6,
+            43 => IPProtocol::IPV6ROUTE,
+            44 => IPProtocol::IPV6FRAG,
+            47 => IPProtocol::GRE,
+            50 => IPProtocol::ESP,
+            51 => IPProtocol::AH,
             58 => IPProtocol::ICMP6,
+            59 => IPProtocol::IPV6NONXT,
+            60 => IPProtocol::IPV6OPTS,
+            89 => IPProtocol::OSPF,
+            112 => IPProtocol::VRRP,
+            132 => IPProtocol::SCTP,
             other => IPProtocol::Other(other),
         }
     }
@@ -72,3 +94,36 @@ pub(crate) fn protocol(input: &[u8]) -> IResult<&[u8], IPProtocol> {
 
     Ok((input, protocol.into()))
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IPHeader {
+    V4(IPv4Header),
+    V6(IPv6Header),
+}
+
+impl IPHeader {
+    /// The protocol of the payload that follows this header
+    pub fn protocol(&self) -> IPProtocol {
+        match self {
+            IPHeader::V4(header) => header.protocol,
+            IPHeader::V6(header) => header.next_header,
+        }
+    }
+}
+
+/// Parses either an IPv4 or an IPv6 header, as selected by the version nibble.
+pub fn parse_ip_header(input: &[u8]) -> IResult<&[u8], IPHeader> {
+    let (_, version) = number::streaming::be_u8(input)?;
+    match version >> 4 {
+        4 => {
+            let (input, header) = ipv4::parse_ipv4_header(input)?;
+            Ok((input, IPHeader::V4(header)))
+        }
+        6 => {
+            let (input, header) = ipv6::parse_ipv6_header(input)?;
+            Ok((input, IPHeader::V6(header)))
+        }
+        _ => Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    }
+}
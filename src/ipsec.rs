@@ -0,0 +1,160 @@
+//! Handles parsing of IPsec AH and ESP headers, and of ESP in UDP (NAT traversal)
+
+use crate::ip::{self, IPProtocol};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// Authentication Header Format (RFC 4302)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | Next Header   |  Payload Len  |          RESERVED             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                 Security Parameters Index (SPI)               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Sequence Number Field                      |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                Integrity Check Value-ICV (variable)           |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// ESP Packet Format (RFC 4303), only the first two words are sent in clear
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |               Security Parameters Index (SPI)                 |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                      Sequence Number                          |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Payload Data (encrypted)                   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const NAT_T_PORT: u16 = 4500;
+
+const NAT_KEEPALIVE: u8 = 0xff;
+const NON_ESP_MARKER: u32 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AhHeader {
+    pub next_header: IPProtocol,
+    /// Length of the AH in 4-byte words, minus 2
+    pub payload_length: u8,
+    pub reserved: u16,
+    pub spi: u32,
+    pub sequence_no: u32,
+    pub icv: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EspHeader {
+    pub spi: u32,
+    pub sequence_no: u32,
+}
+
+/// What a UDP-encapsulated IPsec payload (RFC 3948) carries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NatTPacket {
+    /// A single 0xFF byte keeping the NAT mapping alive
+    Keepalive,
+    /// An IKE message, announced by the 4-byte non-ESP marker
+    Ike,
+    Esp(EspHeader),
+}
+
+pub fn parse_ah_header(input: &[u8]) -> IResult<&[u8], AhHeader> {
+    let (input, next_header) = ip::protocol(input)?;
+    let (input, payload_length) = number::streaming::be_u8(input)?;
+    let (input, reserved) = number::streaming::be_u16(input)?;
+    let (input, spi) = number::streaming::be_u32(input)?;
+    let (input, sequence_no) = number::streaming::be_u32(input)?;
+    // The whole header is (payload_length + 2) words, of which 3 are fixed fields
+    let icv_length = (usize::from(payload_length) + 2)
+        .checked_sub(3)
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::LengthValue)))?;
+    let (input, icv) = bytes::streaming::take(icv_length * 4)(input)?;
+
+    Ok((
+        input,
+        AhHeader {
+            next_header,
+            payload_length,
+            reserved,
+            spi,
+            sequence_no,
+            icv: icv.to_vec(),
+        },
+    ))
+}
+
+pub fn parse_esp_header(input: &[u8]) -> IResult<&[u8], EspHeader> {
+    let (input, spi) = number::streaming::be_u32(input)?;
+    let (input, sequence_no) = number::streaming::be_u32(input)?;
+
+    Ok((input, EspHeader { spi, sequence_no }))
+}
+
+/// Classifies the payload of a UDP datagram on the NAT-T port. For IKE the remaining input is
+/// the IKE message following the non-ESP marker; for ESP it is the encrypted payload.
+pub fn parse_nat_t_packet(input: &[u8]) -> IResult<&[u8], NatTPacket> {
+    if input == [NAT_KEEPALIVE] {
+        return Ok((&input[1..], NatTPacket::Keepalive));
+    }
+
+    let (rest, marker) = number::streaming::be_u32(input)?;
+    if marker == NON_ESP_MARKER {
+        return Ok((rest, NatTPacket::Ike));
+    }
+
+    let (input, esp) = parse_esp_header(input)?;
+    Ok((input, NatTPacket::Esp(esp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn ah_header_works() {
+        let bytes = [
+            0x06, 0x04, 0x00, 0x00, /* next header, payload length, reserved */
+            0x00, 0x00, 0x10, 0x01, /* SPI */
+            0x00, 0x00, 0x00, 0x02, /* sequence number */
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, /* ICV */
+        ];
+        let expectation = AhHeader {
+            next_header: IPProtocol::TCP,
+            payload_length: 4,
+            reserved: 0,
+            spi: 0x1001,
+            sequence_no: 2,
+            icv: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        };
+        assert_eq!(parse_ah_header(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn nat_t_packet_works() {
+        assert_eq!(
+            parse_nat_t_packet(&[0xff]),
+            Ok((EMPTY_SLICE, NatTPacket::Keepalive))
+        );
+
+        let ike = [0x00, 0x00, 0x00, 0x00, 0x8a, 0x2b];
+        assert_eq!(parse_nat_t_packet(&ike), Ok((&ike[4..], NatTPacket::Ike)));
+
+        let esp = [0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00, 0x07, 0xaa];
+        let expectation = NatTPacket::Esp(EspHeader {
+            spi: 0x1001,
+            sequence_no: 7,
+        });
+        assert_eq!(parse_nat_t_packet(&esp), Ok((&esp[8..], expectation)));
+    }
+}
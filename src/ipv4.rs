@@ -3,10 +3,10 @@
 use crate::ip::{self, IPProtocol};
 use nom::bits;
 use nom::bytes;
-use nom::error::Error;
+use nom::error::{Error, ErrorKind};
 use nom::number;
 use nom::sequence;
-use nom::IResult;
+use nom::{Err, IResult};
 use std::convert::TryFrom;
 use std::net::Ipv4Addr;
 
@@ -27,6 +27,23 @@ pub struct IPv4Header {
     pub dest_addr: Ipv4Addr,
 }
 
+const OPTION_END_OF_LIST: u8 = 0;
+const OPTION_NO_OPERATION: u8 = 1;
+const OPTION_ROUTER_ALERT: u8 = 148;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IPv4Option {
+    NoOperation,
+    /// Router Alert (RFC 2113), carried by IGMP and RSVP. A value of 0 asks every router on the
+    /// path to examine the packet.
+    RouterAlert(u16),
+    Other {
+        option_type: u8,
+        data: Vec<u8>,
+    },
+}
+
 fn flag_frag_offset(input: &[u8]) -> IResult<&[u8], (u8, u16)> {
     bits::bits::<_, _, Error<_>, _, _>(sequence::pair(
         bits::streaming::take(3u8),
@@ -71,9 +88,56 @@ pub fn parse_ipv4_header(input: &[u8]) -> IResult<&[u8], IPv4Header> {
     ))
 }
 
+/// Parses the options between a header and its payload, so that the remaining input starts at
+/// the payload. Options after End of Options List are padding and are skipped.
+pub fn parse_ipv4_options<'a>(
+    input: &'a [u8],
+    header: &IPv4Header,
+) -> IResult<&'a [u8], Vec<IPv4Option>> {
+    if header.ihl < 5 {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (input, mut options_data) = bytes::streaming::take(usize::from(header.ihl - 5) * 4)(input)?;
+
+    let mut options = vec![];
+    while let Some((&option_type, rest)) = options_data.split_first() {
+        match option_type {
+            OPTION_END_OF_LIST => break,
+            OPTION_NO_OPERATION => {
+                options.push(IPv4Option::NoOperation);
+                options_data = rest;
+                continue;
+            }
+            _ => (),
+        }
+
+        let length = match rest.first() {
+            Some(&length) if length >= 2 && usize::from(length) <= options_data.len() => {
+                usize::from(length)
+            }
+            _ => return Err(Err::Error(Error::new(options_data, ErrorKind::LengthValue))),
+        };
+        let data = &options_data[2..length];
+        options.push(match (option_type, data) {
+            (OPTION_ROUTER_ALERT, &[high, low]) => {
+                IPv4Option::RouterAlert(u16::from_be_bytes([high, low]))
+            }
+            _ => IPv4Option::Other {
+                option_type,
+                data: data.to_vec(),
+            },
+        });
+        options_data = &options_data[length..];
+    }
+
+    Ok((input, options))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ip::protocol, parse_ipv4_header, IPProtocol, IPv4Header};
+    use super::{
+        ip::protocol, parse_ipv4_header, parse_ipv4_options, IPProtocol, IPv4Header, IPv4Option,
+    };
     use std::net::Ipv4Addr;
 
     const EMPTY_SLICE: &'static [u8] = &[];
@@ -90,6 +154,10 @@ mod tests {
     mk_protocol_test!(protocol_gets_icmp_correct, [1], IPProtocol::ICMP);
     mk_protocol_test!(protocol_gets_tcp_correct, [6], IPProtocol::TCP);
     mk_protocol_test!(protocol_gets_udp_correct, [17], IPProtocol::UDP);
+    mk_protocol_test!(protocol_gets_gre_correct, [47], IPProtocol::GRE);
+    mk_protocol_test!(protocol_gets_ospf_correct, [89], IPProtocol::OSPF);
+    mk_protocol_test!(protocol_gets_vrrp_correct, [112], IPProtocol::VRRP);
+    mk_protocol_test!(protocol_gets_sctp_correct, [132], IPProtocol::SCTP);
 
     #[test]
     fn ipparse_gets_packet_correct() {
@@ -122,4 +190,20 @@ mod tests {
         };
         assert_eq!(parse_ipv4_header(&bytes), Ok((EMPTY_SLICE, expectation)));
     }
+
+    #[test]
+    fn router_alert_option_works() {
+        let bytes = [
+            0x46, 0xc0, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00, 0x01, 0x02, 0x41, 0x89, 0xc0, 0xa8,
+            0x01, 0x0a, 0xe0, 0x00, 0x00, 0x16, // IPv4 header with IHL 6, protocol IGMP
+            0x94, 0x04, 0x00, 0x00, // Router Alert
+            0x22, 0x00, 0xf9, 0x02, // IGMP payload
+        ];
+
+        let (remaining, header) = parse_ipv4_header(&bytes).unwrap();
+        assert_eq!(header.protocol, IPProtocol::IGMP);
+        let (remaining, options) = parse_ipv4_options(remaining, &header).unwrap();
+        assert_eq!(options, vec![IPv4Option::RouterAlert(0)]);
+        assert_eq!(remaining, &[0x22, 0x00, 0xf9, 0x02]);
+    }
 }
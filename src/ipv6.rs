@@ -1,6 +1,7 @@
 //! Handles parsing of IPv6 headers
 
 use crate::ip::{self, IPProtocol};
+use crate::ipsec::{self, AhHeader};
 use nom::bits;
 use nom::bytes;
 use nom::error::Error;
@@ -23,6 +24,34 @@ pub struct IPv6Header {
     pub dest_addr: Ipv6Addr,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IPv6Option {
+    pub option_type: u8,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IPv6ExtensionHeader {
+    HopByHop(Vec<IPv6Option>),
+    Routing {
+        routing_type: u8,
+        segments_left: u8,
+        data: Vec<u8>,
+    },
+    Fragment {
+        fragment_offset: u16,
+        more_fragments: bool,
+        identification: u32,
+    },
+    DestinationOptions(Vec<IPv6Option>),
+    Authentication(AhHeader),
+}
+
+const OPTION_PAD1: u8 = 0;
+const OPTION_PADN: u8 = 1;
+
 pub(crate) fn address(input: &[u8]) -> IResult<&[u8], Ipv6Addr> {
     let (input, ipv6) = bytes::streaming::take(16u8)(input)?;
 
@@ -64,9 +93,129 @@ pub fn parse_ipv6_header(input: &[u8]) -> IResult<&[u8], IPv6Header> {
     ))
 }
 
+/// Parses the TLV-encoded options of Hop-by-Hop and Destination Options headers, leaving out
+/// the padding options.
+fn ipv6_options(mut input: &[u8]) -> IResult<&[u8], Vec<IPv6Option>> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, option_type) = number::complete::be_u8(input)?;
+        if option_type == OPTION_PAD1 {
+            input = rest;
+            continue;
+        }
+        let (rest, length) = number::complete::be_u8(rest)?;
+        let (rest, data) = bytes::complete::take(length)(rest)?;
+        if option_type != OPTION_PADN {
+            options.push(IPv6Option {
+                option_type,
+                data: data.to_vec(),
+            });
+        }
+        input = rest;
+    }
+
+    Ok((input, options))
+}
+
+/// Parses the generic layout shared by extension headers: next header, length in 8-octet units
+/// not counting the first 8 octets, and data.
+fn extension_header(input: &[u8]) -> IResult<&[u8], (IPProtocol, &[u8])> {
+    let (input, next_header) = ip::protocol(input)?;
+    let (input, length) = number::streaming::be_u8(input)?;
+    let (input, data) = bytes::streaming::take(usize::from(length) * 8 + 6)(input)?;
+
+    Ok((input, (next_header, data)))
+}
+
+fn ipv6_extension_header(
+    input: &[u8],
+    header_type: IPProtocol,
+) -> IResult<&[u8], Option<(IPv6ExtensionHeader, IPProtocol)>> {
+    match header_type {
+        IPProtocol::HOPOPT => {
+            let (input, (next_header, data)) = extension_header(input)?;
+            let (_, options) = ipv6_options(data)?;
+            Ok((
+                input,
+                Some((IPv6ExtensionHeader::HopByHop(options), next_header)),
+            ))
+        }
+        IPProtocol::IPV6OPTS => {
+            let (input, (next_header, data)) = extension_header(input)?;
+            let (_, options) = ipv6_options(data)?;
+            let header = IPv6ExtensionHeader::DestinationOptions(options);
+            Ok((input, Some((header, next_header))))
+        }
+        IPProtocol::IPV6ROUTE => {
+            let (input, (next_header, data)) = extension_header(input)?;
+            let header = IPv6ExtensionHeader::Routing {
+                routing_type: data[0],
+                segments_left: data[1],
+                data: data[2..].to_vec(),
+            };
+            Ok((input, Some((header, next_header))))
+        }
+        IPProtocol::IPV6FRAG => {
+            let (input, next_header) = ip::protocol(input)?;
+            let (input, _reserved) = number::streaming::be_u8(input)?;
+            let (input, offset_flags) = number::streaming::be_u16(input)?;
+            let (input, identification) = number::streaming::be_u32(input)?;
+            let header = IPv6ExtensionHeader::Fragment {
+                fragment_offset: offset_flags >> 3,
+                more_fragments: offset_flags & 1 == 1,
+                identification,
+            };
+            Ok((input, Some((header, next_header))))
+        }
+        IPProtocol::AH => {
+            let (input, ah) = ipsec::parse_ah_header(input)?;
+            let next_header = ah.next_header;
+            Ok((
+                input,
+                Some((IPv6ExtensionHeader::Authentication(ah), next_header)),
+            ))
+        }
+        _ => Ok((input, None)),
+    }
+}
+
+/// Walks the chain of extension headers following an IPv6 header, starting with `next_header`
+/// from that header. Returns the extension headers and the protocol of the upper-layer payload,
+/// which the remaining input starts with. Walking stops after a non-first fragment, since what
+/// follows it is not a header.
+pub fn parse_ipv6_extension_headers(
+    input: &[u8],
+    next_header: IPProtocol,
+) -> IResult<&[u8], (Vec<IPv6ExtensionHeader>, IPProtocol)> {
+    let mut input = input;
+    let mut next_header = next_header;
+    let mut headers = vec![];
+
+    while let (rest, Some((header, next))) = ipv6_extension_header(input, next_header)? {
+        let last = matches!(
+            header,
+            IPv6ExtensionHeader::Fragment { fragment_offset, .. } if fragment_offset != 0
+        );
+        headers.push(header);
+        next_header = next;
+        input = rest;
+        if last {
+            break;
+        }
+    }
+
+    Ok((input, (headers, next_header)))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ip::protocol, parse_ipv6_header, IPProtocol, IPv6Header};
+    use super::{
+        ip::protocol, parse_ipv6_extension_headers, parse_ipv6_header, IPProtocol,
+        IPv6ExtensionHeader, IPv6Header, IPv6Option,
+    };
+    use crate::ipsec::AhHeader;
+    use nom::error::{Error, ErrorKind};
+    use nom::Err;
     use std::net::Ipv6Addr;
 
     const EMPTY_SLICE: &'static [u8] = &[];
@@ -83,6 +232,8 @@ mod tests {
     mk_protocol_test!(protocol_gets_icmp_correct, [1], IPProtocol::ICMP);
     mk_protocol_test!(protocol_gets_tcp_correct, [6], IPProtocol::TCP);
     mk_protocol_test!(protocol_gets_udp_correct, [17], IPProtocol::UDP);
+    mk_protocol_test!(protocol_gets_esp_correct, [50], IPProtocol::ESP);
+    mk_protocol_test!(protocol_gets_ah_correct, [51], IPProtocol::AH);
 
     #[test]
     fn ipparse_gets_packet_correct() {
@@ -118,4 +269,62 @@ mod tests {
         };
         assert_eq!(parse_ipv6_header(&bytes), Ok((EMPTY_SLICE, expectation)));
     }
+
+    #[test]
+    fn extension_headers_with_ah_work() {
+        let bytes = [
+            0x33, 0x00, /* Hop-by-Hop: next header AH, length */
+            0x05, 0x02, 0x00, 0x00, /* Router Alert option */
+            0x01, 0x00, /* PadN option */
+            0x2c, 0x04, 0x00, 0x00, /* AH: next header Fragment, payload length, reserved */
+            0x00, 0x00, 0x01, 0x00, /* SPI */
+            0x00, 0x00, 0x00, 0x01, /* sequence number */
+            0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, /* ICV */
+            0x11, 0x00, 0x00, 0x01, /* Fragment: next header UDP, offset 0, more fragments */
+            0x00, 0x00, 0x30, 0x39, /* identification */
+            0xde, 0xad, /* UDP */
+        ];
+
+        let expectation = vec![
+            IPv6ExtensionHeader::HopByHop(vec![IPv6Option {
+                option_type: 5,
+                data: vec![0, 0],
+            }]),
+            IPv6ExtensionHeader::Authentication(AhHeader {
+                next_header: IPProtocol::IPV6FRAG,
+                payload_length: 4,
+                reserved: 0,
+                spi: 0x100,
+                sequence_no: 1,
+                icv: vec![
+                    0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15,
+                ],
+            }),
+            IPv6ExtensionHeader::Fragment {
+                fragment_offset: 0,
+                more_fragments: true,
+                identification: 12345,
+            },
+        ];
+        assert_eq!(
+            parse_ipv6_extension_headers(&bytes, IPProtocol::HOPOPT),
+            Ok((&[0xde, 0xad][..], (expectation, IPProtocol::UDP)))
+        );
+    }
+
+    #[test]
+    fn hop_by_hop_option_overrunning_header_fails() {
+        let bytes = [
+            0x11, 0x00, /* Hop-by-Hop: next header UDP, length */
+            0x05, 0x08, 0x00, 0x00, 0x00, 0x00, /* Router Alert option, length too large */
+            0xde, 0xad, /* UDP */
+        ];
+        assert!(matches!(
+            parse_ipv6_extension_headers(&bytes, IPProtocol::HOPOPT),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+    }
 }
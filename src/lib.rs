@@ -1,8 +1,44 @@
 pub mod arp;
+pub mod ber;
+pub mod bgp;
+pub mod cdp;
+pub mod dhcp;
+pub mod dhcpv6;
+pub mod dns;
+pub mod eap;
+pub mod eapol;
+pub mod erspan;
 pub mod ethernet;
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+pub mod geneve;
+pub mod gre;
+pub mod gtp;
+pub mod hpack;
+pub mod hsrp;
+pub mod http;
+pub mod http2;
 pub mod icmp;
+pub mod igmp;
 pub mod ip;
+pub mod ipsec;
 pub mod ipv4;
 pub mod ipv6;
+pub mod llc;
+pub mod lldp;
+pub mod mld;
+pub mod ntp;
+pub mod ospf;
+pub mod ptp;
+pub mod quic;
+pub mod sctp;
+pub mod slow;
+pub mod snmp;
+pub mod stp;
 pub mod tcp;
+pub mod teredo;
+pub mod tls;
+pub mod tunnel;
 pub mod udp;
+pub mod vrrp;
+pub mod vxlan;
@@ -0,0 +1,102 @@
+//! Handles parsing of IEEE 802.2 LLC headers and their SNAP extension, which follow the length
+//! field of IEEE 802.3 frames
+
+use nom::bytes;
+use nom::number;
+use nom::IResult;
+
+// LLC Header (IEEE 802.2), followed by a SNAP header when both SAPs are 0xAA
+//
+//   +--------+--------+-----------------+
+//   |  DSAP  |  SSAP  | Control (1 or 2)|
+//   +--------+--------+-----------------+
+//   |      OUI (3)    | Protocol ID (2) |   SNAP
+//   +-----------------+-----------------+
+
+pub const LLC_SAP_SPANNING_TREE: u8 = 0x42;
+pub const LLC_SAP_SNAP: u8 = 0xaa;
+
+/// Unnumbered frames have a single control octet, whose two low bits are set
+const CONTROL_UNNUMBERED: u8 = 0x03;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapHeader {
+    pub oui: [u8; 3],
+    /// An EtherType when the OUI is zero, otherwise defined by the organization
+    pub protocol_id: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LlcHeader {
+    pub dsap: u8,
+    /// The low bit is the command/response bit
+    pub ssap: u8,
+    /// One octet for unnumbered frames, two for information and supervisory frames
+    pub control: u16,
+    pub snap: Option<SnapHeader>,
+}
+
+pub fn parse_llc_header(input: &[u8]) -> IResult<&[u8], LlcHeader> {
+    let (input, dsap) = number::streaming::be_u8(input)?;
+    let (input, ssap) = number::streaming::be_u8(input)?;
+    let (input, first) = number::streaming::be_u8(input)?;
+    let (input, control) = if first & CONTROL_UNNUMBERED == CONTROL_UNNUMBERED {
+        (input, u16::from(first))
+    } else {
+        let (input, second) = number::streaming::be_u8(input)?;
+        (input, u16::from_be_bytes([first, second]))
+    };
+    let (input, snap) = if dsap == LLC_SAP_SNAP && ssap == LLC_SAP_SNAP {
+        let (input, oui) = bytes::streaming::take(3u8)(input)?;
+        let (input, protocol_id) = number::streaming::be_u16(input)?;
+        let snap = SnapHeader {
+            oui: [oui[0], oui[1], oui[2]],
+            protocol_id,
+        };
+        (input, Some(snap))
+    } else {
+        (input, None)
+    };
+
+    Ok((
+        input,
+        LlcHeader {
+            dsap,
+            ssap,
+            control,
+            snap,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn llc_and_snap_work() {
+        let bytes = [
+            0xaa, 0xaa, 0x03, // SNAP, unnumbered information
+            0x00, 0x00, 0x0c, 0x20, 0x00, // Cisco, CDP
+        ];
+        let expectation = LlcHeader {
+            dsap: 0xaa,
+            ssap: 0xaa,
+            control: 0x03,
+            snap: Some(SnapHeader {
+                oui: [0x00, 0x00, 0x0c],
+                protocol_id: 0x2000,
+            }),
+        };
+        assert_eq!(parse_llc_header(&bytes), Ok((EMPTY_SLICE, expectation)));
+
+        let (_, header) = parse_llc_header(&[0x42, 0x42, 0x03]).unwrap();
+        assert_eq!(header.snap, None);
+        let (_, header) = parse_llc_header(&[0xe0, 0xe0, 0x00, 0x02]).unwrap();
+        assert_eq!(header.control, 0x0002);
+    }
+}
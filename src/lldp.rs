@@ -0,0 +1,429 @@
+//! Handles parsing of LLDP data units, carried in Ethernet frames of EtherType 0x88CC
+
+use crate::ethernet::MacAddress;
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// LLDP TLV Format (IEEE 802.1AB); an LLDPDU starts with the Chassis ID, Port ID and Time To Live
+// TLVs and ends with an End Of LLDPDU TLV
+//
+//   +-------------+------------------+--------------------------+
+//   | Type (7 bit)| Length (9 bit)   |  Value (0-511 octets)    |
+//   +-------------+------------------+--------------------------+
+//
+// Organizationally Specific TLV value
+//
+//   +----------------+-----------+-----------------------------+
+//   |    OUI (3)     | Subtype(1)| Information (0-507 octets)  |
+//   +----------------+-----------+-----------------------------+
+
+pub const LLDP_CAPABILITY_OTHER: u16 = 0x0001;
+pub const LLDP_CAPABILITY_REPEATER: u16 = 0x0002;
+pub const LLDP_CAPABILITY_BRIDGE: u16 = 0x0004;
+pub const LLDP_CAPABILITY_WLAN_ACCESS_POINT: u16 = 0x0008;
+pub const LLDP_CAPABILITY_ROUTER: u16 = 0x0010;
+pub const LLDP_CAPABILITY_TELEPHONE: u16 = 0x0020;
+pub const LLDP_CAPABILITY_DOCSIS_CABLE_DEVICE: u16 = 0x0040;
+pub const LLDP_CAPABILITY_STATION_ONLY: u16 = 0x0080;
+
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TIME_TO_LIVE: u8 = 3;
+const TLV_PORT_DESCRIPTION: u8 = 4;
+const TLV_SYSTEM_NAME: u8 = 5;
+const TLV_SYSTEM_DESCRIPTION: u8 = 6;
+const TLV_SYSTEM_CAPABILITIES: u8 = 7;
+const TLV_MANAGEMENT_ADDRESS: u8 = 8;
+const TLV_ORGANIZATIONALLY_SPECIFIC: u8 = 127;
+
+const OUI_IEEE_802_1: [u8; 3] = [0x00, 0x80, 0xc2];
+const OUI_IEEE_802_3: [u8; 3] = [0x00, 0x12, 0x0f];
+
+const IEEE_802_1_PORT_VLAN_ID: u8 = 1;
+const IEEE_802_1_PORT_AND_PROTOCOL_VLAN_ID: u8 = 2;
+const IEEE_802_1_VLAN_NAME: u8 = 3;
+const IEEE_802_3_MAC_PHY_CONFIGURATION: u8 = 1;
+const IEEE_802_3_LINK_AGGREGATION: u8 = 3;
+const IEEE_802_3_MAXIMUM_FRAME_SIZE: u8 = 4;
+
+/// IANA address family numbers
+const FAMILY_IPV4: u8 = 1;
+const FAMILY_IPV6: u8 = 2;
+const FAMILY_MAC: u8 = 6;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LldpAddress {
+    Ip(IpAddr),
+    Mac(MacAddress),
+    Other { family: u8, address: Vec<u8> },
+}
+
+/// Textual subtypes are decoded lossily as UTF-8
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LldpChassisId {
+    ChassisComponent(String),
+    InterfaceAlias(String),
+    PortComponent(String),
+    MacAddress(MacAddress),
+    NetworkAddress(LldpAddress),
+    InterfaceName(String),
+    Local(String),
+    Other { subtype: u8, id: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LldpPortId {
+    InterfaceAlias(String),
+    PortComponent(String),
+    MacAddress(MacAddress),
+    NetworkAddress(LldpAddress),
+    InterfaceName(String),
+    AgentCircuitId(Vec<u8>),
+    Local(String),
+    Other { subtype: u8, id: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LldpManagementAddress {
+    pub address: LldpAddress,
+    /// 2 for an ifIndex, 3 for a system port number
+    pub interface_numbering: u8,
+    pub interface_number: u32,
+    pub oid: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LldpTlv {
+    PortDescription(String),
+    SystemName(String),
+    SystemDescription(String),
+    /// Bitmaps of the `LLDP_CAPABILITY_*` values
+    SystemCapabilities {
+        capabilities: u16,
+        enabled: u16,
+    },
+    ManagementAddress(LldpManagementAddress),
+    PortVlanId(u16),
+    PortAndProtocolVlanId {
+        flags: u8,
+        vlan_id: u16,
+    },
+    VlanName {
+        vlan_id: u16,
+        name: String,
+    },
+    MacPhyConfiguration {
+        autonegotiation: u8,
+        advertised_capabilities: u16,
+        operational_mau_type: u16,
+    },
+    LinkAggregation {
+        status: u8,
+        port_id: u32,
+    },
+    MaximumFrameSize(u16),
+    OrganizationallySpecific {
+        oui: [u8; 3],
+        subtype: u8,
+        information: Vec<u8>,
+    },
+    Other {
+        tlv_type: u8,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LldpPdu {
+    pub chassis_id: LldpChassisId,
+    pub port_id: LldpPortId,
+    /// Seconds the information stays valid; 0 when the neighbor is shutting down
+    pub time_to_live: u16,
+    /// The optional TLVs, in order
+    pub tlvs: Vec<LldpTlv>,
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+fn tlv(input: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
+    let (rest, header) = number::streaming::be_u16(input)?;
+    let (rest, value) = bytes::streaming::take(header & 0x01ff)(rest)?;
+
+    Ok((rest, ((header >> 9) as u8, value)))
+}
+
+fn address(family: u8, address: &[u8]) -> LldpAddress {
+    match (family, address.len()) {
+        (FAMILY_IPV4, 4) => LldpAddress::Ip(IpAddr::V4(Ipv4Addr::new(
+            address[0], address[1], address[2], address[3],
+        ))),
+        (FAMILY_IPV6, 16) => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(address);
+            LldpAddress::Ip(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        (FAMILY_MAC, 6) => LldpAddress::Mac(mac_address(address)),
+        _ => LldpAddress::Other {
+            family,
+            address: address.to_vec(),
+        },
+    }
+}
+
+fn mac_address(raw: &[u8]) -> MacAddress {
+    let mut mac = [0; 6];
+    mac.copy_from_slice(raw);
+    MacAddress(mac)
+}
+
+fn network_address(id: &[u8]) -> Option<LldpAddress> {
+    let (&family, raw) = id.split_first()?;
+    Some(address(family, raw))
+}
+
+fn chassis_id(value: &[u8]) -> Option<LldpChassisId> {
+    let (&subtype, id) = value.split_first()?;
+    let chassis_id = match subtype {
+        1 => LldpChassisId::ChassisComponent(text(id)),
+        2 => LldpChassisId::InterfaceAlias(text(id)),
+        3 => LldpChassisId::PortComponent(text(id)),
+        4 if id.len() == 6 => LldpChassisId::MacAddress(mac_address(id)),
+        5 => LldpChassisId::NetworkAddress(network_address(id)?),
+        6 => LldpChassisId::InterfaceName(text(id)),
+        7 => LldpChassisId::Local(text(id)),
+        subtype => LldpChassisId::Other {
+            subtype,
+            id: id.to_vec(),
+        },
+    };
+    Some(chassis_id)
+}
+
+fn port_id(value: &[u8]) -> Option<LldpPortId> {
+    let (&subtype, id) = value.split_first()?;
+    let port_id = match subtype {
+        1 => LldpPortId::InterfaceAlias(text(id)),
+        2 => LldpPortId::PortComponent(text(id)),
+        3 if id.len() == 6 => LldpPortId::MacAddress(mac_address(id)),
+        4 => LldpPortId::NetworkAddress(network_address(id)?),
+        5 => LldpPortId::InterfaceName(text(id)),
+        6 => LldpPortId::AgentCircuitId(id.to_vec()),
+        7 => LldpPortId::Local(text(id)),
+        subtype => LldpPortId::Other {
+            subtype,
+            id: id.to_vec(),
+        },
+    };
+    Some(port_id)
+}
+
+fn management_address(value: &[u8]) -> IResult<&[u8], LldpManagementAddress> {
+    let (rest, address_length) = number::complete::be_u8(value)?;
+    let (rest, raw) = bytes::complete::take(address_length)(rest)?;
+    let (family, raw) = match raw.split_first() {
+        Some((&family, raw)) => (family, raw),
+        None => return verify_error(value),
+    };
+    let (rest, interface_numbering) = number::complete::be_u8(rest)?;
+    let (rest, interface_number) = number::complete::be_u32(rest)?;
+    let (rest, oid_length) = number::complete::be_u8(rest)?;
+    let (rest, oid) = bytes::complete::take(oid_length)(rest)?;
+
+    Ok((
+        rest,
+        LldpManagementAddress {
+            address: address(family, raw),
+            interface_numbering,
+            interface_number,
+            oid: oid.to_vec(),
+        },
+    ))
+}
+
+fn organizationally_specific(value: &[u8]) -> IResult<&[u8], LldpTlv> {
+    let (rest, oui) = bytes::complete::take(3u8)(value)?;
+    let (information, subtype) = number::complete::be_u8(rest)?;
+    let oui = [oui[0], oui[1], oui[2]];
+
+    let tlv = match (oui, subtype) {
+        (OUI_IEEE_802_1, IEEE_802_1_PORT_VLAN_ID) => {
+            LldpTlv::PortVlanId(number::complete::be_u16(information)?.1)
+        }
+        (OUI_IEEE_802_1, IEEE_802_1_PORT_AND_PROTOCOL_VLAN_ID) => {
+            let (rest, flags) = number::complete::be_u8(information)?;
+            let (_, vlan_id) = number::complete::be_u16(rest)?;
+            LldpTlv::PortAndProtocolVlanId { flags, vlan_id }
+        }
+        (OUI_IEEE_802_1, IEEE_802_1_VLAN_NAME) => {
+            let (rest, vlan_id) = number::complete::be_u16(information)?;
+            let (rest, name_length) = number::complete::be_u8(rest)?;
+            let (_, name) = bytes::complete::take(name_length)(rest)?;
+            LldpTlv::VlanName {
+                vlan_id,
+                name: text(name),
+            }
+        }
+        (OUI_IEEE_802_3, IEEE_802_3_MAC_PHY_CONFIGURATION) => {
+            let (rest, autonegotiation) = number::complete::be_u8(information)?;
+            let (rest, advertised_capabilities) = number::complete::be_u16(rest)?;
+            let (_, operational_mau_type) = number::complete::be_u16(rest)?;
+            LldpTlv::MacPhyConfiguration {
+                autonegotiation,
+                advertised_capabilities,
+                operational_mau_type,
+            }
+        }
+        (OUI_IEEE_802_3, IEEE_802_3_LINK_AGGREGATION) => {
+            let (rest, status) = number::complete::be_u8(information)?;
+            let (_, port_id) = number::complete::be_u32(rest)?;
+            LldpTlv::LinkAggregation { status, port_id }
+        }
+        (OUI_IEEE_802_3, IEEE_802_3_MAXIMUM_FRAME_SIZE) => {
+            LldpTlv::MaximumFrameSize(number::complete::be_u16(information)?.1)
+        }
+        (oui, subtype) => LldpTlv::OrganizationallySpecific {
+            oui,
+            subtype,
+            information: information.to_vec(),
+        },
+    };
+
+    Ok((&value[value.len()..], tlv))
+}
+
+/// Reads a mandatory TLV of the expected type and decodes it with `decode`
+fn mandatory<T>(
+    input: &[u8],
+    expected: u8,
+    decode: impl Fn(&[u8]) -> Option<T>,
+) -> IResult<&[u8], T> {
+    let (rest, (tlv_type, value)) = tlv(input)?;
+    if tlv_type != expected {
+        return Err(Err::Error(Error::new(input, ErrorKind::Tag)));
+    }
+    match decode(value) {
+        Some(decoded) => Ok((rest, decoded)),
+        None => verify_error(input),
+    }
+}
+
+/// Parses an LLDPDU. The remaining input starts after the End Of LLDPDU TLV, or is empty when
+/// the frame ends without one.
+pub fn parse_lldp_pdu(input: &[u8]) -> IResult<&[u8], LldpPdu> {
+    let (input, chassis_id) = mandatory(input, TLV_CHASSIS_ID, chassis_id)?;
+    let (input, port_id) = mandatory(input, TLV_PORT_ID, port_id)?;
+    let (mut input, time_to_live) = mandatory(input, TLV_TIME_TO_LIVE, |value| {
+        Some(u16::from_be_bytes([*value.first()?, *value.get(1)?]))
+    })?;
+
+    let mut tlvs = vec![];
+    while !input.is_empty() {
+        let (rest, (tlv_type, value)) = tlv(input)?;
+        input = rest;
+        let tlv = match tlv_type {
+            TLV_END => break,
+            TLV_PORT_DESCRIPTION => LldpTlv::PortDescription(text(value)),
+            TLV_SYSTEM_NAME => LldpTlv::SystemName(text(value)),
+            TLV_SYSTEM_DESCRIPTION => LldpTlv::SystemDescription(text(value)),
+            TLV_SYSTEM_CAPABILITIES => {
+                let (rest, capabilities) = number::complete::be_u16(value)?;
+                let (_, enabled) = number::complete::be_u16(rest)?;
+                LldpTlv::SystemCapabilities {
+                    capabilities,
+                    enabled,
+                }
+            }
+            TLV_MANAGEMENT_ADDRESS => LldpTlv::ManagementAddress(management_address(value)?.1),
+            TLV_ORGANIZATIONALLY_SPECIFIC => organizationally_specific(value)?.1,
+            tlv_type => LldpTlv::Other {
+                tlv_type,
+                value: value.to_vec(),
+            },
+        };
+        tlvs.push(tlv);
+    }
+
+    Ok((
+        input,
+        LldpPdu {
+            chassis_id,
+            port_id,
+            time_to_live,
+            tlvs,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn lldp_pdu_works() {
+        let bytes = [
+            0x02, 0x07, 0x04, 0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, // chassis ID: MAC
+            0x04, 0x06, 0x05, b'e', b't', b'h', b'0', b'1', // port ID: interface name
+            0x06, 0x02, 0x00, 0x78, // TTL 120
+            0x0a, 0x04, b's', b'w', b'-', b'1', // system name
+            0x0e, 0x04, 0x00, 0x14, 0x00, 0x10, // capabilities: bridge and router, router on
+            0x10, 0x0c, // management address
+            0x05, 0x01, 0xc0, 0x00, 0x02, 0x01, // IPv4 192.0.2.1
+            0x02, 0x00, 0x00, 0x00, 0x03, // ifIndex 3
+            0x00, // no OID
+            0xfe, 0x06, 0x00, 0x80, 0xc2, 0x01, 0x00, 0x0a, // port VLAN 10
+            0xfe, 0x06, 0x00, 0x12, 0x0f, 0x04, 0x05, 0xee, // maximum frame size 1518
+            0x00, 0x00, // end
+            0x00, 0x00, // padding
+        ];
+        let expectation = LldpPdu {
+            chassis_id: LldpChassisId::MacAddress(MacAddress([0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b])),
+            port_id: LldpPortId::InterfaceName("eth01".to_string()),
+            time_to_live: 120,
+            tlvs: vec![
+                LldpTlv::SystemName("sw-1".to_string()),
+                LldpTlv::SystemCapabilities {
+                    capabilities: LLDP_CAPABILITY_BRIDGE | LLDP_CAPABILITY_ROUTER,
+                    enabled: LLDP_CAPABILITY_ROUTER,
+                },
+                LldpTlv::ManagementAddress(LldpManagementAddress {
+                    address: LldpAddress::Ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+                    interface_numbering: 2,
+                    interface_number: 3,
+                    oid: vec![],
+                }),
+                LldpTlv::PortVlanId(10),
+                LldpTlv::MaximumFrameSize(1518),
+            ],
+        };
+        assert_eq!(parse_lldp_pdu(&bytes), Ok((&[0x00, 0x00][..], expectation)));
+    }
+
+    #[test]
+    fn missing_mandatory_tlv_fails() {
+        // Port ID before chassis ID
+        let bytes = [0x04, 0x02, 0x07, b'1', 0x02, 0x02, 0x07, b'1'];
+        assert_eq!(
+            parse_lldp_pdu(&bytes),
+            Err(Err::Error(Error::new(&bytes[..], ErrorKind::Tag)))
+        );
+        assert!(parse_lldp_pdu(EMPTY_SLICE).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Handles parsing of MLDv1 and MLDv2 messages, which are carried in ICMPv6
+
+use crate::igmp::{self, GroupRecord, SourceQuery};
+use crate::ipv6;
+use nom::error::{Error, ErrorKind};
+use nom::multi::count;
+use nom::number;
+use nom::{Err, IResult};
+use std::net::Ipv6Addr;
+
+// MLDv2 Multicast Listener Query (RFC 3810); MLDv1 messages stop after the multicast address
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Type = 130   |      Code     |           Checksum            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |    Maximum Response Code      |           Reserved            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Multicast Address (16 octets)                |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | Resv  |S| QRV |     QQIC      |     Number of Sources (N)     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                    Source Addresses [1..N]                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const LISTENER_QUERY: u8 = 130;
+const V1_LISTENER_REPORT: u8 = 131;
+const LISTENER_DONE: u8 = 132;
+const V2_LISTENER_REPORT: u8 = 143;
+
+const V1_LENGTH: usize = 24;
+const V2_QUERY_MIN_LENGTH: usize = 28;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MldMessage {
+    /// The multicast address is unspecified for general queries. The maximum response delay is
+    /// in milliseconds.
+    Query {
+        max_response_delay: u32,
+        multicast_address: Ipv6Addr,
+        v2: Option<SourceQuery<Ipv6Addr>>,
+    },
+    V1Report {
+        multicast_address: Ipv6Addr,
+    },
+    Done {
+        multicast_address: Ipv6Addr,
+    },
+    V2Report(Vec<GroupRecord<Ipv6Addr>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MldPacket {
+    pub code: u8,
+    pub checksum: u16,
+    pub message: MldMessage,
+}
+
+/// Decodes the 16-bit Maximum Response Code of MLDv2, which switches to a floating point form
+/// from 32768 on
+fn decode_max_response_code(code: u16) -> u32 {
+    if code < 0x8000 {
+        return u32::from(code);
+    }
+    let exponent = (code >> 12) & 0x07;
+    let mantissa = code & 0x0fff;
+    (u32::from(mantissa) | 0x1000) << (exponent + 3)
+}
+
+/// Parses an MLD message starting at its ICMPv6 header. Other ICMPv6 types fail with
+/// `ErrorKind::Switch`. The input must end where the IPv6 payload ends, as the length of a query
+/// tells its version apart.
+pub fn parse_mld_packet(input: &[u8]) -> IResult<&[u8], MldPacket> {
+    let length = input.len();
+    let (rest, message_type) = number::streaming::be_u8(input)?;
+    let (rest, code) = number::streaming::be_u8(rest)?;
+    let (rest, checksum) = number::streaming::be_u16(rest)?;
+
+    let (rest, message) = match message_type {
+        LISTENER_QUERY | V1_LISTENER_REPORT | LISTENER_DONE => {
+            let (rest, max_response_code) = number::streaming::be_u16(rest)?;
+            let (rest, _reserved) = number::streaming::be_u16(rest)?;
+            let (rest, multicast_address) = ipv6::address(rest)?;
+            match message_type {
+                LISTENER_QUERY if length >= V2_QUERY_MIN_LENGTH => {
+                    let (rest, v2) = igmp::source_query(rest, ipv6::address)?;
+                    let message = MldMessage::Query {
+                        max_response_delay: decode_max_response_code(max_response_code),
+                        multicast_address,
+                        v2: Some(v2),
+                    };
+                    (rest, message)
+                }
+                LISTENER_QUERY if length == V1_LENGTH => {
+                    let message = MldMessage::Query {
+                        max_response_delay: u32::from(max_response_code),
+                        multicast_address,
+                        v2: None,
+                    };
+                    (rest, message)
+                }
+                LISTENER_QUERY => {
+                    return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+                }
+                V1_LISTENER_REPORT => (rest, MldMessage::V1Report { multicast_address }),
+                _ => (rest, MldMessage::Done { multicast_address }),
+            }
+        }
+        V2_LISTENER_REPORT => {
+            let (rest, _reserved) = number::streaming::be_u16(rest)?;
+            let (rest, record_count) = number::streaming::be_u16(rest)?;
+            let (rest, records) =
+                count(igmp::group_record(ipv6::address), usize::from(record_count))(rest)?;
+            (rest, MldMessage::V2Report(records))
+        }
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    };
+
+    Ok((
+        rest,
+        MldPacket {
+            code,
+            checksum,
+            message,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::igmp::GroupRecordType;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn queries_work() {
+        let v1_query = [
+            0x82, 0x00, // Multicast Listener Query, code
+            0x12, 0x34, // checksum
+            0x27, 0x10, // maximum response delay: 10000 ms
+            0x00, 0x00, // reserved
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // general query
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let expectation = MldPacket {
+            code: 0,
+            checksum: 0x1234,
+            message: MldMessage::Query {
+                max_response_delay: 10000,
+                multicast_address: Ipv6Addr::UNSPECIFIED,
+                v2: None,
+            },
+        };
+        assert_eq!(parse_mld_packet(&v1_query), Ok((EMPTY_SLICE, expectation)));
+
+        let mut v2_query = v1_query.to_vec();
+        v2_query[4..6].copy_from_slice(&[0x80, 0x01]); // floating point form
+        v2_query.extend_from_slice(&[
+            0x02, // robustness 2
+            0x7d, // QQIC: 125 s
+            0x00, 0x00, // no sources
+        ]);
+        let (remaining, packet) = parse_mld_packet(&v2_query).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(
+            packet.message,
+            MldMessage::Query {
+                max_response_delay: 0x1001 << 3,
+                multicast_address: Ipv6Addr::UNSPECIFIED,
+                v2: Some(SourceQuery {
+                    suppress_router_processing: false,
+                    robustness: 2,
+                    query_interval: 125,
+                    sources: vec![],
+                }),
+            }
+        );
+
+        // Neighbor Solicitation is not MLD
+        assert_eq!(
+            parse_mld_packet(&[0x87, 0x00, 0x00, 0x00]),
+            Err(Err::Error(Error::new(
+                &[0x87, 0x00, 0x00, 0x00][..],
+                ErrorKind::Switch
+            )))
+        );
+    }
+
+    #[test]
+    fn v2_report_works() {
+        let bytes = [
+            0x8f, 0x00, // Version 2 Multicast Listener Report
+            0x00, 0x00, // checksum
+            0x00, 0x00, 0x00, 0x01, // one record
+            0x01, 0x00, 0x00, 0x01, // MODE_IS_INCLUDE, one source
+            0xff, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // ff3e::1234
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x00, // 2001:db8::1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        let expectation = MldMessage::V2Report(vec![GroupRecord {
+            record_type: GroupRecordType::ModeIsInclude,
+            multicast_address: Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0, 0x1234),
+            sources: vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)],
+            auxiliary_data: vec![],
+        }]);
+
+        let (remaining, packet) = parse_mld_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.message, expectation);
+    }
+}
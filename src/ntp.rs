@@ -0,0 +1,609 @@
+//! Handles parsing of NTP packets, including mode 6 control and mode 7 private messages
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// NTP Packet Header (RFC 5905)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |LI | VN  |Mode |    Stratum    |     Poll      |   Precision   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                          Root Delay                           |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                        Root Dispersion                        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                          Reference ID                         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                   Reference Timestamp (64)                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Origin Timestamp (64)                     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Receive Timestamp (64)                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Transmit Timestamp (64)                   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   .                    Extension Fields (RFC 7822)                .
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Key Identifier, Digest (optional)            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// NTP Control Message, mode 6 (RFC 9327)
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |LI | VN  |Mode |R|E|M| OpCode  |        Sequence Number        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |            Status             |        Association ID         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |            Offset             |             Count             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Data (padded to 32 bits) ...                 |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// NTP Private Message, mode 7 (ntpd's ntp_request.h)
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |R|M| VN  |Mode |A|  Sequence   | Implementation| Request Code  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |  Err  |    Number of Items    |  MBZ  |       Item Size       |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Data (Items * Item Size) ...                 |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const NTP_PORT: u16 = 123;
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970)
+const UNIX_EPOCH_OFFSET: u64 = 2_208_988_800;
+
+/// MON_GETLIST and MON_GETLIST_1, the requests behind `ntpdc -c monlist`
+const REQUEST_MON_GETLIST: u8 = 20;
+const REQUEST_MON_GETLIST_1: u8 = 42;
+
+const MIN_EXTENSION_LENGTH: u16 = 16;
+/// Key identifier followed by an MD5 or SHA-1 digest
+const MAC_LENGTHS: [usize; 2] = [20, 24];
+/// A key identifier of 0 on its own (RFC 5905 section 7.4)
+const CRYPTO_NAK_LENGTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtpLeapIndicator {
+    NoWarning,
+    LastMinute61,
+    LastMinute59,
+    Unsynchronized,
+}
+
+impl From<u8> for NtpLeapIndicator {
+    fn from(raw: u8) -> Self {
+        match raw & 0b11 {
+            0 => Self::NoWarning,
+            1 => Self::LastMinute61,
+            2 => Self::LastMinute59,
+            _ => Self::Unsynchronized,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtpMode {
+    Reserved,
+    SymmetricActive,
+    SymmetricPassive,
+    Client,
+    Server,
+    Broadcast,
+    Control,
+    Private,
+}
+
+impl From<u8> for NtpMode {
+    fn from(raw: u8) -> Self {
+        match raw & 0b111 {
+            0 => Self::Reserved,
+            1 => Self::SymmetricActive,
+            2 => Self::SymmetricPassive,
+            3 => Self::Client,
+            4 => Self::Server,
+            5 => Self::Broadcast,
+            6 => Self::Control,
+            _ => Self::Private,
+        }
+    }
+}
+
+/// A 64-bit NTP timestamp: seconds since 1900 and a binary fraction of a second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpTimestamp {
+    pub seconds: u32,
+    pub fraction: u32,
+}
+
+impl NtpTimestamp {
+    /// Converts the timestamp, or returns `None` for the all-zero timestamp which stands for an
+    /// unknown time. Following RFC 4330, seconds with the top bit clear are taken to be in the
+    /// era starting in 2036.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        if self.seconds == 0 && self.fraction == 0 {
+            return None;
+        }
+        let seconds = if self.seconds & 0x8000_0000 == 0 {
+            u64::from(self.seconds) + (1 << 32)
+        } else {
+            u64::from(self.seconds)
+        };
+        let nanos = (u64::from(self.fraction) * 1_000_000_000) >> 32;
+        let since_ntp_epoch = Duration::new(seconds, nanos as u32);
+        let offset = Duration::from_secs(UNIX_EPOCH_OFFSET);
+
+        if since_ntp_epoch >= offset {
+            UNIX_EPOCH.checked_add(since_ntp_epoch - offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset - since_ntp_epoch)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtpReferenceId {
+    /// Kiss-o'-Death code sent by a server with stratum 0, such as "RATE" or "DENY"
+    KissCode(String),
+    /// Raw reference ID of a stratum 0 packet that is not from a server, such as a client request
+    Unspecified([u8; 4]),
+    /// Reference clock of a stratum 1 server, such as "GPS" or "PPS"
+    Source(String),
+    /// Upstream server of a stratum 2+ server. For IPv6 upstreams this holds the first four
+    /// octets of the MD5 hash of the address instead.
+    Address(Ipv4Addr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpExtension {
+    pub field_type: u16,
+    /// Value including any padding
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpMac {
+    pub key_id: u32,
+    /// Empty for a crypto-NAK
+    pub digest: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpMessage {
+    pub leap_indicator: NtpLeapIndicator,
+    pub version: u8,
+    pub mode: NtpMode,
+    pub stratum: u8,
+    /// Log2 of the poll interval in seconds
+    pub poll: i8,
+    /// Log2 of the clock precision in seconds
+    pub precision: i8,
+    pub root_delay: Duration,
+    pub root_dispersion: Duration,
+    pub reference_id: NtpReferenceId,
+    pub reference_timestamp: NtpTimestamp,
+    pub origin_timestamp: NtpTimestamp,
+    pub receive_timestamp: NtpTimestamp,
+    pub transmit_timestamp: NtpTimestamp,
+    pub extensions: Vec<NtpExtension>,
+    pub mac: Option<NtpMac>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpControlMessage {
+    pub leap_indicator: NtpLeapIndicator,
+    pub version: u8,
+    pub response: bool,
+    pub error: bool,
+    pub more: bool,
+    pub opcode: u8,
+    pub sequence: u16,
+    pub status: u16,
+    pub association_id: u16,
+    pub offset: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtpPrivateMessage {
+    pub response: bool,
+    pub more: bool,
+    pub version: u8,
+    pub authenticated: bool,
+    pub sequence: u8,
+    pub implementation: u8,
+    pub request_code: u8,
+    pub error: u8,
+    pub item_count: u16,
+    pub item_size: u16,
+    pub data: Vec<u8>,
+}
+
+impl NtpPrivateMessage {
+    /// Whether this is a monlist request or response, the usual amplification vector
+    pub fn is_monlist(&self) -> bool {
+        self.request_code == REQUEST_MON_GETLIST || self.request_code == REQUEST_MON_GETLIST_1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtpPacket {
+    Message(NtpMessage),
+    Control(NtpControlMessage),
+    Private(NtpPrivateMessage),
+}
+
+fn timestamp(input: &[u8]) -> IResult<&[u8], NtpTimestamp> {
+    let (input, seconds) = number::streaming::be_u32(input)?;
+    let (input, fraction) = number::streaming::be_u32(input)?;
+    Ok((input, NtpTimestamp { seconds, fraction }))
+}
+
+/// Converts a 32-bit NTP short format value, 16.16 fixed point seconds
+fn short_duration(raw: u32) -> Duration {
+    let nanos = (u64::from(raw & 0xffff) * 1_000_000_000) >> 16;
+    Duration::new(u64::from(raw >> 16), nanos as u32)
+}
+
+fn reference_id(mode: NtpMode, stratum: u8, raw: [u8; 4]) -> NtpReferenceId {
+    let ascii = || {
+        let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+        String::from_utf8_lossy(&raw[..end]).into_owned()
+    };
+    match stratum {
+        0 if matches!(mode, NtpMode::Server | NtpMode::Broadcast) => {
+            NtpReferenceId::KissCode(ascii())
+        }
+        0 => NtpReferenceId::Unspecified(raw),
+        1 => NtpReferenceId::Source(ascii()),
+        _ => NtpReferenceId::Address(Ipv4Addr::from(raw)),
+    }
+}
+
+fn extension(input: &[u8]) -> IResult<&[u8], NtpExtension> {
+    let (rest, field_type) = number::complete::be_u16(input)?;
+    let (rest, length) = number::complete::be_u16(rest)?;
+    if length < MIN_EXTENSION_LENGTH || length % 4 != 0 {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (rest, value) = bytes::complete::take(length - 4)(rest)?;
+
+    Ok((
+        rest,
+        NtpExtension {
+            field_type,
+            value: value.to_vec(),
+        },
+    ))
+}
+
+/// Parses the extension fields and MAC after the header, which together fill the rest of the
+/// datagram. A trailer of MAC length is always taken to be a MAC, as RFC 7822 requires.
+fn trailer(mut input: &[u8]) -> IResult<&[u8], (Vec<NtpExtension>, Option<NtpMac>)> {
+    let mut extensions = vec![];
+    while input.len() > CRYPTO_NAK_LENGTH && !MAC_LENGTHS.contains(&input.len()) {
+        let (rest, extension) = extension(input)?;
+        extensions.push(extension);
+        input = rest;
+    }
+
+    if input.is_empty() {
+        return Ok((input, (extensions, None)));
+    }
+    let (rest, key_id) = number::complete::be_u32(input)?;
+    if input.len() == CRYPTO_NAK_LENGTH && key_id != 0 {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let mac = NtpMac {
+        key_id,
+        digest: rest.to_vec(),
+    };
+
+    Ok((&rest[rest.len()..], (extensions, Some(mac))))
+}
+
+fn control_message(input: &[u8], first: u8) -> IResult<&[u8], NtpControlMessage> {
+    let (input, flags) = number::complete::be_u8(input)?;
+    let (input, sequence) = number::complete::be_u16(input)?;
+    let (input, status) = number::complete::be_u16(input)?;
+    let (input, association_id) = number::complete::be_u16(input)?;
+    let (input, offset) = number::complete::be_u16(input)?;
+    let (input, count) = number::complete::be_u16(input)?;
+    let (input, data) = bytes::complete::take(count)(input)?;
+    // Padding may be left out of the last datagram, so only skip what is there
+    let padding = (4 - data.len() % 4) % 4;
+    let input = &input[padding.min(input.len())..];
+
+    Ok((
+        input,
+        NtpControlMessage {
+            leap_indicator: NtpLeapIndicator::from(first >> 6),
+            version: (first >> 3) & 0b111,
+            response: flags & 0x80 != 0,
+            error: flags & 0x40 != 0,
+            more: flags & 0x20 != 0,
+            opcode: flags & 0x1f,
+            sequence,
+            status,
+            association_id,
+            offset,
+            data: data.to_vec(),
+        },
+    ))
+}
+
+fn private_message(input: &[u8], first: u8) -> IResult<&[u8], NtpPrivateMessage> {
+    let (input, auth_sequence) = number::complete::be_u8(input)?;
+    let (input, implementation) = number::complete::be_u8(input)?;
+    let (input, request_code) = number::complete::be_u8(input)?;
+    let (input, error_items) = number::complete::be_u16(input)?;
+    let (input, item_size) = number::complete::be_u16(input)?;
+    let item_count = error_items & 0x0fff;
+    let item_size = item_size & 0x0fff;
+    let (input, data) =
+        bytes::complete::take(usize::from(item_count) * usize::from(item_size))(input)?;
+
+    Ok((
+        input,
+        NtpPrivateMessage {
+            response: first & 0x80 != 0,
+            more: first & 0x40 != 0,
+            version: (first >> 3) & 0b111,
+            authenticated: auth_sequence & 0x80 != 0,
+            sequence: auth_sequence & 0x7f,
+            implementation,
+            request_code,
+            error: (error_items >> 12) as u8,
+            item_count,
+            item_size,
+            data: data.to_vec(),
+        },
+    ))
+}
+
+/// Parses an NTP packet from a whole UDP payload
+pub fn parse_ntp_packet(input: &[u8]) -> IResult<&[u8], NtpPacket> {
+    let (rest, first) = number::streaming::be_u8(input)?;
+    let version = (first >> 3) & 0b111;
+    if version == 0 || version > 4 {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+
+    let mode = NtpMode::from(first);
+    match mode {
+        NtpMode::Control => {
+            let (rest, message) = control_message(rest, first)?;
+            return Ok((rest, NtpPacket::Control(message)));
+        }
+        NtpMode::Private => {
+            let (rest, message) = private_message(rest, first)?;
+            return Ok((rest, NtpPacket::Private(message)));
+        }
+        _ => (),
+    }
+
+    let (rest, stratum) = number::streaming::be_u8(rest)?;
+    let (rest, poll) = number::streaming::be_i8(rest)?;
+    let (rest, precision) = number::streaming::be_i8(rest)?;
+    let (rest, root_delay) = number::streaming::be_u32(rest)?;
+    let (rest, root_dispersion) = number::streaming::be_u32(rest)?;
+    let (rest, reference) = bytes::streaming::take(4u8)(rest)?;
+    let (rest, reference_timestamp) = timestamp(rest)?;
+    let (rest, origin_timestamp) = timestamp(rest)?;
+    let (rest, receive_timestamp) = timestamp(rest)?;
+    let (rest, transmit_timestamp) = timestamp(rest)?;
+    let (rest, (extensions, mac)) = trailer(rest)?;
+
+    let mut reference_raw = [0; 4];
+    reference_raw.copy_from_slice(reference);
+
+    Ok((
+        rest,
+        NtpPacket::Message(NtpMessage {
+            leap_indicator: NtpLeapIndicator::from(first >> 6),
+            version,
+            mode,
+            stratum,
+            poll,
+            precision,
+            root_delay: short_duration(root_delay),
+            root_dispersion: short_duration(root_dispersion),
+            reference_id: reference_id(mode, stratum, reference_raw),
+            reference_timestamp,
+            origin_timestamp,
+            receive_timestamp,
+            transmit_timestamp,
+            extensions,
+            mac,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn server_message_works() {
+        let bytes = [
+            0x24, // LI 0, version 4, mode server
+            0x02, // stratum
+            0x03, // poll
+            0xe9, // precision
+            0x00, 0x00, 0x08, 0x00, // root delay
+            0x00, 0x01, 0x80, 0x00, // root dispersion
+            0xc0, 0x00, 0x02, 0x01, // reference id
+            0xe9, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, // reference timestamp
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // origin timestamp
+            0xe9, 0x66, 0x3e, 0x80, 0x80, 0x00, 0x00, 0x00, // receive timestamp
+            0xe9, 0x66, 0x3e, 0x80, 0x80, 0x00, 0x00, 0x00, // transmit timestamp
+        ];
+
+        let (remaining, packet) = parse_ntp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        let message = match packet {
+            NtpPacket::Message(message) => message,
+            other => panic!("unexpected packet {:?}", other),
+        };
+        assert_eq!(message.leap_indicator, NtpLeapIndicator::NoWarning);
+        assert_eq!(message.version, 4);
+        assert_eq!(message.mode, NtpMode::Server);
+        assert_eq!(message.precision, -23);
+        assert_eq!(
+            message.root_delay,
+            Duration::from_millis(31) + Duration::from_micros(250)
+        );
+        assert_eq!(message.root_dispersion, Duration::from_millis(1500));
+        assert_eq!(
+            message.reference_id,
+            NtpReferenceId::Address(Ipv4Addr::new(192, 0, 2, 1))
+        );
+        assert_eq!(message.origin_timestamp.to_system_time(), None);
+        assert_eq!(
+            message.transmit_timestamp.to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1_706_803_200_500))
+        );
+        assert_eq!(message.extensions, vec![]);
+        assert_eq!(message.mac, None);
+    }
+
+    #[test]
+    fn extensions_and_mac_work() {
+        let mut bytes = vec![
+            0x23, 0x00, 0x06, 0x20, // LI 0, version 4, mode client
+        ];
+        bytes.extend_from_slice(&[0; 44]);
+        bytes.extend_from_slice(&[
+            0x01, 0x04, 0x00, 0x10, // unique identifier extension, length 16
+            0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x00,
+            0x00, 0x07, // key id
+        ]);
+        bytes.extend_from_slice(&[0xee; 16]); // MD5 digest
+
+        let (remaining, packet) = parse_ntp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        let message = match packet {
+            NtpPacket::Message(message) => message,
+            other => panic!("unexpected packet {:?}", other),
+        };
+        assert_eq!(message.mode, NtpMode::Client);
+        assert_eq!(message.reference_id, NtpReferenceId::Unspecified([0; 4]));
+        assert_eq!(message.extensions.len(), 1);
+        assert_eq!(message.extensions[0].field_type, 0x0104);
+        assert_eq!(message.extensions[0].value.len(), 12);
+        assert_eq!(
+            message.mac,
+            Some(NtpMac {
+                key_id: 7,
+                digest: vec![0xee; 16],
+            })
+        );
+    }
+
+    #[test]
+    fn control_and_private_messages_work() {
+        let monlist = [
+            0x17, // version 2, mode private
+            0x00, // sequence
+            0x03, // implementation XNTPD
+            0x2a, // MON_GETLIST_1
+            0x00, 0x00, 0x00, 0x00, // no items
+        ];
+        let (_, packet) = parse_ntp_packet(&monlist).unwrap();
+        match packet {
+            NtpPacket::Private(message) => {
+                assert!(!message.response);
+                assert_eq!(message.version, 2);
+                assert_eq!(message.implementation, 3);
+                assert!(message.is_monlist());
+            }
+            other => panic!("unexpected packet {:?}", other),
+        }
+
+        let readvar = [
+            0x16, // version 2, mode control
+            0x02, // read variables
+            0x00, 0x01, // sequence
+            0x00, 0x00, // status
+            0x00, 0x00, // association id
+            0x00, 0x00, // offset
+            0x00, 0x00, // count
+        ];
+        let expectation = NtpControlMessage {
+            leap_indicator: NtpLeapIndicator::NoWarning,
+            version: 2,
+            response: false,
+            error: false,
+            more: false,
+            opcode: 2,
+            sequence: 1,
+            status: 0,
+            association_id: 0,
+            offset: 0,
+            data: vec![],
+        };
+        assert_eq!(
+            parse_ntp_packet(&readvar),
+            Ok((EMPTY_SLICE, NtpPacket::Control(expectation)))
+        );
+    }
+
+    #[test]
+    fn truncated_trailers_and_messages_fail() {
+        let mut header = vec![
+            0x24, 0x00, 0x06, 0xe9, // LI 0, version 4, mode server, stratum 0
+        ];
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(b"RATE");
+        header.extend_from_slice(&[0; 32]);
+        match parse_ntp_packet(&header) {
+            Ok((_, NtpPacket::Message(message))) => assert_eq!(
+                message.reference_id,
+                NtpReferenceId::KissCode("RATE".to_string())
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let stray = [&header[..], &[0x00, 0x00]].concat();
+        let overrun = [&header[..], &[0x01, 0x04, 0x00, 0x40], &[0; 28]].concat();
+        let control = [
+            0x16, 0x82, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, // read variables response
+            0x00, 0x08, // count
+            0x61, 0x3d, 0x31, // data cut short
+        ];
+        let private = [
+            0x97, 0x00, 0x03, 0x2a, // MON_GETLIST_1 response
+            0x00, 0x01, 0x00, 0x48, // one item of 72 octets
+            0x00, 0x00, 0x00, 0x00, // item cut short
+        ];
+        for bytes in &[&stray[..], &overrun[..], &control[..], &private[..]] {
+            assert!(matches!(
+                parse_ntp_packet(bytes),
+                Err(Err::Error(Error {
+                    code: ErrorKind::Eof,
+                    ..
+                }))
+            ));
+        }
+    }
+}
@@ -0,0 +1,1069 @@
+//! Handles parsing of OSPFv2 and OSPFv3 packets and the LSAs they carry
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::multi::count;
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// OSPFv2 Packet Header (RFC 2328)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |   Version #   |     Type      |         Packet length         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                          Router ID                            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                           Area ID                             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |           Checksum            |             AuType            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                       Authentication                          |
+//   |                                                               |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// OSPFv3 (RFC 5340) replaces AuType and Authentication with an Instance ID octet and a reserved
+// octet.
+
+// LSA Header (RFC 2328); OSPFv3 merges Options and LS type into a 16-bit LS type
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |            LS age             |    Options    |    LS type    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                        Link State ID                          |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Advertising Router                        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     LS sequence number                        |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |         LS checksum           |             length            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const OSPF_V2: u8 = 2;
+const OSPF_V3: u8 = 3;
+
+const V2_HEADER_LENGTH: u16 = 24;
+const V3_HEADER_LENGTH: u16 = 16;
+const LSA_HEADER_LENGTH: u16 = 20;
+
+const AUTH_NULL: u16 = 0;
+const AUTH_SIMPLE_PASSWORD: u16 = 1;
+const AUTH_CRYPTOGRAPHIC: u16 = 2;
+
+const DD_FLAG_INIT: u8 = 0x04;
+const DD_FLAG_MORE: u8 = 0x02;
+const DD_FLAG_MASTER: u8 = 0x01;
+
+/// The E bit of OSPFv2 AS-external LSAs
+const V2_EXTERNAL_TYPE_2: u8 = 0x80;
+/// The E, F and T bits of OSPFv3 AS-external LSAs
+const V3_EXTERNAL_TYPE_2: u8 = 0x04;
+const V3_EXTERNAL_FORWARDING: u8 = 0x02;
+const V3_EXTERNAL_TAG: u8 = 0x01;
+
+/// OSPFv3 LS types carry the U bit and the flooding scope above the function code
+const V3_FUNCTION_CODE_MASK: u16 = 0x1fff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfPacketType {
+    Hello,
+    DatabaseDescription,
+    LinkStateRequest,
+    LinkStateUpdate,
+    LinkStateAcknowledgment,
+    Other(u8),
+}
+
+impl From<u8> for OspfPacketType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::Hello,
+            2 => Self::DatabaseDescription,
+            3 => Self::LinkStateRequest,
+            4 => Self::LinkStateUpdate,
+            5 => Self::LinkStateAcknowledgment,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfAuthentication {
+    None,
+    SimplePassword([u8; 8]),
+    /// The message digest follows the packet, outside of its length
+    Cryptographic {
+        key_id: u8,
+        data_length: u8,
+        sequence_number: u32,
+    },
+    Other {
+        auth_type: u16,
+        data: [u8; 8],
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfHeader {
+    pub version: u8,
+    pub packet_type: OspfPacketType,
+    pub length: u16,
+    pub router_id: Ipv4Addr,
+    pub area_id: Ipv4Addr,
+    pub checksum: u16,
+    /// Only present in OSPFv2
+    pub authentication: Option<OspfAuthentication>,
+    /// Only present in OSPFv3
+    pub instance_id: Option<u8>,
+}
+
+/// LS types of both versions. The OSPFv3 inter-area-prefix and inter-area-router LSAs take the
+/// place of the OSPFv2 summary LSAs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfLsaType {
+    Router,
+    Network,
+    Summary,
+    AsbrSummary,
+    AsExternal,
+    Nssa,
+    Link,
+    IntraAreaPrefix,
+    Other(u16),
+}
+
+impl OspfLsaType {
+    pub fn from_v2(raw: u8) -> Self {
+        match raw {
+            1 => Self::Router,
+            2 => Self::Network,
+            3 => Self::Summary,
+            4 => Self::AsbrSummary,
+            5 => Self::AsExternal,
+            7 => Self::Nssa,
+            other => Self::Other(u16::from(other)),
+        }
+    }
+
+    pub fn from_v3(raw: u16) -> Self {
+        match raw & V3_FUNCTION_CODE_MASK {
+            1 => Self::Router,
+            2 => Self::Network,
+            3 => Self::Summary,
+            4 => Self::AsbrSummary,
+            5 => Self::AsExternal,
+            7 => Self::Nssa,
+            8 => Self::Link,
+            9 => Self::IntraAreaPrefix,
+            _ => Self::Other(raw),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfLsaHeader {
+    pub age: u16,
+    /// Only present in OSPFv2, where the options are part of each LSA header
+    pub options: Option<u8>,
+    pub ls_type: OspfLsaType,
+    pub link_state_id: Ipv4Addr,
+    pub advertising_router: Ipv4Addr,
+    /// Signed, so that newer instances compare greater from 0x80000001 on
+    pub sequence_number: i32,
+    pub checksum: u16,
+    pub length: u16,
+}
+
+/// Identifies an LSA in a Link State Request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfLsaKey {
+    pub ls_type: OspfLsaType,
+    pub link_state_id: Ipv4Addr,
+    pub advertising_router: Ipv4Addr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfRouterLinkType {
+    PointToPoint,
+    TransitNetwork,
+    StubNetwork,
+    VirtualLink,
+    Other(u8),
+}
+
+impl From<u8> for OspfRouterLinkType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            1 => Self::PointToPoint,
+            2 => Self::TransitNetwork,
+            3 => Self::StubNetwork,
+            4 => Self::VirtualLink,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfRouterLink {
+    /// The meaning of Link ID and Link Data depends on the link type. TOS metrics are skipped.
+    V2 {
+        link_type: OspfRouterLinkType,
+        link_id: Ipv4Addr,
+        link_data: Ipv4Addr,
+        metric: u16,
+    },
+    V3 {
+        link_type: OspfRouterLinkType,
+        metric: u16,
+        interface_id: u32,
+        neighbor_interface_id: u32,
+        neighbor_router_id: Ipv4Addr,
+    },
+}
+
+/// An OSPFv3 address prefix
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfPrefix {
+    pub length: u8,
+    pub options: u8,
+    /// Only carried by intra-area-prefix LSAs, zero elsewhere
+    pub metric: u16,
+    pub address: Ipv6Addr,
+}
+
+/// An AS-external or NSSA LSA of either version
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfExternalLsa {
+    /// Type 2 metrics are considered larger than any path cost inside the AS
+    pub type_2_metric: bool,
+    pub metric: u32,
+    /// OSPFv2 only; the destination is the link state ID under this mask
+    pub network_mask: Option<Ipv4Addr>,
+    /// OSPFv3 only
+    pub prefix: Option<OspfPrefix>,
+    pub forwarding_address: Option<IpAddr>,
+    pub route_tag: Option<u32>,
+    pub referenced_ls_type: Option<OspfLsaType>,
+    pub referenced_link_state_id: Option<Ipv4Addr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfLsaBody {
+    /// The flags hold the V, E and B bits, and in OSPFv3 the Nt bit
+    Router {
+        flags: u8,
+        /// Only present in OSPFv3
+        options: Option<u32>,
+        links: Vec<OspfRouterLink>,
+    },
+    Network {
+        /// Only present in OSPFv2
+        network_mask: Option<Ipv4Addr>,
+        /// Only present in OSPFv3
+        options: Option<u32>,
+        attached_routers: Vec<Ipv4Addr>,
+    },
+    /// OSPFv2 summary LSA of either type. TOS metrics are skipped.
+    Summary {
+        network_mask: Ipv4Addr,
+        metric: u32,
+    },
+    InterAreaPrefix {
+        metric: u32,
+        prefix: OspfPrefix,
+    },
+    InterAreaRouter {
+        options: u32,
+        metric: u32,
+        destination_router_id: Ipv4Addr,
+    },
+    External(OspfExternalLsa),
+    Link {
+        router_priority: u8,
+        options: u32,
+        link_local_address: Ipv6Addr,
+        prefixes: Vec<OspfPrefix>,
+    },
+    IntraAreaPrefix {
+        referenced_ls_type: OspfLsaType,
+        referenced_link_state_id: Ipv4Addr,
+        referenced_advertising_router: Ipv4Addr,
+        prefixes: Vec<OspfPrefix>,
+    },
+    Unknown(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfLsa {
+    pub header: OspfLsaHeader,
+    pub body: OspfLsaBody,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfHello {
+    /// Only present in OSPFv2
+    pub network_mask: Option<Ipv4Addr>,
+    /// Only present in OSPFv3
+    pub interface_id: Option<u32>,
+    pub hello_interval: u16,
+    pub options: u32,
+    pub router_priority: u8,
+    pub router_dead_interval: u32,
+    pub designated_router: Ipv4Addr,
+    pub backup_designated_router: Ipv4Addr,
+    pub neighbors: Vec<Ipv4Addr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfDatabaseDescription {
+    pub interface_mtu: u16,
+    pub options: u32,
+    pub init: bool,
+    pub more: bool,
+    pub master: bool,
+    pub sequence_number: u32,
+    pub lsa_headers: Vec<OspfLsaHeader>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OspfBody {
+    Hello(OspfHello),
+    DatabaseDescription(OspfDatabaseDescription),
+    LinkStateRequest(Vec<OspfLsaKey>),
+    LinkStateUpdate(Vec<OspfLsa>),
+    LinkStateAcknowledgment(Vec<OspfLsaHeader>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OspfPacket {
+    pub header: OspfHeader,
+    pub body: OspfBody,
+}
+
+fn length_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::LengthValue)))
+}
+
+fn address(input: &[u8]) -> IResult<&[u8], Ipv4Addr> {
+    let (input, raw) = number::complete::be_u32(input)?;
+
+    Ok((input, Ipv4Addr::from(raw)))
+}
+
+fn ipv6_address(input: &[u8]) -> IResult<&[u8], Ipv6Addr> {
+    let (input, raw) = number::complete::be_u128(input)?;
+
+    Ok((input, Ipv6Addr::from(raw)))
+}
+
+/// Applies `parser` until `input`, which must hold nothing else, is used up
+fn list<'a, T>(
+    mut input: &'a [u8],
+    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+) -> IResult<&'a [u8], Vec<T>> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        let (rest, item) = parser(input)?;
+        items.push(item);
+        input = rest;
+    }
+    Ok((input, items))
+}
+
+/// Parses an OSPFv3 prefix, whose address is padded to a multiple of 32 bits
+fn prefix(input: &[u8]) -> IResult<&[u8], OspfPrefix> {
+    let (rest, length) = number::complete::be_u8(input)?;
+    let (rest, options) = number::complete::be_u8(rest)?;
+    let (rest, metric) = number::complete::be_u16(rest)?;
+    if length > 128 {
+        return length_error(input);
+    }
+    let (rest, raw) = bytes::complete::take(usize::from(length).div_ceil(32) * 4)(rest)?;
+    let mut octets = [0; 16];
+    octets[..raw.len()].copy_from_slice(raw);
+
+    Ok((
+        rest,
+        OspfPrefix {
+            length,
+            options,
+            metric,
+            address: Ipv6Addr::from(octets),
+        },
+    ))
+}
+
+fn authentication(input: &[u8]) -> IResult<&[u8], OspfAuthentication> {
+    let (rest, auth_type) = number::streaming::be_u16(input)?;
+    let (rest, raw) = bytes::streaming::take(8u8)(rest)?;
+    let mut data = [0; 8];
+    data.copy_from_slice(raw);
+
+    let authentication = match auth_type {
+        AUTH_NULL => OspfAuthentication::None,
+        AUTH_SIMPLE_PASSWORD => OspfAuthentication::SimplePassword(data),
+        AUTH_CRYPTOGRAPHIC => OspfAuthentication::Cryptographic {
+            key_id: data[2],
+            data_length: data[3],
+            sequence_number: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        },
+        auth_type => OspfAuthentication::Other { auth_type, data },
+    };
+
+    Ok((rest, authentication))
+}
+
+pub fn parse_ospf_header(input: &[u8]) -> IResult<&[u8], OspfHeader> {
+    let (rest, version) = number::streaming::be_u8(input)?;
+    if version != OSPF_V2 && version != OSPF_V3 {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (rest, packet_type) = number::streaming::be_u8(rest)?;
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    let (rest, router_id) = crate::ipv4::address(rest)?;
+    let (rest, area_id) = crate::ipv4::address(rest)?;
+    let (rest, checksum) = number::streaming::be_u16(rest)?;
+    let (rest, authentication, instance_id) = if version == OSPF_V2 {
+        let (rest, authentication) = authentication(rest)?;
+        (rest, Some(authentication), None)
+    } else {
+        let (rest, instance_id) = number::streaming::be_u8(rest)?;
+        let (rest, _reserved) = number::streaming::be_u8(rest)?;
+        (rest, None, Some(instance_id))
+    };
+
+    Ok((
+        rest,
+        OspfHeader {
+            version,
+            packet_type: packet_type.into(),
+            length,
+            router_id,
+            area_id,
+            checksum,
+            authentication,
+            instance_id,
+        },
+    ))
+}
+
+fn lsa_header(version: u8) -> impl Fn(&[u8]) -> IResult<&[u8], OspfLsaHeader> {
+    move |input| {
+        let (rest, age) = number::complete::be_u16(input)?;
+        let (rest, options, ls_type) = if version == OSPF_V2 {
+            let (rest, options) = number::complete::be_u8(rest)?;
+            let (rest, ls_type) = number::complete::be_u8(rest)?;
+            (rest, Some(options), OspfLsaType::from_v2(ls_type))
+        } else {
+            let (rest, ls_type) = number::complete::be_u16(rest)?;
+            (rest, None, OspfLsaType::from_v3(ls_type))
+        };
+        let (rest, link_state_id) = address(rest)?;
+        let (rest, advertising_router) = address(rest)?;
+        let (rest, sequence_number) = number::complete::be_i32(rest)?;
+        let (rest, checksum) = number::complete::be_u16(rest)?;
+        let (rest, length) = number::complete::be_u16(rest)?;
+
+        Ok((
+            rest,
+            OspfLsaHeader {
+                age,
+                options,
+                ls_type,
+                link_state_id,
+                advertising_router,
+                sequence_number,
+                checksum,
+                length,
+            },
+        ))
+    }
+}
+
+fn router_link_v2(input: &[u8]) -> IResult<&[u8], OspfRouterLink> {
+    let (rest, link_id) = address(input)?;
+    let (rest, link_data) = address(rest)?;
+    let (rest, link_type) = number::complete::be_u8(rest)?;
+    let (rest, tos_count) = number::complete::be_u8(rest)?;
+    let (rest, metric) = number::complete::be_u16(rest)?;
+    let (rest, _tos_metrics) = bytes::complete::take(usize::from(tos_count) * 4)(rest)?;
+
+    Ok((
+        rest,
+        OspfRouterLink::V2 {
+            link_type: link_type.into(),
+            link_id,
+            link_data,
+            metric,
+        },
+    ))
+}
+
+fn router_link_v3(input: &[u8]) -> IResult<&[u8], OspfRouterLink> {
+    let (rest, link_type) = number::complete::be_u8(input)?;
+    let (rest, _reserved) = number::complete::be_u8(rest)?;
+    let (rest, metric) = number::complete::be_u16(rest)?;
+    let (rest, interface_id) = number::complete::be_u32(rest)?;
+    let (rest, neighbor_interface_id) = number::complete::be_u32(rest)?;
+    let (rest, neighbor_router_id) = address(rest)?;
+
+    Ok((
+        rest,
+        OspfRouterLink::V3 {
+            link_type: link_type.into(),
+            metric,
+            interface_id,
+            neighbor_interface_id,
+            neighbor_router_id,
+        },
+    ))
+}
+
+fn lsa_body_v2(ls_type: OspfLsaType, body: &[u8]) -> IResult<&[u8], OspfLsaBody> {
+    match ls_type {
+        OspfLsaType::Router => {
+            let (rest, flags) = number::complete::be_u8(body)?;
+            let (rest, _reserved) = number::complete::be_u8(rest)?;
+            let (rest, link_count) = number::complete::be_u16(rest)?;
+            let (rest, links) = count(router_link_v2, usize::from(link_count))(rest)?;
+            let lsa = OspfLsaBody::Router {
+                flags,
+                options: None,
+                links,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::Network => {
+            let (rest, network_mask) = address(body)?;
+            let (rest, attached_routers) = list(rest, address)?;
+            let lsa = OspfLsaBody::Network {
+                network_mask: Some(network_mask),
+                options: None,
+                attached_routers,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::Summary | OspfLsaType::AsbrSummary => {
+            let (rest, network_mask) = address(body)?;
+            let (rest, metric) = number::complete::be_u32(rest)?;
+            let lsa = OspfLsaBody::Summary {
+                network_mask,
+                metric: metric & 0x00ff_ffff,
+            };
+            Ok((&rest[rest.len()..], lsa))
+        }
+        OspfLsaType::AsExternal | OspfLsaType::Nssa => {
+            let (rest, network_mask) = address(body)?;
+            let (rest, flags) = number::complete::be_u8(rest)?;
+            let (rest, metric) = number::complete::be_u24(rest)?;
+            let (rest, forwarding_address) = address(rest)?;
+            let (rest, route_tag) = number::complete::be_u32(rest)?;
+            let lsa = OspfLsaBody::External(OspfExternalLsa {
+                type_2_metric: flags & V2_EXTERNAL_TYPE_2 != 0,
+                metric,
+                network_mask: Some(network_mask),
+                prefix: None,
+                forwarding_address: Some(IpAddr::V4(forwarding_address)),
+                route_tag: Some(route_tag),
+                referenced_ls_type: None,
+                referenced_link_state_id: None,
+            });
+            // TOS metrics may follow
+            Ok((&rest[rest.len()..], lsa))
+        }
+        _ => Ok((&body[body.len()..], OspfLsaBody::Unknown(body.to_vec()))),
+    }
+}
+
+fn external_lsa_v3(body: &[u8]) -> IResult<&[u8], OspfExternalLsa> {
+    let (rest, flags) = number::complete::be_u8(body)?;
+    let (rest, metric) = number::complete::be_u24(rest)?;
+    let (rest, mut prefix) = prefix(rest)?;
+    // The field holding the metric in other prefixes holds the referenced LS type here
+    let referenced_ls_type = prefix.metric;
+    prefix.metric = 0;
+    let (rest, forwarding_address) = if flags & V3_EXTERNAL_FORWARDING != 0 {
+        let (rest, address) = ipv6_address(rest)?;
+        (rest, Some(IpAddr::V6(address)))
+    } else {
+        (rest, None)
+    };
+    let (rest, route_tag) = if flags & V3_EXTERNAL_TAG != 0 {
+        let (rest, tag) = number::complete::be_u32(rest)?;
+        (rest, Some(tag))
+    } else {
+        (rest, None)
+    };
+    let (rest, referenced_ls_type, referenced_link_state_id) = if referenced_ls_type != 0 {
+        let (rest, id) = address(rest)?;
+        (
+            rest,
+            Some(OspfLsaType::from_v3(referenced_ls_type)),
+            Some(id),
+        )
+    } else {
+        (rest, None, None)
+    };
+
+    Ok((
+        rest,
+        OspfExternalLsa {
+            type_2_metric: flags & V3_EXTERNAL_TYPE_2 != 0,
+            metric,
+            network_mask: None,
+            prefix: Some(prefix),
+            forwarding_address,
+            route_tag,
+            referenced_ls_type,
+            referenced_link_state_id,
+        },
+    ))
+}
+
+fn lsa_body_v3(ls_type: OspfLsaType, body: &[u8]) -> IResult<&[u8], OspfLsaBody> {
+    match ls_type {
+        OspfLsaType::Router => {
+            let (rest, flags) = number::complete::be_u8(body)?;
+            let (rest, options) = number::complete::be_u24(rest)?;
+            let (rest, links) = list(rest, router_link_v3)?;
+            let lsa = OspfLsaBody::Router {
+                flags,
+                options: Some(options),
+                links,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::Network => {
+            let (rest, options) = number::complete::be_u32(body)?;
+            let (rest, attached_routers) = list(rest, address)?;
+            let lsa = OspfLsaBody::Network {
+                network_mask: None,
+                options: Some(options & 0x00ff_ffff),
+                attached_routers,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::Summary => {
+            let (rest, metric) = number::complete::be_u32(body)?;
+            let (rest, prefix) = prefix(rest)?;
+            let lsa = OspfLsaBody::InterAreaPrefix {
+                metric: metric & 0x00ff_ffff,
+                prefix: OspfPrefix {
+                    metric: 0,
+                    ..prefix
+                },
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::AsbrSummary => {
+            let (rest, options) = number::complete::be_u32(body)?;
+            let (rest, metric) = number::complete::be_u32(rest)?;
+            let (rest, destination_router_id) = address(rest)?;
+            let lsa = OspfLsaBody::InterAreaRouter {
+                options: options & 0x00ff_ffff,
+                metric: metric & 0x00ff_ffff,
+                destination_router_id,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::AsExternal | OspfLsaType::Nssa => {
+            let (rest, lsa) = external_lsa_v3(body)?;
+            Ok((rest, OspfLsaBody::External(lsa)))
+        }
+        OspfLsaType::Link => {
+            let (rest, router_priority) = number::complete::be_u8(body)?;
+            let (rest, options) = number::complete::be_u24(rest)?;
+            let (rest, link_local_address) = ipv6_address(rest)?;
+            let (rest, prefix_count) = number::complete::be_u32(rest)?;
+            if prefix_count as usize > rest.len() / 4 {
+                return length_error(body);
+            }
+            let (rest, prefixes) = count(prefix, prefix_count as usize)(rest)?;
+            let lsa = OspfLsaBody::Link {
+                router_priority,
+                options,
+                link_local_address,
+                prefixes,
+            };
+            Ok((rest, lsa))
+        }
+        OspfLsaType::IntraAreaPrefix => {
+            let (rest, prefix_count) = number::complete::be_u16(body)?;
+            let (rest, referenced_ls_type) = number::complete::be_u16(rest)?;
+            let (rest, referenced_link_state_id) = address(rest)?;
+            let (rest, referenced_advertising_router) = address(rest)?;
+            let (rest, prefixes) = count(prefix, usize::from(prefix_count))(rest)?;
+            let lsa = OspfLsaBody::IntraAreaPrefix {
+                referenced_ls_type: OspfLsaType::from_v3(referenced_ls_type),
+                referenced_link_state_id,
+                referenced_advertising_router,
+                prefixes,
+            };
+            Ok((rest, lsa))
+        }
+        _ => Ok((&body[body.len()..], OspfLsaBody::Unknown(body.to_vec()))),
+    }
+}
+
+fn lsa(version: u8) -> impl Fn(&[u8]) -> IResult<&[u8], OspfLsa> {
+    move |input| {
+        let (rest, header) = lsa_header(version)(input)?;
+        if header.length < LSA_HEADER_LENGTH {
+            return length_error(input);
+        }
+        let (rest, body) = bytes::complete::take(header.length - LSA_HEADER_LENGTH)(rest)?;
+        let (_, body) = if version == OSPF_V2 {
+            lsa_body_v2(header.ls_type, body)?
+        } else {
+            lsa_body_v3(header.ls_type, body)?
+        };
+
+        Ok((rest, OspfLsa { header, body }))
+    }
+}
+
+fn hello(version: u8, input: &[u8]) -> IResult<&[u8], OspfHello> {
+    let (rest, network_mask, interface_id) = if version == OSPF_V2 {
+        let (rest, network_mask) = address(input)?;
+        (rest, Some(network_mask), None)
+    } else {
+        let (rest, interface_id) = number::complete::be_u32(input)?;
+        (rest, None, Some(interface_id))
+    };
+    let (rest, hello_interval, options, router_priority, router_dead_interval) =
+        if version == OSPF_V2 {
+            let (rest, hello_interval) = number::complete::be_u16(rest)?;
+            let (rest, options) = number::complete::be_u8(rest)?;
+            let (rest, router_priority) = number::complete::be_u8(rest)?;
+            let (rest, router_dead_interval) = number::complete::be_u32(rest)?;
+            let options = u32::from(options);
+            (
+                rest,
+                hello_interval,
+                options,
+                router_priority,
+                router_dead_interval,
+            )
+        } else {
+            let (rest, router_priority) = number::complete::be_u8(rest)?;
+            let (rest, options) = number::complete::be_u24(rest)?;
+            let (rest, hello_interval) = number::complete::be_u16(rest)?;
+            let (rest, router_dead_interval) = number::complete::be_u16(rest)?;
+            let router_dead_interval = u32::from(router_dead_interval);
+            (
+                rest,
+                hello_interval,
+                options,
+                router_priority,
+                router_dead_interval,
+            )
+        };
+    let (rest, designated_router) = address(rest)?;
+    let (rest, backup_designated_router) = address(rest)?;
+    let (rest, neighbors) = list(rest, address)?;
+
+    Ok((
+        rest,
+        OspfHello {
+            network_mask,
+            interface_id,
+            hello_interval,
+            options,
+            router_priority,
+            router_dead_interval,
+            designated_router,
+            backup_designated_router,
+            neighbors,
+        },
+    ))
+}
+
+fn database_description(version: u8, input: &[u8]) -> IResult<&[u8], OspfDatabaseDescription> {
+    let (rest, interface_mtu, options) = if version == OSPF_V2 {
+        let (rest, interface_mtu) = number::complete::be_u16(input)?;
+        let (rest, options) = number::complete::be_u8(rest)?;
+        (rest, interface_mtu, u32::from(options))
+    } else {
+        let (rest, options) = number::complete::be_u32(input)?;
+        let (rest, interface_mtu) = number::complete::be_u16(rest)?;
+        let (rest, _reserved) = number::complete::be_u8(rest)?;
+        (rest, interface_mtu, options & 0x00ff_ffff)
+    };
+    let (rest, flags) = number::complete::be_u8(rest)?;
+    let (rest, sequence_number) = number::complete::be_u32(rest)?;
+    let (rest, lsa_headers) = list(rest, lsa_header(version))?;
+
+    Ok((
+        rest,
+        OspfDatabaseDescription {
+            interface_mtu,
+            options,
+            init: flags & DD_FLAG_INIT != 0,
+            more: flags & DD_FLAG_MORE != 0,
+            master: flags & DD_FLAG_MASTER != 0,
+            sequence_number,
+            lsa_headers,
+        },
+    ))
+}
+
+fn lsa_key(version: u8) -> impl Fn(&[u8]) -> IResult<&[u8], OspfLsaKey> {
+    move |input| {
+        let (rest, ls_type) = number::complete::be_u32(input)?;
+        let ls_type = if version == OSPF_V2 {
+            match ls_type {
+                0..=0xff => OspfLsaType::from_v2(ls_type as u8),
+                _ => return length_error(input),
+            }
+        } else {
+            OspfLsaType::from_v3(ls_type as u16)
+        };
+        let (rest, link_state_id) = address(rest)?;
+        let (rest, advertising_router) = address(rest)?;
+
+        Ok((
+            rest,
+            OspfLsaKey {
+                ls_type,
+                link_state_id,
+                advertising_router,
+            },
+        ))
+    }
+}
+
+fn link_state_update(version: u8, input: &[u8]) -> IResult<&[u8], Vec<OspfLsa>> {
+    let (rest, lsa_count) = number::complete::be_u32(input)?;
+    if lsa_count as usize > rest.len() / usize::from(LSA_HEADER_LENGTH) {
+        return length_error(input);
+    }
+    count(lsa(version), lsa_count as usize)(rest)
+}
+
+/// Parses an OSPF packet of either version. Only `length` octets are consumed, so with OSPFv2
+/// cryptographic authentication the remaining input starts with the message digest.
+pub fn parse_ospf_packet(input: &[u8]) -> IResult<&[u8], OspfPacket> {
+    let (rest, header) = parse_ospf_header(input)?;
+    let header_length = if header.version == OSPF_V2 {
+        V2_HEADER_LENGTH
+    } else {
+        V3_HEADER_LENGTH
+    };
+    if header.length < header_length {
+        return length_error(input);
+    }
+    let (rest, body) = bytes::streaming::take(header.length - header_length)(rest)?;
+
+    let version = header.version;
+    let (_, body) = match header.packet_type {
+        OspfPacketType::Hello => {
+            let (body, hello) = hello(version, body)?;
+            (body, OspfBody::Hello(hello))
+        }
+        OspfPacketType::DatabaseDescription => {
+            let (body, description) = database_description(version, body)?;
+            (body, OspfBody::DatabaseDescription(description))
+        }
+        OspfPacketType::LinkStateRequest => {
+            let (body, keys) = list(body, lsa_key(version))?;
+            (body, OspfBody::LinkStateRequest(keys))
+        }
+        OspfPacketType::LinkStateUpdate => {
+            let (body, lsas) = link_state_update(version, body)?;
+            (body, OspfBody::LinkStateUpdate(lsas))
+        }
+        OspfPacketType::LinkStateAcknowledgment => {
+            let (body, headers) = list(body, lsa_header(version))?;
+            (body, OspfBody::LinkStateAcknowledgment(headers))
+        }
+        OspfPacketType::Other(_) => (&body[body.len()..], OspfBody::Unknown(body.to_vec())),
+    };
+
+    Ok((rest, OspfPacket { header, body }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn v2_hello_works() {
+        let bytes = [
+            0x02, 0x01, 0x00, 0x30, // version 2, Hello, length 48
+            0x0a, 0x00, 0x00, 0x01, // router ID
+            0x00, 0x00, 0x00, 0x00, // backbone area
+            0x12, 0x34, // checksum
+            0x00, 0x02, // cryptographic authentication
+            0x00, 0x00, 0x01, 0x10, // key ID 1, 16 octets of digest
+            0x00, 0x00, 0x00, 0x2a, // cryptographic sequence number
+            0xff, 0xff, 0xff, 0x00, // network mask
+            0x00, 0x0a, 0x02, 0x01, // hello interval 10, options E, priority 1
+            0x00, 0x00, 0x00, 0x28, // dead interval 40
+            0x0a, 0x00, 0x00, 0x01, // designated router
+            0x00, 0x00, 0x00, 0x00, // no backup designated router
+            0x0a, 0x00, 0x00, 0x02, // neighbor
+            0xaa, 0xbb, // start of the digest
+        ];
+        let expectation = OspfPacket {
+            header: OspfHeader {
+                version: 2,
+                packet_type: OspfPacketType::Hello,
+                length: 48,
+                router_id: Ipv4Addr::new(10, 0, 0, 1),
+                area_id: Ipv4Addr::UNSPECIFIED,
+                checksum: 0x1234,
+                authentication: Some(OspfAuthentication::Cryptographic {
+                    key_id: 1,
+                    data_length: 16,
+                    sequence_number: 42,
+                }),
+                instance_id: None,
+            },
+            body: OspfBody::Hello(OspfHello {
+                network_mask: Some(Ipv4Addr::new(255, 255, 255, 0)),
+                interface_id: None,
+                hello_interval: 10,
+                options: 0x02,
+                router_priority: 1,
+                router_dead_interval: 40,
+                designated_router: Ipv4Addr::new(10, 0, 0, 1),
+                backup_designated_router: Ipv4Addr::UNSPECIFIED,
+                neighbors: vec![Ipv4Addr::new(10, 0, 0, 2)],
+            }),
+        };
+        assert_eq!(
+            parse_ospf_packet(&bytes),
+            Ok((&[0xaa, 0xbb][..], expectation))
+        );
+    }
+
+    #[test]
+    fn v2_link_state_update_works() {
+        let bytes = [
+            0x02, 0x04, 0x00, 0x64, // version 2, LS Update, length 100
+            0x0a, 0x00, 0x00, 0x01, // router ID
+            0x00, 0x00, 0x00, 0x00, // backbone area
+            0x00, 0x00, 0x00, 0x00, // checksum, null authentication
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // authentication data
+            0x00, 0x00, 0x00, 0x02, // two LSAs
+            // Router LSA
+            0x00, 0x01, 0x02, 0x01, // age 1, options E, router
+            0x0a, 0x00, 0x00, 0x01, // link state ID
+            0x0a, 0x00, 0x00, 0x01, // advertising router
+            0x80, 0x00, 0x00, 0x01, // initial sequence number
+            0x00, 0x00, 0x00, 0x24, // checksum, length 36
+            0x02, 0x00, 0x00, 0x01, // ASBR, one link
+            0xc0, 0x00, 0x02, 0x00, // stub network
+            0xff, 0xff, 0xff, 0x00, // mask
+            0x03, 0x00, 0x00, 0x0a, // stub, no TOS, metric 10
+            // AS-external LSA
+            0x00, 0x01, 0x02, 0x05, // age 1, options E, AS-external
+            0xcb, 0x00, 0x71, 0x00, // link state ID
+            0x0a, 0x00, 0x00, 0x01, // advertising router
+            0x80, 0x00, 0x00, 0x02, // sequence number
+            0x00, 0x00, 0x00, 0x24, // checksum, length 36
+            0xff, 0xff, 0xff, 0x00, // mask
+            0x80, 0x00, 0x00, 0x14, // type 2, metric 20
+            0x00, 0x00, 0x00, 0x00, // no forwarding address
+            0x00, 0x00, 0x00, 0x07, // route tag
+        ];
+
+        let (remaining, packet) = parse_ospf_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.header.authentication, Some(OspfAuthentication::None));
+        let lsas = match packet.body {
+            OspfBody::LinkStateUpdate(lsas) => lsas,
+            other => panic!("unexpected body {:?}", other),
+        };
+        assert_eq!(lsas.len(), 2);
+        assert_eq!(lsas[0].header.sequence_number, -0x7fff_ffff);
+        assert_eq!(
+            lsas[0].body,
+            OspfLsaBody::Router {
+                flags: 0x02,
+                options: None,
+                links: vec![OspfRouterLink::V2 {
+                    link_type: OspfRouterLinkType::StubNetwork,
+                    link_id: Ipv4Addr::new(192, 0, 2, 0),
+                    link_data: Ipv4Addr::new(255, 255, 255, 0),
+                    metric: 10,
+                }],
+            }
+        );
+        assert_eq!(lsas[1].header.ls_type, OspfLsaType::AsExternal);
+        assert_eq!(
+            lsas[1].body,
+            OspfLsaBody::External(OspfExternalLsa {
+                type_2_metric: true,
+                metric: 20,
+                network_mask: Some(Ipv4Addr::new(255, 255, 255, 0)),
+                prefix: None,
+                forwarding_address: Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                route_tag: Some(7),
+                referenced_ls_type: None,
+                referenced_link_state_id: None,
+            })
+        );
+    }
+
+    #[test]
+    fn v3_intra_area_prefix_lsa_works() {
+        let bytes = [
+            0x03, 0x04, 0x00, 0x40, // version 3, LS Update, length 64
+            0x0a, 0x00, 0x00, 0x01, // router ID
+            0x00, 0x00, 0x00, 0x01, // area 0.0.0.1
+            0x00, 0x00, 0x00, 0x00, // checksum, instance 0
+            0x00, 0x00, 0x00, 0x01, // one LSA
+            0x00, 0x01, 0x20, 0x09, // age 1, area scope intra-area-prefix
+            0x00, 0x00, 0x00, 0x00, // link state ID
+            0x0a, 0x00, 0x00, 0x01, // advertising router
+            0x80, 0x00, 0x00, 0x01, // sequence number
+            0x00, 0x00, 0x00, 0x2c, // checksum, length 44
+            0x00, 0x01, 0x20, 0x01, // one prefix, referencing a router LSA
+            0x00, 0x00, 0x00, 0x00, // referenced link state ID
+            0x0a, 0x00, 0x00, 0x01, // referenced advertising router
+            0x40, 0x00, 0x00, 0x0a, // /64, metric 10
+            0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x01, // 2001:db8:0:1::/64
+        ];
+        let expectation = OspfLsaBody::IntraAreaPrefix {
+            referenced_ls_type: OspfLsaType::Router,
+            referenced_link_state_id: Ipv4Addr::UNSPECIFIED,
+            referenced_advertising_router: Ipv4Addr::new(10, 0, 0, 1),
+            prefixes: vec![OspfPrefix {
+                length: 64,
+                options: 0,
+                metric: 10,
+                address: Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0),
+            }],
+        };
+
+        let (remaining, packet) = parse_ospf_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.header.instance_id, Some(0));
+        match packet.body {
+            OspfBody::LinkStateUpdate(lsas) => assert_eq!(lsas[0].body, expectation),
+            other => panic!("unexpected body {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,684 @@
+//! Handles parsing of Precision Time Protocol (IEEE 1588-2008, PTPv2) messages, carried over UDP
+//! or in Ethernet frames of EtherType 0x88F7
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::time::Duration;
+
+// PTPv2 Common Header
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |MajSdo | Type  |MinVer |Version|        Message Length         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |    Domain     |  Minor SdoId  |            Flags              |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Correction Field (64)                     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                 Message Type Specific (32)                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |          Source Port Identity (80): Clock ID and Port         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |          Sequence ID          |    Control    | Log Interval  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The message body follows, then TLVs up to the message length
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |           TLV Type            |          Length Field         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Used by Sync, Delay_Req, Pdelay_Req and Pdelay_Resp messages
+pub const PTP_EVENT_PORT: u16 = 319;
+/// Used by all other messages
+pub const PTP_GENERAL_PORT: u16 = 320;
+
+pub const PTP_FLAG_LEAP_61: u16 = 0x0001;
+pub const PTP_FLAG_LEAP_59: u16 = 0x0002;
+pub const PTP_FLAG_CURRENT_UTC_OFFSET_VALID: u16 = 0x0004;
+pub const PTP_FLAG_PTP_TIMESCALE: u16 = 0x0008;
+pub const PTP_FLAG_TIME_TRACEABLE: u16 = 0x0010;
+pub const PTP_FLAG_FREQUENCY_TRACEABLE: u16 = 0x0020;
+pub const PTP_FLAG_ALTERNATE_MASTER: u16 = 0x0100;
+pub const PTP_FLAG_TWO_STEP: u16 = 0x0200;
+pub const PTP_FLAG_UNICAST: u16 = 0x0400;
+pub const PTP_FLAG_PROFILE_SPECIFIC_1: u16 = 0x2000;
+pub const PTP_FLAG_PROFILE_SPECIFIC_2: u16 = 0x4000;
+
+const HEADER_LENGTH: u16 = 34;
+
+const TLV_MANAGEMENT: u16 = 0x0001;
+const TLV_ORGANIZATION_EXTENSION: u16 = 0x0003;
+const TLV_REQUEST_UNICAST_TRANSMISSION: u16 = 0x0004;
+const TLV_GRANT_UNICAST_TRANSMISSION: u16 = 0x0005;
+const TLV_CANCEL_UNICAST_TRANSMISSION: u16 = 0x0006;
+const TLV_ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION: u16 = 0x0007;
+const TLV_PATH_TRACE: u16 = 0x0008;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PtpMessageType {
+    Sync,
+    DelayReq,
+    PdelayReq,
+    PdelayResp,
+    FollowUp,
+    DelayResp,
+    PdelayRespFollowUp,
+    Announce,
+    Signaling,
+    Management,
+    Other(u8),
+}
+
+impl From<u8> for PtpMessageType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0x0 => Self::Sync,
+            0x1 => Self::DelayReq,
+            0x2 => Self::PdelayReq,
+            0x3 => Self::PdelayResp,
+            0x8 => Self::FollowUp,
+            0x9 => Self::DelayResp,
+            0xa => Self::PdelayRespFollowUp,
+            0xb => Self::Announce,
+            0xc => Self::Signaling,
+            0xd => Self::Management,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpPortIdentity {
+    pub clock_identity: [u8; 8],
+    pub port_number: u16,
+}
+
+/// An 80-bit PTP timestamp: 48-bit seconds and nanoseconds since the PTP epoch, 1970-01-01 TAI
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpTimestamp {
+    pub seconds: u64,
+    pub nanoseconds: u32,
+}
+
+impl PtpTimestamp {
+    /// The time since the PTP epoch. Unlike UTC, it does not account for leap seconds; the
+    /// offset is announced in `PtpAnnounce::current_utc_offset`.
+    pub fn to_duration(&self) -> Duration {
+        Duration::from_secs(self.seconds) + Duration::from_nanos(u64::from(self.nanoseconds))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpHeader {
+    /// Transport specific in IEEE 1588-2008
+    pub major_sdo_id: u8,
+    pub message_type: PtpMessageType,
+    pub minor_version: u8,
+    pub version: u8,
+    pub message_length: u16,
+    pub domain_number: u8,
+    pub minor_sdo_id: u8,
+    /// Bitmap of the `PTP_FLAG_*` values
+    pub flags: u16,
+    /// Nanoseconds multiplied by 2^16
+    pub correction_field: i64,
+    pub message_type_specific: u32,
+    pub source_port_identity: PtpPortIdentity,
+    pub sequence_id: u16,
+    /// Obsolete in PTPv2, kept for PTPv1 hardware
+    pub control_field: u8,
+    /// Log base 2 of the message interval in seconds
+    pub log_message_interval: i8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpClockQuality {
+    pub clock_class: u8,
+    pub clock_accuracy: u8,
+    pub offset_scaled_log_variance: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpAnnounce {
+    pub origin_timestamp: PtpTimestamp,
+    /// Seconds between TAI and UTC
+    pub current_utc_offset: i16,
+    pub grandmaster_priority_1: u8,
+    pub grandmaster_clock_quality: PtpClockQuality,
+    pub grandmaster_priority_2: u8,
+    pub grandmaster_identity: [u8; 8],
+    pub steps_removed: u16,
+    pub time_source: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PtpBody {
+    Sync {
+        origin_timestamp: PtpTimestamp,
+    },
+    DelayReq {
+        origin_timestamp: PtpTimestamp,
+    },
+    PdelayReq {
+        origin_timestamp: PtpTimestamp,
+    },
+    PdelayResp {
+        request_receipt_timestamp: PtpTimestamp,
+        requesting_port_identity: PtpPortIdentity,
+    },
+    FollowUp {
+        precise_origin_timestamp: PtpTimestamp,
+    },
+    DelayResp {
+        receive_timestamp: PtpTimestamp,
+        requesting_port_identity: PtpPortIdentity,
+    },
+    PdelayRespFollowUp {
+        response_origin_timestamp: PtpTimestamp,
+        requesting_port_identity: PtpPortIdentity,
+    },
+    Announce(PtpAnnounce),
+    Signaling {
+        target_port_identity: PtpPortIdentity,
+    },
+    Management {
+        target_port_identity: PtpPortIdentity,
+        starting_boundary_hops: u8,
+        boundary_hops: u8,
+        action: u8,
+    },
+    /// The body and TLVs of an unknown message type
+    Unknown(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PtpTlv {
+    Management {
+        management_id: u16,
+        data: Vec<u8>,
+    },
+    OrganizationExtension {
+        organization_id: [u8; 3],
+        organization_subtype: [u8; 3],
+        data: Vec<u8>,
+    },
+    RequestUnicastTransmission {
+        message_type: PtpMessageType,
+        log_inter_message_period: i8,
+        duration: u32,
+    },
+    GrantUnicastTransmission {
+        message_type: PtpMessageType,
+        log_inter_message_period: i8,
+        /// Seconds; 0 when the request is denied
+        duration: u32,
+        renewal_invited: bool,
+    },
+    CancelUnicastTransmission {
+        message_type: PtpMessageType,
+    },
+    AcknowledgeCancelUnicastTransmission {
+        message_type: PtpMessageType,
+    },
+    PathTrace(Vec<[u8; 8]>),
+    Other {
+        tlv_type: u16,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtpMessage {
+    pub header: PtpHeader,
+    pub body: PtpBody,
+    pub tlvs: Vec<PtpTlv>,
+}
+
+fn list<'a, T>(
+    mut input: &'a [u8],
+    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+) -> IResult<&'a [u8], Vec<T>> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        let (rest, item) = parser(input)?;
+        items.push(item);
+        input = rest;
+    }
+    Ok((input, items))
+}
+
+fn clock_identity(input: &[u8]) -> IResult<&[u8], [u8; 8]> {
+    let (input, raw) = bytes::complete::take(8u8)(input)?;
+    let mut identity = [0; 8];
+    identity.copy_from_slice(raw);
+
+    Ok((input, identity))
+}
+
+fn port_identity(input: &[u8]) -> IResult<&[u8], PtpPortIdentity> {
+    let (input, clock_identity) = clock_identity(input)?;
+    let (input, port_number) = number::complete::be_u16(input)?;
+
+    Ok((
+        input,
+        PtpPortIdentity {
+            clock_identity,
+            port_number,
+        },
+    ))
+}
+
+fn timestamp(input: &[u8]) -> IResult<&[u8], PtpTimestamp> {
+    let (input, high) = number::complete::be_u16(input)?;
+    let (input, low) = number::complete::be_u32(input)?;
+    let (input, nanoseconds) = number::complete::be_u32(input)?;
+
+    Ok((
+        input,
+        PtpTimestamp {
+            seconds: u64::from(high) << 32 | u64::from(low),
+            nanoseconds,
+        },
+    ))
+}
+
+fn header(input: &[u8]) -> IResult<&[u8], PtpHeader> {
+    let (input, first) = number::complete::be_u8(input)?;
+    let (input, second) = number::complete::be_u8(input)?;
+    let (input, message_length) = number::complete::be_u16(input)?;
+    let (input, domain_number) = number::complete::be_u8(input)?;
+    let (input, minor_sdo_id) = number::complete::be_u8(input)?;
+    let (input, flags) = number::complete::be_u16(input)?;
+    let (input, correction_field) = number::complete::be_i64(input)?;
+    let (input, message_type_specific) = number::complete::be_u32(input)?;
+    let (input, source_port_identity) = port_identity(input)?;
+    let (input, sequence_id) = number::complete::be_u16(input)?;
+    let (input, control_field) = number::complete::be_u8(input)?;
+    let (input, log_message_interval) = number::complete::be_i8(input)?;
+
+    Ok((
+        input,
+        PtpHeader {
+            major_sdo_id: first >> 4,
+            message_type: PtpMessageType::from(first & 0x0f),
+            minor_version: second >> 4,
+            version: second & 0x0f,
+            message_length,
+            domain_number,
+            minor_sdo_id,
+            flags,
+            correction_field,
+            message_type_specific,
+            source_port_identity,
+            sequence_id,
+            control_field,
+            log_message_interval,
+        },
+    ))
+}
+
+fn timestamp_and_port_identity(input: &[u8]) -> IResult<&[u8], (PtpTimestamp, PtpPortIdentity)> {
+    let (input, timestamp) = timestamp(input)?;
+    let (input, port_identity) = port_identity(input)?;
+
+    Ok((input, (timestamp, port_identity)))
+}
+
+fn announce(input: &[u8]) -> IResult<&[u8], PtpAnnounce> {
+    let (input, origin_timestamp) = timestamp(input)?;
+    let (input, current_utc_offset) = number::complete::be_i16(input)?;
+    let (input, _reserved) = number::complete::be_u8(input)?;
+    let (input, grandmaster_priority_1) = number::complete::be_u8(input)?;
+    let (input, clock_class) = number::complete::be_u8(input)?;
+    let (input, clock_accuracy) = number::complete::be_u8(input)?;
+    let (input, offset_scaled_log_variance) = number::complete::be_u16(input)?;
+    let (input, grandmaster_priority_2) = number::complete::be_u8(input)?;
+    let (input, grandmaster_identity) = clock_identity(input)?;
+    let (input, steps_removed) = number::complete::be_u16(input)?;
+    let (input, time_source) = number::complete::be_u8(input)?;
+
+    Ok((
+        input,
+        PtpAnnounce {
+            origin_timestamp,
+            current_utc_offset,
+            grandmaster_priority_1,
+            grandmaster_clock_quality: PtpClockQuality {
+                clock_class,
+                clock_accuracy,
+                offset_scaled_log_variance,
+            },
+            grandmaster_priority_2,
+            grandmaster_identity,
+            steps_removed,
+            time_source,
+        },
+    ))
+}
+
+fn body(input: &[u8], message_type: PtpMessageType) -> IResult<&[u8], PtpBody> {
+    match message_type {
+        PtpMessageType::Sync => {
+            let (input, origin_timestamp) = timestamp(input)?;
+            Ok((input, PtpBody::Sync { origin_timestamp }))
+        }
+        PtpMessageType::DelayReq => {
+            let (input, origin_timestamp) = timestamp(input)?;
+            Ok((input, PtpBody::DelayReq { origin_timestamp }))
+        }
+        PtpMessageType::PdelayReq => {
+            let (input, origin_timestamp) = timestamp(input)?;
+            let (input, _reserved) = bytes::complete::take(10u8)(input)?;
+            Ok((input, PtpBody::PdelayReq { origin_timestamp }))
+        }
+        PtpMessageType::PdelayResp => {
+            let (input, (timestamp, port_identity)) = timestamp_and_port_identity(input)?;
+            let body = PtpBody::PdelayResp {
+                request_receipt_timestamp: timestamp,
+                requesting_port_identity: port_identity,
+            };
+            Ok((input, body))
+        }
+        PtpMessageType::FollowUp => {
+            let (input, precise_origin_timestamp) = timestamp(input)?;
+            Ok((
+                input,
+                PtpBody::FollowUp {
+                    precise_origin_timestamp,
+                },
+            ))
+        }
+        PtpMessageType::DelayResp => {
+            let (input, (timestamp, port_identity)) = timestamp_and_port_identity(input)?;
+            let body = PtpBody::DelayResp {
+                receive_timestamp: timestamp,
+                requesting_port_identity: port_identity,
+            };
+            Ok((input, body))
+        }
+        PtpMessageType::PdelayRespFollowUp => {
+            let (input, (timestamp, port_identity)) = timestamp_and_port_identity(input)?;
+            let body = PtpBody::PdelayRespFollowUp {
+                response_origin_timestamp: timestamp,
+                requesting_port_identity: port_identity,
+            };
+            Ok((input, body))
+        }
+        PtpMessageType::Announce => {
+            let (input, announce) = announce(input)?;
+            Ok((input, PtpBody::Announce(announce)))
+        }
+        PtpMessageType::Signaling => {
+            let (input, target_port_identity) = port_identity(input)?;
+            Ok((
+                input,
+                PtpBody::Signaling {
+                    target_port_identity,
+                },
+            ))
+        }
+        PtpMessageType::Management => {
+            let (input, target_port_identity) = port_identity(input)?;
+            let (input, starting_boundary_hops) = number::complete::be_u8(input)?;
+            let (input, boundary_hops) = number::complete::be_u8(input)?;
+            let (input, action) = number::complete::be_u8(input)?;
+            let (input, _reserved) = number::complete::be_u8(input)?;
+            let body = PtpBody::Management {
+                target_port_identity,
+                starting_boundary_hops,
+                boundary_hops,
+                action: action & 0x0f,
+            };
+            Ok((input, body))
+        }
+        PtpMessageType::Other(_) => Ok((&input[input.len()..], PtpBody::Unknown(input.to_vec()))),
+    }
+}
+
+/// The message type of unicast negotiation TLVs is in the top 4 bits
+fn unicast_message_type(input: &[u8]) -> IResult<&[u8], PtpMessageType> {
+    let (input, raw) = number::complete::be_u8(input)?;
+
+    Ok((input, PtpMessageType::from(raw >> 4)))
+}
+
+fn tlv(input: &[u8]) -> IResult<&[u8], PtpTlv> {
+    let (input, tlv_type) = number::complete::be_u16(input)?;
+    let (input, length) = number::complete::be_u16(input)?;
+    let (input, value) = bytes::complete::take(length)(input)?;
+
+    let tlv = match tlv_type {
+        TLV_MANAGEMENT => {
+            let (data, management_id) = number::complete::be_u16(value)?;
+            PtpTlv::Management {
+                management_id,
+                data: data.to_vec(),
+            }
+        }
+        TLV_ORGANIZATION_EXTENSION => {
+            let (data, ids) = bytes::complete::take(6u8)(value)?;
+            PtpTlv::OrganizationExtension {
+                organization_id: [ids[0], ids[1], ids[2]],
+                organization_subtype: [ids[3], ids[4], ids[5]],
+                data: data.to_vec(),
+            }
+        }
+        TLV_REQUEST_UNICAST_TRANSMISSION => {
+            let (value, message_type) = unicast_message_type(value)?;
+            let (value, log_inter_message_period) = number::complete::be_i8(value)?;
+            let (_, duration) = number::complete::be_u32(value)?;
+            PtpTlv::RequestUnicastTransmission {
+                message_type,
+                log_inter_message_period,
+                duration,
+            }
+        }
+        TLV_GRANT_UNICAST_TRANSMISSION => {
+            let (value, message_type) = unicast_message_type(value)?;
+            let (value, log_inter_message_period) = number::complete::be_i8(value)?;
+            let (value, duration) = number::complete::be_u32(value)?;
+            let (value, _reserved) = number::complete::be_u8(value)?;
+            let (_, renewal) = number::complete::be_u8(value)?;
+            PtpTlv::GrantUnicastTransmission {
+                message_type,
+                log_inter_message_period,
+                duration,
+                renewal_invited: renewal & 0x01 != 0,
+            }
+        }
+        TLV_CANCEL_UNICAST_TRANSMISSION => PtpTlv::CancelUnicastTransmission {
+            message_type: unicast_message_type(value)?.1,
+        },
+        TLV_ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION => {
+            PtpTlv::AcknowledgeCancelUnicastTransmission {
+                message_type: unicast_message_type(value)?.1,
+            }
+        }
+        TLV_PATH_TRACE => PtpTlv::PathTrace(list(value, clock_identity)?.1),
+        tlv_type => PtpTlv::Other {
+            tlv_type,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((input, tlv))
+}
+
+pub fn parse_ptp_header(input: &[u8]) -> IResult<&[u8], PtpHeader> {
+    let (input, raw) = bytes::streaming::take(HEADER_LENGTH)(input)?;
+    let (_, header) = header(raw)?;
+
+    Ok((input, header))
+}
+
+/// Parses a PTP message. The remaining input starts after the message length, which skips any
+/// Ethernet padding.
+pub fn parse_ptp_message(input: &[u8]) -> IResult<&[u8], PtpMessage> {
+    let (rest, header) = parse_ptp_header(input)?;
+    if header.message_length < HEADER_LENGTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (rest, message) = bytes::streaming::take(header.message_length - HEADER_LENGTH)(rest)?;
+    let (message, body) = body(message, header.message_type)?;
+    let (_, tlvs) = list(message, tlv)?;
+
+    Ok((rest, PtpMessage { header, body, tlvs }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    const CLOCK: [u8; 8] = [0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f];
+
+    #[test]
+    fn sync_and_delay_resp_work() {
+        let bytes = [
+            0x00, 0x02, 0x00, 0x2c, // Sync, version 2, length 44
+            0x00, 0x00, 0x02, 0x00, // domain 0, two-step
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x80, 0x00, // correction 1.5ns
+            0x00, 0x00, 0x00, 0x00, // reserved
+            0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f, 0x00, 0x01, // source port
+            0x04, 0xd2, 0x00, 0x00, // sequence 1234, control, log interval 0
+            0x00, 0x00, 0x65, 0x5b, 0x6d, 0x80, 0x1d, 0xcd, 0x65, 0x00, // origin timestamp
+        ];
+        let expectation = PtpMessage {
+            header: PtpHeader {
+                major_sdo_id: 0,
+                message_type: PtpMessageType::Sync,
+                minor_version: 0,
+                version: 2,
+                message_length: 44,
+                domain_number: 0,
+                minor_sdo_id: 0,
+                flags: PTP_FLAG_TWO_STEP,
+                correction_field: 0x18000,
+                message_type_specific: 0,
+                source_port_identity: PtpPortIdentity {
+                    clock_identity: CLOCK,
+                    port_number: 1,
+                },
+                sequence_id: 1234,
+                control_field: 0,
+                log_message_interval: 0,
+            },
+            body: PtpBody::Sync {
+                origin_timestamp: PtpTimestamp {
+                    seconds: 0x655b_6d80,
+                    nanoseconds: 500_000_000,
+                },
+            },
+            tlvs: vec![],
+        };
+        assert_eq!(parse_ptp_message(&bytes), Ok((EMPTY_SLICE, expectation)));
+        assert_eq!(
+            PtpTimestamp {
+                seconds: 10,
+                nanoseconds: 500_000_000,
+            }
+            .to_duration(),
+            Duration::from_millis(10_500)
+        );
+
+        let bytes = [
+            0x09, 0x12, 0x00, 0x36, // Delay_Resp, version 2.1, length 54
+            0x18, 0x00, 0x00, 0x00, // domain 24
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // correction
+            0x00, 0x00, 0x00, 0x00, // reserved
+            0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f, 0x00, 0x01, // source port
+            0x00, 0x07, 0x03, 0xfe, // sequence 7, control, log interval -2
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00, 0x01, // receive timestamp
+            0x00, 0x11, 0x22, 0xff, 0xfe, 0x33, 0x44, 0x55, 0x00, 0x02, // requesting port
+            0x00, 0x00, // padding
+        ];
+        let (rest, message) = parse_ptp_message(&bytes).unwrap();
+        assert_eq!(rest, &[0x00, 0x00][..]);
+        assert_eq!(message.header.minor_version, 1);
+        assert_eq!(message.header.domain_number, 24);
+        assert_eq!(message.header.log_message_interval, -2);
+        assert_eq!(
+            message.body,
+            PtpBody::DelayResp {
+                receive_timestamp: PtpTimestamp {
+                    seconds: 100,
+                    nanoseconds: 1,
+                },
+                requesting_port_identity: PtpPortIdentity {
+                    clock_identity: [0x00, 0x11, 0x22, 0xff, 0xfe, 0x33, 0x44, 0x55],
+                    port_number: 2,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn announce_with_path_trace_works() {
+        let bytes = [
+            0x0b, 0x02, 0x00, 0x4c, // Announce, version 2, length 76
+            0x00, 0x00, 0x00, 0x0c, // UTC offset valid, PTP timescale
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // correction
+            0x00, 0x00, 0x00, 0x00, // reserved
+            0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f, 0x00, 0x01, // source port
+            0x00, 0x03, 0x05, 0x01, // sequence 3, control, log interval 1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // origin timestamp
+            0x00, 0x25, 0x00, 0x80, // UTC offset 37, reserved, priority 1
+            0x06, 0x21, 0x4e, 0x5d, 0x80, // clock class 6, accuracy, variance, priority 2
+            0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f, // grandmaster
+            0x00, 0x00, 0x20, // steps removed, GPS
+            0x00, 0x08, 0x00, 0x08, // path trace
+            0x00, 0x1b, 0x2c, 0xff, 0xfe, 0x3d, 0x4e, 0x5f,
+        ];
+        let (rest, message) = parse_ptp_message(&bytes).unwrap();
+        assert_eq!(rest, EMPTY_SLICE);
+        assert_eq!(
+            message.header.flags,
+            PTP_FLAG_CURRENT_UTC_OFFSET_VALID | PTP_FLAG_PTP_TIMESCALE
+        );
+        assert_eq!(
+            message.body,
+            PtpBody::Announce(PtpAnnounce {
+                origin_timestamp: PtpTimestamp {
+                    seconds: 0,
+                    nanoseconds: 0,
+                },
+                current_utc_offset: 37,
+                grandmaster_priority_1: 128,
+                grandmaster_clock_quality: PtpClockQuality {
+                    clock_class: 6,
+                    clock_accuracy: 0x21,
+                    offset_scaled_log_variance: 0x4e5d,
+                },
+                grandmaster_priority_2: 128,
+                grandmaster_identity: CLOCK,
+                steps_removed: 0,
+                time_source: 0x20,
+            })
+        );
+        assert_eq!(message.tlvs, vec![PtpTlv::PathTrace(vec![CLOCK])]);
+
+        let mut truncated = bytes.to_vec();
+        truncated[3] = 0x10;
+        assert_eq!(
+            parse_ptp_message(&truncated),
+            Err(Err::Error(Error::new(
+                &truncated[..],
+                ErrorKind::LengthValue
+            )))
+        );
+    }
+}
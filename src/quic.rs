@@ -0,0 +1,748 @@
+//! Handles parsing of QUIC packet headers, and decryption of Initial packets to reach the TLS
+//! ClientHello. Decryption requires the `quic-decrypt` feature.
+
+use crate::tls::{self, ClientHello, TlsHandshake};
+#[cfg(feature = "quic-decrypt")]
+use aes::cipher::generic_array::GenericArray;
+#[cfg(feature = "quic-decrypt")]
+use aes::cipher::{BlockEncrypt, KeyInit};
+#[cfg(feature = "quic-decrypt")]
+use aes::Aes128;
+#[cfg(feature = "quic-decrypt")]
+use aes_gcm::aead::{Aead, Payload};
+#[cfg(feature = "quic-decrypt")]
+use aes_gcm::{Aes128Gcm, Nonce};
+#[cfg(feature = "quic-decrypt")]
+use hkdf::Hkdf;
+use nom::bytes;
+use nom::combinator;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+#[cfg(feature = "quic-decrypt")]
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+// QUIC Long Header Packet (RFC 9000)
+//
+//   +-+-+-+-+-+-+-+-+
+//   |1|1|T T|X X X X|
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                         Version (32)                          |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | DCID Len (8)  |     Destination Connection ID (0..160)      ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   | SCID Len (8)  |       Source Connection ID (0..160)         ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                  Type-Specific Payload (*)                  ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// QUIC Short Header Packet (RFC 9000)
+//
+//   +-+-+-+-+-+-+-+-+
+//   |0|1|S|R|R|K|P P|
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                Destination Connection ID (0..160)           ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Packet Number (8/16/24/32)              ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     Protected Payload (*)                   ...
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const QUIC_PORT: u16 = 443;
+
+/// Reassembled CRYPTO data beyond this offset is ignored
+pub const MAX_CRYPTO_LENGTH: u64 = 1 << 16;
+
+const VERSION_NEGOTIATION: u32 = 0;
+const VERSION_1: u32 = 0x0000_0001;
+const VERSION_2: u32 = 0x6b33_43cf;
+
+const FORM_LONG: u8 = 0x80;
+const SPIN_BIT: u8 = 0x20;
+const RETRY_INTEGRITY_TAG_LENGTH: usize = 16;
+
+/// Initial salts of RFC 9001 section 5.2 and RFC 9369 section 3.3.1
+#[cfg(feature = "quic-decrypt")]
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+#[cfg(feature = "quic-decrypt")]
+const INITIAL_SALT_V2: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93, 0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb,
+    0xf9, 0xbd, 0x2e, 0xd9,
+];
+
+const FRAME_PADDING: u64 = 0x00;
+const FRAME_PING: u64 = 0x01;
+const FRAME_ACK: u64 = 0x02;
+const FRAME_ACK_ECN: u64 = 0x03;
+const FRAME_CRYPTO: u64 = 0x06;
+const FRAME_CONNECTION_CLOSE: u64 = 0x1c;
+const FRAME_CONNECTION_CLOSE_APPLICATION: u64 = 0x1d;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuicVersion {
+    V1,
+    V2,
+    Other(u32),
+}
+
+impl From<u32> for QuicVersion {
+    fn from(raw: u32) -> Self {
+        match raw {
+            VERSION_1 => Self::V1,
+            VERSION_2 => Self::V2,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuicLongPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+}
+
+impl QuicLongPacketType {
+    /// QUIC v2 shuffles the type bits; every other version is read with the v1 encoding
+    fn new(bits: u8, version: QuicVersion) -> Self {
+        match (version, bits & 0x03) {
+            (QuicVersion::V2, 0) => Self::Retry,
+            (QuicVersion::V2, 1) => Self::Initial,
+            (QuicVersion::V2, 2) => Self::ZeroRtt,
+            (QuicVersion::V2, _) => Self::Handshake,
+            (_, 0) => Self::Initial,
+            (_, 1) => Self::ZeroRtt,
+            (_, 2) => Self::Handshake,
+            (_, _) => Self::Retry,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuicLongHeader {
+    pub packet_type: QuicLongPacketType,
+    pub version: QuicVersion,
+    pub dcid: Vec<u8>,
+    pub scid: Vec<u8>,
+    /// The token of an Initial packet, or the retry token of a Retry packet
+    pub token: Vec<u8>,
+    /// Length of the packet number and payload; `None` for Retry packets
+    pub length: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuicShortHeader {
+    pub spin_bit: bool,
+    pub dcid: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuicHeader {
+    Long(QuicLongHeader),
+    Short(QuicShortHeader),
+    VersionNegotiation {
+        dcid: Vec<u8>,
+        scid: Vec<u8>,
+        supported_versions: Vec<QuicVersion>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuicAckRange {
+    pub gap: u64,
+    pub length: u64,
+}
+
+/// The frames allowed in Initial packets
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuicFrame {
+    /// A run of consecutive PADDING frames
+    Padding(usize),
+    Ping,
+    Ack {
+        largest_acknowledged: u64,
+        ack_delay: u64,
+        first_range: u64,
+        ranges: Vec<QuicAckRange>,
+        /// ECT(0), ECT(1) and ECN-CE counts
+        ecn_counts: Option<[u64; 3]>,
+    },
+    Crypto {
+        offset: u64,
+        data: Vec<u8>,
+    },
+    ConnectionClose {
+        error_code: u64,
+        /// `None` for an application CONNECTION_CLOSE
+        frame_type: Option<u64>,
+        reason: String,
+    },
+}
+
+/// A client Initial packet, decrypted with the keys derived from its Destination Connection ID
+#[cfg(feature = "quic-decrypt")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuicInitialPacket {
+    pub header: QuicLongHeader,
+    /// The truncated packet number, as sent
+    pub packet_number: u32,
+    pub packet_number_length: u8,
+    pub frames: Vec<QuicFrame>,
+}
+
+/// Joins the data of CRYPTO frames, which may arrive out of order and across several Initial
+/// packets, into the TLS handshake stream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuicCryptoReassembler {
+    fragments: BTreeMap<u64, Vec<u8>>,
+}
+
+impl QuicCryptoReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, offset: u64, data: &[u8]) {
+        if offset >= MAX_CRYPTO_LENGTH {
+            return;
+        }
+        let length = data.len().min((MAX_CRYPTO_LENGTH - offset) as usize);
+        let fragment = self.fragments.entry(offset).or_default();
+        if fragment.len() < length {
+            *fragment = data[..length].to_vec();
+        }
+    }
+
+    /// Adds the data of every CRYPTO frame in `frames`
+    pub fn add_frames(&mut self, frames: &[QuicFrame]) {
+        for frame in frames {
+            if let QuicFrame::Crypto { offset, data } = frame {
+                self.add(*offset, data);
+            }
+        }
+    }
+
+    /// The handshake stream received without gaps from offset 0
+    pub fn contiguous(&self) -> Vec<u8> {
+        let mut stream: Vec<u8> = vec![];
+        for (&offset, data) in &self.fragments {
+            let end = stream.len() as u64;
+            if offset > end {
+                break;
+            }
+            let skip = (end - offset) as usize;
+            if skip < data.len() {
+                stream.extend_from_slice(&data[skip..]);
+            }
+        }
+        stream
+    }
+
+    /// The ClientHello, once it has been entirely received
+    pub fn client_hello(&self) -> Option<ClientHello> {
+        match tls::parse_tls_handshake(&self.contiguous()) {
+            Ok((_, TlsHandshake::ClientHello(hello))) => Some(hello),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "quic-decrypt")]
+struct InitialKeys {
+    key: [u8; 16],
+    iv: [u8; 12],
+    hp: [u8; 16],
+}
+
+/// Variable-length integer (RFC 9000 section 16)
+fn varint(input: &[u8]) -> IResult<&[u8], u64> {
+    let (_, first) = number::streaming::be_u8(input)?;
+    match first >> 6 {
+        0 => number::streaming::be_u8(input).map(|(i, v)| (i, u64::from(v))),
+        1 => number::streaming::be_u16(input).map(|(i, v)| (i, u64::from(v & 0x3fff))),
+        2 => number::streaming::be_u32(input).map(|(i, v)| (i, u64::from(v & 0x3fff_ffff))),
+        _ => number::streaming::be_u64(input).map(|(i, v)| (i, v & 0x3fff_ffff_ffff_ffff)),
+    }
+}
+
+fn connection_id(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = number::streaming::be_u8(input)?;
+    bytes::streaming::take(length)(input)
+}
+
+fn varint_data(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = varint(input)?;
+    bytes::streaming::take(length as usize)(input)
+}
+
+/// `varint` for input that is known to be whole, such as a decrypted payload
+fn complete_varint(input: &[u8]) -> IResult<&[u8], u64> {
+    combinator::complete(varint)(input)
+}
+
+fn complete_varint_data(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    combinator::complete(varint_data)(input)
+}
+
+/// HKDF-Expand-Label of TLS 1.3 (RFC 8446 section 7.1), with an empty context
+#[cfg(feature = "quic-decrypt")]
+fn expand_label(secret: &[u8], label: &str, okm: &mut [u8]) {
+    let hkdf = Hkdf::<Sha256>::from_prk(secret).expect("secret is a SHA-256 output");
+    let label = format!("tls13 {}", label);
+    let mut info = (okm.len() as u16).to_be_bytes().to_vec();
+    info.push(label.len() as u8);
+    info.extend_from_slice(label.as_bytes());
+    info.push(0);
+    hkdf.expand(&info, okm).expect("output is short enough");
+}
+
+#[cfg(feature = "quic-decrypt")]
+fn client_initial_keys(version: QuicVersion, dcid: &[u8]) -> Option<InitialKeys> {
+    let (salt, prefix) = match version {
+        QuicVersion::V1 => (&INITIAL_SALT_V1, "quic"),
+        QuicVersion::V2 => (&INITIAL_SALT_V2, "quicv2"),
+        QuicVersion::Other(_) => return None,
+    };
+
+    let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(salt), dcid);
+    let mut client_secret = [0; 32];
+    expand_label(&initial_secret, "client in", &mut client_secret);
+
+    let mut keys = InitialKeys {
+        key: [0; 16],
+        iv: [0; 12],
+        hp: [0; 16],
+    };
+    expand_label(&client_secret, &format!("{} key", prefix), &mut keys.key);
+    expand_label(&client_secret, &format!("{} iv", prefix), &mut keys.iv);
+    expand_label(&client_secret, &format!("{} hp", prefix), &mut keys.hp);
+    Some(keys)
+}
+
+/// Removes header protection and decrypts a long header packet whose packet number starts at
+/// `pn_offset`, returning the packet number, its length and the plaintext payload
+#[cfg(feature = "quic-decrypt")]
+fn decrypt(packet: &[u8], pn_offset: usize, keys: &InitialKeys) -> Option<(u32, u8, Vec<u8>)> {
+    let sample = packet.get(pn_offset + 4..pn_offset + 20)?;
+    let mut mask = GenericArray::clone_from_slice(sample);
+    Aes128::new(GenericArray::from_slice(&keys.hp)).encrypt_block(&mut mask);
+
+    let mut header = packet[..pn_offset + 4].to_vec();
+    header[0] ^= mask[0] & 0x0f;
+    let pn_length = usize::from(header[0] & 0x03) + 1;
+    header.truncate(pn_offset + pn_length);
+    let mut packet_number = 0;
+    for i in 0..pn_length {
+        header[pn_offset + i] ^= mask[1 + i];
+        packet_number = packet_number << 8 | u32::from(header[pn_offset + i]);
+    }
+
+    let mut nonce = keys.iv;
+    for (n, p) in nonce[8..]
+        .iter_mut()
+        .zip(packet_number.to_be_bytes().iter())
+    {
+        *n ^= p;
+    }
+    let payload = Payload {
+        msg: &packet[pn_offset + pn_length..],
+        aad: &header,
+    };
+    let plaintext = Aes128Gcm::new(GenericArray::from_slice(&keys.key))
+        .decrypt(Nonce::from_slice(&nonce), payload)
+        .ok()?;
+
+    Some((packet_number, pn_length as u8, plaintext))
+}
+
+fn ack(input: &[u8], with_ecn: bool) -> IResult<&[u8], QuicFrame> {
+    let (input, largest_acknowledged) = complete_varint(input)?;
+    let (input, ack_delay) = complete_varint(input)?;
+    let (mut input, range_count) = complete_varint(input)?;
+    let (rest, first_range) = complete_varint(input)?;
+    input = rest;
+    let mut ranges = vec![];
+    for _ in 0..range_count {
+        let (rest, gap) = complete_varint(input)?;
+        let (rest, length) = complete_varint(rest)?;
+        ranges.push(QuicAckRange { gap, length });
+        input = rest;
+    }
+    let ecn_counts = if with_ecn {
+        let (rest, ect0) = complete_varint(input)?;
+        let (rest, ect1) = complete_varint(rest)?;
+        let (rest, ce) = complete_varint(rest)?;
+        input = rest;
+        Some([ect0, ect1, ce])
+    } else {
+        None
+    };
+
+    Ok((
+        input,
+        QuicFrame::Ack {
+            largest_acknowledged,
+            ack_delay,
+            first_range,
+            ranges,
+            ecn_counts,
+        },
+    ))
+}
+
+fn frame(input: &[u8]) -> IResult<&[u8], QuicFrame> {
+    let (rest, frame_type) = complete_varint(input)?;
+
+    match frame_type {
+        FRAME_PADDING => {
+            let count = rest.iter().take_while(|&&b| b == 0).count();
+            Ok((&rest[count..], QuicFrame::Padding(count + 1)))
+        }
+        FRAME_PING => Ok((rest, QuicFrame::Ping)),
+        FRAME_ACK | FRAME_ACK_ECN => ack(rest, frame_type == FRAME_ACK_ECN),
+        FRAME_CRYPTO => {
+            let (rest, offset) = complete_varint(rest)?;
+            let (rest, data) = complete_varint_data(rest)?;
+            Ok((
+                rest,
+                QuicFrame::Crypto {
+                    offset,
+                    data: data.to_vec(),
+                },
+            ))
+        }
+        FRAME_CONNECTION_CLOSE | FRAME_CONNECTION_CLOSE_APPLICATION => {
+            let (rest, error_code) = complete_varint(rest)?;
+            let (rest, frame_type) = if frame_type == FRAME_CONNECTION_CLOSE {
+                let (rest, frame_type) = complete_varint(rest)?;
+                (rest, Some(frame_type))
+            } else {
+                (rest, None)
+            };
+            let (rest, reason) = complete_varint_data(rest)?;
+            Ok((
+                rest,
+                QuicFrame::ConnectionClose {
+                    error_code,
+                    frame_type,
+                    reason: String::from_utf8_lossy(reason).into_owned(),
+                },
+            ))
+        }
+        _ => Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    }
+}
+
+/// Parses the frames of a decrypted packet payload
+pub fn parse_quic_frames(mut input: &[u8]) -> IResult<&[u8], Vec<QuicFrame>> {
+    let mut frames = vec![];
+    while !input.is_empty() {
+        let (rest, frame) = frame(input)?;
+        frames.push(frame);
+        input = rest;
+    }
+
+    Ok((input, frames))
+}
+
+/// Parses a QUIC packet header. Short headers do not encode the length of their Destination
+/// Connection ID, so it must be given as `short_dcid_length`.
+///
+/// The packet number is header-protected, so for long and short headers the remaining input
+/// starts at the packet number. For Initial, 0-RTT and Handshake packets, `length` tells where
+/// the next coalesced packet starts. For Retry packets the remaining input is the integrity tag.
+pub fn parse_quic_header(input: &[u8], short_dcid_length: u8) -> IResult<&[u8], QuicHeader> {
+    let (input, first) = number::streaming::be_u8(input)?;
+
+    if first & FORM_LONG == 0 {
+        let (input, dcid) = bytes::streaming::take(short_dcid_length)(input)?;
+        return Ok((
+            input,
+            QuicHeader::Short(QuicShortHeader {
+                spin_bit: first & SPIN_BIT != 0,
+                dcid: dcid.to_vec(),
+            }),
+        ));
+    }
+
+    let (input, version) = number::streaming::be_u32(input)?;
+    let (input, dcid) = connection_id(input)?;
+    let (mut input, scid) = connection_id(input)?;
+
+    if version == VERSION_NEGOTIATION {
+        let mut supported_versions = vec![];
+        while !input.is_empty() {
+            let (rest, version) = number::complete::be_u32(input)?;
+            supported_versions.push(version.into());
+            input = rest;
+        }
+        return Ok((
+            input,
+            QuicHeader::VersionNegotiation {
+                dcid: dcid.to_vec(),
+                scid: scid.to_vec(),
+                supported_versions,
+            },
+        ));
+    }
+
+    let version = QuicVersion::from(version);
+    let packet_type = QuicLongPacketType::new(first >> 4, version);
+    let (input, token, length) = match packet_type {
+        QuicLongPacketType::Retry => {
+            let token_length = input
+                .len()
+                .checked_sub(RETRY_INTEGRITY_TAG_LENGTH)
+                .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::LengthValue)))?;
+            (&input[token_length..], &input[..token_length], None)
+        }
+        QuicLongPacketType::Initial => {
+            let (input, token) = varint_data(input)?;
+            let (input, length) = varint(input)?;
+            (input, token, Some(length))
+        }
+        _ => {
+            let (input, length) = varint(input)?;
+            (input, &input[..0], Some(length))
+        }
+    };
+
+    Ok((
+        input,
+        QuicHeader::Long(QuicLongHeader {
+            packet_type,
+            version,
+            dcid: dcid.to_vec(),
+            scid: scid.to_vec(),
+            token: token.to_vec(),
+            length,
+        }),
+    ))
+}
+
+/// Parses and decrypts a client Initial packet of QUIC v1 or v2, using the initial secrets
+/// derived from its Destination Connection ID. The remaining input is the next coalesced packet,
+/// if any. Packets that fail authentication are rejected with `ErrorKind::Verify`.
+#[cfg(feature = "quic-decrypt")]
+pub fn parse_quic_initial(input: &[u8]) -> IResult<&[u8], QuicInitialPacket> {
+    let (rest, header) = parse_quic_header(input, 0)?;
+    let header = match header {
+        QuicHeader::Long(header) if header.packet_type == QuicLongPacketType::Initial => header,
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    };
+    let keys = client_initial_keys(header.version, &header.dcid)
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::Switch)))?;
+
+    let length = header.length.unwrap_or(0) as usize;
+    let (next, _) = bytes::streaming::take(length)(rest)?;
+    let pn_offset = input.len() - rest.len();
+    let packet = &input[..pn_offset + length];
+    let (packet_number, packet_number_length, plaintext) = decrypt(packet, pn_offset, &keys)
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::Verify)))?;
+    let (_, frames) = parse_quic_frames(&plaintext).map_err(|e| e.map_input(|_| input))?;
+
+    Ok((
+        next,
+        QuicInitialPacket {
+            header,
+            packet_number,
+            packet_number_length,
+            frames,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+    #[cfg(feature = "quic-decrypt")]
+    const DCID: [u8; 8] = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+    #[cfg(feature = "quic-decrypt")]
+    #[test]
+    fn initial_keys_work() {
+        // Test vectors from RFC 9001 appendix A.1 and RFC 9369 appendix A.1
+        let keys = client_initial_keys(QuicVersion::V1, &DCID).unwrap();
+        assert_eq!(
+            keys.key,
+            [
+                0x1f, 0x36, 0x96, 0x13, 0xdd, 0x76, 0xd5, 0x46, 0x77, 0x30, 0xef, 0xcb, 0xe3, 0xb1,
+                0xa2, 0x2d
+            ]
+        );
+        assert_eq!(
+            keys.iv,
+            [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c]
+        );
+        assert_eq!(
+            keys.hp,
+            [
+                0x9f, 0x50, 0x44, 0x9e, 0x04, 0xa0, 0xe8, 0x10, 0x28, 0x3a, 0x1e, 0x99, 0x33, 0xad,
+                0xed, 0xd2
+            ]
+        );
+
+        let keys = client_initial_keys(QuicVersion::V2, &DCID).unwrap();
+        assert_eq!(
+            keys.key,
+            [
+                0x8b, 0x1a, 0x0b, 0xc1, 0x21, 0x28, 0x42, 0x90, 0xa2, 0x9e, 0x09, 0x71, 0xb5, 0xcd,
+                0x04, 0x5d
+            ]
+        );
+        assert_eq!(
+            keys.hp,
+            [
+                0x45, 0xb9, 0x5e, 0x15, 0x23, 0x5d, 0x6f, 0x45, 0xa6, 0xb1, 0x9c, 0xbc, 0xb0, 0x29,
+                0x4b, 0xa9
+            ]
+        );
+    }
+
+    #[test]
+    fn version_negotiation_works() {
+        let bytes = [
+            0x80, 0x00, 0x00, 0x00, 0x00, /* long form, version 0 */
+            0x01, 0xaa, 0x00, /* DCID, SCID */
+            0x00, 0x00, 0x00, 0x01, 0x6b, 0x33, 0x43, 0xcf, /* supported versions */
+        ];
+        let expectation = QuicHeader::VersionNegotiation {
+            dcid: vec![0xaa],
+            scid: vec![],
+            supported_versions: vec![QuicVersion::V1, QuicVersion::V2],
+        };
+        assert_eq!(parse_quic_header(&bytes, 0), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn truncated_version_list_and_frames_fail() {
+        let bytes = [
+            0x80, 0x00, 0x00, 0x00, 0x00, /* long form, version 0 */
+            0x01, 0xaa, 0x00, /* DCID, SCID */
+            0x00, 0x00, 0x00, 0x01, 0x6b, 0x33, /* supported versions, cut short */
+        ];
+        assert!(matches!(
+            parse_quic_header(&bytes, 0),
+            Err(Err::Error(Error {
+                code: ErrorKind::Eof,
+                ..
+            }))
+        ));
+
+        let frames = [
+            0x01, /* PING */
+            0x06, 0x00, 0x10, 0x01, 0x00, /* CRYPTO, data running past the payload */
+        ];
+        assert!(matches!(
+            parse_quic_frames(&frames),
+            Err(Err::Error(Error {
+                code: ErrorKind::Complete,
+                ..
+            }))
+        ));
+    }
+
+    /// Builds a protected client Initial packet the way a client would
+    #[cfg(feature = "quic-decrypt")]
+    fn protect(version: QuicVersion, first: u8, payload: &[u8]) -> Vec<u8> {
+        let keys = client_initial_keys(version, &DCID).unwrap();
+        let version = match version {
+            QuicVersion::V2 => VERSION_2,
+            _ => VERSION_1,
+        };
+        let mut header = vec![first];
+        header.extend_from_slice(&version.to_be_bytes());
+        header.push(DCID.len() as u8);
+        header.extend_from_slice(&DCID);
+        header.extend_from_slice(&[0x00, 0x00]); // SCID, token
+        let length = 0x4000 | (2 + payload.len() + 16) as u16;
+        header.extend_from_slice(&length.to_be_bytes());
+        let pn_offset = header.len();
+        header.extend_from_slice(&[0x00, 0x02]); // packet number
+
+        let mut nonce = keys.iv;
+        nonce[11] ^= 2;
+        let cipher = Aes128Gcm::new(GenericArray::from_slice(&keys.key));
+        let payload = Payload {
+            msg: payload,
+            aad: &header,
+        };
+        let mut packet = header.clone();
+        packet.extend(cipher.encrypt(Nonce::from_slice(&nonce), payload).unwrap());
+
+        let mut mask = GenericArray::clone_from_slice(&packet[pn_offset + 4..pn_offset + 20]);
+        Aes128::new(GenericArray::from_slice(&keys.hp)).encrypt_block(&mut mask);
+        packet[0] ^= mask[0] & 0x0f;
+        packet[pn_offset] ^= mask[1];
+        packet[pn_offset + 1] ^= mask[2];
+        packet
+    }
+
+    #[cfg(feature = "quic-decrypt")]
+    #[test]
+    fn initial_client_hello_works() {
+        let mut hello = vec![0x01, 0x00, 0x00, 0x44, 0x03, 0x03];
+        hello.extend_from_slice(&[0x00; 32]);
+        hello.extend_from_slice(&[
+            0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00, /* session, ciphers, compression */
+            0x00, 0x19, /* extensions length */
+            0x00, 0x00, 0x00, 0x0c, 0x00, 0x0a, 0x00, 0x00, 0x07, /* SNI */
+            b'q', b'u', b'i', b'c', b'.', b'i', b'o', 0x00, 0x10, 0x00, 0x05, 0x00, 0x03, 0x02,
+            b'h', b'3', /* ALPN */
+        ]);
+        // The ClientHello is split across two packets, its second half coming first
+        let mut second = vec![0x06, 0x14, 0x34];
+        second.extend_from_slice(&hello[20..]);
+        second.extend_from_slice(&[0x00; 20]);
+        let mut first = vec![0x06, 0x00, 0x14];
+        first.extend_from_slice(&hello[..20]);
+        first.extend_from_slice(&[0x01, 0x00, 0x00]);
+
+        let mut bytes = protect(QuicVersion::V2, 0xd1, &second);
+        let coalesced = protect(QuicVersion::V1, 0xc1, &first);
+        bytes.extend(&coalesced);
+
+        let mut crypto = QuicCryptoReassembler::new();
+        let (remaining, packet) = parse_quic_initial(&bytes).unwrap();
+        assert_eq!(remaining, &coalesced[..]);
+        assert_eq!(packet.header.version, QuicVersion::V2);
+        assert_eq!(packet.packet_number, 2);
+        assert_eq!(packet.packet_number_length, 2);
+        assert_eq!(packet.frames[1], QuicFrame::Padding(20));
+        crypto.add_frames(&packet.frames);
+        assert_eq!(crypto.client_hello(), None);
+
+        let (remaining, packet) = parse_quic_initial(remaining).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.frames[1], QuicFrame::Ping);
+        crypto.add_frames(&packet.frames);
+        let hello = crypto.client_hello().unwrap();
+        assert_eq!(hello.server_name(), Some(&b"quic.io"[..]));
+        assert_eq!(hello.alpn(), &[b"h3".to_vec()]);
+
+        let mut tampered = coalesced.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            parse_quic_initial(&tampered),
+            Err(Err::Error(Error {
+                code: ErrorKind::Verify,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,673 @@
+//! Handles parsing of SCTP packets: the common header and its chunks
+
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// SCTP Common Header (RFC 9260)
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |     Source Port Number        |     Destination Port Number   |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                      Verification Tag                         |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                           Checksum                            |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// SCTP Chunk Field Format, also used by parameters and error causes with a 16-bit type
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |   Chunk Type  | Chunk  Flags  |        Chunk Length           |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   \                                                               \
+//   /                          Chunk Value                          /
+//   \                                                               \
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const CHECKSUM_OFFSET: usize = 8;
+/// Chunks, parameters and error causes all start with a 4-octet header
+const TLV_HEADER_LENGTH: u16 = 4;
+
+const CHUNK_DATA: u8 = 0;
+const CHUNK_INIT: u8 = 1;
+const CHUNK_INIT_ACK: u8 = 2;
+const CHUNK_SACK: u8 = 3;
+const CHUNK_HEARTBEAT: u8 = 4;
+const CHUNK_HEARTBEAT_ACK: u8 = 5;
+const CHUNK_ABORT: u8 = 6;
+const CHUNK_SHUTDOWN: u8 = 7;
+const CHUNK_SHUTDOWN_ACK: u8 = 8;
+const CHUNK_ERROR: u8 = 9;
+const CHUNK_COOKIE_ECHO: u8 = 10;
+const CHUNK_COOKIE_ACK: u8 = 11;
+const CHUNK_SHUTDOWN_COMPLETE: u8 = 14;
+const CHUNK_I_DATA: u8 = 64;
+
+const FLAG_ENDING: u8 = 0x01;
+const FLAG_BEGINNING: u8 = 0x02;
+const FLAG_UNORDERED: u8 = 0x04;
+const FLAG_IMMEDIATE: u8 = 0x08;
+/// The T bit of ABORT and SHUTDOWN COMPLETE: the verification tag is reflected
+const FLAG_TAG_REFLECTED: u8 = 0x01;
+
+/// CRC32c (Castagnoli) lookup table, for the reflected polynomial 0x82f63b78
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpCommonHeader {
+    pub source_port: u16,
+    pub dest_port: u16,
+    pub verification_tag: u32,
+    /// As found on the wire, where the CRC32c is stored least significant octet first
+    pub checksum: u32,
+}
+
+/// Payload protocol identifiers assigned by IANA, for the protocols seen in signalling networks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SctpPayloadProtocol {
+    Unspecified,
+    Iua,
+    M2ua,
+    M3ua,
+    Sua,
+    M2pa,
+    H248,
+    S1ap,
+    X2ap,
+    Diameter,
+    DiameterDtls,
+    Ngap,
+    Xnap,
+    F1ap,
+    Other(u32),
+}
+
+impl From<u32> for SctpPayloadProtocol {
+    fn from(raw: u32) -> Self {
+        match raw {
+            0 => Self::Unspecified,
+            1 => Self::Iua,
+            2 => Self::M2ua,
+            3 => Self::M3ua,
+            4 => Self::Sua,
+            5 => Self::M2pa,
+            7 => Self::H248,
+            18 => Self::S1ap,
+            27 => Self::X2ap,
+            46 => Self::Diameter,
+            47 => Self::DiameterDtls,
+            60 => Self::Ngap,
+            61 => Self::Xnap,
+            62 => Self::F1ap,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SctpParameter {
+    HeartbeatInfo(Vec<u8>),
+    Ipv4Address(Ipv4Addr),
+    Ipv6Address(Ipv6Addr),
+    StateCookie(Vec<u8>),
+    UnrecognizedParameter(Vec<u8>),
+    /// Suggested cookie life-span increment in milliseconds
+    CookiePreservative(u32),
+    HostNameAddress(String),
+    SupportedAddressTypes(Vec<u16>),
+    EcnCapable,
+    /// RFC 5061
+    SupportedExtensions(Vec<u8>),
+    /// RFC 3758
+    ForwardTsnSupported,
+    Other {
+        parameter_type: u16,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SctpCauseCode {
+    InvalidStreamIdentifier,
+    MissingMandatoryParameter,
+    StaleCookie,
+    OutOfResource,
+    UnresolvableAddress,
+    UnrecognizedChunkType,
+    InvalidMandatoryParameter,
+    UnrecognizedParameters,
+    NoUserData,
+    CookieWhileShuttingDown,
+    RestartWithNewAddresses,
+    UserInitiatedAbort,
+    ProtocolViolation,
+    Other(u16),
+}
+
+impl From<u16> for SctpCauseCode {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => Self::InvalidStreamIdentifier,
+            2 => Self::MissingMandatoryParameter,
+            3 => Self::StaleCookie,
+            4 => Self::OutOfResource,
+            5 => Self::UnresolvableAddress,
+            6 => Self::UnrecognizedChunkType,
+            7 => Self::InvalidMandatoryParameter,
+            8 => Self::UnrecognizedParameters,
+            9 => Self::NoUserData,
+            10 => Self::CookieWhileShuttingDown,
+            11 => Self::RestartWithNewAddresses,
+            12 => Self::UserInitiatedAbort,
+            13 => Self::ProtocolViolation,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpErrorCause {
+    pub code: SctpCauseCode,
+    pub data: Vec<u8>,
+}
+
+/// The flags shared by DATA and I-DATA chunks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpDataFlags {
+    pub immediate: bool,
+    pub unordered: bool,
+    pub beginning: bool,
+    pub ending: bool,
+}
+
+impl From<u8> for SctpDataFlags {
+    fn from(raw: u8) -> Self {
+        SctpDataFlags {
+            immediate: raw & FLAG_IMMEDIATE != 0,
+            unordered: raw & FLAG_UNORDERED != 0,
+            beginning: raw & FLAG_BEGINNING != 0,
+            ending: raw & FLAG_ENDING != 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpDataChunk {
+    pub flags: SctpDataFlags,
+    pub tsn: u32,
+    pub stream_id: u16,
+    pub stream_sequence: u16,
+    pub payload_protocol: SctpPayloadProtocol,
+    pub data: Vec<u8>,
+}
+
+/// I-DATA chunk (RFC 8260). The payload protocol is only sent with the first fragment of a
+/// message, whose fragment sequence number is implicitly 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpIDataChunk {
+    pub flags: SctpDataFlags,
+    pub tsn: u32,
+    pub stream_id: u16,
+    pub message_id: u32,
+    pub payload_protocol: Option<SctpPayloadProtocol>,
+    pub fragment_sequence: u32,
+    pub data: Vec<u8>,
+}
+
+/// INIT and INIT ACK chunks
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpInitChunk {
+    pub initiate_tag: u32,
+    pub advertised_receiver_window: u32,
+    pub outbound_streams: u16,
+    pub inbound_streams: u16,
+    pub initial_tsn: u32,
+    pub parameters: Vec<SctpParameter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpSackChunk {
+    pub cumulative_tsn_ack: u32,
+    pub advertised_receiver_window: u32,
+    /// Start and end offsets from the cumulative TSN ack
+    pub gap_ack_blocks: Vec<(u16, u16)>,
+    pub duplicate_tsns: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SctpChunk {
+    Data(SctpDataChunk),
+    Init(SctpInitChunk),
+    InitAck(SctpInitChunk),
+    Sack(SctpSackChunk),
+    Heartbeat(Vec<SctpParameter>),
+    HeartbeatAck(Vec<SctpParameter>),
+    Abort {
+        tag_reflected: bool,
+        causes: Vec<SctpErrorCause>,
+    },
+    Shutdown {
+        cumulative_tsn_ack: u32,
+    },
+    ShutdownAck,
+    Error(Vec<SctpErrorCause>),
+    CookieEcho(Vec<u8>),
+    CookieAck,
+    ShutdownComplete {
+        tag_reflected: bool,
+    },
+    IData(SctpIDataChunk),
+    Unknown {
+        chunk_type: u8,
+        flags: u8,
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SctpPacket {
+    pub header: SctpCommonHeader,
+    pub chunks: Vec<SctpChunk>,
+}
+
+/// Computes the CRC32c of a whole SCTP packet, taking the checksum field as zero
+pub fn sctp_checksum(packet: &[u8]) -> u32 {
+    let crc = packet.iter().enumerate().fold(!0u32, |crc, (i, &b)| {
+        let b = if (CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4).contains(&i) {
+            0
+        } else {
+            b
+        };
+        CRC32C_TABLE[usize::from(crc as u8 ^ b)] ^ (crc >> 8)
+    });
+    !crc
+}
+
+/// Checks the checksum of a whole SCTP packet, starting at the common header
+pub fn verify_sctp_checksum(packet: &[u8]) -> bool {
+    match packet.get(CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4) {
+        Some(checksum) => checksum == sctp_checksum(packet).to_le_bytes(),
+        None => false,
+    }
+}
+
+fn length_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::LengthValue)))
+}
+
+/// Splits off one chunk, parameter or error cause, with its padding to a multiple of 4 octets.
+/// The padding after the last one may be missing.
+fn tlv(input: &[u8]) -> IResult<&[u8], (u8, u8, u16, &[u8])> {
+    let (rest, type_high) = number::streaming::be_u8(input)?;
+    let (rest, type_low) = number::streaming::be_u8(rest)?;
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    if length < TLV_HEADER_LENGTH {
+        return length_error(input);
+    }
+    let (rest, value) = bytes::streaming::take(length - TLV_HEADER_LENGTH)(rest)?;
+    let padding = usize::from((4 - length % 4) % 4);
+    let rest = &rest[padding.min(rest.len())..];
+
+    Ok((rest, (type_high, type_low, length, value)))
+}
+
+/// Applies `parser` to each TLV in `input`, which must hold nothing else
+fn tlv_list<'a, T>(
+    mut input: &'a [u8],
+    parser: impl Fn(u16, &'a [u8]) -> Option<T>,
+) -> IResult<&'a [u8], Vec<T>> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        let (rest, (type_high, type_low, _, value)) = match tlv(input) {
+            Err(Err::Incomplete(_)) => return length_error(input),
+            result => result?,
+        };
+        match parser(u16::from_be_bytes([type_high, type_low]), value) {
+            Some(item) => items.push(item),
+            None => return length_error(input),
+        }
+        input = rest;
+    }
+    Ok((input, items))
+}
+
+fn parameter(parameter_type: u16, value: &[u8]) -> Option<SctpParameter> {
+    let parameter = match (parameter_type, value.len()) {
+        (1, _) => SctpParameter::HeartbeatInfo(value.to_vec()),
+        (5, 4) => SctpParameter::Ipv4Address(Ipv4Addr::new(value[0], value[1], value[2], value[3])),
+        (5, _) => return None,
+        (6, 16) => {
+            let mut address = [0; 16];
+            address.copy_from_slice(value);
+            SctpParameter::Ipv6Address(Ipv6Addr::from(address))
+        }
+        (6, _) => return None,
+        (7, _) => SctpParameter::StateCookie(value.to_vec()),
+        (8, _) => SctpParameter::UnrecognizedParameter(value.to_vec()),
+        (9, 4) => SctpParameter::CookiePreservative(u32::from_be_bytes([
+            value[0], value[1], value[2], value[3],
+        ])),
+        (9, _) => return None,
+        (11, _) => {
+            let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+            SctpParameter::HostNameAddress(String::from_utf8_lossy(&value[..end]).into_owned())
+        }
+        (12, length) if length % 2 == 0 => SctpParameter::SupportedAddressTypes(
+            value
+                .chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        (12, _) => return None,
+        (0x8000, _) => SctpParameter::EcnCapable,
+        (0x8008, _) => SctpParameter::SupportedExtensions(value.to_vec()),
+        (0xc000, _) => SctpParameter::ForwardTsnSupported,
+        (parameter_type, _) => SctpParameter::Other {
+            parameter_type,
+            value: value.to_vec(),
+        },
+    };
+    Some(parameter)
+}
+
+fn error_cause(code: u16, data: &[u8]) -> Option<SctpErrorCause> {
+    Some(SctpErrorCause {
+        code: code.into(),
+        data: data.to_vec(),
+    })
+}
+
+fn data_chunk(flags: u8, value: &[u8]) -> IResult<&[u8], SctpDataChunk> {
+    let (value, tsn) = number::complete::be_u32(value)?;
+    let (value, stream_id) = number::complete::be_u16(value)?;
+    let (value, stream_sequence) = number::complete::be_u16(value)?;
+    let (value, payload_protocol) = number::complete::be_u32(value)?;
+
+    Ok((
+        &[],
+        SctpDataChunk {
+            flags: flags.into(),
+            tsn,
+            stream_id,
+            stream_sequence,
+            payload_protocol: payload_protocol.into(),
+            data: value.to_vec(),
+        },
+    ))
+}
+
+fn i_data_chunk(flags: u8, value: &[u8]) -> IResult<&[u8], SctpIDataChunk> {
+    let flags = SctpDataFlags::from(flags);
+    let (value, tsn) = number::complete::be_u32(value)?;
+    let (value, stream_id) = number::complete::be_u16(value)?;
+    let (value, _reserved) = number::complete::be_u16(value)?;
+    let (value, message_id) = number::complete::be_u32(value)?;
+    let (value, protocol_or_fragment) = number::complete::be_u32(value)?;
+    let (payload_protocol, fragment_sequence) = if flags.beginning {
+        (Some(protocol_or_fragment.into()), 0)
+    } else {
+        (None, protocol_or_fragment)
+    };
+
+    Ok((
+        &[],
+        SctpIDataChunk {
+            flags,
+            tsn,
+            stream_id,
+            message_id,
+            payload_protocol,
+            fragment_sequence,
+            data: value.to_vec(),
+        },
+    ))
+}
+
+fn init_chunk(value: &[u8]) -> IResult<&[u8], SctpInitChunk> {
+    let (value, initiate_tag) = number::complete::be_u32(value)?;
+    let (value, advertised_receiver_window) = number::complete::be_u32(value)?;
+    let (value, outbound_streams) = number::complete::be_u16(value)?;
+    let (value, inbound_streams) = number::complete::be_u16(value)?;
+    let (value, initial_tsn) = number::complete::be_u32(value)?;
+    let (value, parameters) = tlv_list(value, parameter)?;
+
+    Ok((
+        value,
+        SctpInitChunk {
+            initiate_tag,
+            advertised_receiver_window,
+            outbound_streams,
+            inbound_streams,
+            initial_tsn,
+            parameters,
+        },
+    ))
+}
+
+fn sack_chunk(value: &[u8]) -> IResult<&[u8], SctpSackChunk> {
+    let (rest, cumulative_tsn_ack) = number::complete::be_u32(value)?;
+    let (rest, advertised_receiver_window) = number::complete::be_u32(rest)?;
+    let (rest, gap_count) = number::complete::be_u16(rest)?;
+    let (rest, duplicate_count) = number::complete::be_u16(rest)?;
+    if rest.len() != usize::from(gap_count) * 4 + usize::from(duplicate_count) * 4 {
+        return length_error(value);
+    }
+    let (gaps, duplicates) = rest.split_at(usize::from(gap_count) * 4);
+
+    Ok((
+        &[],
+        SctpSackChunk {
+            cumulative_tsn_ack,
+            advertised_receiver_window,
+            gap_ack_blocks: gaps
+                .chunks(4)
+                .map(|c| {
+                    (
+                        u16::from_be_bytes([c[0], c[1]]),
+                        u16::from_be_bytes([c[2], c[3]]),
+                    )
+                })
+                .collect(),
+            duplicate_tsns: duplicates
+                .chunks(4)
+                .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        },
+    ))
+}
+
+fn chunk(input: &[u8]) -> IResult<&[u8], SctpChunk> {
+    let (rest, (chunk_type, flags, _, value)) = tlv(input)?;
+    let tag_reflected = flags & FLAG_TAG_REFLECTED != 0;
+
+    let chunk = match chunk_type {
+        CHUNK_DATA => SctpChunk::Data(data_chunk(flags, value)?.1),
+        CHUNK_INIT => SctpChunk::Init(init_chunk(value)?.1),
+        CHUNK_INIT_ACK => SctpChunk::InitAck(init_chunk(value)?.1),
+        CHUNK_SACK => SctpChunk::Sack(sack_chunk(value)?.1),
+        CHUNK_HEARTBEAT => SctpChunk::Heartbeat(tlv_list(value, parameter)?.1),
+        CHUNK_HEARTBEAT_ACK => SctpChunk::HeartbeatAck(tlv_list(value, parameter)?.1),
+        CHUNK_ABORT => SctpChunk::Abort {
+            tag_reflected,
+            causes: tlv_list(value, error_cause)?.1,
+        },
+        CHUNK_SHUTDOWN => SctpChunk::Shutdown {
+            cumulative_tsn_ack: number::complete::be_u32(value)?.1,
+        },
+        CHUNK_SHUTDOWN_ACK => SctpChunk::ShutdownAck,
+        CHUNK_ERROR => SctpChunk::Error(tlv_list(value, error_cause)?.1),
+        CHUNK_COOKIE_ECHO => SctpChunk::CookieEcho(value.to_vec()),
+        CHUNK_COOKIE_ACK => SctpChunk::CookieAck,
+        CHUNK_SHUTDOWN_COMPLETE => SctpChunk::ShutdownComplete { tag_reflected },
+        CHUNK_I_DATA => SctpChunk::IData(i_data_chunk(flags, value)?.1),
+        chunk_type => SctpChunk::Unknown {
+            chunk_type,
+            flags,
+            value: value.to_vec(),
+        },
+    };
+
+    Ok((rest, chunk))
+}
+
+pub fn parse_sctp_common_header(input: &[u8]) -> IResult<&[u8], SctpCommonHeader> {
+    let (input, source_port) = number::streaming::be_u16(input)?;
+    let (input, dest_port) = number::streaming::be_u16(input)?;
+    let (input, verification_tag) = number::streaming::be_u32(input)?;
+    let (input, checksum) = number::streaming::be_u32(input)?;
+
+    Ok((
+        input,
+        SctpCommonHeader {
+            source_port,
+            dest_port,
+            verification_tag,
+            checksum,
+        },
+    ))
+}
+
+/// Parses the common header and every chunk of a packet. The checksum is not checked here;
+/// use `verify_sctp_checksum` on the same input for that.
+pub fn parse_sctp_packet(input: &[u8]) -> IResult<&[u8], SctpPacket> {
+    let (mut input, header) = parse_sctp_common_header(input)?;
+    let mut chunks = vec![];
+    while !input.is_empty() {
+        let (rest, chunk) = chunk(input)?;
+        chunks.push(chunk);
+        input = rest;
+    }
+
+    Ok((input, SctpPacket { header, chunks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn init_chunk_works() {
+        let bytes = [
+            0x0b, 0x59, 0x0b, 0x59, // ports: 2905
+            0x00, 0x00, 0x00, 0x00, // verification tag
+            0xe1, 0x3e, 0x3a, 0x57, // checksum
+            0x01, 0x00, 0x00, 0x28, // INIT, length 40
+            0x11, 0x22, 0x33, 0x44, // initiate tag
+            0x00, 0x01, 0x00, 0x00, // receiver window
+            0x00, 0x0a, 0xff, 0xff, // outbound and inbound streams
+            0x01, 0x02, 0x03, 0x04, // initial TSN
+            0x00, 0x05, 0x00, 0x08, 0xc0, 0x00, 0x02, 0x01, // IPv4 address
+            0x00, 0x0c, 0x00, 0x06, 0x00, 0x05, 0x00, 0x00, // supported address types
+            0x80, 0x00, 0x00, 0x04, // ECN capable
+        ];
+
+        assert!(verify_sctp_checksum(&bytes));
+        let mut corrupted = bytes;
+        corrupted[20] ^= 1;
+        assert!(!verify_sctp_checksum(&corrupted));
+
+        let expectation = SctpPacket {
+            header: SctpCommonHeader {
+                source_port: 2905,
+                dest_port: 2905,
+                verification_tag: 0,
+                checksum: 0xe13e3a57,
+            },
+            chunks: vec![SctpChunk::Init(SctpInitChunk {
+                initiate_tag: 0x11223344,
+                advertised_receiver_window: 65536,
+                outbound_streams: 10,
+                inbound_streams: 65535,
+                initial_tsn: 0x01020304,
+                parameters: vec![
+                    SctpParameter::Ipv4Address(Ipv4Addr::new(192, 0, 2, 1)),
+                    SctpParameter::SupportedAddressTypes(vec![5]),
+                    SctpParameter::EcnCapable,
+                ],
+            })],
+        };
+        assert_eq!(parse_sctp_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn data_and_sack_chunks_work() {
+        let bytes = [
+            0x0b, 0x59, 0x0b, 0x59, // ports: 2905
+            0x11, 0x22, 0x33, 0x44, // verification tag
+            0x00, 0x00, 0x00, 0x00, // checksum
+            0x03, 0x00, 0x00, 0x18, // SACK, length 24
+            0x00, 0x00, 0x00, 0x10, // cumulative TSN ack
+            0x00, 0x01, 0x00, 0x00, // receiver window
+            0x00, 0x01, 0x00, 0x01, // one gap block, one duplicate
+            0x00, 0x02, 0x00, 0x03, // gap block
+            0x00, 0x00, 0x00, 0x0f, // duplicate TSN
+            0x00, 0x03, 0x00, 0x13, // DATA, flags B and E, length 19
+            0x00, 0x00, 0x00, 0x11, // TSN
+            0x00, 0x01, 0x00, 0x00, // stream 1, sequence 0
+            0x00, 0x00, 0x00, 0x03, // payload protocol: M3UA
+            0x01, 0x00, 0x03, // data
+            0x00, // padding
+        ];
+
+        let (remaining, packet) = parse_sctp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(
+            packet.chunks[0],
+            SctpChunk::Sack(SctpSackChunk {
+                cumulative_tsn_ack: 16,
+                advertised_receiver_window: 65536,
+                gap_ack_blocks: vec![(2, 3)],
+                duplicate_tsns: vec![15],
+            })
+        );
+        match &packet.chunks[1] {
+            SctpChunk::Data(data) => {
+                assert!(data.flags.beginning && data.flags.ending);
+                assert!(!data.flags.unordered);
+                assert_eq!(data.tsn, 17);
+                assert_eq!(data.stream_id, 1);
+                assert_eq!(data.payload_protocol, SctpPayloadProtocol::M3ua);
+                assert_eq!(data.data, vec![0x01, 0x00, 0x03]);
+            }
+            other => panic!("unexpected chunk {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! Handles parsing of IEEE 802.3 Slow Protocols (EtherType 0x8809): LACP, Marker, OAM and ESMC
+
+use crate::ethernet::{self, MacAddress};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+
+// LACPDU (IEEE 802.1AX); each TLV length includes its 2 octet header
+//
+//   +---------+---------+
+//   | Subtype | Version |
+//   +---------+---------+-------------------------------------------------------+
+//   | Type = 1| Len = 20| System Priority (2) | System (6) | Key (2)             |
+//   |         |         | Port Priority (2) | Port (2) | State | Reserved (3)    |   Actor
+//   +---------+---------+-------------------------------------------------------+
+//   | Type = 2| Len = 20| ... as above                                          |   Partner
+//   +---------+---------+-------------------------------------------------------+
+//   | Type = 3| Len = 16| Collector Max Delay (2) | Reserved (12)               |
+//   +---------+---------+-------------------------------------------------------+
+//   | Type = 0| Len = 0 | Reserved (50)                                         |
+//   +---------+---------+-------------------------------------------------------+
+//
+// Marker PDU
+//
+//   +---------+---------+---------+---------+-------------------------------------+
+//   | Subtype | Version | Type    | Len = 16| Requester Port (2) | System (6)     |
+//   |         |         |         |         | Transaction ID (4) | Pad (2)        |
+//   +---------+---------+---------+---------+-------------------------------------+
+//
+// OAMPDU (IEEE 802.3 clause 57)
+//
+//   +---------+-------------+---------+---------------------------+
+//   | Subtype |  Flags (2)  |  Code   |  Data                     |
+//   +---------+-------------+---------+---------------------------+
+//
+// ESMC PDU (ITU-T G.8264), an Organization Specific Slow Protocol
+//
+//   +---------+-------------+-----------------+--------------------+--------------+
+//   | Subtype |  ITU OUI (3)| ITU Subtype (2) | Version/Event (1)  | Reserved (3) |
+//   +---------+-------------+-----------------+--------------------+--------------+
+//   | QL TLV: Type = 1 | Length = 4 (2) | SSM Code (1)                            |
+//   +------------------------------------------------------------------------------+
+
+pub const OAM_FLAG_LINK_FAULT: u16 = 0x0001;
+pub const OAM_FLAG_DYING_GASP: u16 = 0x0002;
+pub const OAM_FLAG_CRITICAL_EVENT: u16 = 0x0004;
+pub const OAM_FLAG_LOCAL_EVALUATING: u16 = 0x0008;
+pub const OAM_FLAG_LOCAL_STABLE: u16 = 0x0010;
+pub const OAM_FLAG_REMOTE_EVALUATING: u16 = 0x0020;
+pub const OAM_FLAG_REMOTE_STABLE: u16 = 0x0040;
+
+const SUBTYPE_LACP: u8 = 1;
+const SUBTYPE_MARKER: u8 = 2;
+const SUBTYPE_OAM: u8 = 3;
+const SUBTYPE_ORGANIZATION_SPECIFIC: u8 = 10;
+
+const LACP_TLV_ACTOR: u8 = 1;
+const LACP_TLV_PARTNER: u8 = 2;
+const LACP_TLV_COLLECTOR: u8 = 3;
+const LACP_PORT_INFORMATION_LENGTH: u8 = 20;
+const LACP_COLLECTOR_INFORMATION_LENGTH: u8 = 16;
+const MARKER_TLV_INFORMATION: u8 = 1;
+const MARKER_TLV_RESPONSE: u8 = 2;
+const MARKER_INFORMATION_LENGTH: u8 = 16;
+const TLV_TERMINATOR: u8 = 0;
+
+const OAM_CODE_INFORMATION: u8 = 0x00;
+const OAM_CODE_LOOPBACK_CONTROL: u8 = 0x04;
+const OAM_TLV_END: u8 = 0x00;
+const OAM_TLV_LOCAL_INFORMATION: u8 = 0x01;
+const OAM_TLV_REMOTE_INFORMATION: u8 = 0x02;
+const OAM_INFORMATION_LENGTH: u8 = 16;
+
+const ITU_OUI: [u8; 3] = [0x00, 0x19, 0xa7];
+const ITU_SUBTYPE_ESMC: u16 = 0x0001;
+const ESMC_TLV_QUALITY_LEVEL: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LacpState {
+    pub activity: bool,
+    /// Short timeout when set
+    pub timeout: bool,
+    pub aggregation: bool,
+    pub synchronization: bool,
+    pub collecting: bool,
+    pub distributing: bool,
+    pub defaulted: bool,
+    pub expired: bool,
+}
+
+impl From<u8> for LacpState {
+    fn from(raw: u8) -> Self {
+        LacpState {
+            activity: raw & 0b0000_0001 != 0,
+            timeout: raw & 0b0000_0010 != 0,
+            aggregation: raw & 0b0000_0100 != 0,
+            synchronization: raw & 0b0000_1000 != 0,
+            collecting: raw & 0b0001_0000 != 0,
+            distributing: raw & 0b0010_0000 != 0,
+            defaulted: raw & 0b0100_0000 != 0,
+            expired: raw & 0b1000_0000 != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LacpPortInformation {
+    pub system_priority: u16,
+    pub system: MacAddress,
+    pub key: u16,
+    pub port_priority: u16,
+    pub port: u16,
+    pub state: LacpState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LacpPdu {
+    pub version: u8,
+    pub actor: LacpPortInformation,
+    pub partner: LacpPortInformation,
+    /// In tens of microseconds
+    pub collector_max_delay: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarkerPdu {
+    pub version: u8,
+    /// A Marker Response echoes the requester's information
+    pub response: bool,
+    pub requester_port: u16,
+    pub requester_system: MacAddress,
+    pub requester_transaction_id: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OamInformation {
+    pub oam_version: u8,
+    pub revision: u16,
+    /// Parser action in the low 2 bits, multiplexer action in the third
+    pub state: u8,
+    /// Active mode, unidirectional, loopback, link events and variable retrieval support bits
+    pub configuration: u8,
+    /// Maximum OAMPDU size in the low 11 bits
+    pub pdu_configuration: u16,
+    pub oui: [u8; 3],
+    pub vendor_specific: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OamInformationTlv {
+    Local(OamInformation),
+    Remote(OamInformation),
+    Other { tlv_type: u8, value: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OamPayload {
+    Information(Vec<OamInformationTlv>),
+    /// 1 enables and 2 disables remote loopback
+    LoopbackControl(u8),
+    /// Event notifications, variable requests and responses and organization specific data,
+    /// including any frame padding
+    Other {
+        code: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OamPdu {
+    /// Bitmap of the `OAM_FLAG_*` values
+    pub flags: u16,
+    pub payload: OamPayload,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EsmcPdu {
+    pub version: u8,
+    /// Set in event PDUs, sent when the quality level changes, unset in information PDUs
+    pub event: bool,
+    /// The SSM code of the Quality Level TLV
+    pub quality_level: Option<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SlowProtocolPdu {
+    Lacp(LacpPdu),
+    Marker(MarkerPdu),
+    Oam(OamPdu),
+    Esmc(EsmcPdu),
+    OrganizationSpecific { oui: [u8; 3], data: Vec<u8> },
+    Other { subtype: u8, data: Vec<u8> },
+}
+
+fn tag_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Tag)))
+}
+
+fn oui(input: &[u8]) -> IResult<&[u8], [u8; 3]> {
+    let (input, oui) = bytes::streaming::take(3u8)(input)?;
+
+    Ok((input, [oui[0], oui[1], oui[2]]))
+}
+
+/// Reads a TLV header of the given type and length, as LACP and Marker PDUs have a fixed layout
+fn tlv_header(input: &[u8], tlv_type: u8, length: u8) -> IResult<&[u8], ()> {
+    let (rest, actual_type) = number::streaming::be_u8(input)?;
+    let (rest, actual_length) = number::streaming::be_u8(rest)?;
+    if actual_type != tlv_type || actual_length != length {
+        return tag_error(input);
+    }
+
+    Ok((rest, ()))
+}
+
+fn lacp_port_information(input: &[u8], tlv_type: u8) -> IResult<&[u8], LacpPortInformation> {
+    let (input, ()) = tlv_header(input, tlv_type, LACP_PORT_INFORMATION_LENGTH)?;
+    let (input, system_priority) = number::streaming::be_u16(input)?;
+    let (input, system) = ethernet::mac_address(input)?;
+    let (input, key) = number::streaming::be_u16(input)?;
+    let (input, port_priority) = number::streaming::be_u16(input)?;
+    let (input, port) = number::streaming::be_u16(input)?;
+    let (input, state) = number::streaming::be_u8(input)?;
+    let (input, _reserved) = bytes::streaming::take(3u8)(input)?;
+
+    Ok((
+        input,
+        LacpPortInformation {
+            system_priority,
+            system,
+            key,
+            port_priority,
+            port,
+            state: LacpState::from(state),
+        },
+    ))
+}
+
+/// The remaining input starts at the reserved octets after the terminator TLV
+fn lacp_pdu(input: &[u8]) -> IResult<&[u8], LacpPdu> {
+    let (input, version) = number::streaming::be_u8(input)?;
+    let (input, actor) = lacp_port_information(input, LACP_TLV_ACTOR)?;
+    let (input, partner) = lacp_port_information(input, LACP_TLV_PARTNER)?;
+    let (input, ()) = tlv_header(input, LACP_TLV_COLLECTOR, LACP_COLLECTOR_INFORMATION_LENGTH)?;
+    let (input, collector_max_delay) = number::streaming::be_u16(input)?;
+    let (input, _reserved) = bytes::streaming::take(12u8)(input)?;
+    let (input, ()) = tlv_header(input, TLV_TERMINATOR, 0)?;
+
+    Ok((
+        input,
+        LacpPdu {
+            version,
+            actor,
+            partner,
+            collector_max_delay,
+        },
+    ))
+}
+
+fn marker_pdu(input: &[u8]) -> IResult<&[u8], MarkerPdu> {
+    let (input, version) = number::streaming::be_u8(input)?;
+    let response = input.first() == Some(&MARKER_TLV_RESPONSE);
+    let tlv_type = if response {
+        MARKER_TLV_RESPONSE
+    } else {
+        MARKER_TLV_INFORMATION
+    };
+    let (input, ()) = tlv_header(input, tlv_type, MARKER_INFORMATION_LENGTH)?;
+    let (input, requester_port) = number::streaming::be_u16(input)?;
+    let (input, requester_system) = ethernet::mac_address(input)?;
+    let (input, requester_transaction_id) = number::streaming::be_u32(input)?;
+    let (input, _pad) = bytes::streaming::take(2u8)(input)?;
+    let (input, ()) = tlv_header(input, TLV_TERMINATOR, 0)?;
+
+    Ok((
+        input,
+        MarkerPdu {
+            version,
+            response,
+            requester_port,
+            requester_system,
+            requester_transaction_id,
+        },
+    ))
+}
+
+fn oam_information(value: &[u8]) -> IResult<&[u8], OamInformation> {
+    let (value, oam_version) = number::complete::be_u8(value)?;
+    let (value, revision) = number::complete::be_u16(value)?;
+    let (value, state) = number::complete::be_u8(value)?;
+    let (value, configuration) = number::complete::be_u8(value)?;
+    let (value, pdu_configuration) = number::complete::be_u16(value)?;
+    let (value, oui) = bytes::complete::take(3u8)(value)?;
+    let (value, vendor_specific) = number::complete::be_u32(value)?;
+
+    Ok((
+        value,
+        OamInformation {
+            oam_version,
+            revision,
+            state,
+            configuration,
+            pdu_configuration,
+            oui: [oui[0], oui[1], oui[2]],
+            vendor_specific,
+        },
+    ))
+}
+
+/// Reads Information TLVs up to the end marker or the end of the input
+fn oam_information_tlvs(mut input: &[u8]) -> IResult<&[u8], Vec<OamInformationTlv>> {
+    let mut tlvs = vec![];
+    while let Some(&tlv_type) = input.first() {
+        if tlv_type == OAM_TLV_END {
+            break;
+        }
+        let (rest, length) = number::complete::be_u8(&input[1..])?;
+        if length < 2 {
+            return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+        }
+        let (rest, value) = bytes::complete::take(length - 2)(rest)?;
+        let tlv = match (tlv_type, length) {
+            (OAM_TLV_LOCAL_INFORMATION, OAM_INFORMATION_LENGTH) => {
+                OamInformationTlv::Local(oam_information(value)?.1)
+            }
+            (OAM_TLV_REMOTE_INFORMATION, OAM_INFORMATION_LENGTH) => {
+                OamInformationTlv::Remote(oam_information(value)?.1)
+            }
+            (tlv_type, _) => OamInformationTlv::Other {
+                tlv_type,
+                value: value.to_vec(),
+            },
+        };
+        tlvs.push(tlv);
+        input = rest;
+    }
+
+    Ok((input, tlvs))
+}
+
+fn oam_pdu(input: &[u8]) -> IResult<&[u8], OamPdu> {
+    let (input, flags) = number::streaming::be_u16(input)?;
+    let (input, code) = number::streaming::be_u8(input)?;
+    let (input, payload) = match code {
+        OAM_CODE_INFORMATION => {
+            let (input, tlvs) = oam_information_tlvs(input)?;
+            (input, OamPayload::Information(tlvs))
+        }
+        OAM_CODE_LOOPBACK_CONTROL => {
+            let (input, command) = number::streaming::be_u8(input)?;
+            (input, OamPayload::LoopbackControl(command))
+        }
+        code => (
+            &input[input.len()..],
+            OamPayload::Other {
+                code,
+                data: input.to_vec(),
+            },
+        ),
+    };
+
+    Ok((input, OamPdu { flags, payload }))
+}
+
+fn esmc_pdu(input: &[u8]) -> IResult<&[u8], EsmcPdu> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (mut input, _reserved) = bytes::streaming::take(3u8)(input)?;
+
+    let mut quality_level = None;
+    if input.first() == Some(&ESMC_TLV_QUALITY_LEVEL) {
+        let (rest, _type) = number::complete::be_u8(input)?;
+        let (rest, _length) = number::complete::be_u16(rest)?;
+        let (rest, ssm_code) = number::complete::be_u8(rest)?;
+        quality_level = Some(ssm_code & 0x0f);
+        input = rest;
+    }
+
+    Ok((
+        input,
+        EsmcPdu {
+            version: flags >> 4,
+            event: flags & 0b0000_1000 != 0,
+            quality_level,
+        },
+    ))
+}
+
+/// Parses a Slow Protocols PDU, starting after the EtherType. Frames are padded to the minimum
+/// Ethernet size, so the remaining input may hold padding.
+pub fn parse_slow_protocol_pdu(input: &[u8]) -> IResult<&[u8], SlowProtocolPdu> {
+    let (input, subtype) = number::streaming::be_u8(input)?;
+    match subtype {
+        SUBTYPE_LACP => {
+            let (input, pdu) = lacp_pdu(input)?;
+            Ok((input, SlowProtocolPdu::Lacp(pdu)))
+        }
+        SUBTYPE_MARKER => {
+            let (input, pdu) = marker_pdu(input)?;
+            Ok((input, SlowProtocolPdu::Marker(pdu)))
+        }
+        SUBTYPE_OAM => {
+            let (input, pdu) = oam_pdu(input)?;
+            Ok((input, SlowProtocolPdu::Oam(pdu)))
+        }
+        SUBTYPE_ORGANIZATION_SPECIFIC => {
+            let (rest, oui) = oui(input)?;
+            if oui == ITU_OUI {
+                let (after_subtype, itu_subtype) = number::streaming::be_u16(rest)?;
+                if itu_subtype == ITU_SUBTYPE_ESMC {
+                    let (input, pdu) = esmc_pdu(after_subtype)?;
+                    return Ok((input, SlowProtocolPdu::Esmc(pdu)));
+                }
+            }
+            Ok((
+                &rest[rest.len()..],
+                SlowProtocolPdu::OrganizationSpecific {
+                    oui,
+                    data: rest.to_vec(),
+                },
+            ))
+        }
+        subtype => Ok((
+            &input[input.len()..],
+            SlowProtocolPdu::Other {
+                subtype,
+                data: input.to_vec(),
+            },
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn lacp_pdu_works() {
+        let mut bytes = vec![
+            0x01, 0x01, // LACP, version 1
+            0x01, 0x14, // actor
+            0x80, 0x00, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // priority, system
+            0x00, 0x0d, 0x00, 0xff, 0x00, 0x02, // key 13, port priority, port 2
+            0x3d, 0x00, 0x00, 0x00, // in sync, collecting, distributing
+            0x02, 0x14, // partner
+            0x80, 0x00, 0x00, 0x66, 0x77, 0x88, 0x99, 0xaa, // priority, system
+            0x00, 0x0e, 0x00, 0xff, 0x00, 0x05, // key 14, port priority, port 5
+            0x47, 0x00, 0x00, 0x00, // active, short timeout, aggregatable, defaulted
+            0x03, 0x10, 0x00, 0x05, // collector, max delay 50us
+        ];
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&[0x00, 0x00]); // terminator
+        bytes.extend_from_slice(&[0; 50]);
+
+        let (rest, pdu) = parse_slow_protocol_pdu(&bytes).unwrap();
+        assert_eq!(rest, &[0; 50][..]);
+        let pdu = match pdu {
+            SlowProtocolPdu::Lacp(pdu) => pdu,
+            pdu => panic!("unexpected PDU {:?}", pdu),
+        };
+        assert_eq!(pdu.version, 1);
+        assert_eq!(
+            pdu.actor,
+            LacpPortInformation {
+                system_priority: 32768,
+                system: MacAddress([0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f]),
+                key: 13,
+                port_priority: 255,
+                port: 2,
+                state: LacpState {
+                    activity: true,
+                    timeout: false,
+                    aggregation: true,
+                    synchronization: true,
+                    collecting: true,
+                    distributing: true,
+                    defaulted: false,
+                    expired: false,
+                },
+            }
+        );
+        assert_eq!(pdu.partner.key, 14);
+        assert!(pdu.partner.state.timeout);
+        assert!(pdu.partner.state.defaulted);
+        assert!(!pdu.partner.state.synchronization);
+        assert_eq!(pdu.collector_max_delay, 5);
+
+        bytes[3] = 0x13;
+        assert_eq!(
+            parse_slow_protocol_pdu(&bytes),
+            Err(Err::Error(Error::new(&bytes[2..], ErrorKind::Tag)))
+        );
+    }
+
+    #[test]
+    fn marker_oam_and_esmc_work() {
+        let bytes = [
+            0x02, 0x01, // Marker, version 1
+            0x02, 0x10, // Marker Response
+            0x00, 0x02, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // requester port and system
+            0x00, 0x00, 0x00, 0x2a, 0x00, 0x00, // transaction ID, pad
+            0x00, 0x00, // terminator
+        ];
+        let expectation = SlowProtocolPdu::Marker(MarkerPdu {
+            version: 1,
+            response: true,
+            requester_port: 2,
+            requester_system: MacAddress([0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f]),
+            requester_transaction_id: 42,
+        });
+        assert_eq!(
+            parse_slow_protocol_pdu(&bytes),
+            Ok((EMPTY_SLICE, expectation))
+        );
+
+        let bytes = [
+            0x03, 0x00, 0x50, 0x00, // OAM, local and remote stable, Information
+            0x01, 0x10, 0x01, 0x00, 0x02, 0x00, 0x15, 0x05, 0xee, // local information
+            0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, // OUI, vendor specific
+            0x00, 0x00, // end, padding
+        ];
+        let expectation = SlowProtocolPdu::Oam(OamPdu {
+            flags: OAM_FLAG_LOCAL_STABLE | OAM_FLAG_REMOTE_STABLE,
+            payload: OamPayload::Information(vec![OamInformationTlv::Local(OamInformation {
+                oam_version: 1,
+                revision: 2,
+                state: 0,
+                configuration: 0x15,
+                pdu_configuration: 0x05ee,
+                oui: [0x00, 0x00, 0x0c],
+                vendor_specific: 0,
+            })]),
+        });
+        assert_eq!(
+            parse_slow_protocol_pdu(&bytes),
+            Ok((&[0x00, 0x00][..], expectation))
+        );
+
+        let bytes = [
+            0x0a, 0x00, 0x19, 0xa7, 0x00, 0x01, // ESMC
+            0x18, 0x00, 0x00, 0x00, // version 1, event
+            0x01, 0x00, 0x04, 0x02, // QL-PRC
+        ];
+        let expectation = SlowProtocolPdu::Esmc(EsmcPdu {
+            version: 1,
+            event: true,
+            quality_level: Some(2),
+        });
+        assert_eq!(
+            parse_slow_protocol_pdu(&bytes),
+            Ok((EMPTY_SLICE, expectation))
+        );
+    }
+}
@@ -0,0 +1,605 @@
+//! Handles parsing of SNMP v1, v2c and v3 messages, including the v3 USM security parameters
+
+use crate::ber::{self, BerClass, BerTag, ObjectIdentifier};
+use nom::combinator::complete;
+use nom::error::{Error, ErrorKind};
+use nom::multi::many0;
+use nom::{Err, IResult};
+use std::net::Ipv4Addr;
+
+// SNMP Message (RFC 3416, RFC 3412)
+//
+//   Message ::= SEQUENCE {                   SNMPv3Message ::= SEQUENCE {
+//       version    INTEGER,                      msgVersion            INTEGER (3),
+//       community  OCTET STRING,                 msgGlobalData         HeaderData,
+//       data       PDUs                          msgSecurityParameters OCTET STRING,
+//   }                                            msgData               ScopedPduData
+//                                            }
+//   PDU ::= [n] IMPLICIT SEQUENCE {
+//       request-id   INTEGER,                HeaderData ::= SEQUENCE {
+//       error-status INTEGER,                    msgID            INTEGER,
+//       error-index  INTEGER,                    msgMaxSize       INTEGER,
+//       variable-bindings SEQUENCE OF            msgFlags         OCTET STRING (SIZE(1)),
+//           SEQUENCE { name OID, value }         msgSecurityModel INTEGER
+//   }                                        }
+
+pub const SNMP_PORT: u16 = 161;
+pub const SNMP_TRAP_PORT: u16 = 162;
+
+const SECURITY_MODEL_USM: i64 = 3;
+
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+
+// Application types of RFC 2578
+const APPLICATION_IP_ADDRESS: u32 = 0;
+const APPLICATION_COUNTER32: u32 = 1;
+const APPLICATION_GAUGE32: u32 = 2;
+const APPLICATION_TIME_TICKS: u32 = 3;
+const APPLICATION_OPAQUE: u32 = 4;
+const APPLICATION_COUNTER64: u32 = 6;
+
+// Exceptions in v2 responses, context-specific and primitive
+const NO_SUCH_OBJECT: u32 = 0;
+const NO_SUCH_INSTANCE: u32 = 1;
+const END_OF_MIB_VIEW: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpVersion {
+    V1,
+    V2c,
+    V3,
+    Other(i64),
+}
+
+impl From<i64> for SnmpVersion {
+    fn from(raw: i64) -> Self {
+        match raw {
+            0 => Self::V1,
+            1 => Self::V2c,
+            3 => Self::V3,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpPduType {
+    GetRequest,
+    GetNextRequest,
+    Response,
+    SetRequest,
+    TrapV1,
+    GetBulkRequest,
+    InformRequest,
+    TrapV2,
+    Report,
+    Other(u32),
+}
+
+impl From<u32> for SnmpPduType {
+    fn from(raw: u32) -> Self {
+        match raw {
+            0 => Self::GetRequest,
+            1 => Self::GetNextRequest,
+            2 => Self::Response,
+            3 => Self::SetRequest,
+            4 => Self::TrapV1,
+            5 => Self::GetBulkRequest,
+            6 => Self::InformRequest,
+            7 => Self::TrapV2,
+            8 => Self::Report,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpErrorStatus {
+    NoError,
+    TooBig,
+    NoSuchName,
+    BadValue,
+    ReadOnly,
+    GenErr,
+    NoAccess,
+    WrongType,
+    WrongLength,
+    WrongEncoding,
+    WrongValue,
+    NoCreation,
+    InconsistentValue,
+    ResourceUnavailable,
+    CommitFailed,
+    UndoFailed,
+    AuthorizationError,
+    NotWritable,
+    InconsistentName,
+    Other(i64),
+}
+
+impl From<i64> for SnmpErrorStatus {
+    fn from(raw: i64) -> Self {
+        match raw {
+            0 => Self::NoError,
+            1 => Self::TooBig,
+            2 => Self::NoSuchName,
+            3 => Self::BadValue,
+            4 => Self::ReadOnly,
+            5 => Self::GenErr,
+            6 => Self::NoAccess,
+            7 => Self::WrongType,
+            8 => Self::WrongLength,
+            9 => Self::WrongEncoding,
+            10 => Self::WrongValue,
+            11 => Self::NoCreation,
+            12 => Self::InconsistentValue,
+            13 => Self::ResourceUnavailable,
+            14 => Self::CommitFailed,
+            15 => Self::UndoFailed,
+            16 => Self::AuthorizationError,
+            17 => Self::NotWritable,
+            18 => Self::InconsistentName,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    ObjectIdentifier(ObjectIdentifier),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Opaque(Vec<u8>),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Unknown { tag: BerTag, data: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnmpVarBind {
+    pub oid: ObjectIdentifier,
+    pub value: SnmpValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpPdu {
+    /// Every PDU sharing the request-id, error-status and error-index layout
+    Generic {
+        pdu_type: SnmpPduType,
+        request_id: i64,
+        error_status: SnmpErrorStatus,
+        error_index: i64,
+        varbinds: Vec<SnmpVarBind>,
+    },
+    GetBulkRequest {
+        request_id: i64,
+        non_repeaters: i64,
+        max_repetitions: i64,
+        varbinds: Vec<SnmpVarBind>,
+    },
+    TrapV1 {
+        enterprise: ObjectIdentifier,
+        agent_address: Ipv4Addr,
+        generic_trap: i64,
+        specific_trap: i64,
+        timestamp: u32,
+        varbinds: Vec<SnmpVarBind>,
+    },
+}
+
+impl SnmpPdu {
+    pub fn pdu_type(&self) -> SnmpPduType {
+        match self {
+            SnmpPdu::Generic { pdu_type, .. } => *pdu_type,
+            SnmpPdu::GetBulkRequest { .. } => SnmpPduType::GetBulkRequest,
+            SnmpPdu::TrapV1 { .. } => SnmpPduType::TrapV1,
+        }
+    }
+
+    pub fn varbinds(&self) -> &[SnmpVarBind] {
+        match self {
+            SnmpPdu::Generic { varbinds, .. }
+            | SnmpPdu::GetBulkRequest { varbinds, .. }
+            | SnmpPdu::TrapV1 { varbinds, .. } => varbinds,
+        }
+    }
+}
+
+/// User-based Security Model parameters (RFC 3414)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsmSecurityParameters {
+    pub authoritative_engine_id: Vec<u8>,
+    pub authoritative_engine_boots: i64,
+    pub authoritative_engine_time: i64,
+    pub user_name: String,
+    pub authentication_parameters: Vec<u8>,
+    pub privacy_parameters: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpScopedPdu {
+    Plaintext {
+        context_engine_id: Vec<u8>,
+        context_name: String,
+        pdu: SnmpPdu,
+    },
+    Encrypted(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnmpV3Message {
+    pub message_id: i64,
+    pub max_size: i64,
+    pub flag_auth: bool,
+    pub flag_priv: bool,
+    pub flag_reportable: bool,
+    pub security_model: i64,
+    /// Only decoded for the User-based Security Model
+    pub usm: Option<UsmSecurityParameters>,
+    pub scoped_pdu: SnmpScopedPdu,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SnmpMessage {
+    Community {
+        version: SnmpVersion,
+        community: String,
+        pdu: SnmpPdu,
+    },
+    V3(SnmpV3Message),
+}
+
+fn verify_error<T>(input: &[u8]) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+}
+
+fn application(number: u32) -> BerTag {
+    BerTag {
+        class: BerClass::Application,
+        constructed: false,
+        number,
+    }
+}
+
+fn value(input: &[u8]) -> IResult<&[u8], SnmpValue> {
+    let (rest, object) = ber::parse_ber_object(input)?;
+    let tag = object.tag;
+    let content = object.content;
+
+    let unsigned32 = || match ber::decode_unsigned(content) {
+        Some(value) if value <= u64::from(u32::MAX) => Ok(value as u32),
+        _ => Err(Err::Error(Error::new(input, ErrorKind::Verify))),
+    };
+    let value = match (tag.class, tag.constructed, tag.number) {
+        (BerClass::Universal, false, ber::TAG_INTEGER) => match ber::decode_integer(content) {
+            Some(value) => SnmpValue::Integer(value),
+            None => return verify_error(input),
+        },
+        (BerClass::Universal, false, ber::TAG_OCTET_STRING) => {
+            SnmpValue::OctetString(content.to_vec())
+        }
+        (BerClass::Universal, false, ber::TAG_NULL) => SnmpValue::Null,
+        (BerClass::Universal, false, ber::TAG_OBJECT_IDENTIFIER) => {
+            match ber::decode_object_identifier(content) {
+                Some(oid) => SnmpValue::ObjectIdentifier(oid),
+                None => return verify_error(input),
+            }
+        }
+        (BerClass::Application, false, APPLICATION_IP_ADDRESS) => match content {
+            &[a, b, c, d] => SnmpValue::IpAddress(Ipv4Addr::new(a, b, c, d)),
+            _ => return verify_error(input),
+        },
+        (BerClass::Application, false, APPLICATION_COUNTER32) => {
+            SnmpValue::Counter32(unsigned32()?)
+        }
+        (BerClass::Application, false, APPLICATION_GAUGE32) => SnmpValue::Gauge32(unsigned32()?),
+        (BerClass::Application, false, APPLICATION_TIME_TICKS) => {
+            SnmpValue::TimeTicks(unsigned32()?)
+        }
+        (BerClass::Application, false, APPLICATION_OPAQUE) => SnmpValue::Opaque(content.to_vec()),
+        (BerClass::Application, false, APPLICATION_COUNTER64) => {
+            match ber::decode_unsigned(content) {
+                Some(value) => SnmpValue::Counter64(value),
+                None => return verify_error(input),
+            }
+        }
+        (BerClass::ContextSpecific, false, NO_SUCH_OBJECT) => SnmpValue::NoSuchObject,
+        (BerClass::ContextSpecific, false, NO_SUCH_INSTANCE) => SnmpValue::NoSuchInstance,
+        (BerClass::ContextSpecific, false, END_OF_MIB_VIEW) => SnmpValue::EndOfMibView,
+        _ => SnmpValue::Unknown {
+            tag,
+            data: content.to_vec(),
+        },
+    };
+
+    Ok((rest, value))
+}
+
+fn varbind(input: &[u8]) -> IResult<&[u8], SnmpVarBind> {
+    let (rest, content) = ber::parse_ber_sequence(input)?;
+    let (content, oid) = ber::parse_ber_object_identifier(content)?;
+    let (_, value) = complete(value)(content)?;
+    Ok((rest, SnmpVarBind { oid, value }))
+}
+
+fn varbinds(input: &[u8]) -> IResult<&[u8], Vec<SnmpVarBind>> {
+    let (rest, content) = ber::parse_ber_sequence(input)?;
+    let (content, varbinds) = many0(complete(varbind))(content)?;
+    if !content.is_empty() {
+        return verify_error(content);
+    }
+    Ok((rest, varbinds))
+}
+
+fn pdu(input: &[u8]) -> IResult<&[u8], SnmpPdu> {
+    let (rest, object) = ber::parse_ber_object(input)?;
+    if object.tag.class != BerClass::ContextSpecific || !object.tag.constructed {
+        return Err(Err::Error(Error::new(input, ErrorKind::Tag)));
+    }
+    let content = object.content;
+
+    let pdu = match SnmpPduType::from(object.tag.number) {
+        SnmpPduType::TrapV1 => {
+            let (content, enterprise) = ber::parse_ber_object_identifier(content)?;
+            let (content, address) =
+                ber::parse_ber_tagged(application(APPLICATION_IP_ADDRESS))(content)?;
+            let agent_address = match address {
+                &[a, b, c, d] => Ipv4Addr::new(a, b, c, d),
+                _ => return verify_error(input),
+            };
+            let (content, generic_trap) = ber::parse_ber_integer(content)?;
+            let (content, specific_trap) = ber::parse_ber_integer(content)?;
+            let (content, timestamp) =
+                ber::parse_ber_tagged(application(APPLICATION_TIME_TICKS))(content)?;
+            let timestamp = match ber::decode_unsigned(timestamp) {
+                Some(value) if value <= u64::from(u32::MAX) => value as u32,
+                _ => return verify_error(input),
+            };
+            let (_, varbinds) = complete(varbinds)(content)?;
+            SnmpPdu::TrapV1 {
+                enterprise,
+                agent_address,
+                generic_trap,
+                specific_trap,
+                timestamp,
+                varbinds,
+            }
+        }
+        pdu_type => {
+            let (content, request_id) = ber::parse_ber_integer(content)?;
+            let (content, error_status) = ber::parse_ber_integer(content)?;
+            let (content, error_index) = ber::parse_ber_integer(content)?;
+            let (_, varbinds) = complete(varbinds)(content)?;
+            if pdu_type == SnmpPduType::GetBulkRequest {
+                SnmpPdu::GetBulkRequest {
+                    request_id,
+                    non_repeaters: error_status,
+                    max_repetitions: error_index,
+                    varbinds,
+                }
+            } else {
+                SnmpPdu::Generic {
+                    pdu_type,
+                    request_id,
+                    error_status: error_status.into(),
+                    error_index,
+                    varbinds,
+                }
+            }
+        }
+    };
+
+    Ok((rest, pdu))
+}
+
+fn usm_security_parameters(input: &[u8]) -> IResult<&[u8], UsmSecurityParameters> {
+    let (rest, content) = ber::parse_ber_sequence(input)?;
+    let (content, engine_id) = ber::parse_ber_octet_string(content)?;
+    let (content, engine_boots) = ber::parse_ber_integer(content)?;
+    let (content, engine_time) = ber::parse_ber_integer(content)?;
+    let (content, user_name) = ber::parse_ber_octet_string(content)?;
+    let (content, authentication) = ber::parse_ber_octet_string(content)?;
+    let (_, privacy) = ber::parse_ber_octet_string(content)?;
+
+    Ok((
+        rest,
+        UsmSecurityParameters {
+            authoritative_engine_id: engine_id.to_vec(),
+            authoritative_engine_boots: engine_boots,
+            authoritative_engine_time: engine_time,
+            user_name: String::from_utf8_lossy(user_name).into_owned(),
+            authentication_parameters: authentication.to_vec(),
+            privacy_parameters: privacy.to_vec(),
+        },
+    ))
+}
+
+fn scoped_pdu(input: &[u8]) -> IResult<&[u8], SnmpScopedPdu> {
+    if let Ok((rest, encrypted)) = ber::parse_ber_octet_string(input) {
+        return Ok((rest, SnmpScopedPdu::Encrypted(encrypted.to_vec())));
+    }
+    let (rest, content) = ber::parse_ber_sequence(input)?;
+    let (content, context_engine_id) = ber::parse_ber_octet_string(content)?;
+    let (content, context_name) = ber::parse_ber_octet_string(content)?;
+    let (_, pdu) = complete(pdu)(content)?;
+
+    Ok((
+        rest,
+        SnmpScopedPdu::Plaintext {
+            context_engine_id: context_engine_id.to_vec(),
+            context_name: String::from_utf8_lossy(context_name).into_owned(),
+            pdu,
+        },
+    ))
+}
+
+fn v3_message(input: &[u8]) -> IResult<&[u8], SnmpV3Message> {
+    let (rest, header) = ber::parse_ber_sequence(input)?;
+    let (header, message_id) = complete(ber::parse_ber_integer)(header)?;
+    let (header, max_size) = complete(ber::parse_ber_integer)(header)?;
+    let (header, flags) = complete(ber::parse_ber_octet_string)(header)?;
+    let (_, security_model) = complete(ber::parse_ber_integer)(header)?;
+    let flags = match flags {
+        &[flags] => flags,
+        _ => return verify_error(input),
+    };
+
+    let (rest, security_parameters) = ber::parse_ber_octet_string(rest)?;
+    let usm = if security_model == SECURITY_MODEL_USM {
+        let (_, usm) = complete(usm_security_parameters)(security_parameters)?;
+        Some(usm)
+    } else {
+        None
+    };
+    let (rest, scoped_pdu) = scoped_pdu(rest)?;
+
+    Ok((
+        rest,
+        SnmpV3Message {
+            message_id,
+            max_size,
+            flag_auth: flags & FLAG_AUTH != 0,
+            flag_priv: flags & FLAG_PRIV != 0,
+            flag_reportable: flags & FLAG_REPORTABLE != 0,
+            security_model,
+            usm,
+            scoped_pdu,
+        },
+    ))
+}
+
+pub fn parse_snmp_message(input: &[u8]) -> IResult<&[u8], SnmpMessage> {
+    let (rest, content) = ber::parse_ber_sequence(input)?;
+    let (content, version) = complete(ber::parse_ber_integer)(content)?;
+
+    let message = match SnmpVersion::from(version) {
+        SnmpVersion::V3 => {
+            let (_, message) = complete(v3_message)(content)?;
+            SnmpMessage::V3(message)
+        }
+        SnmpVersion::Other(_) => return verify_error(input),
+        version => {
+            let (content, community) = complete(ber::parse_ber_octet_string)(content)?;
+            let (_, pdu) = complete(pdu)(content)?;
+            SnmpMessage::Community {
+                version,
+                community: String::from_utf8_lossy(community).into_owned(),
+                pdu,
+            }
+        }
+    };
+
+    Ok((rest, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn v2c_get_response_works() {
+        let bytes = [
+            0x30, 0x28, // SEQUENCE
+            0x02, 0x01, 0x01, // version: v2c
+            0x04, 0x06, 0x70, 0x75, 0x62, 0x6c, 0x69, 0x63, // community: public
+            0xa2, 0x1b, // Response
+            0x02, 0x01, 0x2a, // request id
+            0x02, 0x01, 0x00, // error status
+            0x02, 0x01, 0x00, // error index
+            0x30, 0x10, // varbinds
+            0x30, 0x0e, // varbind
+            0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x03, 0x00, // sysUpTime.0
+            0x43, 0x02, 0x30, 0x39, // TimeTicks: 12345
+        ];
+
+        let expectation = SnmpMessage::Community {
+            version: SnmpVersion::V2c,
+            community: "public".to_string(),
+            pdu: SnmpPdu::Generic {
+                pdu_type: SnmpPduType::Response,
+                request_id: 42,
+                error_status: SnmpErrorStatus::NoError,
+                error_index: 0,
+                varbinds: vec![SnmpVarBind {
+                    oid: ObjectIdentifier(vec![1, 3, 6, 1, 2, 1, 1, 3, 0]),
+                    value: SnmpValue::TimeTicks(12345),
+                }],
+            },
+        };
+        assert_eq!(parse_snmp_message(&bytes), Ok((EMPTY_SLICE, expectation)));
+
+        // Version 2 was never deployed
+        let mut bytes = bytes;
+        bytes[4] = 2;
+        assert!(parse_snmp_message(&bytes).is_err());
+    }
+
+    #[test]
+    fn v3_usm_works() {
+        let bytes = [
+            0x30, 0x3d, // SEQUENCE
+            0x02, 0x01, 0x03, // version: v3
+            0x30, 0x0e, // header data
+            0x02, 0x02, 0x12, 0x34, // message id
+            0x02, 0x02, 0x05, 0xdc, // max size: 1500
+            0x04, 0x01, 0x04, // flags: reportable
+            0x02, 0x01, 0x03, // security model: USM
+            0x04, 0x15, // security parameters
+            0x30, 0x13, // USM SEQUENCE
+            0x04, 0x00, // engine id
+            0x02, 0x01, 0x00, // engine boots
+            0x02, 0x01, 0x00, // engine time
+            0x04, 0x05, 0x61, 0x64, 0x6d, 0x69, 0x6e, // user name: admin
+            0x04, 0x00, // authentication parameters
+            0x04, 0x00, // privacy parameters
+            0x30, 0x11, // scoped PDU
+            0x04, 0x00, // context engine id
+            0x04, 0x00, // context name
+            0xa0, 0x0b, // GetRequest
+            0x02, 0x01, 0x01, // request id
+            0x02, 0x01, 0x00, // error status
+            0x02, 0x01, 0x00, // error index
+            0x30, 0x00, // varbinds
+        ];
+
+        let (remaining, message) = parse_snmp_message(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        let message = match message {
+            SnmpMessage::V3(message) => message,
+            other => panic!("unexpected message {:?}", other),
+        };
+        assert_eq!(message.message_id, 0x1234);
+        assert_eq!(message.max_size, 1500);
+        assert!(message.flag_reportable);
+        assert!(!message.flag_auth);
+        assert_eq!(message.usm.unwrap().user_name, "admin");
+        match message.scoped_pdu {
+            SnmpScopedPdu::Plaintext { pdu, .. } => {
+                assert_eq!(pdu.pdu_type(), SnmpPduType::GetRequest);
+                assert_eq!(pdu.varbinds(), &[]);
+            }
+            other => panic!("unexpected scoped PDU {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! Handles parsing of STP, RSTP and MSTP BPDUs, which follow an LLC header with both SAPs 0x42
+
+use crate::ethernet::{self, MacAddress};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult};
+use std::time::Duration;
+
+// Configuration and RST BPDU (IEEE 802.1D); a TCN BPDU ends after the BPDU Type
+//
+//   +-----------------+---------+---------+---------+
+//   | Protocol ID (2) | Version |  Type   |  Flags  |
+//   +-----------------+---------+---------+---------+
+//   |      Root Identifier (8)  | Root Path Cost (4)|
+//   +---------------------------+-------------------+
+//   |    Bridge Identifier (8)  |   Port ID (2)     |
+//   +---------------------------+-------------------+
+//   | Message Age, Max Age, Hello Time and Forward  |   1/256 seconds each
+//   | Delay (2 each)                                |
+//   +-----------------------------------------------+
+//   | Version 1 Length (RST and MST BPDUs only)     |
+//   +-----------------------------------------------+
+//
+// MST BPDU extension (IEEE 802.1Q)
+//
+//   +--------------------+------------------------------+
+//   | Version 3 Length(2)| MST Configuration ID (51)    |
+//   +--------------------+------------------------------+
+//   | CIST Internal Root Path Cost (4)                  |
+//   | CIST Bridge Identifier (8) | CIST Remaining Hops  |
+//   +---------------------------------------------------+
+//   | MSTI Configuration Messages (16 each)             |
+//   +---------------------------------------------------+
+
+const PROTOCOL_ID: u16 = 0;
+
+const VERSION_MSTP: u8 = 3;
+
+const TYPE_CONFIGURATION: u8 = 0x00;
+const TYPE_RAPID: u8 = 0x02;
+const TYPE_TOPOLOGY_CHANGE_NOTIFICATION: u8 = 0x80;
+
+const MST_CONFIGURATION_NAME_LENGTH: usize = 32;
+const MST_CONFIGURATION_DIGEST_LENGTH: usize = 16;
+/// The MST extension up to the MSTI configuration messages
+const MST_EXTENSION_LENGTH: usize = 64;
+const MSTI_CONFIGURATION_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StpPortRole {
+    /// Unknown in RST BPDUs, Master in MSTI configuration messages
+    Master,
+    AlternateOrBackup,
+    Root,
+    Designated,
+}
+
+impl From<u8> for StpPortRole {
+    fn from(raw: u8) -> Self {
+        match raw & 0b11 {
+            0 => Self::Master,
+            1 => Self::AlternateOrBackup,
+            2 => Self::Root,
+            _ => Self::Designated,
+        }
+    }
+}
+
+/// Configuration BPDUs only use the topology change flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StpFlags {
+    pub topology_change: bool,
+    pub proposal: bool,
+    pub port_role: StpPortRole,
+    pub learning: bool,
+    pub forwarding: bool,
+    pub agreement: bool,
+    pub topology_change_ack: bool,
+}
+
+impl From<u8> for StpFlags {
+    fn from(raw: u8) -> Self {
+        StpFlags {
+            topology_change: raw & 0b0000_0001 != 0,
+            proposal: raw & 0b0000_0010 != 0,
+            port_role: StpPortRole::from(raw >> 2),
+            learning: raw & 0b0001_0000 != 0,
+            forwarding: raw & 0b0010_0000 != 0,
+            agreement: raw & 0b0100_0000 != 0,
+            topology_change_ack: raw & 0b1000_0000 != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BridgeId {
+    /// A multiple of 4096
+    pub priority: u16,
+    /// The VLAN or MSTI the identifier applies to
+    pub system_id_extension: u16,
+    pub mac: MacAddress,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StpConfiguration {
+    pub flags: StpFlags,
+    pub root_id: BridgeId,
+    pub root_path_cost: u32,
+    pub bridge_id: BridgeId,
+    /// Port priority in the top 4 bits, port number in the rest
+    pub port_id: u16,
+    pub message_age: Duration,
+    pub max_age: Duration,
+    pub hello_time: Duration,
+    pub forward_delay: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MstConfigurationId {
+    pub format_selector: u8,
+    /// Decoded lossily as UTF-8, without the trailing padding
+    pub name: String,
+    pub revision: u16,
+    /// HMAC-MD5 over the VLAN to MSTI mapping
+    pub digest: [u8; 16],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MstiConfiguration {
+    pub flags: StpFlags,
+    /// The MSTI is given by its system ID extension
+    pub regional_root_id: BridgeId,
+    pub internal_root_path_cost: u32,
+    /// A multiple of 4096
+    pub bridge_priority: u16,
+    /// A multiple of 16
+    pub port_priority: u8,
+    pub remaining_hops: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MstExtension {
+    pub configuration_id: MstConfigurationId,
+    pub cist_internal_root_path_cost: u32,
+    pub cist_bridge_id: BridgeId,
+    pub cist_remaining_hops: u8,
+    pub mstis: Vec<MstiConfiguration>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BpduBody {
+    Configuration(StpConfiguration),
+    TopologyChangeNotification,
+    Rapid(StpConfiguration),
+    Multiple(StpConfiguration, MstExtension),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bpdu {
+    /// 0 for STP, 2 for RSTP and 3 for MSTP
+    pub protocol_version: u8,
+    pub body: BpduBody,
+}
+
+fn timer(raw: u16) -> Duration {
+    Duration::from_nanos(u64::from(raw) * 1_000_000_000 / 256)
+}
+
+fn bridge_id(input: &[u8]) -> IResult<&[u8], BridgeId> {
+    let (input, raw) = number::streaming::be_u16(input)?;
+    let (input, mac) = ethernet::mac_address(input)?;
+
+    Ok((
+        input,
+        BridgeId {
+            priority: raw & 0xf000,
+            system_id_extension: raw & 0x0fff,
+            mac,
+        },
+    ))
+}
+
+fn configuration(input: &[u8]) -> IResult<&[u8], StpConfiguration> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, root_id) = bridge_id(input)?;
+    let (input, root_path_cost) = number::streaming::be_u32(input)?;
+    let (input, bridge_id) = bridge_id(input)?;
+    let (input, port_id) = number::streaming::be_u16(input)?;
+    let (input, message_age) = number::streaming::be_u16(input)?;
+    let (input, max_age) = number::streaming::be_u16(input)?;
+    let (input, hello_time) = number::streaming::be_u16(input)?;
+    let (input, forward_delay) = number::streaming::be_u16(input)?;
+
+    Ok((
+        input,
+        StpConfiguration {
+            flags: StpFlags::from(flags),
+            root_id,
+            root_path_cost,
+            bridge_id,
+            port_id,
+            message_age: timer(message_age),
+            max_age: timer(max_age),
+            hello_time: timer(hello_time),
+            forward_delay: timer(forward_delay),
+        },
+    ))
+}
+
+fn mst_configuration_id(input: &[u8]) -> IResult<&[u8], MstConfigurationId> {
+    let (input, format_selector) = number::complete::be_u8(input)?;
+    let (input, name) = bytes::complete::take(MST_CONFIGURATION_NAME_LENGTH)(input)?;
+    let (input, revision) = number::complete::be_u16(input)?;
+    let (input, digest) = bytes::complete::take(MST_CONFIGURATION_DIGEST_LENGTH)(input)?;
+    let name_length = name.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let mut configuration_digest = [0; MST_CONFIGURATION_DIGEST_LENGTH];
+    configuration_digest.copy_from_slice(digest);
+
+    Ok((
+        input,
+        MstConfigurationId {
+            format_selector,
+            name: String::from_utf8_lossy(&name[..name_length]).into_owned(),
+            revision,
+            digest: configuration_digest,
+        },
+    ))
+}
+
+fn msti_configuration(input: &[u8]) -> IResult<&[u8], MstiConfiguration> {
+    let (input, flags) = number::complete::be_u8(input)?;
+    let (input, regional_root_id) = bridge_id(input)?;
+    let (input, internal_root_path_cost) = number::complete::be_u32(input)?;
+    let (input, bridge_priority) = number::complete::be_u8(input)?;
+    let (input, port_priority) = number::complete::be_u8(input)?;
+    let (input, remaining_hops) = number::complete::be_u8(input)?;
+
+    Ok((
+        input,
+        MstiConfiguration {
+            flags: StpFlags::from(flags),
+            regional_root_id,
+            internal_root_path_cost,
+            bridge_priority: u16::from(bridge_priority & 0xf0) << 8,
+            port_priority: port_priority & 0xf0,
+            remaining_hops,
+        },
+    ))
+}
+
+fn mst_extension(input: &[u8]) -> IResult<&[u8], MstExtension> {
+    if input.len() < MST_EXTENSION_LENGTH
+        || (input.len() - MST_EXTENSION_LENGTH) % MSTI_CONFIGURATION_LENGTH != 0
+    {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+    let (input, configuration_id) = mst_configuration_id(input)?;
+    let (input, cist_internal_root_path_cost) = number::complete::be_u32(input)?;
+    let (input, cist_bridge_id) = bridge_id(input)?;
+    let (mut input, cist_remaining_hops) = number::complete::be_u8(input)?;
+
+    let mut mstis = vec![];
+    while !input.is_empty() {
+        let (rest, msti) = msti_configuration(input)?;
+        mstis.push(msti);
+        input = rest;
+    }
+
+    Ok((
+        input,
+        MstExtension {
+            configuration_id,
+            cist_internal_root_path_cost,
+            cist_bridge_id,
+            cist_remaining_hops,
+            mstis,
+        },
+    ))
+}
+
+/// Parses a BPDU, starting after the LLC header
+pub fn parse_bpdu(input: &[u8]) -> IResult<&[u8], Bpdu> {
+    let (input, protocol_id) = number::streaming::be_u16(input)?;
+    if protocol_id != PROTOCOL_ID {
+        return Err(Err::Error(Error::new(input, ErrorKind::Tag)));
+    }
+    let (input, protocol_version) = number::streaming::be_u8(input)?;
+    let (input, bpdu_type) = number::streaming::be_u8(input)?;
+
+    let (input, body) = match bpdu_type {
+        TYPE_CONFIGURATION => {
+            let (input, configuration) = configuration(input)?;
+            (input, BpduBody::Configuration(configuration))
+        }
+        TYPE_TOPOLOGY_CHANGE_NOTIFICATION => (input, BpduBody::TopologyChangeNotification),
+        TYPE_RAPID => {
+            let (input, configuration) = configuration(input)?;
+            let (input, _version_1_length) = number::streaming::be_u8(input)?;
+            if protocol_version < VERSION_MSTP {
+                (input, BpduBody::Rapid(configuration))
+            } else {
+                let (input, version_3_length) = number::streaming::be_u16(input)?;
+                let (input, extension) = bytes::streaming::take(version_3_length)(input)?;
+                let (_, extension) = mst_extension(extension)?;
+                (input, BpduBody::Multiple(configuration, extension))
+            }
+        }
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    };
+
+    Ok((
+        input,
+        Bpdu {
+            protocol_version,
+            body,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::parse_ethernet_frame;
+    use crate::llc::{parse_llc_header, LLC_SAP_SPANNING_TREE};
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn rst_bpdu_works() {
+        let bytes = [
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x00, // destination
+            0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // source
+            0x00, 0x27, // length
+            0x42, 0x42, 0x03, // LLC
+            0x00, 0x00, 0x02, 0x02, // RSTP, RST BPDU
+            0x3c, // designated, learning, forwarding
+            0x80, 0x01, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // root 32768, VLAN 1
+            0x00, 0x00, 0x00, 0x00, // root path cost
+            0x80, 0x01, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // bridge
+            0x80, 0x02, // port ID
+            0x00, 0x00, 0x14, 0x00, 0x02, 0x00, 0x0f, 0x00, // 0, 20, 2 and 15 seconds
+            0x00, // version 1 length
+        ];
+        let bridge = BridgeId {
+            priority: 32768,
+            system_id_extension: 1,
+            mac: MacAddress([0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f]),
+        };
+        let expectation = Bpdu {
+            protocol_version: 2,
+            body: BpduBody::Rapid(StpConfiguration {
+                flags: StpFlags {
+                    topology_change: false,
+                    proposal: false,
+                    port_role: StpPortRole::Designated,
+                    learning: true,
+                    forwarding: true,
+                    agreement: false,
+                    topology_change_ack: false,
+                },
+                root_id: bridge,
+                root_path_cost: 0,
+                bridge_id: bridge,
+                port_id: 0x8002,
+                message_age: Duration::from_secs(0),
+                max_age: Duration::from_secs(20),
+                hello_time: Duration::from_secs(2),
+                forward_delay: Duration::from_secs(15),
+            }),
+        };
+
+        let (rest, frame) = parse_ethernet_frame(&bytes).unwrap();
+        assert!(frame.ethertype.is_length());
+        let (rest, llc) = parse_llc_header(rest).unwrap();
+        assert_eq!(llc.dsap, LLC_SAP_SPANNING_TREE);
+        assert_eq!(parse_bpdu(rest), Ok((EMPTY_SLICE, expectation)));
+
+        let tcn = [0x00, 0x00, 0x00, 0x80];
+        assert_eq!(
+            parse_bpdu(&tcn),
+            Ok((
+                EMPTY_SLICE,
+                Bpdu {
+                    protocol_version: 0,
+                    body: BpduBody::TopologyChangeNotification,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn mst_bpdu_works() {
+        let mut bytes = vec![
+            0x00, 0x00, 0x03, 0x02, // MSTP, MST BPDU
+            0x7c, // designated, learning, forwarding, agreement
+            0x10, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, // CIST root 4096
+            0x00, 0x00, 0x4e, 0x20, // external root path cost 20000
+            0x80, 0x00, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // regional root
+            0x80, 0x01, // port ID
+            0x01, 0x00, 0x14, 0x00, 0x02, 0x00, 0x0f, 0x00, // timers
+            0x00, // version 1 length
+            0x00, 0x50, // version 3 length: 64 + 16
+            0x00, // format selector
+        ];
+        let mut name = b"region".to_vec();
+        name.resize(32, 0);
+        bytes.extend_from_slice(&name);
+        bytes.extend_from_slice(&[0x00, 0x07]); // revision
+        bytes.extend_from_slice(&[0xab; 16]); // digest
+        bytes.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x00, // CIST internal root path cost
+            0x80, 0x00, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // CIST bridge
+            0x14, // remaining hops
+            0x7c, // MSTI flags
+            0x60, 0x05, 0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f, // MSTI 5 regional root 24576
+            0x00, 0x00, 0x00, 0x00, // internal root path cost
+            0x60, 0x80, 0x14, // bridge priority, port priority, remaining hops
+        ]);
+
+        let (rest, bpdu) = parse_bpdu(&bytes).unwrap();
+        assert_eq!(rest, EMPTY_SLICE);
+        assert_eq!(bpdu.protocol_version, 3);
+        let (configuration, extension) = match bpdu.body {
+            BpduBody::Multiple(configuration, extension) => (configuration, extension),
+            body => panic!("unexpected body {:?}", body),
+        };
+        assert_eq!(configuration.root_id.priority, 4096);
+        assert_eq!(configuration.root_path_cost, 20000);
+        assert_eq!(configuration.message_age, Duration::from_secs(1));
+        assert!(configuration.flags.agreement);
+        assert_eq!(extension.configuration_id.name, "region");
+        assert_eq!(extension.configuration_id.revision, 7);
+        assert_eq!(extension.cist_remaining_hops, 20);
+        assert_eq!(
+            extension.mstis,
+            vec![MstiConfiguration {
+                flags: StpFlags::from(0x7c),
+                regional_root_id: BridgeId {
+                    priority: 24576,
+                    system_id_extension: 5,
+                    mac: MacAddress([0x00, 0x1b, 0x2c, 0x3d, 0x4e, 0x5f]),
+                },
+                internal_root_path_cost: 0,
+                bridge_priority: 24576,
+                port_priority: 128,
+                remaining_hops: 20,
+            }]
+        );
+
+        assert_eq!(
+            parse_bpdu(&[0x00, 0x00, 0x00, 0x01]),
+            Err(Err::Error(Error::new(EMPTY_SLICE, ErrorKind::Switch)))
+        );
+    }
+}
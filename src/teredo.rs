@@ -0,0 +1,205 @@
+//! Handles parsing of Teredo indicators and addresses (IPv6 over UDP over IPv4)
+
+use nom::bytes;
+use nom::number;
+use nom::IResult;
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// Teredo Authentication Indicator (RFC 4380)
+//
+//   +--------+--------+-----------------+-----------------+
+//   |  0x00  | 0x01   | ID-len          | AU-len          |
+//   +--------+--------+-----------------+-----------------+
+//   |  Client identifier (ID-len octets)                  |
+//   +-----------------------------------------------------+
+//   |  Authentication value (AU-len octets)               |
+//   +-----------------------------------------------------+
+//   |  Nonce (8 octets)                                   |
+//   +-----------------------------------------------------+
+//   |  Confirmation byte (1 octet)                        |
+//   +-----------------------------------------------------+
+
+// Teredo Origin Indication (RFC 4380)
+//
+//   +--------+--------+-----------------+
+//   |  0x00  | 0x00   | Origin port #   |
+//   +--------+--------+-----------------+
+//   |  Origin IPv4 address              |
+//   +-----------------------------------+
+
+pub const TEREDO_PORT: u16 = 3544;
+
+const AUTHENTICATION_INDICATOR: u16 = 0x0001;
+const ORIGIN_INDICATION: u16 = 0x0000;
+const TEREDO_PREFIX: u32 = 0x2001_0000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TeredoAuthentication {
+    pub client_id: Vec<u8>,
+    pub auth_value: Vec<u8>,
+    pub nonce: [u8; 8],
+    pub confirmation: u8,
+}
+
+/// Origin indication, with port and address already de-obfuscated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TeredoOrigin {
+    pub port: u16,
+    pub address: Ipv4Addr,
+}
+
+/// The optional indicators that can precede the IPv6 packet in a Teredo UDP payload
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TeredoHeader {
+    pub authentication: Option<TeredoAuthentication>,
+    pub origin: Option<TeredoOrigin>,
+}
+
+/// The fields embedded in a Teredo IPv6 address (2001::/32), with client port and address
+/// already de-obfuscated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TeredoAddress {
+    pub server: Ipv4Addr,
+    pub flags: u16,
+    pub client_port: u16,
+    pub client_address: Ipv4Addr,
+}
+
+impl TeredoAddress {
+    /// Splits a Teredo IPv6 address into its fields, or returns `None` if it is not one
+    pub fn from_ipv6(address: &Ipv6Addr) -> Option<Self> {
+        let raw = u128::from(*address);
+        if (raw >> 96) as u32 != TEREDO_PREFIX {
+            return None;
+        }
+
+        Some(TeredoAddress {
+            server: Ipv4Addr::from((raw >> 64) as u32),
+            flags: (raw >> 48) as u16,
+            client_port: !(raw >> 32) as u16,
+            client_address: Ipv4Addr::from(!(raw as u32)),
+        })
+    }
+}
+
+fn authentication(input: &[u8]) -> IResult<&[u8], TeredoAuthentication> {
+    let (input, id_len) = number::streaming::be_u8(input)?;
+    let (input, au_len) = number::streaming::be_u8(input)?;
+    let (input, client_id) = bytes::streaming::take(id_len)(input)?;
+    let (input, auth_value) = bytes::streaming::take(au_len)(input)?;
+    let (input, nonce) = bytes::streaming::take(8u8)(input)?;
+    let (input, confirmation) = number::streaming::be_u8(input)?;
+
+    Ok((
+        input,
+        TeredoAuthentication {
+            client_id: client_id.to_vec(),
+            auth_value: auth_value.to_vec(),
+            nonce: <[u8; 8]>::try_from(nonce).unwrap(),
+            confirmation,
+        },
+    ))
+}
+
+fn origin(input: &[u8]) -> IResult<&[u8], TeredoOrigin> {
+    let (input, port) = number::streaming::be_u16(input)?;
+    let (input, address) = number::streaming::be_u32(input)?;
+
+    Ok((
+        input,
+        TeredoOrigin {
+            port: !port,
+            address: Ipv4Addr::from(!address),
+        },
+    ))
+}
+
+/// Parses the authentication and origin indicators at the start of a Teredo UDP payload. The
+/// remaining input is the encapsulated IPv6 packet.
+pub fn parse_teredo_header(input: &[u8]) -> IResult<&[u8], TeredoHeader> {
+    let mut header = TeredoHeader::default();
+
+    let (_, indicator) = number::streaming::be_u16(input)?;
+    let input = if indicator == AUTHENTICATION_INDICATOR {
+        let (input, _) = number::streaming::be_u16(input)?;
+        let (input, auth) = authentication(input)?;
+        header.authentication = Some(auth);
+        input
+    } else {
+        input
+    };
+
+    let (_, indicator) = number::streaming::be_u16(input)?;
+    let input = if indicator == ORIGIN_INDICATION {
+        let (input, _) = number::streaming::be_u16(input)?;
+        let (input, orig) = origin(input)?;
+        header.origin = Some(orig);
+        input
+    } else {
+        input
+    };
+
+    Ok((input, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teredo_header_with_indicators_works() {
+        let bytes = [
+            0x00, 0x01, 0x00, 0x00, /* authentication indicator, ID-len, AU-len */
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, /* nonce */
+            0x00, /* confirmation */
+            0x00, 0x00, /* origin indication */
+            0xf2, 0x2d, /* obfuscated port */
+            0x3f, 0xf5, 0xfe, 0xfd, /* obfuscated address */
+            0x60, 0x00, /* start of IPv6 header */
+        ];
+        let expectation = TeredoHeader {
+            authentication: Some(TeredoAuthentication {
+                client_id: vec![],
+                auth_value: vec![],
+                nonce: [1, 2, 3, 4, 5, 6, 7, 8],
+                confirmation: 0,
+            }),
+            origin: Some(TeredoOrigin {
+                port: 3538,
+                address: Ipv4Addr::new(192, 10, 1, 2),
+            }),
+        };
+        assert_eq!(
+            parse_teredo_header(&bytes),
+            Ok((&[0x60, 0x00][..], expectation))
+        );
+    }
+
+    #[test]
+    fn teredo_header_without_indicators_works() {
+        let bytes = [0x60, 0x00, 0x00, 0x00];
+        assert_eq!(
+            parse_teredo_header(&bytes),
+            Ok((&bytes[..], TeredoHeader::default()))
+        );
+    }
+
+    #[test]
+    fn teredo_address_works() {
+        // Example from RFC 4380 section 4
+        let address = "2001:0:4136:e378:8000:63bf:3fff:fdd2".parse().unwrap();
+        let expectation = TeredoAddress {
+            server: Ipv4Addr::new(65, 54, 227, 120),
+            flags: 0x8000,
+            client_port: 40000,
+            client_address: Ipv4Addr::new(192, 0, 2, 45),
+        };
+        assert_eq!(TeredoAddress::from_ipv6(&address), Some(expectation));
+        assert_eq!(TeredoAddress::from_ipv6(&"2002::1".parse().unwrap()), None);
+    }
+}
@@ -0,0 +1,718 @@
+//! Handles parsing of the TLS record layer and of handshake messages
+
+use nom::bytes;
+use nom::combinator;
+use nom::error::{Error, ErrorKind};
+use nom::number;
+use nom::{Err, IResult, Needed};
+use std::convert::TryFrom;
+
+// TLS Record Layer (RFC 8446)
+//
+//   +--------------+---------------------+---------------------+
+//   | Content Type |   Legacy Version    |       Length        |
+//   |     (1)      |         (2)         |         (2)         |
+//   +--------------+---------------------+---------------------+
+//   |                   Fragment (Length bytes)                |
+//   +----------------------------------------------------------+
+
+// TLS Handshake Message (RFC 8446)
+//
+//   +--------------+------------------------------+
+//   | Msg Type (1) |          Length (3)          |
+//   +--------------+------------------------------+
+//   |             Body (Length bytes)             |
+//   +---------------------------------------------+
+
+pub const TLS_PORT: u16 = 443;
+
+/// 2^14 bytes of plaintext plus the largest expansion allowed for protected records
+const MAX_RECORD_LENGTH: u16 = (1 << 14) + 2048;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_KEY_SHARE: u16 = 51;
+
+const SERVER_NAME_HOST_NAME: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsContentType {
+    ChangeCipherSpec,
+    Alert,
+    Handshake,
+    ApplicationData,
+    Heartbeat,
+    Other(u8),
+}
+
+impl From<u8> for TlsContentType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            20 => Self::ChangeCipherSpec,
+            21 => Self::Alert,
+            22 => Self::Handshake,
+            23 => Self::ApplicationData,
+            24 => Self::Heartbeat,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsVersion {
+    Ssl30,
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+    Other(u16),
+}
+
+impl From<u16> for TlsVersion {
+    fn from(raw: u16) -> Self {
+        match raw {
+            0x0300 => Self::Ssl30,
+            0x0301 => Self::Tls10,
+            0x0302 => Self::Tls11,
+            0x0303 => Self::Tls12,
+            0x0304 => Self::Tls13,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<TlsVersion> for u16 {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Ssl30 => 0x0300,
+            TlsVersion::Tls10 => 0x0301,
+            TlsVersion::Tls11 => 0x0302,
+            TlsVersion::Tls12 => 0x0303,
+            TlsVersion::Tls13 => 0x0304,
+            TlsVersion::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsHandshakeType {
+    HelloRequest,
+    ClientHello,
+    ServerHello,
+    NewSessionTicket,
+    EndOfEarlyData,
+    EncryptedExtensions,
+    Certificate,
+    ServerKeyExchange,
+    CertificateRequest,
+    ServerHelloDone,
+    CertificateVerify,
+    ClientKeyExchange,
+    Finished,
+    KeyUpdate,
+    Other(u8),
+}
+
+impl From<u8> for TlsHandshakeType {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0 => Self::HelloRequest,
+            1 => Self::ClientHello,
+            2 => Self::ServerHello,
+            4 => Self::NewSessionTicket,
+            5 => Self::EndOfEarlyData,
+            8 => Self::EncryptedExtensions,
+            11 => Self::Certificate,
+            12 => Self::ServerKeyExchange,
+            13 => Self::CertificateRequest,
+            14 => Self::ServerHelloDone,
+            15 => Self::CertificateVerify,
+            16 => Self::ClientKeyExchange,
+            20 => Self::Finished,
+            24 => Self::KeyUpdate,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsRecordHeader {
+    pub content_type: TlsContentType,
+    pub version: TlsVersion,
+    pub length: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyShareEntry {
+    pub group: u16,
+    pub key_exchange: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsExtension {
+    /// Host names from the server_name extension, as sent; empty in a ServerHello
+    ServerName(Vec<Vec<u8>>),
+    SupportedGroups(Vec<u16>),
+    EcPointFormats(Vec<u8>),
+    SignatureAlgorithms(Vec<u16>),
+    /// Protocol IDs, as sent
+    Alpn(Vec<Vec<u8>>),
+    /// The client's list, or the single version selected by the server
+    SupportedVersions(Vec<TlsVersion>),
+    /// The client's shares, or the single share (or group, for a HelloRetryRequest) selected by
+    /// the server
+    KeyShare(Vec<KeyShareEntry>),
+    Unknown {
+        extension_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl TlsExtension {
+    /// The extension type as it appears on the wire
+    pub fn extension_type(&self) -> u16 {
+        match self {
+            Self::ServerName(_) => EXT_SERVER_NAME,
+            Self::SupportedGroups(_) => EXT_SUPPORTED_GROUPS,
+            Self::EcPointFormats(_) => EXT_EC_POINT_FORMATS,
+            Self::SignatureAlgorithms(_) => EXT_SIGNATURE_ALGORITHMS,
+            Self::Alpn(_) => EXT_ALPN,
+            Self::SupportedVersions(_) => EXT_SUPPORTED_VERSIONS,
+            Self::KeyShare(_) => EXT_KEY_SHARE,
+            Self::Unknown { extension_type, .. } => *extension_type,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientHello {
+    pub version: TlsVersion,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suites: Vec<u16>,
+    pub compression_methods: Vec<u8>,
+    pub extensions: Vec<TlsExtension>,
+}
+
+impl ClientHello {
+    /// The first host name of the server_name extension
+    pub fn server_name(&self) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                TlsExtension::ServerName(names) => names.first().map(Vec::as_slice),
+                _ => None,
+            })
+    }
+
+    /// The protocols offered in the ALPN extension
+    pub fn alpn(&self) -> &[Vec<u8>] {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                TlsExtension::Alpn(protocols) => Some(&protocols[..]),
+                _ => None,
+            })
+            .unwrap_or(&[])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerHello {
+    pub version: TlsVersion,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suite: u16,
+    pub compression_method: u8,
+    pub extensions: Vec<TlsExtension>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsHandshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+    /// DER-encoded certificates, end-entity first. Only the plaintext (TLS 1.2 and earlier)
+    /// layout is decoded, since TLS 1.3 certificates are always encrypted.
+    Certificate(Vec<Vec<u8>>),
+    Other {
+        handshake_type: TlsHandshakeType,
+        body: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TlsMessage {
+    Handshake(TlsHandshake),
+    ChangeCipherSpec,
+    Alert {
+        level: u8,
+        description: u8,
+    },
+    /// Application data, or any record sent after the ChangeCipherSpec, which is encrypted
+    Opaque {
+        content_type: TlsContentType,
+        length: u16,
+    },
+}
+
+/// The records found in a reassembled stream, and the messages they carry once handshake
+/// messages are joined across records
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlsPacket {
+    pub records: Vec<TlsRecordHeader>,
+    pub messages: Vec<TlsMessage>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HelloKind {
+    Client,
+    Server,
+}
+
+fn u16_list(mut input: &[u8]) -> IResult<&[u8], Vec<u16>> {
+    let mut list = vec![];
+    while !input.is_empty() {
+        let (rest, value) = number::streaming::be_u16(input)?;
+        list.push(value);
+        input = rest;
+    }
+
+    Ok((input, list))
+}
+
+fn length_u8_data(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = number::streaming::be_u8(input)?;
+    bytes::streaming::take(length)(input)
+}
+
+fn length_u16_data(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (input, length) = number::streaming::be_u16(input)?;
+    bytes::streaming::take(length)(input)
+}
+
+fn server_names(input: &[u8]) -> IResult<&[u8], Vec<Vec<u8>>> {
+    if input.is_empty() {
+        return Ok((input, vec![]));
+    }
+
+    let (rest, mut list) = length_u16_data(input)?;
+    let mut names = vec![];
+    while !list.is_empty() {
+        let (r, name_type) = number::streaming::be_u8(list)?;
+        let (r, name) = length_u16_data(r)?;
+        if name_type == SERVER_NAME_HOST_NAME {
+            names.push(name.to_vec());
+        }
+        list = r;
+    }
+
+    Ok((rest, names))
+}
+
+fn alpn(input: &[u8]) -> IResult<&[u8], Vec<Vec<u8>>> {
+    let (rest, mut list) = length_u16_data(input)?;
+    let mut protocols = vec![];
+    while !list.is_empty() {
+        let (r, protocol) = length_u8_data(list)?;
+        protocols.push(protocol.to_vec());
+        list = r;
+    }
+
+    Ok((rest, protocols))
+}
+
+fn key_share_entry(input: &[u8]) -> IResult<&[u8], KeyShareEntry> {
+    let (input, group) = number::streaming::be_u16(input)?;
+    let (input, key_exchange) = length_u16_data(input)?;
+
+    Ok((
+        input,
+        KeyShareEntry {
+            group,
+            key_exchange: key_exchange.to_vec(),
+        },
+    ))
+}
+
+fn extension(input: &[u8], kind: HelloKind) -> IResult<&[u8], TlsExtension> {
+    let (input, extension_type) = number::streaming::be_u16(input)?;
+    let (input, data) = length_u16_data(input)?;
+
+    let extension = match (extension_type, kind) {
+        (EXT_SERVER_NAME, _) => TlsExtension::ServerName(server_names(data)?.1),
+        (EXT_SUPPORTED_GROUPS, _) => {
+            TlsExtension::SupportedGroups(u16_list(length_u16_data(data)?.1)?.1)
+        }
+        (EXT_EC_POINT_FORMATS, _) => TlsExtension::EcPointFormats(length_u8_data(data)?.1.to_vec()),
+        (EXT_SIGNATURE_ALGORITHMS, _) => {
+            TlsExtension::SignatureAlgorithms(u16_list(length_u16_data(data)?.1)?.1)
+        }
+        (EXT_ALPN, _) => TlsExtension::Alpn(alpn(data)?.1),
+        (EXT_SUPPORTED_VERSIONS, HelloKind::Client) => {
+            let (_, versions) = u16_list(length_u8_data(data)?.1)?;
+            TlsExtension::SupportedVersions(versions.into_iter().map(TlsVersion::from).collect())
+        }
+        (EXT_SUPPORTED_VERSIONS, HelloKind::Server) => {
+            let (_, version) = number::streaming::be_u16(data)?;
+            TlsExtension::SupportedVersions(vec![version.into()])
+        }
+        (EXT_KEY_SHARE, HelloKind::Client) => {
+            let (_, mut list) = length_u16_data(data)?;
+            let mut entries = vec![];
+            while !list.is_empty() {
+                let (r, entry) = key_share_entry(list)?;
+                entries.push(entry);
+                list = r;
+            }
+            TlsExtension::KeyShare(entries)
+        }
+        (EXT_KEY_SHARE, HelloKind::Server) if data.len() == 2 => {
+            let (_, group) = number::streaming::be_u16(data)?;
+            TlsExtension::KeyShare(vec![KeyShareEntry {
+                group,
+                key_exchange: vec![],
+            }])
+        }
+        (EXT_KEY_SHARE, HelloKind::Server) => {
+            TlsExtension::KeyShare(vec![key_share_entry(data)?.1])
+        }
+        _ => TlsExtension::Unknown {
+            extension_type,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok((input, extension))
+}
+
+/// Parses the optional extensions block ending a hello message
+fn extensions(input: &[u8], kind: HelloKind) -> IResult<&[u8], Vec<TlsExtension>> {
+    if input.is_empty() {
+        return Ok((input, vec![]));
+    }
+
+    let (input, mut list) = length_u16_data(input)?;
+    let mut extensions = vec![];
+    while !list.is_empty() {
+        let (r, ext) = extension(list, kind)?;
+        extensions.push(ext);
+        list = r;
+    }
+
+    Ok((input, extensions))
+}
+
+fn random(input: &[u8]) -> IResult<&[u8], [u8; 32]> {
+    let (input, random) = bytes::streaming::take(32u8)(input)?;
+
+    Ok((input, <[u8; 32]>::try_from(random).unwrap()))
+}
+
+fn client_hello(input: &[u8]) -> IResult<&[u8], ClientHello> {
+    let (input, version) = number::streaming::be_u16(input)?;
+    let (input, random) = random(input)?;
+    let (input, session_id) = length_u8_data(input)?;
+    let (input, cipher_suites) = length_u16_data(input)?;
+    let (_, cipher_suites) = u16_list(cipher_suites)?;
+    let (input, compression_methods) = length_u8_data(input)?;
+    let (input, extensions) = extensions(input, HelloKind::Client)?;
+
+    Ok((
+        input,
+        ClientHello {
+            version: version.into(),
+            random,
+            session_id: session_id.to_vec(),
+            cipher_suites,
+            compression_methods: compression_methods.to_vec(),
+            extensions,
+        },
+    ))
+}
+
+fn server_hello(input: &[u8]) -> IResult<&[u8], ServerHello> {
+    let (input, version) = number::streaming::be_u16(input)?;
+    let (input, random) = random(input)?;
+    let (input, session_id) = length_u8_data(input)?;
+    let (input, cipher_suite) = number::streaming::be_u16(input)?;
+    let (input, compression_method) = number::streaming::be_u8(input)?;
+    let (input, extensions) = extensions(input, HelloKind::Server)?;
+
+    Ok((
+        input,
+        ServerHello {
+            version: version.into(),
+            random,
+            session_id: session_id.to_vec(),
+            cipher_suite,
+            compression_method,
+            extensions,
+        },
+    ))
+}
+
+fn certificates(input: &[u8]) -> IResult<&[u8], Vec<Vec<u8>>> {
+    let (input, length) = number::streaming::be_u24(input)?;
+    let (input, mut list) = bytes::streaming::take(length)(input)?;
+    let mut certificates = vec![];
+    while !list.is_empty() {
+        let (r, length) = number::streaming::be_u24(list)?;
+        let (r, certificate) = bytes::streaming::take(length)(r)?;
+        certificates.push(certificate.to_vec());
+        list = r;
+    }
+
+    Ok((input, certificates))
+}
+
+/// Parses one handshake message, without the record layer around it. This is the form in which
+/// QUIC carries the handshake in its CRYPTO frames.
+pub fn parse_tls_handshake(input: &[u8]) -> IResult<&[u8], TlsHandshake> {
+    let (input, handshake_type) = number::streaming::be_u8(input)?;
+    let (input, length) = number::streaming::be_u24(input)?;
+    let (input, body) = bytes::streaming::take(length)(input)?;
+
+    // The body is whole once taken, so running short inside it means it is malformed
+    let handshake = match TlsHandshakeType::from(handshake_type) {
+        TlsHandshakeType::ClientHello => {
+            TlsHandshake::ClientHello(combinator::complete(client_hello)(body)?.1)
+        }
+        TlsHandshakeType::ServerHello => {
+            TlsHandshake::ServerHello(combinator::complete(server_hello)(body)?.1)
+        }
+        TlsHandshakeType::Certificate => {
+            TlsHandshake::Certificate(combinator::complete(certificates)(body)?.1)
+        }
+        handshake_type => TlsHandshake::Other {
+            handshake_type,
+            body: body.to_vec(),
+        },
+    };
+
+    Ok((input, handshake))
+}
+
+pub fn parse_tls_record_header(input: &[u8]) -> IResult<&[u8], TlsRecordHeader> {
+    let (rest, content_type) = number::streaming::be_u8(input)?;
+    let content_type = TlsContentType::from(content_type);
+    if let TlsContentType::Other(_) = content_type {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (rest, version) = number::streaming::be_u16(rest)?;
+    if version >> 8 != 3 {
+        return Err(Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    let (rest, length) = number::streaming::be_u16(rest)?;
+    if length > MAX_RECORD_LENGTH {
+        return Err(Err::Error(Error::new(input, ErrorKind::LengthValue)));
+    }
+
+    Ok((
+        rest,
+        TlsRecordHeader {
+            content_type,
+            version: version.into(),
+            length,
+        },
+    ))
+}
+
+/// Parses every record in `input`, which must be reassembled from the TCP stream and start on a
+/// record boundary. Handshake messages may span several records. Returns `Incomplete` when the
+/// last record, or the last handshake message, is cut short; a handshake message whose body is
+/// malformed is an error even when it fits in the records.
+pub fn parse_tls_packet(input: &[u8]) -> IResult<&[u8], TlsPacket> {
+    let mut input = input;
+    let mut records = vec![];
+    let mut messages = vec![];
+    let mut handshake = vec![];
+    let mut encrypted = false;
+
+    while !input.is_empty() {
+        let (rest, header) = parse_tls_record_header(input)?;
+        let (rest, fragment) = bytes::streaming::take(header.length)(rest)?;
+
+        match header.content_type {
+            TlsContentType::Handshake if !encrypted => {
+                handshake.extend_from_slice(fragment);
+                let mut consumed = 0;
+                while let Ok((r, message)) = parse_tls_handshake(&handshake[consumed..]) {
+                    consumed = handshake.len() - r.len();
+                    messages.push(TlsMessage::Handshake(message));
+                }
+                // Distinguish a message still awaiting records from a malformed one
+                if let Err(e @ Err::Error(_)) | Err(e @ Err::Failure(_)) =
+                    parse_tls_handshake(&handshake[consumed..])
+                {
+                    return Err(e.map_input(|_| input));
+                }
+                handshake.drain(..consumed);
+            }
+            TlsContentType::ChangeCipherSpec => {
+                encrypted = true;
+                messages.push(TlsMessage::ChangeCipherSpec);
+            }
+            TlsContentType::Alert if !encrypted && fragment.len() == 2 => {
+                messages.push(TlsMessage::Alert {
+                    level: fragment[0],
+                    description: fragment[1],
+                });
+            }
+            content_type => messages.push(TlsMessage::Opaque {
+                content_type,
+                length: header.length,
+            }),
+        }
+
+        records.push(header);
+        input = rest;
+    }
+
+    if !handshake.is_empty() {
+        return Err(Err::Incomplete(Needed::Unknown));
+    }
+
+    Ok((input, TlsPacket { records, messages }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    fn record(content_type: u8, fragment: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![content_type, 0x03, 0x01];
+        bytes.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(fragment);
+        bytes
+    }
+
+    fn client_hello_bytes() -> Vec<u8> {
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x5d, 0x03, 0x03]; // type, length, version
+        bytes.extend_from_slice(&[0xab; 32]); // random
+        bytes.extend_from_slice(&[
+            0x00, /* session ID */
+            0x00, 0x04, 0x13, 0x01, 0xc0, 0x2f, /* cipher suites */
+            0x01, 0x00, /* compression methods */
+            0x00, 0x30, /* extensions length */
+            0x00, 0x00, 0x00, 0x10, 0x00, 0x0e, 0x00, 0x00, 0x0b, /* SNI */
+            b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm', 0x00, 0x10, 0x00,
+            0x05, 0x00, 0x03, 0x02, b'h', b'2', /* ALPN */
+            0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04, /* supported versions */
+            0x00, 0x33, 0x00, 0x08, 0x00, 0x06, 0x00, 0x1d, 0x00, 0x02, 0xaa,
+            0xbb, /* key share */
+        ]);
+        bytes
+    }
+
+    #[test]
+    fn client_hello_across_records_works() {
+        let hello = client_hello_bytes();
+        let mut bytes = record(22, &hello[..40]);
+        bytes.extend(record(22, &hello[40..]));
+
+        let (remaining, packet) = parse_tls_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.records.len(), 2);
+        assert_eq!(packet.records[1].length, 57);
+        let expectation = TlsHandshake::ClientHello(ClientHello {
+            version: TlsVersion::Tls12,
+            random: [0xab; 32],
+            session_id: vec![],
+            cipher_suites: vec![0x1301, 0xc02f],
+            compression_methods: vec![0],
+            extensions: vec![
+                TlsExtension::ServerName(vec![b"example.com".to_vec()]),
+                TlsExtension::Alpn(vec![b"h2".to_vec()]),
+                TlsExtension::SupportedVersions(vec![TlsVersion::Tls13]),
+                TlsExtension::KeyShare(vec![KeyShareEntry {
+                    group: 0x001d,
+                    key_exchange: vec![0xaa, 0xbb],
+                }]),
+            ],
+        });
+        assert_eq!(packet.messages, vec![TlsMessage::Handshake(expectation)]);
+
+        let first_record = record(22, &hello[..40]);
+        assert_eq!(
+            parse_tls_packet(&first_record),
+            Err(Err::Incomplete(Needed::Unknown))
+        );
+    }
+
+    #[test]
+    fn truncated_client_hello_body_fails() {
+        // The handshake length covers the bytes present, but the extensions run past them
+        let mut hello = client_hello_bytes();
+        hello.truncate(60);
+        hello[1..4].copy_from_slice(&[0x00, 0x00, 0x38]);
+        let bytes = record(22, &hello);
+
+        assert!(matches!(
+            parse_tls_packet(&bytes),
+            Err(Err::Error(Error {
+                code: ErrorKind::Complete,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn server_flight_works() {
+        let mut server_hello = vec![0x02, 0x00, 0x00, 0x2e, 0x03, 0x03];
+        server_hello.extend_from_slice(&[0x01; 32]);
+        server_hello.extend_from_slice(&[
+            0x00, 0xc0, 0x2f, 0x00, /* session ID, cipher suite, compression */
+            0x00, 0x06, 0xff, 0x01, 0x00, 0x02, 0x00, 0x00, /* renegotiation_info */
+        ]);
+        let certificate = [
+            0x0b, 0x00, 0x00, 0x0b, /* type, length */
+            0x00, 0x00, 0x08, /* certificates length */
+            0x00, 0x00, 0x05, 0x30, 0x03, 0x02, 0x01, 0x00, /* DER blob */
+        ];
+        let mut bytes = record(22, &server_hello);
+        bytes.extend(record(22, &certificate));
+        bytes.extend(record(20, &[0x01]));
+        bytes.extend(record(22, &[0x00; 40]));
+
+        let (_, packet) = parse_tls_packet(&bytes).unwrap();
+        assert_eq!(packet.messages.len(), 4);
+        match &packet.messages[0] {
+            TlsMessage::Handshake(TlsHandshake::ServerHello(hello)) => {
+                assert_eq!(hello.cipher_suite, 0xc02f);
+                assert_eq!(hello.extensions[0].extension_type(), 0xff01);
+            }
+            _ => panic!(),
+        }
+        assert_eq!(
+            packet.messages[1],
+            TlsMessage::Handshake(TlsHandshake::Certificate(vec![vec![
+                0x30, 0x03, 0x02, 0x01, 0x00
+            ]]))
+        );
+        assert_eq!(packet.messages[2], TlsMessage::ChangeCipherSpec);
+        assert_eq!(
+            packet.messages[3],
+            TlsMessage::Opaque {
+                content_type: TlsContentType::Handshake,
+                length: 40,
+            }
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Handles recursive decapsulation of IP tunnels (IP-in-IP, 6in4, 6to4, 4in6, 6in6 and Teredo)
+
+use crate::ip::{self, IPHeader, IPProtocol};
+use crate::ipv4;
+use crate::ipv6::{self, IPv6Header};
+use crate::teredo::{self, TeredoHeader};
+use crate::udp::{self, UdpHeader, UdpTunnel, UdpTunnelPorts};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::{Err, IResult};
+
+/// A nesting depth that comfortably covers legitimate deployments
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+const PREFIX_6TO4: u16 = 0x2002;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TunnelKind {
+    /// IPv4 in IPv4 (protocol 4)
+    IPinIP,
+    /// IPv6 in IPv4 (protocol 41)
+    SixInFour,
+    /// IPv6 in IPv4 where an IPv6 endpoint is a 6to4 address (2002::/16)
+    SixToFour,
+    /// IPv4 in IPv6 (next header 4)
+    FourInSix,
+    /// IPv6 in IPv6 (next header 41)
+    SixInSix,
+    /// IPv6 in UDP in IPv4
+    Teredo,
+}
+
+/// One level of encapsulation: the outer header and the header it carries
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TunnelLayer {
+    pub kind: TunnelKind,
+    pub outer: IPHeader,
+    pub udp: Option<UdpHeader>,
+    pub teredo: Option<TeredoHeader>,
+    pub inner: IPHeader,
+}
+
+fn is_6to4(header: &IPv6Header) -> bool {
+    header.source_addr.segments()[0] == PREFIX_6TO4 || header.dest_addr.segments()[0] == PREFIX_6TO4
+}
+
+/// Skips the IPv4 options following a header, so that the input starts at the IP payload
+fn skip_options<'a>(input: &'a [u8], header: &IPHeader) -> IResult<&'a [u8], ()> {
+    match header {
+        IPHeader::V4(header) if header.ihl > 5 => {
+            let (input, _) = bytes::streaming::take(usize::from(header.ihl - 5) * 4)(input)?;
+            Ok((input, ()))
+        }
+        IPHeader::V4(header) if header.ihl < 5 => {
+            Err(Err::Error(Error::new(input, ErrorKind::Verify)))
+        }
+        _ => Ok((input, ())),
+    }
+}
+
+fn layer(kind: TunnelKind, outer: &IPHeader, inner: IPHeader) -> TunnelLayer {
+    TunnelLayer {
+        kind,
+        outer: *outer,
+        udp: None,
+        teredo: None,
+        inner,
+    }
+}
+
+fn next_layer<'a>(
+    input: &'a [u8],
+    outer: &IPHeader,
+    ports: &UdpTunnelPorts,
+) -> IResult<&'a [u8], Option<TunnelLayer>> {
+    // Only the first fragment starts with the encapsulated header
+    if let IPHeader::V4(header) = outer {
+        if header.fragment_offset != 0 {
+            return Ok((input, None));
+        }
+    }
+
+    match (outer, outer.protocol()) {
+        (IPHeader::V4(_), IPProtocol::IPINIP) => {
+            let (input, inner) = ipv4::parse_ipv4_header(input)?;
+            let inner = IPHeader::V4(inner);
+            Ok((input, Some(layer(TunnelKind::IPinIP, outer, inner))))
+        }
+        (IPHeader::V4(_), IPProtocol::IPV6) => {
+            let (input, inner) = ipv6::parse_ipv6_header(input)?;
+            let kind = if is_6to4(&inner) {
+                TunnelKind::SixToFour
+            } else {
+                TunnelKind::SixInFour
+            };
+            Ok((input, Some(layer(kind, outer, IPHeader::V6(inner)))))
+        }
+        (IPHeader::V6(_), IPProtocol::IPINIP) => {
+            let (input, inner) = ipv4::parse_ipv4_header(input)?;
+            let inner = IPHeader::V4(inner);
+            Ok((input, Some(layer(TunnelKind::FourInSix, outer, inner))))
+        }
+        (IPHeader::V6(_), IPProtocol::IPV6) => {
+            let (input, inner) = ipv6::parse_ipv6_header(input)?;
+            let inner = IPHeader::V6(inner);
+            Ok((input, Some(layer(TunnelKind::SixInSix, outer, inner))))
+        }
+        (IPHeader::V4(_), IPProtocol::UDP) => match teredo_layer(input, outer, ports) {
+            Some((rest, layer)) => Ok((rest, Some(layer))),
+            None => Ok((input, None)),
+        },
+        _ => Ok((input, None)),
+    }
+}
+
+/// Decapsulates a Teredo packet. Any UDP packet can use the Teredo port, so a payload that is
+/// truncated or does not carry IPv6 ends the decapsulation instead of failing it.
+fn teredo_layer<'a>(
+    input: &'a [u8],
+    outer: &IPHeader,
+    ports: &UdpTunnelPorts,
+) -> Option<(&'a [u8], TunnelLayer)> {
+    let (rest, udp_header) = udp::parse_udp_header(input).ok()?;
+    if ports.lookup(&udp_header) != Some(UdpTunnel::Teredo) {
+        return None;
+    }
+    let (rest, teredo_header) = teredo::parse_teredo_header(rest).ok()?;
+    let (rest, inner) = ipv6::parse_ipv6_header(rest).ok()?;
+    if inner.version != 6 {
+        return None;
+    }
+    let mut layer = layer(TunnelKind::Teredo, outer, IPHeader::V6(inner));
+    layer.udp = Some(udp_header);
+    layer.teredo = Some(teredo_header);
+    Some((rest, layer))
+}
+
+/// Parses an IP header and follows every tunnel encapsulation below it, returning one layer per
+/// level of nesting, outermost first. The remaining input is the payload of the innermost IP
+/// header. When the packet is not tunnelled, an empty list is returned and no input is consumed.
+///
+/// Teredo is recognised on the ports registered in `ports`. Nesting deeper than `max_depth`
+/// levels fails with `ErrorKind::TooLarge`.
+pub fn parse_ip_tunnels<'a>(
+    input: &'a [u8],
+    ports: &UdpTunnelPorts,
+    max_depth: usize,
+) -> IResult<&'a [u8], Vec<TunnelLayer>> {
+    let (rest, mut outer) = ip::parse_ip_header(input)?;
+    let (mut rest, _) = skip_options(rest, &outer)?;
+    let mut layers = vec![];
+
+    while let (next, Some(layer)) = next_layer(rest, &outer, ports)? {
+        if layers.len() == max_depth {
+            return Err(Err::Failure(Error::new(rest, ErrorKind::TooLarge)));
+        }
+        let (next, _) = skip_options(next, &layer.inner)?;
+        outer = layer.inner;
+        rest = next;
+        layers.push(layer);
+    }
+
+    if layers.is_empty() {
+        Ok((input, layers))
+    } else {
+        Ok((rest, layers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipv4::IPv4Header;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn ipv4_header(protocol: u8) -> Vec<u8> {
+        vec![
+            0x45, 0x00, 0x00, 0x50, /* version, IHL, TOS, length */
+            0x00, 0x01, 0x00, 0x00, /* id, flags, fragment offset */
+            0x40, protocol, 0x00, 0x00, /* TTL, protocol, checksum */
+            0x0a, 0x00, 0x00, 0x01, /* source IP */
+            0x0a, 0x00, 0x00, 0x02, /* destination IP */
+        ]
+    }
+
+    fn ipv6_header(next_header: u8, prefix: u8) -> Vec<u8> {
+        let mut bytes = vec![0x60, 0x00, 0x00, 0x00]; // version, traffic class, flow label
+        bytes.extend_from_slice(&[0x00, 0x28, next_header, 0x40]); // length, next header, hop limit
+        bytes.extend_from_slice(&[0x20, prefix, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        bytes.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        bytes
+    }
+
+    #[test]
+    fn six_to_four_then_four_in_six_works() {
+        let mut bytes = ipv4_header(41);
+        bytes.extend(ipv6_header(4, 0x02));
+        bytes.extend(ipv4_header(6));
+        bytes.extend_from_slice(&[0xaa, 0xbb]);
+
+        let (remaining, layers) =
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(remaining, &[0xaa, 0xbb]);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].kind, TunnelKind::SixToFour);
+        assert_eq!(layers[1].kind, TunnelKind::FourInSix);
+        assert_eq!(layers[0].inner, layers[1].outer);
+        match layers[1].inner {
+            IPHeader::V4(IPv4Header { protocol, .. }) => assert_eq!(protocol, IPProtocol::TCP),
+            _ => panic!(),
+        }
+        match layers[1].outer {
+            IPHeader::V6(header) => assert_eq!(
+                header.source_addr,
+                Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 1)
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn teredo_works() {
+        let mut bytes = ipv4_header(17);
+        bytes.extend_from_slice(&[
+            0x0d, 0xd8, 0xc0, 0x00, /* source & destination ports */
+            0x00, 0x30, 0x00, 0x00, /* length & checksum */
+        ]);
+        bytes.extend(ipv6_header(59, 0x01));
+
+        let (remaining, layers) =
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), DEFAULT_MAX_DEPTH).unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].kind, TunnelKind::Teredo);
+        assert_eq!(layers[0].udp.unwrap().source_port, 3544);
+        assert_eq!(layers[0].teredo, Some(TeredoHeader::default()));
+        match layers[0].outer {
+            IPHeader::V4(header) => assert_eq!(header.source_addr, Ipv4Addr::new(10, 0, 0, 1)),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn plain_udp_on_teredo_port_is_not_a_tunnel() {
+        let mut bytes = ipv4_header(17);
+        bytes.extend_from_slice(&[
+            0xc0, 0x00, 0x0d, 0xd8, /* source & destination ports */
+            0x00, 0x0c, 0x00, 0x00, /* length & checksum */
+            0xde, 0xad, 0xbe, 0xef, /* not IPv6 */
+        ]);
+        assert_eq!(
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), DEFAULT_MAX_DEPTH),
+            Ok((&bytes[..], vec![]))
+        );
+
+        // Truncated UDP to the Teredo port inside IP-in-IP keeps the IP-in-IP layer
+        let mut bytes = ipv4_header(4);
+        bytes.extend(ipv4_header(17));
+        bytes.extend_from_slice(&[0xc0, 0x00, 0x0d, 0xd8]);
+        let (remaining, layers) =
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(remaining, &[0xc0, 0x00, 0x0d, 0xd8]);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].kind, TunnelKind::IPinIP);
+        assert_eq!(layers[0].teredo, None);
+    }
+
+    #[test]
+    fn untunnelled_packet_consumes_nothing() {
+        let bytes = ipv4_header(6);
+        assert_eq!(
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), DEFAULT_MAX_DEPTH),
+            Ok((&bytes[..], vec![]))
+        );
+    }
+
+    #[test]
+    fn nesting_beyond_max_depth_fails() {
+        let mut bytes = vec![];
+        for _ in 0..4 {
+            bytes.extend(ipv4_header(4));
+        }
+        bytes.extend(ipv4_header(6));
+
+        assert!(parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), 4).is_ok());
+        assert!(matches!(
+            parse_ip_tunnels(&bytes, &UdpTunnelPorts::default(), 3),
+            Err(Err::Failure(Error {
+                code: ErrorKind::TooLarge,
+                ..
+            }))
+        ));
+    }
+}
@@ -1,7 +1,14 @@
 //! Handles parsing of UDP header
 
+use crate::geneve::{self, GenevePacket};
+use crate::gtp::{self, GtpuPacket};
+use crate::ipsec::{self, NatTPacket};
+use crate::ipv6::{self, IPv6Header};
+use crate::teredo::{self, TeredoHeader};
+use crate::vxlan::{self, VxlanPacket};
 use nom::number;
 use nom::IResult;
+use std::collections::HashMap;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -29,9 +36,104 @@ pub fn parse_udp_header(input: &[u8]) -> IResult<&[u8], UdpHeader> {
     ))
 }
 
+/// Encapsulations carried in UDP payloads that are recognised by port number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UdpTunnel {
+    Vxlan,
+    VxlanGpe,
+    Geneve,
+    Teredo,
+    GtpU,
+    NatT,
+}
+
+/// Maps UDP ports to the encapsulation they carry. The default map holds the IANA-assigned
+/// ports; deployments using other ports can register them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UdpTunnelPorts {
+    ports: HashMap<u16, UdpTunnel>,
+}
+
+impl Default for UdpTunnelPorts {
+    fn default() -> Self {
+        let mut ports = HashMap::new();
+        ports.insert(vxlan::VXLAN_PORT, UdpTunnel::Vxlan);
+        ports.insert(vxlan::VXLAN_GPE_PORT, UdpTunnel::VxlanGpe);
+        ports.insert(geneve::GENEVE_PORT, UdpTunnel::Geneve);
+        ports.insert(teredo::TEREDO_PORT, UdpTunnel::Teredo);
+        ports.insert(gtp::GTPU_PORT, UdpTunnel::GtpU);
+        ports.insert(ipsec::NAT_T_PORT, UdpTunnel::NatT);
+        Self { ports }
+    }
+}
+
+impl UdpTunnelPorts {
+    /// Registers `port` as carrying `tunnel`, replacing any previous registration
+    pub fn register(&mut self, port: u16, tunnel: UdpTunnel) {
+        self.ports.insert(port, tunnel);
+    }
+
+    /// Returns the encapsulation registered for the destination port, or else the source port
+    pub fn lookup(&self, header: &UdpHeader) -> Option<UdpTunnel> {
+        self.ports
+            .get(&header.dest_port)
+            .or_else(|| self.ports.get(&header.source_port))
+            .copied()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UdpTunnelPacket {
+    Vxlan(VxlanPacket),
+    Geneve(GenevePacket),
+    Teredo(TeredoHeader, IPv6Header),
+    GtpU(GtpuPacket),
+    NatT(NatTPacket),
+}
+
+/// Decapsulates the payload following `header` if its ports are registered in `ports`. Returns
+/// `None` without consuming any input when the ports carry no known encapsulation.
+pub fn parse_udp_tunnel<'a>(
+    input: &'a [u8],
+    header: &UdpHeader,
+    ports: &UdpTunnelPorts,
+) -> IResult<&'a [u8], Option<UdpTunnelPacket>> {
+    match ports.lookup(header) {
+        Some(UdpTunnel::Vxlan) | Some(UdpTunnel::VxlanGpe) => {
+            let (input, packet) = vxlan::parse_vxlan_packet(input)?;
+            Ok((input, Some(UdpTunnelPacket::Vxlan(packet))))
+        }
+        Some(UdpTunnel::Geneve) => {
+            let (input, packet) = geneve::parse_geneve_packet(input)?;
+            Ok((input, Some(UdpTunnelPacket::Geneve(packet))))
+        }
+        Some(UdpTunnel::Teredo) => {
+            let (input, teredo_header) = teredo::parse_teredo_header(input)?;
+            let (input, ip) = ipv6::parse_ipv6_header(input)?;
+            Ok((input, Some(UdpTunnelPacket::Teredo(teredo_header, ip))))
+        }
+        Some(UdpTunnel::GtpU) => {
+            let (input, packet) = gtp::parse_gtpu_packet(input)?;
+            Ok((input, Some(UdpTunnelPacket::GtpU(packet))))
+        }
+        Some(UdpTunnel::NatT) => {
+            let (input, packet) = ipsec::parse_nat_t_packet(input)?;
+            Ok((input, Some(UdpTunnelPacket::NatT(packet))))
+        }
+        None => Ok((input, None)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{parse_udp_header, UdpHeader};
+    use super::{
+        parse_udp_header, parse_udp_tunnel, UdpHeader, UdpTunnel, UdpTunnelPacket, UdpTunnelPorts,
+    };
+    use crate::ethernet::{EtherType, MacAddress, VlanEthernetFrame};
+    use crate::vxlan::{VxlanHeader, VxlanPacket, VxlanPayload};
     const EMPTY_SLICE: &'static [u8] = &[];
 
     #[test]
@@ -48,4 +150,63 @@ mod tests {
         };
         assert_eq!(parse_udp_header(&bytes), Ok((EMPTY_SLICE, expectation)));
     }
+
+    #[test]
+    fn udp_tunnel_registered_port_works() {
+        let bytes = [
+            0x08, 0x00, 0x00, 0x00, /* VXLAN flags and reserved */
+            0x00, 0x00, 0x64, 0x00, /* VNI and reserved */
+            0x00, 0x23, 0x54, 0x07, 0x93, 0x6c, /* dest MAC */
+            0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, /* src MAC */
+            0x08, 0x00, /* Ethertype */
+        ];
+        let header = UdpHeader {
+            source_port: 51234,
+            dest_port: 8472,
+            length: 30,
+            checksum: 0,
+        };
+
+        let mut ports = UdpTunnelPorts::default();
+        assert_eq!(
+            parse_udp_tunnel(&bytes, &header, &ports),
+            Ok((&bytes[..], None))
+        );
+
+        ports.register(8472, UdpTunnel::Vxlan);
+        assert_eq!(ports.lookup(&header), Some(UdpTunnel::Vxlan));
+        let expectation = UdpTunnelPacket::Vxlan(VxlanPacket {
+            header: VxlanHeader {
+                version: 0,
+                flag_vni: true,
+                flag_next_protocol: false,
+                flag_bum: false,
+                flag_oam: false,
+                vni: 100,
+                next_protocol: None,
+            },
+            payload: VxlanPayload::Ethernet(VlanEthernetFrame {
+                source_mac: MacAddress([0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b]),
+                dest_mac: MacAddress([0x00, 0x23, 0x54, 0x07, 0x93, 0x6c]),
+                ethertype: EtherType::IPv4,
+                vid: None,
+            }),
+        });
+        assert_eq!(
+            parse_udp_tunnel(&bytes, &header, &ports),
+            Ok((EMPTY_SLICE, Some(expectation)))
+        );
+
+        let unregistered = UdpHeader {
+            source_port: 51234,
+            dest_port: 9999,
+            length: 30,
+            checksum: 0,
+        };
+        assert_eq!(ports.lookup(&unregistered), None);
+        assert_eq!(
+            parse_udp_tunnel(&bytes, &unregistered, &ports),
+            Ok((&bytes[..], None))
+        );
+    }
 }
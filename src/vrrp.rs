@@ -0,0 +1,313 @@
+//! Handles parsing of VRRPv2, VRRPv3 and CARP advertisements, which share IP protocol 112
+
+use crate::ip::IPHeader;
+use crate::{ipv4, ipv6};
+use nom::bytes;
+use nom::error::{Error, ErrorKind};
+use nom::multi::count;
+use nom::number;
+use nom::{Err, IResult};
+use std::net::IpAddr;
+use std::time::Duration;
+
+// VRRPv3 Packet Format (RFC 5798); VRRPv2 (RFC 3768) has an Auth Type octet and an 8-bit
+// advertisement interval in seconds in place of the Max Adver Int, and ends with 8 octets of
+// authentication data
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |Version| Type  | Virtual Rtr ID|   Priority    |Count IPvX Addr|
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |(rsvd) |     Max Adver Int     |          Checksum             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     IPvX Address(es)                          |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// CARP Packet Format (OpenBSD)
+//
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |Version| Type  |     VHID      |    AdvSkew    |    AuthLen    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |    Demotion   |    AdvBase    |          Checksum             |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                        Counter (8 octets)                     |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                     SHA-1 HMAC (20 octets)                    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+const VRRP_V2: u8 = 2;
+const VRRP_V3: u8 = 3;
+
+const AUTH_NONE: u8 = 0;
+const AUTH_SIMPLE_TEXT: u8 = 1;
+const AUTH_IP_AH: u8 = 2;
+
+const CARP_LENGTH: usize = 36;
+/// CARP's AuthLen, in 32-bit words, is always that of its counter and HMAC
+const CARP_AUTH_LENGTH: u8 = 7;
+/// Both CARP and VRRPv2 advertisements start with version 2, type 1
+const ADVERTISEMENT_V2: u8 = 0x21;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VrrpAuthentication {
+    None,
+    SimpleText([u8; 8]),
+    IpAuthenticationHeader,
+    Other { auth_type: u8, data: [u8; 8] },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VrrpPacket {
+    pub version: u8,
+    /// 1 for advertisements, the only type defined
+    pub packet_type: u8,
+    pub virtual_router_id: u8,
+    /// 255 for the address owner, 0 when the master stops participating
+    pub priority: u8,
+    pub advertisement_interval: Duration,
+    pub checksum: u16,
+    pub addresses: Vec<IpAddr>,
+    /// Only present in VRRPv2
+    pub authentication: Option<VrrpAuthentication>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CarpPacket {
+    pub version: u8,
+    pub packet_type: u8,
+    pub virtual_host_id: u8,
+    pub advertisement_skew: u8,
+    pub authentication_length: u8,
+    pub demotion: u8,
+    pub advertisement_base: u8,
+    pub checksum: u16,
+    pub counter: u64,
+    pub hmac: [u8; 20],
+}
+
+impl CarpPacket {
+    /// AdvBase seconds plus AdvSkew 256ths of a second; the host with the shortest interval
+    /// becomes master
+    pub fn advertisement_interval(&self) -> Duration {
+        Duration::from_secs(u64::from(self.advertisement_base))
+            + Duration::from_secs(1) * u32::from(self.advertisement_skew) / 256
+    }
+}
+
+/// Tells a CARP advertisement apart from a VRRPv2 one, which has the same protocol number, version
+/// and type. The input must end where the IP payload ends: a VRRPv2 advertisement with the count
+/// of 7 addresses found where CARP has its AuthLen is 44 octets long.
+pub fn is_carp(input: &[u8]) -> bool {
+    input.len() == CARP_LENGTH && input[0] == ADVERTISEMENT_V2 && input[3] == CARP_AUTH_LENGTH
+}
+
+fn v2_authentication(input: &[u8], auth_type: u8) -> IResult<&[u8], VrrpAuthentication> {
+    let (rest, raw) = bytes::streaming::take(8u8)(input)?;
+    let mut data = [0; 8];
+    data.copy_from_slice(raw);
+
+    let authentication = match auth_type {
+        AUTH_NONE => VrrpAuthentication::None,
+        AUTH_SIMPLE_TEXT => VrrpAuthentication::SimpleText(data),
+        AUTH_IP_AH => VrrpAuthentication::IpAuthenticationHeader,
+        auth_type => VrrpAuthentication::Other { auth_type, data },
+    };
+
+    Ok((rest, authentication))
+}
+
+/// Parses a VRRP advertisement. VRRPv3 addresses are IPv4 or IPv6 like those of `ip_header`.
+pub fn parse_vrrp_packet<'a>(
+    input: &'a [u8],
+    ip_header: &IPHeader,
+) -> IResult<&'a [u8], VrrpPacket> {
+    let (rest, version_type) = number::streaming::be_u8(input)?;
+    let (rest, virtual_router_id) = number::streaming::be_u8(rest)?;
+    let (rest, priority) = number::streaming::be_u8(rest)?;
+    let (rest, address_count) = number::streaming::be_u8(rest)?;
+    let version = version_type >> 4;
+    let address_count = usize::from(address_count);
+
+    let (rest, auth_type, advertisement_interval) = match version {
+        VRRP_V2 => {
+            let (rest, auth_type) = number::streaming::be_u8(rest)?;
+            let (rest, interval) = number::streaming::be_u8(rest)?;
+            let interval = Duration::from_secs(u64::from(interval));
+            (rest, Some(auth_type), interval)
+        }
+        VRRP_V3 => {
+            let (rest, interval) = number::streaming::be_u16(rest)?;
+            let interval = Duration::from_millis(u64::from(interval & 0x0fff) * 10);
+            (rest, None, interval)
+        }
+        _ => return Err(Err::Error(Error::new(input, ErrorKind::Verify))),
+    };
+    let (rest, checksum) = number::streaming::be_u16(rest)?;
+
+    let (rest, addresses) = match ip_header {
+        IPHeader::V6(_) if version == VRRP_V3 => {
+            let (rest, addresses) = count(ipv6::address, address_count)(rest)?;
+            (rest, addresses.into_iter().map(IpAddr::V6).collect())
+        }
+        _ => {
+            let (rest, addresses) = count(ipv4::address, address_count)(rest)?;
+            (rest, addresses.into_iter().map(IpAddr::V4).collect())
+        }
+    };
+    let (rest, authentication) = match auth_type {
+        Some(auth_type) => {
+            let (rest, authentication) = v2_authentication(rest, auth_type)?;
+            (rest, Some(authentication))
+        }
+        None => (rest, None),
+    };
+
+    Ok((
+        rest,
+        VrrpPacket {
+            version,
+            packet_type: version_type & 0x0f,
+            virtual_router_id,
+            priority,
+            advertisement_interval,
+            checksum,
+            addresses,
+            authentication,
+        },
+    ))
+}
+
+pub fn parse_carp_packet(input: &[u8]) -> IResult<&[u8], CarpPacket> {
+    let (rest, version_type) = number::streaming::be_u8(input)?;
+    let (rest, virtual_host_id) = number::streaming::be_u8(rest)?;
+    let (rest, advertisement_skew) = number::streaming::be_u8(rest)?;
+    let (rest, authentication_length) = number::streaming::be_u8(rest)?;
+    let (rest, demotion) = number::streaming::be_u8(rest)?;
+    let (rest, advertisement_base) = number::streaming::be_u8(rest)?;
+    let (rest, checksum) = number::streaming::be_u16(rest)?;
+    let (rest, counter) = number::streaming::be_u64(rest)?;
+    let (rest, raw) = bytes::streaming::take(20u8)(rest)?;
+    let mut hmac = [0; 20];
+    hmac.copy_from_slice(raw);
+
+    Ok((
+        rest,
+        CarpPacket {
+            version: version_type >> 4,
+            packet_type: version_type & 0x0f,
+            virtual_host_id,
+            advertisement_skew,
+            authentication_length,
+            demotion,
+            advertisement_base,
+            checksum,
+            counter,
+            hmac,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip::IPProtocol;
+    use crate::ipv4::IPv4Header;
+    use crate::ipv6::IPv6Header;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    fn ipv4_header() -> IPHeader {
+        IPHeader::V4(IPv4Header {
+            version: 4,
+            ihl: 5,
+            tos: 0xc0,
+            length: 40,
+            id: 0,
+            flags: 0,
+            fragment_offset: 0,
+            ttl: 255,
+            protocol: IPProtocol::VRRP,
+            chksum: 0,
+            source_addr: Ipv4Addr::new(192, 0, 2, 2),
+            dest_addr: Ipv4Addr::new(224, 0, 0, 18),
+        })
+    }
+
+    #[test]
+    fn vrrp_v2_and_v3_work() {
+        let v2 = [
+            0x21, 0x0a, 0x64, 0x02, // version 2, VRID 10, priority 100, two addresses
+            0x01, 0x01, 0x12, 0x34, // simple text, interval 1 s, checksum
+            0xc0, 0x00, 0x02, 0x01, // 192.0.2.1
+            0xc0, 0x00, 0x02, 0x02, // 192.0.2.2
+            b's', b'e', b'c', b'r', b'e', b't', 0x00, 0x00, // password
+        ];
+        let expectation = VrrpPacket {
+            version: 2,
+            packet_type: 1,
+            virtual_router_id: 10,
+            priority: 100,
+            advertisement_interval: Duration::from_secs(1),
+            checksum: 0x1234,
+            addresses: vec![
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+            ],
+            authentication: Some(VrrpAuthentication::SimpleText(*b"secret\0\0")),
+        };
+        assert!(!is_carp(&v2));
+        assert_eq!(
+            parse_vrrp_packet(&v2, &ipv4_header()),
+            Ok((EMPTY_SLICE, expectation))
+        );
+
+        let v3 = [
+            0x31, 0x01, 0xff, 0x01, // version 3, VRID 1, owner, one address
+            0x00, 0x64, 0x00, 0x00, // interval 100 cs, checksum
+            0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fe80::1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        let ipv6_header = IPHeader::V6(IPv6Header {
+            version: 6,
+            ds: 0x30,
+            ecn: 0,
+            flow_label: 0,
+            length: 24,
+            next_header: IPProtocol::VRRP,
+            hop_limit: 255,
+            source_addr: Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2),
+            dest_addr: Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x12),
+        });
+        let (remaining, packet) = parse_vrrp_packet(&v3, &ipv6_header).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.advertisement_interval, Duration::from_secs(1));
+        assert_eq!(packet.authentication, None);
+        assert_eq!(
+            packet.addresses,
+            vec![IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn carp_works() {
+        let mut bytes = vec![
+            0x21, 0x01, 0x80, 0x07, // version 2, VHID 1, skew 128, auth length 7
+            0x00, 0x01, 0xab, 0xcd, // no demotion, base 1 s, checksum
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a, // counter
+        ];
+        bytes.extend_from_slice(&[0x55; 20]); // HMAC
+
+        assert!(is_carp(&bytes));
+        let (remaining, packet) = parse_carp_packet(&bytes).unwrap();
+        assert_eq!(remaining, EMPTY_SLICE);
+        assert_eq!(packet.virtual_host_id, 1);
+        assert_eq!(packet.counter, 42);
+        assert_eq!(packet.hmac, [0x55; 20]);
+        assert_eq!(packet.advertisement_interval(), Duration::from_millis(1500));
+    }
+}
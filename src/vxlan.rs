@@ -0,0 +1,184 @@
+//! Handles parsing of VXLAN and VXLAN-GPE headers
+
+use crate::ethernet::{self, VlanEthernetFrame};
+use crate::ipv4::{self, IPv4Header};
+use crate::ipv6::{self, IPv6Header};
+use nom::number;
+use nom::IResult;
+
+// VXLAN Header Format (RFC 7348), with the VXLAN-GPE extensions
+//
+//    0                   1                   2                   3
+//    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |R|R|Ver|I|P|B|O|       Reserved                |Next Protocol  |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//   |                VXLAN Network Identifier (VNI) |   Reserved    |
+//   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+pub const VXLAN_PORT: u16 = 4789;
+pub const VXLAN_GPE_PORT: u16 = 4790;
+
+const FLAG_VNI: u8 = 0x08;
+const FLAG_NEXT_PROTOCOL: u8 = 0x04;
+const FLAG_BUM: u8 = 0x02;
+const FLAG_OAM: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VxlanNextProtocol {
+    IPv4,
+    IPv6,
+    Ethernet,
+    Nsh,
+    Mpls,
+    Other(u8),
+}
+
+impl From<u8> for VxlanNextProtocol {
+    fn from(raw: u8) -> Self {
+        match raw {
+            0x01 => Self::IPv4,
+            0x02 => Self::IPv6,
+            0x03 => Self::Ethernet,
+            0x04 => Self::Nsh,
+            0x05 => Self::Mpls,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VxlanHeader {
+    pub version: u8,
+    pub flag_vni: bool,
+    pub flag_next_protocol: bool,
+    pub flag_bum: bool,
+    pub flag_oam: bool,
+    pub vni: u32,
+    /// Only present on VXLAN-GPE packets (P flag set)
+    pub next_protocol: Option<VxlanNextProtocol>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VxlanPayload {
+    Ethernet(VlanEthernetFrame),
+    IPv4(IPv4Header),
+    IPv6(IPv6Header),
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VxlanPacket {
+    pub header: VxlanHeader,
+    pub payload: VxlanPayload,
+}
+
+pub fn parse_vxlan_header(input: &[u8]) -> IResult<&[u8], VxlanHeader> {
+    let (input, flags) = number::streaming::be_u8(input)?;
+    let (input, _reserved) = number::streaming::be_u16(input)?;
+    let (input, next_protocol) = number::streaming::be_u8(input)?;
+    let (input, vni_reserved) = number::streaming::be_u32(input)?;
+
+    let flag_next_protocol = flags & FLAG_NEXT_PROTOCOL != 0;
+
+    Ok((
+        input,
+        VxlanHeader {
+            version: (flags >> 4) & 0b11,
+            flag_vni: flags & FLAG_VNI != 0,
+            flag_next_protocol,
+            flag_bum: flags & FLAG_BUM != 0,
+            flag_oam: flags & FLAG_OAM != 0,
+            vni: vni_reserved >> 8,
+            next_protocol: if flag_next_protocol {
+                Some(next_protocol.into())
+            } else {
+                None
+            },
+        },
+    ))
+}
+
+/// Parses a VXLAN header and the header of the encapsulated packet. Plain VXLAN always carries
+/// Ethernet; VXLAN-GPE may announce another protocol. The remaining input is the payload of the
+/// innermost parsed header.
+pub fn parse_vxlan_packet(input: &[u8]) -> IResult<&[u8], VxlanPacket> {
+    let (input, header) = parse_vxlan_header(input)?;
+
+    let (input, payload) = match header.next_protocol {
+        None | Some(VxlanNextProtocol::Ethernet) => {
+            let (input, frame) = ethernet::parse_vlan_ethernet_frame(input)?;
+            (input, VxlanPayload::Ethernet(frame))
+        }
+        Some(VxlanNextProtocol::IPv4) => {
+            let (input, ip) = ipv4::parse_ipv4_header(input)?;
+            (input, VxlanPayload::IPv4(ip))
+        }
+        Some(VxlanNextProtocol::IPv6) => {
+            let (input, ip) = ipv6::parse_ipv6_header(input)?;
+            (input, VxlanPayload::IPv6(ip))
+        }
+        Some(_) => (input, VxlanPayload::Other),
+    };
+
+    Ok((input, VxlanPacket { header, payload }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::{EtherType, MacAddress};
+
+    const EMPTY_SLICE: &[u8] = &[];
+
+    #[test]
+    fn vxlan_packet_works() {
+        let bytes = [
+            0x08, 0x00, 0x00, 0x00, /* flags and reserved */
+            0x00, 0x30, 0x39, 0x00, /* VNI and reserved */
+            0x00, 0x23, 0x54, 0x07, 0x93, 0x6c, /* dest MAC */
+            0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b, /* src MAC */
+            0x08, 0x00, /* Ethertype */
+        ];
+        let expectation = VxlanPacket {
+            header: VxlanHeader {
+                version: 0,
+                flag_vni: true,
+                flag_next_protocol: false,
+                flag_bum: false,
+                flag_oam: false,
+                vni: 12345,
+                next_protocol: None,
+            },
+            payload: VxlanPayload::Ethernet(VlanEthernetFrame {
+                source_mac: MacAddress([0x00, 0x1b, 0x21, 0x0f, 0x91, 0x9b]),
+                dest_mac: MacAddress([0x00, 0x23, 0x54, 0x07, 0x93, 0x6c]),
+                ethertype: EtherType::IPv4,
+                vid: None,
+            }),
+        };
+        assert_eq!(parse_vxlan_packet(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+
+    #[test]
+    fn vxlan_gpe_header_works() {
+        let bytes = [
+            0x0c, 0x00, 0x00, 0x04, /* flags, reserved and next protocol */
+            0x00, 0x00, 0x01, 0x00, /* VNI and reserved */
+        ];
+        let expectation = VxlanHeader {
+            version: 0,
+            flag_vni: true,
+            flag_next_protocol: true,
+            flag_bum: false,
+            flag_oam: false,
+            vni: 1,
+            next_protocol: Some(VxlanNextProtocol::Nsh),
+        };
+        assert_eq!(parse_vxlan_header(&bytes), Ok((EMPTY_SLICE, expectation)));
+    }
+}
@@ -3,7 +3,7 @@ extern crate pktparse;
 
 mod tests {
     use pktparse::tcp::TcpOption;
-    use pktparse::{ipv4, tcp};
+    use pktparse::{http, ipv4, tcp};
 
     #[test]
     fn parse_tcp_packet() {
@@ -27,6 +27,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_tcp_packet_with_http_request() {
+        let bytes = [
+            0x45, 0x00, 0x00, 0x57, 0x76, 0xf4, 0x40, 0x00, 0x40, 0x06, 0x80, 0xba, 0xc0, 0xa8,
+            0x00, 0x6c, 0xd0, 0x61, 0xb1, 0x7c, 0xb0, 0xc2, 0x00, 0x50, 0xb0, 0xee, 0x32, 0xa6,
+            0x04, 0x39, 0xae, 0xe6, 0x50, 0x18, 0x00, 0xe5, 0x76, 0x92, 0x00, 0x00, 0x47, 0x45,
+            0x54, 0x20, 0x2f, 0x69, 0x6e, 0x64, 0x65, 0x78, 0x2e, 0x68, 0x74, 0x6d, 0x6c, 0x20,
+            0x48, 0x54, 0x54, 0x50, 0x2f, 0x31, 0x2e, 0x31, 0x0d, 0x0a, 0x48, 0x6f, 0x73, 0x74,
+            0x3a, 0x20, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x0d,
+            0x0a, 0x0d, 0x0a,
+        ];
+
+        if let Ok((remaining, _)) = ipv4::parse_ipv4_header(&bytes) {
+            if let Ok((remaining, _)) = tcp::parse_tcp_header(remaining) {
+                let limits = http::HttpLimits::default();
+                if let Ok((remaining, request)) = http::parse_http_request(remaining, &limits) {
+                    assert_eq!(remaining.len(), 0);
+                    assert_eq!(request.method, http::HttpMethod::Get);
+                    assert_eq!(request.target, "/index.html");
+                    assert_eq!(request.header("Host"), Some("example.com"));
+                } else {
+                    panic!();
+                }
+            } else {
+                panic!();
+            }
+        } else {
+            panic!();
+        }
+    }
+
     #[test]
     fn parse_tcp_packet_with_options() {
         let bytes = [